@@ -0,0 +1,366 @@
+//! Subagent definitions: `~/.claude/agents/*.md` and `.claude/agents/*.md`.
+//!
+//! An agent file is markdown with YAML frontmatter — `name` and
+//! `description` are required, `tools` (list or comma-separated) and `model`
+//! are optional — followed by the agent's system prompt. Parsing goes through
+//! the same `parse_frontmatter` the skill scanner uses, and validation runs
+//! the skill linter's shared `LintFrontmatter` checks so both report
+//! `SkillDiagnostic`s with the same codes and line numbers.
+//!
+//! A project agent with the same `name` as a global one takes precedence in
+//! the CLI; `list_agents` flags the shadowed pair with `duplicate-name`.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+use super::skill_lint::{self, LintFrontmatter, SkillDiagnostic};
+use crate::path_access::{PathAccessManager, PathCapability};
+
+/// Frontmatter keys the CLI reads from agent files.
+const KNOWN_AGENT_KEYS: &[&str] = &["name", "description", "tools", "model", "color"];
+
+/// YAML frontmatter fields for agent .md files
+#[derive(Debug, Deserialize, Default)]
+pub(crate) struct AgentFrontmatter {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default, deserialize_with = "crate::deserialize_tool_list")]
+    tools: Option<Vec<String>>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    color: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AgentInfo {
+    /// Frontmatter `name`, falling back to the file stem.
+    pub name: String,
+    pub description: String,
+    pub path: String,
+    /// "global" | "project"
+    pub scope: String,
+    /// `None` means the agent inherits every tool of the main thread.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<SkillDiagnostic>,
+}
+
+/// Agent names are lowercase letters, digits and hyphens.
+fn valid_agent_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// `~/.claude/agents` for "global", `{cwd}/.claude/agents` for "project".
+pub(crate) fn agents_dir(scope: &str, cwd: Option<&str>) -> Option<PathBuf> {
+    match scope {
+        "global" => dirs::home_dir().map(|h| h.join(".claude").join("agents")),
+        "project" => cwd.map(|c| Path::new(c).join(".claude").join("agents")),
+        _ => None,
+    }
+}
+
+/// Agent files must be `.md` directly inside a `.claude/agents` directory.
+fn ensure_agent_path(path: &Path) -> Result<(), String> {
+    let in_agents_dir = path
+        .parent()
+        .is_some_and(|dir| dir.ends_with(Path::new(".claude").join("agents")));
+    if path.extension().and_then(|e| e.to_str()) != Some("md") || !in_agents_dir {
+        return Err(format!(
+            "Not an agent file (expected .claude/agents/<name>.md): {}",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Validate one agent file's content.
+pub(crate) fn lint_agent_content(content: &str) -> Vec<SkillDiagnostic> {
+    let mut diags = Vec::new();
+
+    let Some((yaml, yaml_first_line, body, body_first_line)) =
+        skill_lint::split_with_lines(content)
+    else {
+        diags.push(SkillDiagnostic::new(
+            "error",
+            "missing-name",
+            "No YAML frontmatter — agents need `name` and `description` to be loaded".to_string(),
+            Some(1),
+        ));
+        return diags;
+    };
+
+    let Some(fm) =
+        LintFrontmatter::parse(yaml, yaml_first_line, "the agent will not load", &mut diags)
+    else {
+        return diags;
+    };
+    fm.check_schema::<AgentFrontmatter>("its settings fall back to defaults", &mut diags);
+
+    match fm.get_str("name") {
+        None => diags.push(SkillDiagnostic::new(
+            "error",
+            "missing-name",
+            "Missing `name` — the CLI skips agents without one".to_string(),
+            Some(fm.first_line()),
+        )),
+        Some(name) if !valid_agent_name(name) => diags.push(SkillDiagnostic::new(
+            "error",
+            "invalid-name",
+            format!(
+                "Agent name '{}' must use lowercase letters, digits and hyphens",
+                name
+            ),
+            fm.line_of("name"),
+        )),
+        Some(_) => {}
+    }
+
+    if fm.get_str("description").is_none() {
+        diags.push(SkillDiagnostic::new(
+            "error",
+            "missing-description",
+            "Missing `description` — the model uses it to decide when to delegate".to_string(),
+            Some(fm.first_line()),
+        ));
+    }
+
+    fm.check_keys(
+        KNOWN_AGENT_KEYS,
+        |k| (k == "allowed-tools").then(|| "agents use `tools`".to_string()),
+        &mut diags,
+    );
+    fm.check_tools("tools", &mut diags);
+
+    if let Some(model) = fm.get_str("model") {
+        let aliases = super::skill_packages::MODEL_ALIASES;
+        if !aliases.contains(&model) && !model.starts_with("claude-") {
+            diags.push(SkillDiagnostic::new(
+                "warning",
+                "unknown-model",
+                format!(
+                    "`model` '{}' is not a known alias ({}) or claude-* model id",
+                    model,
+                    aliases.join(", ")
+                ),
+                fm.line_of("model"),
+            ));
+        }
+    }
+
+    if body.trim().is_empty() {
+        diags.push(SkillDiagnostic::new(
+            "warning",
+            "empty-prompt",
+            "The body is empty — it becomes the agent's system prompt".to_string(),
+            Some(body_first_line),
+        ));
+    }
+
+    diags
+}
+
+fn scan_agents_dir(dir: &Path, scope: &str) -> Vec<AgentInfo> {
+    let mut found = vec![];
+    let entries = match std::fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return found,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() || path.extension().and_then(|e| e.to_str()) != Some("md") {
+            continue;
+        }
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let content = std::fs::read_to_string(&path).unwrap_or_default();
+        let (fm, body) = crate::parse_frontmatter::<AgentFrontmatter>(&content);
+
+        let name = fm.name.filter(|n| !n.trim().is_empty()).unwrap_or(stem);
+        let description = fm
+            .description
+            .or_else(|| {
+                body.lines()
+                    .find(|line| !line.trim().is_empty())
+                    .map(|line| line.trim_start_matches('#').trim().to_string())
+            })
+            .unwrap_or_else(|| name.clone());
+
+        found.push(AgentInfo {
+            name,
+            description,
+            path: path.to_string_lossy().to_string(),
+            scope: scope.to_string(),
+            tools: fm.tools,
+            model: fm.model,
+            color: fm.color,
+            diagnostics: lint_agent_content(&content),
+        });
+    }
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    found
+}
+
+/// Global then project agents, with diagnostics attached.
+pub(crate) fn collect_agents(cwd: Option<&str>) -> Vec<AgentInfo> {
+    let mut agents = vec![];
+    for scope in ["global", "project"] {
+        if let Some(dir) = agents_dir(scope, cwd) {
+            agents.extend(scan_agents_dir(&dir, scope));
+        }
+    }
+    skill_lint::add_duplicate_diagnostics(
+        "Agent",
+        agents
+            .iter_mut()
+            .map(|a| (a.name.as_str(), a.scope.as_str(), &mut a.diagnostics)),
+    );
+    agents
+}
+
+/// Scan and return all agent definitions (global + project)
+#[tauri::command]
+pub async fn list_agents(cwd: Option<String>) -> Result<Vec<AgentInfo>, String> {
+    Ok(collect_agents(cwd.as_deref()))
+}
+
+/// Read an agent file and return its content
+#[tauri::command]
+pub async fn read_agent(
+    path_access: State<'_, PathAccessManager>,
+    path: String,
+    tab_id: Option<String>,
+) -> Result<String, String> {
+    let p = path_access
+        .validate(Path::new(&path), tab_id.as_deref(), PathCapability::Read)
+        .await?;
+    ensure_agent_path(&p)?;
+    std::fs::read_to_string(&p).map_err(|e| format!("Cannot read agent file: {}", e))
+}
+
+/// Write an agent file, creating `.claude/agents` if needed. The content is
+/// saved even when it has problems; the diagnostics are returned so the
+/// editor can show them.
+#[tauri::command]
+pub async fn write_agent(
+    path_access: State<'_, PathAccessManager>,
+    path: String,
+    content: String,
+    tab_id: Option<String>,
+) -> Result<Vec<SkillDiagnostic>, String> {
+    let p = path_access
+        .validate(Path::new(&path), tab_id.as_deref(), PathCapability::Write)
+        .await?;
+    ensure_agent_path(&p)?;
+    if let Some(parent) = p.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directories: {}", e))?;
+    }
+    std::fs::write(&p, &content).map_err(|e| format!("Cannot write agent file: {}", e))?;
+    Ok(lint_agent_content(&content))
+}
+
+/// Delete an agent file
+#[tauri::command]
+pub async fn delete_agent(
+    path_access: State<'_, PathAccessManager>,
+    path: String,
+    tab_id: Option<String>,
+) -> Result<(), String> {
+    let p = path_access
+        .validate(Path::new(&path), tab_id.as_deref(), PathCapability::Delete)
+        .await?;
+    ensure_agent_path(&p)?;
+    std::fs::remove_file(&p).map_err(|e| format!("Failed to delete agent file: {}", e))
+}
+
+/// Validate agent content without writing it (editor live feedback).
+#[tauri::command]
+pub async fn validate_agent(content: String) -> Result<Vec<SkillDiagnostic>, String> {
+    Ok(lint_agent_content(&content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(diags: &[SkillDiagnostic]) -> Vec<&str> {
+        diags.iter().map(|d| d.code.as_str()).collect()
+    }
+
+    #[test]
+    fn valid_agent_has_no_diagnostics() {
+        let content = "---\nname: code-reviewer\ndescription: Reviews diffs\ntools: Read, Grep, Bash(git diff:*)\nmodel: sonnet\ncolor: blue\n---\nYou review code.\n";
+        assert!(lint_agent_content(content).is_empty());
+
+        let (fm, body) = crate::parse_frontmatter::<AgentFrontmatter>(content);
+        assert_eq!(fm.name.as_deref(), Some("code-reviewer"));
+        assert_eq!(
+            fm.tools,
+            Some(vec![
+                "Read".to_string(),
+                "Grep".to_string(),
+                "Bash(git diff:*)".to_string()
+            ])
+        );
+        assert_eq!(body, "You review code.\n");
+    }
+
+    #[test]
+    fn reports_agent_specific_problems() {
+        let content =
+            "---\nname: Test Writer\ntools: [Read, Wrte]\nmodel: gpt-4\nallowed-tools: Read\n---\n";
+        let diags = lint_agent_content(content);
+        let c = codes(&diags);
+        assert!(c.contains(&"invalid-name"));
+        assert!(c.contains(&"missing-description"));
+        assert!(c.contains(&"unknown-tool"));
+        assert!(c.contains(&"unknown-model"));
+        assert!(c.contains(&"unknown-key"));
+        assert!(c.contains(&"empty-prompt"));
+        let model = diags.iter().find(|d| d.code == "unknown-model").unwrap();
+        assert_eq!(model.line, Some(4));
+
+        assert_eq!(
+            codes(&lint_agent_content("just a prompt")),
+            ["missing-name"]
+        );
+    }
+
+    #[test]
+    fn agent_paths_must_live_in_agents_dir() {
+        assert!(ensure_agent_path(Path::new("/p/.claude/agents/reviewer.md")).is_ok());
+        assert!(ensure_agent_path(Path::new("/p/.claude/skills/reviewer.md")).is_err());
+        assert!(ensure_agent_path(Path::new("/p/.claude/agents/reviewer.txt")).is_err());
+        assert!(ensure_agent_path(Path::new("/p/src/agents/x.md")).is_err());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("agents");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("helper.md"),
+            "---\ndescription: Helps\n---\nBody\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        let agents = scan_agents_dir(&dir, "project");
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0].name, "helper");
+        assert_eq!(agents[0].description, "Helps");
+        assert_eq!(codes(&agents[0].diagnostics), ["missing-name"]);
+    }
+}
@@ -0,0 +1,626 @@
+//! Garbage collection for attachment files.
+//!
+//! `save_attachment` and `save_temp_file` write into each project's
+//! `.tokenicode/tmp` and into the system temp `tokenicode` dir. Deleting a
+//! session only drops what that session alone referenced, so everything
+//! else accumulated. This pass works out which sessions reference each
+//! attachment by scanning the JSONL under `~/.claude/projects/` (and the
+//! gzipped sessions retention moved to `~/.tokenicode/archive/`). It then
+//! deletes the files no session references that are older than
+//! `min_age_days`; younger ones may belong to a message still being typed.
+//! If any session cannot be read to the end, nothing is deleted: the run is
+//! reported as a dry run with the read errors.
+//!
+//! Attachment dirs come from the sessions' `cwd`s plus
+//! `~/.tokenicode/attachment_dirs.json`, which the save commands append to,
+//! so projects whose sessions are all gone are still covered. Settings live
+//! in `~/.tokenicode/attachment_gc.json`. The pass runs once at startup and
+//! on demand via `collect_attachment_garbage`.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use super::attachments::{hash_regex, stored_hash};
+
+const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+const DIRS_FILE: &str = "attachment_dirs.json";
+/// Lines read from the top of a session looking for its `cwd`.
+const CWD_SCAN_LINES: usize = 50;
+/// Depth of session dirs walked (project dir, then subagent dirs).
+const MAX_SESSION_DEPTH: usize = 4;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct AttachmentGcConfig {
+    /// Run the pass at startup.
+    pub enabled: bool,
+    /// Unreferenced files younger than this are kept.
+    pub min_age_days: u32,
+}
+
+impl Default for AttachmentGcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_age_days: 7,
+        }
+    }
+}
+
+/// One attachment file and the sessions that mention it.
+#[derive(Debug, Serialize, Clone)]
+pub struct AttachmentUsage {
+    pub path: String,
+    pub size: u64,
+    pub modified_ms: u64,
+    /// Session ids (JSONL file stems) referencing the file.
+    pub sessions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct AttachmentGcReport {
+    pub dry_run: bool,
+    pub scanned: usize,
+    pub referenced: usize,
+    /// Unreferenced but younger than `min_age_days`.
+    pub kept_recent: usize,
+    pub removed: Vec<String>,
+    pub reclaimed_bytes: u64,
+    pub errors: Vec<String>,
+}
+
+fn config_path() -> Result<PathBuf, String> {
+    crate::tokenicode_data_path("attachment_gc.json")
+}
+
+fn read_config() -> Result<AttachmentGcConfig, String> {
+    let path = config_path()?;
+    if !path.exists() {
+        return Ok(AttachmentGcConfig::default());
+    }
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read attachment GC config: {}", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse attachment GC config: {}", e))
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn system_attachment_dir() -> PathBuf {
+    std::env::temp_dir().join("tokenicode")
+}
+
+// ---------------------------------------------------------------------------
+// Attachment dir registry
+// ---------------------------------------------------------------------------
+
+fn read_dirs(path: &Path) -> BTreeSet<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+/// Remember a project attachment dir, so the GC still visits it after all
+/// of the project's sessions are deleted.
+pub(crate) fn remember_attachment_dir(dir: &Path) {
+    static LOCK: Mutex<()> = Mutex::new(());
+    if dir == system_attachment_dir() {
+        return;
+    }
+    let Ok(path) = crate::tokenicode_data_path(DIRS_FILE) else {
+        return;
+    };
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut dirs = read_dirs(&path);
+    if dirs.insert(dir.to_string_lossy().to_string()) {
+        if let Ok(content) = serde_json::to_string_pretty(&dirs) {
+            let _ = std::fs::write(&path, content);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Survey
+// ---------------------------------------------------------------------------
+
+/// Session files (`.jsonl`, and `.jsonl.gz` archives) under `root`.
+fn session_files(root: &Path, depth: usize, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let name = entry.file_name().to_string_lossy().to_string();
+        if file_type.is_dir() && depth > 0 {
+            session_files(&path, depth - 1, out);
+        } else if file_type.is_file() && (name.ends_with(".jsonl") || name.ends_with(".jsonl.gz")) {
+            out.push(path);
+        }
+    }
+}
+
+fn open_lines(path: &Path) -> Result<Box<dyn BufRead>, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    if path.extension().is_some_and(|e| e == "gz") {
+        Ok(Box::new(BufReader::new(flate2::read::GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// Feed each line of session file `path` (plain or gzipped) to `f` until it
+/// returns false. Invalid UTF-8 is decoded lossily rather than ending the
+/// scan; an I/O or decompression error is returned, since the lines after
+/// it were never seen.
+pub(crate) fn for_each_session_line(
+    path: &Path,
+    mut f: impl FnMut(&str) -> bool,
+) -> Result<(), String> {
+    let mut reader = open_lines(path)?;
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let n = reader
+            .read_until(b'\n', &mut buf)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if n == 0 {
+            return Ok(());
+        }
+        if !f(&String::from_utf8_lossy(&buf)) {
+            return Ok(());
+        }
+    }
+}
+
+fn session_id(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    name.trim_end_matches(".gz")
+        .trim_end_matches(".jsonl")
+        .to_string()
+}
+
+fn session_cwd(path: &Path) -> Option<String> {
+    let mut cwd = None;
+    let mut seen = 0;
+    let _ = for_each_session_line(path, |line| {
+        seen += 1;
+        if line.contains("\"cwd\"") {
+            cwd = serde_json::from_str::<serde_json::Value>(line)
+                .ok()
+                .and_then(|v| v["cwd"].as_str().map(str::to_string));
+        }
+        cwd.is_none() && seen < CWD_SCAN_LINES
+    });
+    cwd
+}
+
+/// What `survey` found.
+struct Survey {
+    usages: Vec<AttachmentUsage>,
+    errors: Vec<String>,
+    /// Every session was read to the end; only then is "unreferenced" safe
+    /// to act on.
+    complete: bool,
+}
+
+/// Every attachment file in `dirs` (plus each session's project dir), with
+/// the sessions referencing it. Hash-named files match on their content
+/// hash; older timestamp-named files match on their file name.
+fn survey(sessions: &[PathBuf], dirs: &[PathBuf]) -> Survey {
+    let mut errors = Vec::new();
+    let mut complete = true;
+
+    let mut all_dirs: BTreeSet<PathBuf> = dirs.iter().cloned().collect();
+    for session in sessions {
+        if let Some(cwd) = session_cwd(session) {
+            all_dirs.insert(Path::new(&cwd).join(".tokenicode").join("tmp"));
+        }
+    }
+
+    let mut usages: Vec<AttachmentUsage> = Vec::new();
+    let mut seen: BTreeSet<PathBuf> = BTreeSet::new();
+    for dir in &all_dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            let path = entry.path();
+            // Dotfiles are in-flight writes or bookkeeping, not attachments.
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let key = path.canonicalize().unwrap_or_else(|_| path.clone());
+            if !meta.is_file() || !seen.insert(key) {
+                continue;
+            }
+            let modified_ms = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0);
+            usages.push(AttachmentUsage {
+                path: path.to_string_lossy().to_string(),
+                size: meta.len(),
+                modified_ms,
+                sessions: Vec::new(),
+            });
+        }
+    }
+
+    // Index: content hash -> usages, legacy file name -> usages.
+    let mut by_hash: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, usage) in usages.iter().enumerate() {
+        let name = Path::new(&usage.path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        match stored_hash(&name) {
+            Some(hash) => by_hash.entry(hash.to_string()).or_default().push(i),
+            None => by_name.entry(name).or_default().push(i),
+        }
+    }
+    let hash_re = hash_regex();
+    let name_re = if by_name.is_empty() {
+        None
+    } else {
+        let mut names: Vec<&String> = by_name.keys().collect();
+        // Longest first, so a name is not shadowed by a shorter one it contains.
+        names.sort_by_key(|n| std::cmp::Reverse(n.len()));
+        let pattern = names
+            .iter()
+            .map(|n| regex::escape(n))
+            .collect::<Vec<_>>()
+            .join("|");
+        match Regex::new(&pattern) {
+            Ok(re) => Some(re),
+            Err(e) => {
+                // Without a matcher, treat every legacy file as referenced.
+                errors.push(format!("Failed to build attachment matcher: {}", e));
+                for i in by_name.values().flatten() {
+                    usages[*i].sessions.push("?".to_string());
+                }
+                None
+            }
+        }
+    };
+
+    let mut refs: BTreeMap<usize, BTreeSet<String>> = BTreeMap::new();
+    for session in sessions {
+        let id = session_id(session);
+        let read = for_each_session_line(session, |line| {
+            for cap in hash_re.captures_iter(line) {
+                for i in by_hash.get(&cap[1]).into_iter().flatten() {
+                    refs.entry(*i).or_default().insert(id.clone());
+                }
+            }
+            if let Some(re) = &name_re {
+                for m in re.find_iter(line) {
+                    for i in by_name.get(m.as_str()).into_iter().flatten() {
+                        refs.entry(*i).or_default().insert(id.clone());
+                    }
+                }
+            }
+            true
+        });
+        if let Err(e) = read {
+            errors.push(e);
+            complete = false;
+        }
+    }
+    for (i, ids) in refs {
+        usages[i].sessions.extend(ids);
+    }
+    usages.sort_by(|a, b| a.path.cmp(&b.path));
+    Survey {
+        usages,
+        errors,
+        complete,
+    }
+}
+
+/// Delete unreferenced attachments older than `min_age_ms`. When a session
+/// could not be read completely the run degrades to a dry run.
+fn collect(
+    sessions: &[PathBuf],
+    dirs: &[PathBuf],
+    min_age_ms: u64,
+    dry_run: bool,
+) -> AttachmentGcReport {
+    let Survey {
+        usages,
+        mut errors,
+        complete,
+    } = survey(sessions, dirs);
+    if !complete && !dry_run {
+        errors.push("Some sessions could not be read completely; nothing was deleted".into());
+    }
+    let dry_run = dry_run || !complete;
+    let mut report = AttachmentGcReport {
+        dry_run,
+        scanned: usages.len(),
+        errors,
+        ..Default::default()
+    };
+    let now = now_ms();
+    for usage in usages {
+        if !usage.sessions.is_empty() {
+            report.referenced += 1;
+            continue;
+        }
+        if now.saturating_sub(usage.modified_ms) < min_age_ms {
+            report.kept_recent += 1;
+            continue;
+        }
+        if !dry_run {
+            if let Err(e) = std::fs::remove_file(&usage.path) {
+                report
+                    .errors
+                    .push(format!("Failed to remove {}: {}", usage.path, e));
+                continue;
+            }
+        }
+        report.reclaimed_bytes += usage.size;
+        report.removed.push(usage.path);
+    }
+    report
+}
+
+/// Session files and attachment dirs of this machine.
+fn default_sources() -> Result<(Vec<PathBuf>, Vec<PathBuf>), String> {
+    let home = dirs::home_dir().ok_or("Cannot find home dir")?;
+    let mut sessions = Vec::new();
+    session_files(
+        &home.join(".claude").join("projects"),
+        MAX_SESSION_DEPTH,
+        &mut sessions,
+    );
+    session_files(
+        &home.join(".tokenicode").join("archive"),
+        MAX_SESSION_DEPTH,
+        &mut sessions,
+    );
+    let mut dirs = vec![system_attachment_dir()];
+    dirs.extend(
+        read_dirs(&crate::tokenicode_data_path(DIRS_FILE)?)
+            .into_iter()
+            .map(PathBuf::from),
+    );
+    Ok((sessions, dirs))
+}
+
+fn run_gc(min_age_days: u32, dry_run: bool) -> Result<AttachmentGcReport, String> {
+    let (sessions, dirs) = default_sources()?;
+    Ok(collect(
+        &sessions,
+        &dirs,
+        min_age_days as u64 * MS_PER_DAY,
+        dry_run,
+    ))
+}
+
+pub fn run_gc_at_startup() {
+    std::thread::spawn(|| {
+        let config = match read_config() {
+            Ok(c) if c.enabled => c,
+            Ok(_) => return,
+            Err(e) => {
+                eprintln!("[TOKENICODE] attachment GC skipped: {}", e);
+                return;
+            }
+        };
+        match run_gc(config.min_age_days, false) {
+            Ok(report) if !report.removed.is_empty() || !report.errors.is_empty() => eprintln!(
+                "[TOKENICODE] attachment GC: removed={} reclaimed={}B kept={} errors={}",
+                report.removed.len(),
+                report.reclaimed_bytes,
+                report.referenced + report.kept_recent,
+                report.errors.len()
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!("[TOKENICODE] attachment GC failed: {}", e),
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// Commands
+// ---------------------------------------------------------------------------
+
+#[tauri::command]
+pub async fn load_attachment_gc_config() -> Result<AttachmentGcConfig, String> {
+    read_config()
+}
+
+#[tauri::command]
+pub async fn save_attachment_gc_config(config: AttachmentGcConfig) -> Result<(), String> {
+    let path = config_path()?;
+    let content = serde_json::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialize attachment GC config: {}", e))?;
+    std::fs::write(&path, content)
+        .map_err(|e| format!("Failed to write attachment GC config: {}", e))
+}
+
+/// Delete unreferenced attachments older than `min_age_days` (default from
+/// the config). With `dry_run`, only report what would be removed.
+#[tauri::command]
+pub async fn collect_attachment_garbage(
+    min_age_days: Option<u32>,
+    dry_run: Option<bool>,
+) -> Result<AttachmentGcReport, String> {
+    let min_age_days = match min_age_days {
+        Some(days) => days,
+        None => read_config()?.min_age_days,
+    };
+    tokio::task::spawn_blocking(move || run_gc(min_age_days, dry_run.unwrap_or(false)))
+        .await
+        .map_err(|e| format!("Attachment GC task failed: {}", e))?
+}
+
+/// Every attachment file and the sessions referencing it.
+#[tauri::command]
+pub async fn list_attachment_usage() -> Result<Vec<AttachmentUsage>, String> {
+    tokio::task::spawn_blocking(|| {
+        let (sessions, dirs) = default_sources()?;
+        Ok(survey(&sessions, &dirs).usages)
+    })
+    .await
+    .map_err(|e| format!("Attachment survey failed: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::attachments::{store_attachment, DIR_QUOTA_BYTES};
+    use super::*;
+    use std::io::Write;
+
+    fn session_line(cwd: &Path, text: &str) -> String {
+        serde_json::json!({
+            "type": "user",
+            "cwd": cwd.to_string_lossy(),
+            "message": { "content": text },
+        })
+        .to_string()
+            + "\n"
+    }
+
+    fn backdate(path: &Path, days: u64) {
+        let when = std::time::SystemTime::now() - std::time::Duration::from_secs(days * 86_400);
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(when)
+            .unwrap();
+    }
+
+    #[test]
+    fn survey_tracks_sessions_by_hash_and_legacy_name() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path().join("project");
+        let dir = project.join(".tokenicode").join("tmp");
+        std::fs::create_dir_all(&dir).unwrap();
+        let hashed = store_attachment(&dir, "shot.txt", b"pixels", DIR_QUOTA_BYTES).unwrap();
+        let legacy = dir.join("image_1700000000000.png");
+        std::fs::write(&legacy, b"old").unwrap();
+
+        let projects = tmp.path().join("projects");
+        std::fs::create_dir_all(projects.join("p/s1/subagents")).unwrap();
+        let s1 = projects.join("p/s1.jsonl");
+        std::fs::write(&s1, session_line(&project, &hashed.path)).unwrap();
+        // A subagent transcript and a gzipped archive count as well.
+        std::fs::write(
+            projects.join("p/s1/subagents/agent-1.jsonl"),
+            session_line(&project, "image_1700000000000.png"),
+        )
+        .unwrap();
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        gz.write_all(session_line(&project, &hashed.path).as_bytes())
+            .unwrap();
+        std::fs::write(projects.join("p/s2.jsonl.gz"), gz.finish().unwrap()).unwrap();
+
+        let mut sessions = Vec::new();
+        session_files(&projects, MAX_SESSION_DEPTH, &mut sessions);
+        assert_eq!(sessions.len(), 3);
+        // No explicit dirs: the project dir is found through the sessions' cwd.
+        let Survey {
+            usages,
+            errors,
+            complete,
+        } = survey(&sessions, &[]);
+        assert!(errors.is_empty() && complete, "{:?}", errors);
+        let by_path: HashMap<_, _> = usages.iter().map(|u| (u.path.clone(), u)).collect();
+        assert_eq!(by_path[&hashed.path].sessions, vec!["s1", "s2"]);
+        assert_eq!(
+            by_path[&legacy.to_string_lossy().to_string()].sessions,
+            vec!["agent-1"]
+        );
+    }
+
+    #[test]
+    fn collect_removes_only_old_unreferenced_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path().join("project");
+        let dir = project.join(".tokenicode").join("tmp");
+        std::fs::create_dir_all(&dir).unwrap();
+        let used = store_attachment(&dir, "used.txt", b"used", DIR_QUOTA_BYTES).unwrap();
+        let stale = store_attachment(&dir, "stale.txt", b"stale!", DIR_QUOTA_BYTES).unwrap();
+        let fresh = store_attachment(&dir, "fresh.txt", b"fresh", DIR_QUOTA_BYTES).unwrap();
+        for p in [&used.path, &stale.path] {
+            backdate(Path::new(p), 30);
+        }
+        let session = tmp.path().join("s.jsonl");
+        std::fs::write(&session, session_line(&project, &used.path)).unwrap();
+        let sessions = vec![session];
+        let dirs = vec![dir.clone()];
+
+        let dry = collect(&sessions, &dirs, 7 * MS_PER_DAY, true);
+        assert_eq!(dry.removed, vec![stale.path.clone()]);
+        assert!(Path::new(&stale.path).exists());
+
+        let report = collect(&sessions, &dirs, 7 * MS_PER_DAY, false);
+        assert_eq!(
+            (report.scanned, report.referenced, report.kept_recent),
+            (3, 1, 1)
+        );
+        assert_eq!(report.removed, vec![stale.path.clone()]);
+        assert_eq!(report.reclaimed_bytes, 6);
+        assert!(!Path::new(&stale.path).exists());
+        assert!(Path::new(&used.path).exists() && Path::new(&fresh.path).exists());
+    }
+
+    #[test]
+    fn unreadable_session_blocks_deletion() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path().join("project");
+        let dir = project.join(".tokenicode").join("tmp");
+        std::fs::create_dir_all(&dir).unwrap();
+        let used = store_attachment(&dir, "used.txt", b"used", DIR_QUOTA_BYTES).unwrap();
+        let stale = store_attachment(&dir, "stale.txt", b"stale", DIR_QUOTA_BYTES).unwrap();
+        for p in [&used.path, &stale.path] {
+            backdate(Path::new(p), 30);
+        }
+        // Invalid UTF-8 before the reference must not end the scan.
+        let mut bytes = b"\xff\xfe not utf-8\n".to_vec();
+        bytes.extend(session_line(&project, &used.path).as_bytes());
+        let readable = tmp.path().join("ok.jsonl");
+        std::fs::write(&readable, bytes).unwrap();
+        // A corrupt archive: whatever it referenced is unknown.
+        let corrupt = tmp.path().join("broken.jsonl.gz");
+        std::fs::write(&corrupt, b"not gzip at all").unwrap();
+        let dirs = vec![dir.clone()];
+
+        let report = collect(&[readable.clone(), corrupt], &dirs, 7 * MS_PER_DAY, false);
+        assert!(report.dry_run);
+        assert_eq!(report.errors.len(), 2, "{:?}", report.errors);
+        assert_eq!(report.removed, vec![stale.path.clone()]);
+        assert!(Path::new(&stale.path).exists());
+
+        let report = collect(&[readable], &dirs, 7 * MS_PER_DAY, false);
+        assert!(!report.dry_run && report.errors.is_empty());
+        assert_eq!((report.referenced, report.removed.len()), (1, 1));
+        assert!(Path::new(&used.path).exists() && !Path::new(&stale.path).exists());
+    }
+}
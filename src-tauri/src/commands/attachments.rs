@@ -0,0 +1,1049 @@
+//! Attachment pipeline for files pasted or dropped into the input bar.
+//!
+//! `save_attachment` stores the file where the CLI can read it (the
+//! project's `.tokenicode/tmp`, or the system temp `tokenicode` dir) and
+//! returns metadata for the chip and the message:
+//!
+//! - the type comes from magic bytes, not the (often missing) file name;
+//! - images bigger than the model uses are downscaled before storing;
+//! - PDF and DOCX text is extracted for the preview;
+//! - content is deduplicated by SHA-256, so the same screenshot pasted twice
+//!   is stored once;
+//! - per-file and per-directory size quotas are enforced.
+//!
+//! Stored names embed the first 16 hex digits of the content hash
+//! (`<stem>-<hash>.<ext>`). That is also how references are found again:
+//! deleting a session scans its JSONL for those hashes and removes the
+//! attachments that no other session of the project mentions, and
+//! `attachment_gc` sweeps up whatever is left unreferenced.
+
+use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Largest single attachment accepted.
+const MAX_ATTACHMENT_BYTES: usize = 50 * 1024 * 1024;
+/// Total size of one attachment directory.
+pub(crate) const DIR_QUOTA_BYTES: u64 = 1024 * 1024 * 1024;
+/// Long edge images are scaled down to; larger images only cost tokens.
+const MAX_IMAGE_EDGE: u32 = 1568;
+/// Per-image size limit of the API.
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+const JPEG_QUALITY: u8 = 85;
+const PREVIEW_CHARS: usize = 2000;
+/// Cap on decompressed PDF streams / DOCX XML read for a preview.
+const MAX_EXTRACT_BYTES: u64 = 16 * 1024 * 1024;
+/// Hex digits of the SHA-256 kept in stored names.
+const HASH_PREFIX_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentKind {
+    Image,
+    Pdf,
+    Document,
+    Text,
+    Archive,
+    Binary,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AttachmentInfo {
+    /// Stored path, as passed to the CLI.
+    pub path: String,
+    /// Name the user attached it under.
+    pub name: String,
+    pub mime: String,
+    pub kind: AttachmentKind,
+    /// Stored size (after downscaling).
+    pub size: u64,
+    pub original_size: u64,
+    pub sha256: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub downscaled: bool,
+    /// An identical file was already stored and is reused.
+    pub deduplicated: bool,
+    /// Start of the text content, for PDF, DOCX and text files.
+    pub preview_text: Option<String>,
+}
+
+struct Detected {
+    mime: &'static str,
+    kind: AttachmentKind,
+    /// `None` keeps the extension of the original name.
+    ext: Option<&'static str>,
+}
+
+/// Directory attachments for `cwd` are stored in: `<cwd>/.tokenicode/tmp`
+/// (kept out of git), or the system temp `tokenicode` dir without a cwd.
+pub(crate) fn attachment_dir(cwd: Option<&str>) -> Result<PathBuf, String> {
+    let tmp = match cwd {
+        Some(dir) => {
+            let base = PathBuf::from(dir).join(".tokenicode");
+            let p = base.join("tmp");
+            if std::fs::create_dir_all(&p).is_ok() {
+                // Ensure .tokenicode is gitignored in user's project
+                let gitignore = base.join(".gitignore");
+                if !gitignore.exists() {
+                    let _ = std::fs::write(&gitignore, "*\n");
+                }
+                p
+            } else {
+                std::env::temp_dir().join("tokenicode")
+            }
+        }
+        None => std::env::temp_dir().join("tokenicode"),
+    };
+    std::fs::create_dir_all(&tmp).map_err(|e| format!("Failed to create temp dir: {}", e))?;
+    Ok(tmp)
+}
+
+// ---------------------------------------------------------------------------
+// Type detection
+// ---------------------------------------------------------------------------
+
+fn detect(bytes: &[u8], name: &str) -> Detected {
+    use AttachmentKind::*;
+    let found = |mime, kind, ext| Detected {
+        mime,
+        kind,
+        ext: Some(ext),
+    };
+    let starts = |sig: &[u8]| bytes.starts_with(sig);
+
+    if starts(b"\x89PNG\r\n\x1a\n") {
+        return found("image/png", Image, "png");
+    }
+    if starts(b"\xFF\xD8\xFF") {
+        return found("image/jpeg", Image, "jpg");
+    }
+    if starts(b"GIF87a") || starts(b"GIF89a") {
+        return found("image/gif", Image, "gif");
+    }
+    if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return found("image/webp", Image, "webp");
+    }
+    // BMP: "BM", then four reserved zero bytes at offset 6.
+    if bytes.len() > 26 && starts(b"BM") && bytes[6..10] == [0, 0, 0, 0] {
+        return found("image/bmp", Image, "bmp");
+    }
+    if starts(b"%PDF-") {
+        return found("application/pdf", Pdf, "pdf");
+    }
+    if starts(b"PK\x03\x04") {
+        return detect_zip(bytes);
+    }
+    if starts(b"\x1f\x8b") {
+        return found("application/gzip", Archive, "gz");
+    }
+
+    let sample = &bytes[..bytes.len().min(8192)];
+    let is_text = !sample.contains(&0)
+        && match std::str::from_utf8(sample) {
+            Ok(_) => true,
+            // A multi-byte character cut by the sample boundary is fine.
+            Err(e) => e.error_len().is_none(),
+        };
+    if !is_text {
+        return Detected {
+            mime: "application/octet-stream",
+            kind: Binary,
+            ext: None,
+        };
+    }
+    let head = String::from_utf8_lossy(&sample[..sample.len().min(1024)]).to_lowercase();
+    if head.contains("<svg") {
+        return found("image/svg+xml", Image, "svg");
+    }
+    let ext = Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mime = match ext.as_str() {
+        "md" | "markdown" => "text/markdown",
+        "json" => "application/json",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "xml" => "application/xml",
+        _ => "text/plain",
+    };
+    Detected {
+        mime,
+        kind: Text,
+        ext: None,
+    }
+}
+
+/// ZIP containers: tell Office documents apart by their part names.
+fn detect_zip(bytes: &[u8]) -> Detected {
+    let names: Vec<String> = zip::ZipArchive::new(Cursor::new(bytes))
+        .map(|a| a.file_names().map(str::to_string).collect())
+        .unwrap_or_default();
+    let has = |prefix: &str| names.iter().any(|n| n.starts_with(prefix));
+    let (mime, kind, ext) = if has("word/document.xml") {
+        (
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            AttachmentKind::Document,
+            "docx",
+        )
+    } else if has("xl/") {
+        (
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            AttachmentKind::Document,
+            "xlsx",
+        )
+    } else if has("ppt/") {
+        (
+            "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+            AttachmentKind::Document,
+            "pptx",
+        )
+    } else {
+        ("application/zip", AttachmentKind::Archive, "zip")
+    };
+    Detected {
+        mime,
+        kind,
+        ext: Some(ext),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Images
+// ---------------------------------------------------------------------------
+
+struct Downscaled {
+    bytes: Vec<u8>,
+    mime: &'static str,
+    ext: &'static str,
+    width: u32,
+    height: u32,
+}
+
+fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    image::ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+/// Shrink an image whose long edge exceeds `MAX_IMAGE_EDGE` or whose size
+/// exceeds `MAX_IMAGE_BYTES`. PNG stays PNG (keeping transparency) unless it
+/// is still too big; everything else becomes JPEG. `None` when the image
+/// needs no change or cannot be decoded (it is then stored as is).
+fn downscale_image(bytes: &[u8], mime: &str, (w, h): (u32, u32)) -> Option<Downscaled> {
+    if w.max(h) <= MAX_IMAGE_EDGE && bytes.len() <= MAX_IMAGE_BYTES {
+        return None;
+    }
+    let img = image::load_from_memory(bytes).ok()?;
+    let scale = (MAX_IMAGE_EDGE as f64 / w.max(h) as f64).min(1.0);
+    let resized = if scale < 1.0 {
+        let nw = ((w as f64 * scale).round() as u32).max(1);
+        let nh = ((h as f64 * scale).round() as u32).max(1);
+        img.resize(nw, nh, image::imageops::FilterType::Lanczos3)
+    } else {
+        img
+    };
+    let (width, height) = (resized.width(), resized.height());
+
+    if mime == "image/png" {
+        let mut png = Cursor::new(Vec::new());
+        if resized.write_to(&mut png, image::ImageFormat::Png).is_ok()
+            && png.get_ref().len() <= MAX_IMAGE_BYTES
+        {
+            return Some(Downscaled {
+                bytes: png.into_inner(),
+                mime: "image/png",
+                ext: "png",
+                width,
+                height,
+            });
+        }
+    }
+    let mut jpeg = Vec::new();
+    image::DynamicImage::ImageRgb8(resized.to_rgb8())
+        .write_with_encoder(image::codecs::jpeg::JpegEncoder::new_with_quality(
+            &mut jpeg,
+            JPEG_QUALITY,
+        ))
+        .ok()?;
+    Some(Downscaled {
+        bytes: jpeg,
+        mime: "image/jpeg",
+        ext: "jpg",
+        width,
+        height,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Text extraction
+// ---------------------------------------------------------------------------
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| i + from)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    find(haystack, needle, 0).is_some()
+}
+
+/// Best-effort PDF text: inflate the content streams and collect the
+/// strings shown by text operators. Fine for a preview of ordinary
+/// documents; PDFs using embedded CID fonts yield little or nothing.
+fn pdf_text(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    let mut pos = 0;
+    while out.chars().count() < PREVIEW_CHARS {
+        let Some(start) = find(bytes, b"stream", pos) else {
+            break;
+        };
+        pos = start + 6;
+        if bytes[..start].ends_with(b"end") {
+            continue;
+        }
+        let mut data_start = start + 6;
+        if bytes.get(data_start) == Some(&b'\r') {
+            data_start += 1;
+        }
+        if bytes.get(data_start) == Some(&b'\n') {
+            data_start += 1;
+        }
+        let Some(end) = find(bytes, b"endstream", data_start) else {
+            break;
+        };
+        pos = end + 9;
+        // The stream dictionary sits between "N 0 obj" and "stream".
+        let dict_start = bytes[..start]
+            .windows(3)
+            .rposition(|w| w == b"obj")
+            .unwrap_or(0);
+        let dict = &bytes[dict_start..start];
+        let raw = &bytes[data_start..end];
+        let content = if contains(dict, b"/FlateDecode") {
+            let mut inflated = Vec::new();
+            // Keep whatever inflated before an error.
+            let _ = flate2::read::ZlibDecoder::new(raw)
+                .take(MAX_EXTRACT_BYTES)
+                .read_to_end(&mut inflated);
+            inflated
+        } else if contains(dict, b"/Filter") {
+            continue;
+        } else {
+            raw.to_vec()
+        };
+        if contains(&content, b"BT") {
+            collect_pdf_strings(&content, &mut out);
+        }
+    }
+    out
+}
+
+fn is_pdf_delimiter(b: u8) -> bool {
+    b.is_ascii_whitespace() || b"()<>[]{}/%".contains(&b)
+}
+
+/// Walk a content stream, appending the text of `Tj`/`TJ`/`'`/`"` and
+/// turning line moves into newlines.
+fn collect_pdf_strings(c: &[u8], out: &mut String) {
+    let mut pending = String::new();
+    let mut nums: Vec<f64> = Vec::new();
+    let mut i = 0;
+    while i < c.len() {
+        match c[i] {
+            b'(' => {
+                let (raw, next) = pdf_literal(c, i + 1);
+                pending.push_str(&decode_pdf_string(&raw));
+                i = next;
+            }
+            b'<' if c.get(i + 1) == Some(&b'<') => i += 2,
+            b'<' => {
+                let end = find(c, b">", i).unwrap_or(c.len());
+                let hex: Vec<u8> = c[i + 1..end]
+                    .iter()
+                    .filter(|b| b.is_ascii_hexdigit())
+                    .copied()
+                    .collect();
+                let raw: Vec<u8> = hex
+                    .chunks(2)
+                    .filter_map(|pair| {
+                        let s = std::str::from_utf8(pair).ok()?;
+                        u8::from_str_radix(&format!("{:0<2}", s), 16).ok()
+                    })
+                    .collect();
+                pending.push_str(&decode_pdf_string(&raw));
+                i = end + 1;
+            }
+            b'%' => {
+                while i < c.len() && c[i] != b'\n' && c[i] != b'\r' {
+                    i += 1;
+                }
+            }
+            b if is_pdf_delimiter(b) => i += 1,
+            _ => {
+                let start = i;
+                while i < c.len() && !is_pdf_delimiter(c[i]) {
+                    i += 1;
+                }
+                let token = &c[start..i];
+                if let Some(n) = std::str::from_utf8(token)
+                    .ok()
+                    .and_then(|t| t.parse::<f64>().ok())
+                {
+                    // Inside TJ arrays a large negative kern is a word gap.
+                    if n < -180.0 && !pending.is_empty() {
+                        pending.push(' ');
+                    }
+                    nums.push(n);
+                    continue;
+                }
+                match token {
+                    b"Tj" | b"TJ" => out.push_str(&pending),
+                    b"'" | b"\"" => {
+                        out.push('\n');
+                        out.push_str(&pending);
+                    }
+                    b"T*" | b"Tm" | b"ET" => out.push('\n'),
+                    b"Td" | b"TD" => {
+                        let moves_down = nums.last().is_some_and(|ty| *ty != 0.0);
+                        out.push(if moves_down { '\n' } else { ' ' });
+                    }
+                    _ => {}
+                }
+                pending.clear();
+                nums.clear();
+            }
+        }
+    }
+}
+
+/// Bytes of a literal string starting after its `(`, and the index after
+/// its closing `)`.
+fn pdf_literal(c: &[u8], mut i: usize) -> (Vec<u8>, usize) {
+    let mut buf = Vec::new();
+    let mut depth = 1;
+    while i < c.len() {
+        match c[i] {
+            b'\\' => {
+                i += 1;
+                match c.get(i) {
+                    Some(b'n') => buf.push(b'\n'),
+                    Some(b'r') => buf.push(b'\r'),
+                    Some(b't') => buf.push(b'\t'),
+                    Some(b'b') => buf.push(8),
+                    Some(b'f') => buf.push(12),
+                    Some(d @ b'0'..=b'7') => {
+                        let mut value = (d - b'0') as u32;
+                        for _ in 0..2 {
+                            match c.get(i + 1) {
+                                Some(d @ b'0'..=b'7') => {
+                                    value = value * 8 + (d - b'0') as u32;
+                                    i += 1;
+                                }
+                                _ => break,
+                            }
+                        }
+                        buf.push(value as u8);
+                    }
+                    // Line continuation.
+                    Some(b'\r') => {
+                        if c.get(i + 1) == Some(&b'\n') {
+                            i += 1;
+                        }
+                    }
+                    Some(b'\n') => {}
+                    Some(&other) => buf.push(other),
+                    None => break,
+                }
+            }
+            b'(' => {
+                depth += 1;
+                buf.push(b'(');
+            }
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return (buf, i + 1);
+                }
+                buf.push(b')');
+            }
+            b => buf.push(b),
+        }
+        i += 1;
+    }
+    (buf, i)
+}
+
+/// UTF-16BE with a BOM, otherwise bytes as Latin-1 (close enough to
+/// PDFDocEncoding for a preview).
+fn decode_pdf_string(raw: &[u8]) -> String {
+    if raw.starts_with(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = raw[2..]
+            .chunks_exact(2)
+            .map(|p| u16::from_be_bytes([p[0], p[1]]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    raw.iter()
+        .map(|&b| b as char)
+        .filter(|c| !c.is_control() || c.is_whitespace())
+        .collect()
+}
+
+/// Paragraph text of a DOCX (`word/document.xml`).
+fn docx_text(bytes: &[u8]) -> Option<String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).ok()?;
+    let mut xml = String::new();
+    archive
+        .by_name("word/document.xml")
+        .ok()?
+        .take(MAX_EXTRACT_BYTES)
+        .read_to_string(&mut xml)
+        .ok()?;
+    Some(wordml_to_text(&xml))
+}
+
+fn wordml_to_text(xml: &str) -> String {
+    let mut out = String::new();
+    let mut rest = xml;
+    while let Some(lt) = rest.find('<') {
+        out.push_str(&decode_entities(&rest[..lt]));
+        let Some(gt) = rest[lt..].find('>') else {
+            break;
+        };
+        let tag = rest[lt + 1..lt + gt].trim_end_matches('/');
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(t) => (true, t),
+            None => (false, tag),
+        };
+        let name = tag.split_whitespace().next().unwrap_or("");
+        match (closing, name) {
+            (true, "w:p") => out.push('\n'),
+            (false, "w:tab") => out.push('\t'),
+            (false, "w:br") | (false, "w:cr") => out.push('\n'),
+            _ => {}
+        }
+        rest = &rest[lt + gt + 1..];
+    }
+    out
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        let tail = &rest[amp..];
+        let Some(semi) = tail.find(';').filter(|&s| s <= 10) else {
+            out.push('&');
+            rest = &tail[1..];
+            continue;
+        };
+        let entity = &tail[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(ch) => {
+                out.push(ch);
+                rest = &tail[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Trim trailing spaces, squeeze blank-line runs and cut to
+/// `PREVIEW_CHARS`. `None` when nothing readable is left.
+fn tidy_preview(text: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut blank = false;
+    for line in text.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            blank = !out.is_empty();
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str(if blank { "\n\n" } else { "\n" });
+        }
+        blank = false;
+        out.push_str(line);
+        if out.chars().count() >= PREVIEW_CHARS {
+            break;
+        }
+    }
+    if out.is_empty() {
+        return None;
+    }
+    Some(out.chars().take(PREVIEW_CHARS).collect())
+}
+
+fn preview_for(kind: AttachmentKind, ext: &str, bytes: &[u8]) -> Option<String> {
+    match kind {
+        AttachmentKind::Pdf => tidy_preview(&pdf_text(bytes)),
+        AttachmentKind::Document if ext == "docx" => tidy_preview(&docx_text(bytes)?),
+        AttachmentKind::Text => {
+            let head = &bytes[..bytes.len().min(PREVIEW_CHARS * 4)];
+            tidy_preview(&String::from_utf8_lossy(head))
+        }
+        _ => None,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Storage
+// ---------------------------------------------------------------------------
+
+fn stored_stem(name: &str) -> String {
+    let stem = Path::new(name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let clean: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(60)
+        .collect();
+    if clean.is_empty() {
+        "attachment".to_string()
+    } else {
+        clean
+    }
+}
+
+fn original_ext(name: &str) -> Option<String> {
+    let ext = Path::new(name)
+        .extension()?
+        .to_string_lossy()
+        .to_lowercase();
+    let valid =
+        !ext.is_empty() && ext.len() <= 10 && ext.chars().all(|c| c.is_ascii_alphanumeric());
+    valid.then_some(ext)
+}
+
+pub(crate) fn hash_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"-([0-9a-f]{16})\.[A-Za-z0-9]+").unwrap())
+}
+
+/// Content-hash prefix embedded in a stored attachment name.
+pub(crate) fn stored_hash(file_name: &str) -> Option<&str> {
+    hash_regex()
+        .captures(file_name)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str())
+}
+
+fn dir_usage(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| e.metadata().ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn find_stored(dir: &Path, hash_prefix: &str) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .find(|p| p.file_name().and_then(|n| n.to_str()).and_then(stored_hash) == Some(hash_prefix))
+}
+
+/// Store `data` (attached as `name`) in `dir` and describe it.
+pub(crate) fn store_attachment(
+    dir: &Path,
+    name: &str,
+    data: &[u8],
+    quota: u64,
+) -> Result<AttachmentInfo, String> {
+    if data.len() > MAX_ATTACHMENT_BYTES {
+        return Err(format!(
+            "Attachment too large ({} MB, limit {} MB)",
+            data.len() / (1024 * 1024),
+            MAX_ATTACHMENT_BYTES / (1024 * 1024)
+        ));
+    }
+    let sha256 = format!("{:x}", Sha256::digest(data));
+    let prefix = &sha256[..HASH_PREFIX_LEN];
+    let detected = detect(data, name);
+    let mut mime = detected.mime;
+    let mut ext = detected
+        .ext
+        .map(str::to_string)
+        .or_else(|| original_ext(name))
+        .unwrap_or_else(|| {
+            let fallback = if detected.kind == AttachmentKind::Text {
+                "txt"
+            } else {
+                "bin"
+            };
+            fallback.to_string()
+        });
+
+    let dims = if detected.kind == AttachmentKind::Image {
+        image_dimensions(data)
+    } else {
+        None
+    };
+
+    if let Some(existing) = find_stored(dir, prefix) {
+        let stored = std::fs::read(&existing)
+            .map_err(|e| format!("Failed to read stored attachment: {}", e))?;
+        let stored_ext = existing
+            .extension()
+            .map(|e| e.to_string_lossy().to_string())
+            .unwrap_or(ext);
+        let stored_dims = dims.and(image_dimensions(&stored));
+        let downscaled = stored_dims.is_some() && stored_dims != dims;
+        if downscaled && stored_ext == "jpg" {
+            mime = "image/jpeg";
+        }
+        return Ok(AttachmentInfo {
+            path: existing.to_string_lossy().to_string(),
+            name: name.to_string(),
+            mime: mime.to_string(),
+            kind: detected.kind,
+            size: stored.len() as u64,
+            original_size: data.len() as u64,
+            sha256,
+            width: stored_dims.map(|d| d.0),
+            height: stored_dims.map(|d| d.1),
+            downscaled,
+            deduplicated: true,
+            preview_text: preview_for(detected.kind, &stored_ext, &stored),
+        });
+    }
+
+    let mut stored: &[u8] = data;
+    let mut size_px = dims;
+    let downscaled = match dims {
+        Some(d)
+            if matches!(
+                mime,
+                "image/png" | "image/jpeg" | "image/webp" | "image/bmp"
+            ) =>
+        {
+            downscale_image(data, mime, d)
+        }
+        _ => None,
+    };
+    if let Some(d) = &downscaled {
+        stored = &d.bytes;
+        mime = d.mime;
+        ext = d.ext.to_string();
+        size_px = Some((d.width, d.height));
+    }
+
+    let used = dir_usage(dir);
+    if used + stored.len() as u64 > quota {
+        return Err(format!(
+            "Attachment storage is full ({} MB of {} MB used); delete old sessions to free space",
+            used / (1024 * 1024),
+            quota / (1024 * 1024)
+        ));
+    }
+
+    let path = dir.join(format!("{}-{}.{}", stored_stem(name), prefix, ext));
+    super::safe_write::atomic_write(&path, stored)?;
+
+    Ok(AttachmentInfo {
+        path: path.to_string_lossy().to_string(),
+        name: name.to_string(),
+        mime: mime.to_string(),
+        kind: detected.kind,
+        size: stored.len() as u64,
+        original_size: data.len() as u64,
+        sha256,
+        width: size_px.map(|d| d.0),
+        height: size_px.map(|d| d.1),
+        downscaled: downscaled.is_some(),
+        deduplicated: false,
+        preview_text: preview_for(detected.kind, &ext, stored),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Session cleanup
+// ---------------------------------------------------------------------------
+
+/// Attachment hashes mentioned anywhere in a session JSONL (plain or
+/// gzipped), plus the session's cwd (from the first line that records one).
+/// Fails unless the whole file was read.
+pub(crate) fn scan_session_references(
+    session_file: &Path,
+) -> Result<(HashSet<String>, Option<String>), String> {
+    let mut hashes = HashSet::new();
+    let mut cwd = None;
+    super::attachment_gc::for_each_session_line(session_file, |line| {
+        if cwd.is_none() && line.contains("\"cwd\"") {
+            cwd = serde_json::from_str::<serde_json::Value>(line)
+                .ok()
+                .and_then(|v| v["cwd"].as_str().map(str::to_string));
+        }
+        for cap in hash_regex().captures_iter(line) {
+            hashes.insert(cap[1].to_string());
+        }
+        true
+    })?;
+    Ok((hashes, cwd))
+}
+
+/// Session files in `dir` with extension suffix `suffix`; a missing dir has
+/// none.
+fn sessions_in(dir: &Path, suffix: &str) -> Result<Vec<PathBuf>, String> {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => Ok(entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.to_string_lossy().ends_with(suffix))
+            .collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read {}: {}", dir.display(), e)),
+    }
+}
+
+/// Before a session is deleted: remove the attachments in its project's
+/// `.tokenicode/tmp` that it references and no other session of the same
+/// project does, archived ones included. Returns the number of files and
+/// bytes removed. Nothing is removed unless every other session could be
+/// read. Files in the shared system temp dir are left to the attachment GC.
+pub(crate) fn release_session_attachments(session_file: &Path) -> Result<(usize, u64), String> {
+    let archive_dir = match session_file.parent().and_then(|p| p.file_name()) {
+        Some(project) => Some(super::session_retention::archive_root()?.join(project)),
+        None => None,
+    };
+    release_attachments(session_file, archive_dir.as_deref())
+}
+
+fn release_attachments(
+    session_file: &Path,
+    archive_dir: Option<&Path>,
+) -> Result<(usize, u64), String> {
+    let (mut hashes, cwd) = scan_session_references(session_file)?;
+    let Some(cwd) = cwd else {
+        return Ok((0, 0));
+    };
+    if hashes.is_empty() {
+        return Ok((0, 0));
+    }
+
+    let mut siblings = match session_file.parent() {
+        Some(project_dir) => sessions_in(project_dir, ".jsonl")?,
+        None => Vec::new(),
+    };
+    if let Some(archive) = archive_dir {
+        siblings.extend(sessions_in(archive, ".jsonl.gz")?);
+    }
+    for path in siblings.iter().filter(|p| p.as_path() != session_file) {
+        let (others, _) = scan_session_references(path)?;
+        hashes.retain(|h| !others.contains(h));
+        if hashes.is_empty() {
+            return Ok((0, 0));
+        }
+    }
+
+    let dir = Path::new(&cwd).join(".tokenicode").join("tmp");
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Ok((0, 0));
+    };
+    let (mut files, mut bytes) = (0, 0);
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !stored_hash(&name).is_some_and(|h| hashes.contains(h)) {
+            continue;
+        }
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        if std::fs::remove_file(entry.path()).is_ok() {
+            files += 1;
+            bytes += size;
+        }
+    }
+    Ok((files, bytes))
+}
+
+// ---------------------------------------------------------------------------
+// Commands
+// ---------------------------------------------------------------------------
+
+/// Store an attachment for the CLI and describe it (type, dimensions,
+/// preview text). Identical content is stored once.
+#[tauri::command]
+pub async fn save_attachment(
+    name: String,
+    data: Vec<u8>,
+    cwd: Option<String>,
+) -> Result<AttachmentInfo, String> {
+    let dir = attachment_dir(cwd.as_deref())?;
+    super::attachment_gc::remember_attachment_dir(&dir);
+    tokio::task::spawn_blocking(move || store_attachment(&dir, &name, &data, DIR_QUOTA_BYTES))
+        .await
+        .map_err(|e| format!("Failed to save attachment: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn sample_pdf() -> Vec<u8> {
+        let content: &[u8] = b"BT /F1 12 Tf 72 720 Td (Quarterly \\(draft\\)) Tj 0 -14 Td \
+            [(Re) 20 (venue) -300 (up)] TJ ET";
+        let mut compressed = flate2::write::ZlibEncoder::new(Vec::new(), Default::default());
+        compressed.write_all(content).unwrap();
+        let stream = compressed.finish().unwrap();
+        let mut pdf =
+            b"%PDF-1.4\n4 0 obj\n<< /Length 99 /Filter /FlateDecode >>\nstream\n".to_vec();
+        pdf.extend_from_slice(&stream);
+        pdf.extend_from_slice(b"\nendstream\nendobj\n%%EOF\n");
+        pdf
+    }
+
+    fn sample_docx() -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("[Content_Types].xml", options).unwrap();
+        zip.write_all(b"<Types/>").unwrap();
+        zip.start_file("word/document.xml", options).unwrap();
+        zip.write_all(
+            b"<w:document><w:body><w:p><w:r><w:t>Fish &amp; chips</w:t></w:r></w:p>\
+              <w:p><w:r><w:t>a</w:t><w:tab/><w:t>b</w:t></w:r></w:p></w:body></w:document>",
+        )
+        .unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn store_detects_type_extracts_text_and_dedupes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        // The misleading name does not matter; magic bytes do.
+        let pdf = store_attachment(dir, "report.png", &sample_pdf(), DIR_QUOTA_BYTES).unwrap();
+        assert_eq!(pdf.kind, AttachmentKind::Pdf);
+        assert_eq!(pdf.mime, "application/pdf");
+        assert!(pdf
+            .path
+            .ends_with(&format!("report-{}.pdf", &pdf.sha256[..16])));
+        assert_eq!(
+            pdf.preview_text.as_deref(),
+            Some("Quarterly (draft)\nRevenue up")
+        );
+
+        let docx = store_attachment(dir, "menu.docx", &sample_docx(), DIR_QUOTA_BYTES).unwrap();
+        assert_eq!(docx.kind, AttachmentKind::Document);
+        assert_eq!(docx.preview_text.as_deref(), Some("Fish & chips\na\tb"));
+
+        // Same content under another name reuses the stored file.
+        let again = store_attachment(dir, "copy.pdf", &sample_pdf(), DIR_QUOTA_BYTES).unwrap();
+        assert!(again.deduplicated);
+        assert_eq!(again.path, pdf.path);
+        assert_eq!(std::fs::read_dir(dir).unwrap().count(), 2);
+
+        let text = store_attachment(
+            dir,
+            "notes",
+            "héllo\n\n\n\nworld  \n".as_bytes(),
+            DIR_QUOTA_BYTES,
+        )
+        .unwrap();
+        assert_eq!(text.kind, AttachmentKind::Text);
+        assert!(text.path.ends_with(".txt"));
+        assert_eq!(text.preview_text.as_deref(), Some("héllo\n\nworld"));
+
+        let used = dir_usage(dir);
+        let err = store_attachment(dir, "big.txt", b"more text", used + 4).unwrap_err();
+        assert!(err.contains("storage is full"), "{}", err);
+    }
+
+    #[test]
+    fn releasing_a_session_keeps_attachments_other_sessions_use() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path().join("project");
+        let sessions = tmp.path().join("sessions");
+        std::fs::create_dir_all(&sessions).unwrap();
+        let dir = attachment_dir(Some(project.to_str().unwrap())).unwrap();
+
+        let only_a = store_attachment(&dir, "a.txt", b"only in a", DIR_QUOTA_BYTES).unwrap();
+        let shared = store_attachment(&dir, "s.txt", b"shared", DIR_QUOTA_BYTES).unwrap();
+        let archived = store_attachment(&dir, "r.txt", b"archived", DIR_QUOTA_BYTES).unwrap();
+        let line = |text: &str| {
+            serde_json::json!({
+                "type": "user",
+                "cwd": project.to_string_lossy(),
+                "message": { "content": text },
+            })
+            .to_string()
+                + "\n"
+        };
+        let a = sessions.join("a.jsonl");
+        std::fs::write(
+            &a,
+            line(&format!(
+                "see\n{}\n{}\n{}",
+                only_a.path, shared.path, archived.path
+            )),
+        )
+        .unwrap();
+        std::fs::write(sessions.join("b.jsonl"), line(&shared.path)).unwrap();
+
+        // An archived session of the same project still uses `archived`.
+        let archive = tmp.path().join("archive");
+        std::fs::create_dir_all(&archive).unwrap();
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        gz.write_all(line(&archived.path).as_bytes()).unwrap();
+        std::fs::write(archive.join("c.jsonl.gz"), gz.finish().unwrap()).unwrap();
+
+        // A sibling that cannot be read blocks every deletion.
+        let broken = archive.join("d.jsonl.gz");
+        std::fs::write(&broken, b"not gzip").unwrap();
+        assert!(release_attachments(&a, Some(&archive)).is_err());
+        assert!(Path::new(&only_a.path).exists());
+        std::fs::remove_file(&broken).unwrap();
+
+        let (files, bytes) = release_attachments(&a, Some(&archive)).unwrap();
+        assert_eq!((files, bytes), (1, "only in a".len() as u64));
+        assert!(!Path::new(&only_a.path).exists());
+        assert!(Path::new(&shared.path).exists());
+        assert!(Path::new(&archived.path).exists());
+        assert!(project.join(".tokenicode/.gitignore").exists());
+    }
+}
@@ -0,0 +1,660 @@
+//! Built-in MCP server that exposes TOKENICODE's workspace to the CLI.
+//!
+//! At startup the app listens on `127.0.0.1:<random port>` and speaks the
+//! streamable-HTTP MCP transport (plain JSON responses, no server-initiated
+//! stream). Every session gets its own bearer token, and
+//! `build_mcp_scratch_config` injects a `tokenicode` server entry carrying
+//! that token, so a tool call always knows which session (tab) it serves.
+//!
+//! Tools:
+//!   - `open_file`        — show a file in the viewer (path-access checked)
+//!   - `ask_user`         — structured question answered in the UI
+//!   - `list_open_tabs`   — the user's open session tabs
+//!   - `list_path_grants` — workspace roots and this tab's path grants
+//!   - `search_sessions`  — full-text search over past sessions
+//!
+//! Tools that need the UI emit `mcp:request` to the session's window and wait
+//! for `respond_mcp_request` with the same `request_id`; the frontend's
+//! `McpRequestHandler` answers them.
+//!
+//! The session's `McpSelection` applies to this server like to any other,
+//! so disabling `tokenicode` leaves it out of the scratch config.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::oneshot;
+
+use crate::events::emit_session_event;
+use crate::path_access::{PathAccessManager, PathCapability};
+
+/// Server name in the scratch config; tools appear as `mcp__tokenicode__*`.
+pub const SERVER_NAME: &str = "tokenicode";
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// How long UI round trips wait. Questions wait for the human.
+const UI_TIMEOUT: Duration = Duration::from_secs(15);
+const ASK_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+type PendingReply = oneshot::Sender<Result<Value, String>>;
+
+#[derive(Default)]
+pub struct BuiltinMcpState {
+    port: AtomicU16,
+    /// bearer token → session (stdin/tab) id
+    tokens: Mutex<HashMap<String, String>>,
+    /// request_id → waiting tool call
+    pending: Mutex<HashMap<String, PendingReply>>,
+}
+
+impl BuiltinMcpState {
+    /// Scratch-config entry for `session_id`, minting its token. `None` until
+    /// the listener is up.
+    pub fn session_entry(&self, session_id: &str) -> Option<Value> {
+        let port = self.port.load(Ordering::SeqCst);
+        if port == 0 {
+            return None;
+        }
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.retain(|_, sid| sid != session_id);
+        tokens.insert(token.clone(), session_id.to_string());
+        Some(json!({
+            "type": "http",
+            "url": format!("http://127.0.0.1:{}/mcp", port),
+            "headers": { "Authorization": format!("Bearer {}", token) },
+        }))
+    }
+
+    /// Revoke the session's token once its CLI process is gone.
+    pub fn release_session(&self, session_id: &str) {
+        self.tokens
+            .lock()
+            .unwrap()
+            .retain(|_, sid| sid != session_id);
+    }
+
+    fn session_for(&self, token: &str) -> Option<String> {
+        self.tokens.lock().unwrap().get(token).cloned()
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct UiRequest<'a> {
+    request_id: String,
+    session_id: &'a str,
+    /// "open_file" | "ask_user" | "list_tabs"
+    kind: &'a str,
+    payload: Value,
+}
+
+/// Start the listener on a background task. Failures are logged; sessions
+/// then simply run without the built-in server.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let listener = match tokio::net::TcpListener::bind(("127.0.0.1", 0)).await {
+            Ok(l) => l,
+            Err(e) => {
+                eprintln!("[TOKENICODE] built-in MCP server failed to bind: {}", e);
+                return;
+            }
+        };
+        let port = listener.local_addr().map(|a| a.port()).unwrap_or(0);
+        if let Some(state) = app.try_state::<BuiltinMcpState>() {
+            state.port.store(port, Ordering::SeqCst);
+        }
+        eprintln!("[TOKENICODE] built-in MCP server on 127.0.0.1:{}", port);
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    // e.g. EMFILE — back off instead of spinning.
+                    eprintln!("[TOKENICODE] built-in MCP accept failed: {}", e);
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    continue;
+                }
+            };
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = serve_connection(&app, stream).await {
+                    eprintln!("[TOKENICODE] built-in MCP connection: {}", e);
+                }
+            });
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// HTTP
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, PartialEq)]
+struct HttpRequest {
+    method: String,
+    path: String,
+    /// Lower-cased names.
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Index just past the `\r\n\r\n` ending the header block.
+fn header_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(4).position(|w| w == b"\r\n\r\n").map(|p| p + 4)
+}
+
+fn parse_head(head: &[u8]) -> Result<HttpRequest, String> {
+    let text = std::str::from_utf8(head).map_err(|_| "Header is not UTF-8".to_string())?;
+    let mut lines = text.split("\r\n");
+    let request_line = lines.next().unwrap_or("");
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().ok_or("Empty request line")?.to_string();
+    let path = parts.next().ok_or("Missing request path")?.to_string();
+    let headers = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        .collect();
+    Ok(HttpRequest {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    })
+}
+
+async fn read_request<R: tokio::io::AsyncRead + Unpin>(
+    stream: &mut R,
+) -> Result<HttpRequest, String> {
+    let mut buf = Vec::with_capacity(4096);
+    let mut chunk = [0u8; 4096];
+    let end = loop {
+        if let Some(end) = header_end(&buf) {
+            break end;
+        }
+        if buf.len() > MAX_HEADER_BYTES {
+            return Err("Request header too large".to_string());
+        }
+        let n = stream
+            .read(&mut chunk)
+            .await
+            .map_err(|e| format!("Failed to read request: {}", e))?;
+        if n == 0 {
+            return Err("Connection closed mid-request".to_string());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let mut req = parse_head(&buf[..end - 4])?;
+    let length: usize = req
+        .headers
+        .get("content-length")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    if length > MAX_BODY_BYTES {
+        return Err("Request body too large".to_string());
+    }
+    let mut body = buf[end..].to_vec();
+    while body.len() < length {
+        let n = stream
+            .read(&mut chunk)
+            .await
+            .map_err(|e| format!("Failed to read request body: {}", e))?;
+        if n == 0 {
+            return Err("Connection closed mid-body".to_string());
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(length);
+    req.body = body;
+    Ok(req)
+}
+
+async fn write_response<W: tokio::io::AsyncWrite + Unpin>(
+    stream: &mut W,
+    status: &str,
+    body: Option<&Value>,
+) -> Result<(), String> {
+    let body = body.map(|b| b.to_string()).unwrap_or_default();
+    let mut out = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        body.len()
+    );
+    if !body.is_empty() {
+        out.push_str("Content-Type: application/json\r\n");
+    }
+    out.push_str("\r\n");
+    out.push_str(&body);
+    stream
+        .write_all(out.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to write response: {}", e))
+}
+
+/// Browsers attach `Origin`; only local pages may talk to us (DNS rebinding).
+fn origin_allowed(headers: &HashMap<String, String>) -> bool {
+    match headers.get("origin") {
+        None => true,
+        Some(o) => {
+            let host = o
+                .split("://")
+                .nth(1)
+                .unwrap_or("")
+                .split([':', '/'])
+                .next()
+                .unwrap_or("");
+            matches!(host, "127.0.0.1" | "localhost" | "[::1]")
+        }
+    }
+}
+
+async fn serve_connection(
+    app: &AppHandle,
+    mut stream: tokio::net::TcpStream,
+) -> Result<(), String> {
+    let req = read_request(&mut stream).await?;
+    if req.path.split('?').next() != Some("/mcp") {
+        return write_response(&mut stream, "404 Not Found", None).await;
+    }
+    if !origin_allowed(&req.headers) {
+        return write_response(&mut stream, "403 Forbidden", None).await;
+    }
+    let state = app.state::<BuiltinMcpState>();
+    let session_id = req
+        .headers
+        .get("authorization")
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .and_then(|token| state.session_for(token.trim()));
+    let Some(session_id) = session_id else {
+        return write_response(&mut stream, "401 Unauthorized", None).await;
+    };
+    match req.method.as_str() {
+        "POST" => {}
+        // No server-initiated stream and no session state to delete.
+        "GET" | "DELETE" => {
+            return write_response(&mut stream, "405 Method Not Allowed", None).await
+        }
+        _ => return write_response(&mut stream, "400 Bad Request", None).await,
+    }
+
+    let msg: Value = match serde_json::from_slice(&req.body) {
+        Ok(v) => v,
+        Err(e) => {
+            let err = rpc_error(Value::Null, -32700, &format!("Parse error: {}", e));
+            return write_response(&mut stream, "400 Bad Request", Some(&err)).await;
+        }
+    };
+    let Some(id) = msg.get("id").cloned() else {
+        // Notifications and client responses need no answer.
+        return write_response(&mut stream, "202 Accepted", None).await;
+    };
+    let method = msg.get("method").and_then(|m| m.as_str()).unwrap_or("");
+    let params = msg.get("params").cloned().unwrap_or(Value::Null);
+
+    let reply = match protocol_response(method, &params) {
+        Some(result) => rpc_result(id, result),
+        None if method == "tools/call" => {
+            let name = params.get("name").and_then(|n| n.as_str()).unwrap_or("");
+            let args = params.get("arguments").cloned().unwrap_or(json!({}));
+            let outcome = call_tool(app, &session_id, name, &args).await;
+            rpc_result(id, tool_result(outcome))
+        }
+        None => rpc_error(id, -32601, &format!("Method not found: {}", method)),
+    };
+    write_response(&mut stream, "200 OK", Some(&reply)).await
+}
+
+// ---------------------------------------------------------------------------
+// JSON-RPC / MCP
+// ---------------------------------------------------------------------------
+
+fn rpc_result(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn rpc_error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn tool_result(outcome: Result<String, String>) -> Value {
+    match outcome {
+        Ok(text) => json!({ "content": [{ "type": "text", "text": text }] }),
+        Err(e) => json!({ "content": [{ "type": "text", "text": e }], "isError": true }),
+    }
+}
+
+fn tool_definitions() -> Value {
+    json!([
+        {
+            "name": "open_file",
+            "description": "Open a file in the TOKENICODE file viewer so the user sees it, optionally scrolled to a line.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Absolute path, or relative to the session's working directory" },
+                    "line": { "type": "integer", "minimum": 1 }
+                },
+                "required": ["path"]
+            }
+        },
+        {
+            "name": "ask_user",
+            "description": "Ask the user a question in the TOKENICODE UI and wait for the answer. Offer options when the answer is a choice.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "question": { "type": "string" },
+                    "options": { "type": "array", "items": { "type": "string" } },
+                    "multi_select": { "type": "boolean", "description": "Allow picking several options" },
+                    "allow_free_text": { "type": "boolean", "description": "Allow a typed answer besides the options (default true)" }
+                },
+                "required": ["question"]
+            }
+        },
+        {
+            "name": "list_open_tabs",
+            "description": "List the session tabs the user has open in TOKENICODE (title, working directory, status).",
+            "inputSchema": { "type": "object", "properties": {} }
+        },
+        {
+            "name": "list_path_grants",
+            "description": "List the directories TOKENICODE lets this session access: workspace roots plus paths the user granted to this tab.",
+            "inputSchema": { "type": "object", "properties": {} }
+        },
+        {
+            "name": "search_sessions",
+            "description": "Full-text search over the user's past TOKENICODE sessions. Returns session ids with matching snippets.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "minLength": 2 },
+                    "limit": { "type": "integer", "minimum": 1, "maximum": 50 }
+                },
+                "required": ["query"]
+            }
+        }
+    ])
+}
+
+/// Responses for everything except `tools/call`; `None` = not handled here.
+fn protocol_response(method: &str, params: &Value) -> Option<Value> {
+    match method {
+        "initialize" => {
+            let requested = params
+                .get("protocolVersion")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            let version = PROTOCOL_VERSIONS
+                .iter()
+                .find(|v| **v == requested)
+                .unwrap_or(&PROTOCOL_VERSIONS[0]);
+            Some(json!({
+                "protocolVersion": version,
+                "capabilities": { "tools": { "listChanged": false } },
+                "serverInfo": { "name": SERVER_NAME, "version": env!("CARGO_PKG_VERSION") },
+                "instructions": "Tools provided by the TOKENICODE desktop app hosting this session: show files to the user, ask the user questions, and look up open tabs, accessible paths and past sessions.",
+            }))
+        }
+        "ping" => Some(json!({})),
+        "tools/list" => Some(json!({ "tools": tool_definitions() })),
+        _ => None,
+    }
+}
+
+/// Emit a UI request and wait for `respond_mcp_request`.
+async fn ui_round_trip(
+    app: &AppHandle,
+    session_id: &str,
+    kind: &str,
+    payload: Value,
+    timeout: Duration,
+) -> Result<Value, String> {
+    let state = app.state::<BuiltinMcpState>();
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let (tx, rx) = oneshot::channel();
+    state.pending.lock().unwrap().insert(request_id.clone(), tx);
+
+    let request = UiRequest {
+        request_id: request_id.clone(),
+        session_id,
+        kind,
+        payload,
+    };
+    if let Err(e) = emit_session_event(app, session_id, "mcp:request", &request) {
+        state.pending.lock().unwrap().remove(&request_id);
+        return Err(format!("Failed to reach the TOKENICODE window: {}", e));
+    }
+    match tokio::time::timeout(timeout, rx).await {
+        Ok(Ok(reply)) => reply,
+        Ok(Err(_)) => Err("The request was dropped by TOKENICODE".to_string()),
+        Err(_) => {
+            state.pending.lock().unwrap().remove(&request_id);
+            Err(format!(
+                "No answer from TOKENICODE within {}s",
+                timeout.as_secs()
+            ))
+        }
+    }
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+}
+
+async fn call_tool(
+    app: &AppHandle,
+    session_id: &str,
+    name: &str,
+    args: &Value,
+) -> Result<String, String> {
+    let str_arg = |key: &str| args.get(key).and_then(|v| v.as_str()).map(str::trim);
+    match name {
+        "open_file" => {
+            let path = str_arg("path")
+                .filter(|p| !p.is_empty())
+                .ok_or("`path` is required")?;
+            let path_access = app.state::<PathAccessManager>();
+            let canonical = path_access
+                .validate(
+                    std::path::Path::new(path),
+                    Some(session_id),
+                    PathCapability::Read,
+                )
+                .await?;
+            if !canonical.is_file() {
+                return Err(format!("Not a file: {}", canonical.display()));
+            }
+            let shown = canonical.to_string_lossy().to_string();
+            let line = args.get("line").and_then(|l| l.as_u64());
+            ui_round_trip(
+                app,
+                session_id,
+                "open_file",
+                json!({ "path": shown, "line": line }),
+                UI_TIMEOUT,
+            )
+            .await?;
+            Ok(format!("Opened {} in the viewer", shown))
+        }
+        "ask_user" => {
+            let question = str_arg("question")
+                .filter(|q| !q.is_empty())
+                .ok_or("`question` is required")?;
+            let options: Vec<String> = args
+                .get("options")
+                .and_then(|o| o.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|v| v.as_str())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+            let payload = json!({
+                "question": question,
+                "options": options,
+                "multi_select": args.get("multi_select").and_then(|v| v.as_bool()).unwrap_or(false),
+                "allow_free_text": args.get("allow_free_text").and_then(|v| v.as_bool()).unwrap_or(true),
+            });
+            let answer = ui_round_trip(app, session_id, "ask_user", payload, ASK_TIMEOUT).await?;
+            Ok(match answer {
+                Value::String(s) => s,
+                other => pretty(&other),
+            })
+        }
+        "list_open_tabs" => {
+            let tabs = ui_round_trip(app, session_id, "list_tabs", json!({}), UI_TIMEOUT).await?;
+            Ok(pretty(&tabs))
+        }
+        "list_path_grants" => {
+            let path_access = app.state::<PathAccessManager>();
+            let roots: Vec<String> = path_access
+                .roots()
+                .await
+                .into_iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect();
+            let grants: Vec<Value> = path_access
+                .list_grants(Some(session_id))
+                .await?
+                .into_iter()
+                .map(|g| {
+                    json!({
+                        "path": g.path.to_string_lossy(),
+                        "capability": g.capability,
+                        "scope": g.scope,
+                        "expires_at_ms": g.expires_at_ms,
+                    })
+                })
+                .collect();
+            Ok(pretty(&json!({
+                "roots": roots,
+                "granted_to_this_tab": grants,
+            })))
+        }
+        "search_sessions" => {
+            let query = str_arg("query").unwrap_or("").to_string();
+            if query.chars().count() < 2 {
+                return Err("`query` needs at least 2 characters".to_string());
+            }
+            let limit = args
+                .get("limit")
+                .and_then(|l| l.as_u64())
+                .unwrap_or(20)
+                .clamp(1, 50) as usize;
+            let mut results = crate::search_sessions(query).await?;
+            results.truncate(limit);
+            if results.is_empty() {
+                return Ok("No matching sessions".to_string());
+            }
+            Ok(pretty(&Value::Array(results)))
+        }
+        other => Err(format!("Unknown tool: {}", other)),
+    }
+}
+
+/// Answer a pending `mcp:request`. Pass `error` to fail the tool call (e.g.
+/// the user dismissed a question).
+#[tauri::command]
+pub async fn respond_mcp_request(
+    state: State<'_, BuiltinMcpState>,
+    request_id: String,
+    result: Option<Value>,
+    error: Option<String>,
+) -> Result<(), String> {
+    let tx = state
+        .pending
+        .lock()
+        .unwrap()
+        .remove(&request_id)
+        .ok_or_else(|| format!("No pending MCP request {}", request_id))?;
+    let reply = match error {
+        Some(e) => Err(e),
+        None => Ok(result.unwrap_or(Value::Null)),
+    };
+    let _ = tx.send(reply);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn parses_requests_split_across_reads() {
+        let raw = b"POST /mcp HTTP/1.1\r\nHost: 127.0.0.1\r\nAuthorization: Bearer abc\r\nContent-Length: 14\r\n\r\n{\"id\":1,\"x\":2}trailing";
+        // Reader that yields 7 bytes at a time.
+        let (mut client, mut server) = tokio::io::duplex(7);
+        let writer = tokio::spawn(async move {
+            // Fails once the server side stops reading at Content-Length.
+            let _ = client.write_all(raw).await;
+        });
+        let req = read_request(&mut server).await.unwrap();
+        drop(server);
+        writer.await.unwrap();
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.path, "/mcp");
+        assert_eq!(req.headers["authorization"], "Bearer abc");
+        assert_eq!(req.body, b"{\"id\":1,\"x\":2}".to_vec());
+    }
+
+    #[test]
+    fn protocol_handshake_and_tools() {
+        let init =
+            protocol_response("initialize", &json!({ "protocolVersion": "2025-03-26" })).unwrap();
+        assert_eq!(init["protocolVersion"], "2025-03-26");
+        let init = protocol_response("initialize", &json!({ "protocolVersion": "1999" })).unwrap();
+        assert_eq!(init["protocolVersion"], PROTOCOL_VERSIONS[0]);
+
+        let tools = protocol_response("tools/list", &Value::Null).unwrap();
+        let names: Vec<&str> = tools["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "open_file",
+                "ask_user",
+                "list_open_tabs",
+                "list_path_grants",
+                "search_sessions"
+            ]
+        );
+        assert!(protocol_response("tools/call", &Value::Null).is_none());
+    }
+
+    #[test]
+    fn tokens_map_to_sessions_and_origins_are_checked() {
+        let state = BuiltinMcpState::default();
+        assert!(state.session_entry("desk_1").is_none());
+        state.port.store(4242, Ordering::SeqCst);
+        let entry = state.session_entry("desk_1").unwrap();
+        assert_eq!(entry["url"], "http://127.0.0.1:4242/mcp");
+        let token = entry["headers"]["Authorization"]
+            .as_str()
+            .unwrap()
+            .strip_prefix("Bearer ")
+            .unwrap()
+            .to_string();
+        assert_eq!(state.session_for(&token).as_deref(), Some("desk_1"));
+        // Re-entry for the same session rotates the token.
+        state.session_entry("desk_1").unwrap();
+        assert!(state.session_for(&token).is_none());
+        state.release_session("desk_1");
+        assert!(state.tokens.lock().unwrap().is_empty());
+
+        let mut headers = HashMap::new();
+        assert!(origin_allowed(&headers));
+        headers.insert("origin".to_string(), "http://localhost:1420".to_string());
+        assert!(origin_allowed(&headers));
+        headers.insert("origin".to_string(), "https://evil.example".to_string());
+        assert!(!origin_allowed(&headers));
+    }
+}
@@ -18,6 +18,10 @@ pub struct SessionInfo {
     pub cli_session_id: Option<String>,
     pub pid: u32,
     pub cli_path: String,
+    /// Set when the session runs in an isolated git worktree; its
+    /// `session_cwd` is the directory the CLI actually uses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worktree: Option<crate::commands::worktrees::ManagedWorktree>,
 }
 
 /// A managed CLI session whose child process is owned by an independent
@@ -213,4 +217,10 @@ pub struct StartSessionParams {
     /// before resuming. This prevents "invalid thinking signature" 400 errors when switching
     /// to a different model that can't verify the old model's cryptographic signatures.
     pub model_switch: Option<bool>,
+    /// MCP servers to include in this session's scratch config. When absent,
+    /// the selection remembered for this session (or the resumed one) applies.
+    pub mcp_selection: Option<crate::commands::mcp_manager::McpSelection>,
+    /// "worktree" runs the session in a managed `git worktree` on a new
+    /// branch (see `commands::worktrees`); absent = the cwd itself.
+    pub isolation: Option<String>,
 }
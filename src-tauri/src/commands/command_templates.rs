@@ -0,0 +1,671 @@
+//! Render custom slash commands (`.claude/commands/**/*.md`) into the
+//! prompt the CLI would send, so TOKENICODE expands them like the terminal.
+//!
+//! Expansion order mirrors the CLI:
+//!   1. Strip YAML frontmatter (`allowed-tools`, `model`, `argument-hint`, …).
+//!   2. Find `` !`cmd` `` snippets in the template, before any arguments
+//!      are substituted, so an argument can never introduce a snippet.
+//!   3. Substitute `$ARGUMENTS` (the raw argument string) and `$1`…`$9`
+//!      (shell-style split, quotes respected; missing ones become empty).
+//!      Inside a snippet every argument is shell-quoted, so it stays data.
+//!   4. Run the snippets and inline their output. A snippet only runs when
+//!      the frontmatter `allowed-tools` permits every command in it as
+//!      `Bash(...)` AND the caller passed `allow_shell` after asking the
+//!      user; otherwise it is left in place and reported as blocked so the UI
+//!      can prompt and re-render.
+//!   5. Inline `@path` includes. Paths resolve against the session cwd and go
+//!      through `PathAccessManager` like every other file read.
+//!
+//! Namespaced commands (`/frontend:lint`) map to subdirectories
+//! (`frontend/lint.md`); project commands shadow global ones.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::State;
+
+use crate::path_access::{PathAccessManager, PathCapability};
+
+const SHELL_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_SHELL_OUTPUT: usize = 64 * 1024;
+const MAX_INCLUDE_BYTES: u64 = 256 * 1024;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ShellSnippetResult {
+    pub command: String,
+    /// "ran" | "blocked" | "failed"
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Why the snippet was blocked or failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RenderedCommand {
+    pub name: String,
+    /// Command file the template came from.
+    pub path: String,
+    /// Final prompt text to send to the CLI.
+    pub prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_tools: Option<Vec<String>>,
+    pub included_files: Vec<String>,
+    pub shell_snippets: Vec<ShellSnippetResult>,
+    /// True when at least one snippet was blocked only because `allow_shell`
+    /// was not set — ask the user, then render again with `allow_shell: true`.
+    pub needs_shell_permission: bool,
+    pub warnings: Vec<String>,
+}
+
+/// Split an argument string like a shell would for `$1`…`$9`.
+fn split_args(args: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = args.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') => {
+                if let Some(n) = chars.next() {
+                    cur.push(n);
+                }
+            }
+            (Some(_), c) => cur.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '\\') => {
+                if let Some(n) = chars.next() {
+                    cur.push(n);
+                    in_word = true;
+                }
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    out.push(std::mem::take(&mut cur));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                cur.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        out.push(cur);
+    }
+    out
+}
+
+/// Quote `arg` as a single shell word. `None` when it cannot be quoted
+/// safely (cmd.exe has no quoting that stops `%` or `"`).
+fn shell_quote(arg: &str) -> Option<String> {
+    if cfg!(target_os = "windows") {
+        if arg.contains(['"', '%', '!', '\n', '\r']) {
+            return None;
+        }
+        return Some(format!("\"{}\"", arg));
+    }
+    Some(format!("'{}'", arg.replace('\'', "'\\''")))
+}
+
+/// Substitute `$ARGUMENTS` and `$1`…`$9`. `$10` is `$1` followed by `0`,
+/// matching the CLI's single-digit positional args.
+///
+/// With `shell`, the text is a snippet command: each argument is quoted and
+/// `$ARGUMENTS` becomes the quoted positional args. Fails when an argument
+/// cannot be quoted.
+fn substitute_args(template: &str, raw_args: &str, shell: bool) -> Result<String, String> {
+    let positional = split_args(raw_args);
+    let quote = |arg: &str| {
+        shell_quote(arg).ok_or_else(|| format!("argument {:?} cannot be passed to the shell", arg))
+    };
+    let mut out = String::with_capacity(template.len() + raw_args.len());
+    let mut rest = template;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        if let Some(tail) = after.strip_prefix("ARGUMENTS") {
+            if shell {
+                let quoted = positional
+                    .iter()
+                    .map(|a| quote(a))
+                    .collect::<Result<Vec<_>, _>>()?;
+                out.push_str(&quoted.join(" "));
+            } else {
+                out.push_str(raw_args);
+            }
+            rest = tail;
+        } else if let Some(d) = after.chars().next().filter(|c| ('1'..='9').contains(c)) {
+            let idx = d as usize - '1' as usize;
+            match positional.get(idx) {
+                Some(arg) if shell => out.push_str(&quote(arg)?),
+                Some(arg) => out.push_str(arg),
+                None => {}
+            }
+            rest = &after[1..];
+        } else {
+            out.push('$');
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Split a shell command into its simple commands at `;`, `&`, `|`, `&&`,
+/// `||` and newlines, honouring quotes. `None` when the command uses
+/// anything that cannot be checked segment by segment: command or process
+/// substitution and redirections.
+fn command_segments(command: &str) -> Option<Vec<String>> {
+    let single_quotes = !cfg!(target_os = "windows");
+    let mut segments = Vec::new();
+    let mut cur = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => {
+                quote = None;
+                cur.push(c);
+            }
+            (Some('"'), '`') => return None,
+            (Some('"'), '$') if chars.peek() == Some(&'(') => return None,
+            (Some('"'), '\\') if single_quotes => {
+                cur.push(c);
+                cur.extend(chars.next());
+            }
+            (Some(_), c) => cur.push(c),
+            (None, '"') => {
+                quote = Some(c);
+                cur.push(c);
+            }
+            (None, '\'') if single_quotes => {
+                quote = Some(c);
+                cur.push(c);
+            }
+            (None, '\\') if single_quotes => {
+                cur.push(c);
+                cur.extend(chars.next());
+            }
+            (None, '`' | '<' | '>') => return None,
+            (None, '$') if chars.peek() == Some(&'(') => return None,
+            (None, ';' | '&' | '|' | '\n' | '\r') => segments.push(std::mem::take(&mut cur)),
+            (None, c) => cur.push(c),
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    segments.push(cur);
+    Some(
+        segments
+            .into_iter()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect(),
+    )
+}
+
+/// Whether `pattern` (the inside of `Bash(...)`) allows one simple command.
+fn pattern_matches(pattern: &str, command: &str) -> bool {
+    let pattern = pattern.trim();
+    if pattern == "*" {
+        return true;
+    }
+    match pattern.strip_suffix(":*") {
+        Some(prefix) => {
+            command == prefix
+                || command
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with(char::is_whitespace))
+        }
+        None => command == pattern,
+    }
+}
+
+/// Whether `allowed-tools` permits running `command` through Bash.
+/// Accepts `Bash`, `Bash(*)`, `Bash(prefix:*)` and exact `Bash(command)`.
+/// Compound commands need every part allowed; substitutions and
+/// redirections only pass a bare `Bash` / `Bash(*)`.
+fn bash_permitted(allowed_tools: &[String], command: &str) -> bool {
+    let patterns: Vec<&str> = allowed_tools
+        .iter()
+        .filter_map(|entry| {
+            let entry = entry.trim();
+            if entry == "Bash" {
+                return Some("*");
+            }
+            entry
+                .strip_prefix("Bash(")
+                .and_then(|p| p.strip_suffix(')'))
+        })
+        .collect();
+    if patterns.iter().any(|p| p.trim() == "*") {
+        return true;
+    }
+    let Some(segments) = command_segments(command) else {
+        return false;
+    };
+    !segments.is_empty()
+        && segments
+            .iter()
+            .all(|seg| patterns.iter().any(|p| pattern_matches(p, seg)))
+}
+
+/// `` !`cmd` `` snippet spans: (start, end, command) with byte offsets
+/// covering the whole `` !`…` `` token.
+fn find_shell_snippets(text: &str) -> Vec<(usize, usize, String)> {
+    let mut found = Vec::new();
+    let mut search = 0;
+    while let Some(rel) = text[search..].find("!`") {
+        let start = search + rel;
+        let body_start = start + 2;
+        let Some(close) = text[body_start..].find('`') else {
+            break;
+        };
+        let end = body_start + close + 1;
+        let cmd = &text[body_start..body_start + close];
+        if !cmd.trim().is_empty() && !cmd.contains('\n') {
+            found.push((start, end, cmd.to_string()));
+        }
+        search = end;
+    }
+    found
+}
+
+/// `@path` include spans: (start, end, path). An `@` only counts at the
+/// start of a word, so e-mail addresses and decorators are left alone.
+fn find_includes(text: &str) -> Vec<(usize, usize, String)> {
+    let mut found = Vec::new();
+    let bytes = text.as_bytes();
+    let mut in_fence = false;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if !in_fence {
+            for (i, _) in line.match_indices('@') {
+                let at = offset + i;
+                if at > 0 && !bytes[at - 1].is_ascii_whitespace() && bytes[at - 1] != b'(' {
+                    continue;
+                }
+                let path: String = text[at + 1..]
+                    .chars()
+                    .take_while(|c| {
+                        !c.is_whitespace() && !matches!(c, ',' | ')' | '`' | '"' | '\'')
+                    })
+                    .collect();
+                let path = path.trim_end_matches(['.', ':', ';']).to_string();
+                if path.is_empty() || !(path.contains('/') || path.contains('.')) {
+                    continue;
+                }
+                found.push((at, at + 1 + path.len(), path));
+            }
+        }
+        offset += line.len();
+    }
+    found
+}
+
+fn strip_frontmatter(content: &str) -> (Option<&str>, &str) {
+    let trimmed = content.trim_start_matches('\u{feff}');
+    let Some(after_open) = trimmed.strip_prefix("---") else {
+        return (None, content);
+    };
+    match after_open.find("\n---") {
+        Some(close) => {
+            let body = &after_open[close + 4..];
+            let body = body
+                .strip_prefix("\r\n")
+                .or_else(|| body.strip_prefix('\n'))
+                .unwrap_or(body);
+            (Some(&after_open[..close]), body)
+        }
+        None => (None, content),
+    }
+}
+
+/// Resolve `/name` or `/ns:name` to a command file, project before global.
+fn resolve_command_file(name: &str, cwd: Option<&str>) -> Result<PathBuf, String> {
+    let bare = name.trim().trim_start_matches('/');
+    if bare.is_empty() {
+        return Err("Command name is empty".to_string());
+    }
+    let mut rel = PathBuf::new();
+    for part in bare.split(':') {
+        if part.is_empty() || part == "." || part == ".." || part.contains(['/', '\\']) {
+            return Err(format!("Invalid command name: {}", name));
+        }
+        rel.push(part);
+    }
+    rel.set_extension("md");
+
+    let mut roots = Vec::new();
+    if let Some(cwd) = cwd.filter(|c| !c.is_empty()) {
+        roots.push(Path::new(cwd).join(".claude").join("commands"));
+    }
+    if let Some(home) = dirs::home_dir() {
+        roots.push(home.join(".claude").join("commands"));
+    }
+    roots
+        .into_iter()
+        .map(|r| r.join(&rel))
+        .find(|p| p.is_file())
+        .ok_or_else(|| format!("Custom command not found: /{}", bare))
+}
+
+async fn run_shell_snippet(command: &str, cwd: Option<&str>) -> Result<(i32, String), String> {
+    #[cfg(not(target_os = "windows"))]
+    let mut cmd = {
+        let mut c = tokio::process::Command::new("sh");
+        c.arg("-c").arg(command);
+        c
+    };
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        let mut c = tokio::process::Command::new("cmd");
+        c.arg("/C").arg(command);
+        c.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        c
+    };
+    if let Some(dir) = cwd.filter(|c| !c.is_empty()) {
+        cmd.current_dir(dir);
+    }
+    cmd.env("PATH", crate::build_enriched_path())
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true);
+
+    let output = tokio::time::timeout(SHELL_TIMEOUT, cmd.output())
+        .await
+        .map_err(|_| format!("timed out after {}s", SHELL_TIMEOUT.as_secs()))?
+        .map_err(|e| format!("failed to spawn: {}", e))?;
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.trim().is_empty() {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&stderr);
+    }
+    if text.len() > MAX_SHELL_OUTPUT {
+        let mut cut = MAX_SHELL_OUTPUT;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        text.truncate(cut);
+        text.push_str("\n… (output truncated)");
+    }
+    Ok((
+        output.status.code().unwrap_or(-1),
+        text.trim_end().to_string(),
+    ))
+}
+
+/// Render custom command `name` (e.g. `/review` or `/frontend:lint`) with
+/// `arguments` into the final prompt text.
+#[tauri::command]
+pub async fn render_slash_command(
+    path_access: State<'_, PathAccessManager>,
+    name: String,
+    arguments: Option<String>,
+    cwd: Option<String>,
+    allow_shell: Option<bool>,
+    tab_id: Option<String>,
+) -> Result<RenderedCommand, String> {
+    let file = resolve_command_file(&name, cwd.as_deref())?;
+    let file = path_access
+        .validate(&file, tab_id.as_deref(), PathCapability::Read)
+        .await?;
+    let content =
+        std::fs::read_to_string(&file).map_err(|e| format!("Cannot read command file: {}", e))?;
+
+    let (yaml, body) = strip_frontmatter(&content);
+    let mut warnings = Vec::new();
+    // Same typed parse as skills; a broken block is reported, not fatal.
+    let fm = match yaml.map(serde_yaml::from_str::<crate::SkillFrontmatter>) {
+        Some(Ok(fm)) => fm,
+        Some(Err(e)) => {
+            warnings.push(format!("Frontmatter ignored: {}", e));
+            crate::SkillFrontmatter::default()
+        }
+        None => crate::SkillFrontmatter::default(),
+    };
+
+    let raw_args = arguments.unwrap_or_default();
+    let raw_args = raw_args.trim();
+
+    // Shell snippets come from the template itself; arguments are
+    // substituted around and (quoted) into them, never scanned for snippets.
+    let allowed_tools = fm.allowed_tools.clone().unwrap_or_default();
+    let mut shell_snippets = Vec::new();
+    let mut needs_shell_permission = false;
+    let mut text = String::with_capacity(body.len() + raw_args.len());
+    let mut last = 0;
+    for (start, end, template) in find_shell_snippets(body) {
+        text.push_str(&substitute_args(&body[last..start], raw_args, false)?);
+        last = end;
+        let blocked = |command: String, reason: String| ShellSnippetResult {
+            command,
+            status: "blocked".into(),
+            exit_code: None,
+            reason: Some(reason),
+        };
+        let command = match substitute_args(&template, raw_args, true) {
+            Ok(command) => command,
+            Err(reason) => {
+                text.push_str(&body[start..end]);
+                shell_snippets.push(blocked(template, reason));
+                continue;
+            }
+        };
+        if !bash_permitted(&allowed_tools, &command) {
+            text.push_str(&format!("!`{}`", command));
+            shell_snippets.push(blocked(
+                command,
+                "not permitted by the command's allowed-tools".into(),
+            ));
+            continue;
+        }
+        if !allow_shell.unwrap_or(false) {
+            needs_shell_permission = true;
+            text.push_str(&format!("!`{}`", command));
+            shell_snippets.push(blocked(command, "awaiting user confirmation".into()));
+            continue;
+        }
+        match run_shell_snippet(&command, cwd.as_deref()).await {
+            Ok((code, output)) => {
+                text.push_str(&output);
+                shell_snippets.push(ShellSnippetResult {
+                    command,
+                    status: "ran".into(),
+                    exit_code: Some(code),
+                    reason: None,
+                });
+            }
+            Err(reason) => {
+                text.push_str(&format!("!`{}`", command));
+                shell_snippets.push(ShellSnippetResult {
+                    command,
+                    status: "failed".into(),
+                    exit_code: None,
+                    reason: Some(reason),
+                });
+            }
+        }
+    }
+    text.push_str(&substitute_args(&body[last..], raw_args, false)?);
+
+    // @file includes.
+    let mut included_files = Vec::new();
+    let mut includes: Vec<(usize, usize, String)> = Vec::new();
+    for (start, end, rel) in find_includes(&text) {
+        let candidate = match cwd.as_deref().filter(|c| !c.is_empty()) {
+            Some(base) if !Path::new(&rel).is_absolute() => Path::new(base).join(&rel),
+            _ => PathBuf::from(&rel),
+        };
+        if !candidate.is_file() {
+            continue; // not a file reference (e.g. @mention) — leave as typed
+        }
+        let resolved = match path_access
+            .validate(&candidate, tab_id.as_deref(), PathCapability::Read)
+            .await
+        {
+            Ok(p) => p,
+            Err(e) => {
+                warnings.push(format!("@{} not included: {}", rel, e));
+                continue;
+            }
+        };
+        let size = std::fs::metadata(&resolved).map(|m| m.len()).unwrap_or(0);
+        if size > MAX_INCLUDE_BYTES {
+            warnings.push(format!(
+                "@{} not included: larger than {} KB",
+                rel,
+                MAX_INCLUDE_BYTES / 1024
+            ));
+            continue;
+        }
+        match std::fs::read_to_string(&resolved) {
+            Ok(body) => {
+                let fence = if body.contains("```") { "````" } else { "```" };
+                includes.push((
+                    start,
+                    end,
+                    format!("{}:\n{}\n{}\n{}", rel, fence, body.trim_end(), fence),
+                ));
+                included_files.push(resolved.to_string_lossy().to_string());
+            }
+            Err(e) => warnings.push(format!("@{} not included: {}", rel, e)),
+        }
+    }
+    for (start, end, replacement) in includes.into_iter().rev() {
+        text.replace_range(start..end, &replacement);
+    }
+
+    Ok(RenderedCommand {
+        name: format!("/{}", name.trim().trim_start_matches('/')),
+        path: file.to_string_lossy().to_string(),
+        prompt: text.trim().to_string(),
+        model: fm.model,
+        allowed_tools: fm.allowed_tools,
+        included_files,
+        shell_snippets,
+        needs_shell_permission,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitutes_arguments_and_positionals() {
+        assert_eq!(
+            substitute_args(
+                "Fix #$1 in $2 ($ARGUMENTS) $3 costs $5.",
+                "42 'src/a b.rs'",
+                false
+            )
+            .unwrap(),
+            "Fix #42 in src/a b.rs (42 'src/a b.rs')  costs ."
+        );
+        assert_eq!(
+            substitute_args("price: $ and $x", "a", false).unwrap(),
+            "price: $ and $x"
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn shell_arguments_cannot_become_syntax() {
+        let tools = vec!["Bash(git log:*)".to_string()];
+        for args in [
+            "x; rm -rf ~",
+            "x && rm -rf ~",
+            "$(rm -rf ~)",
+            "`id`",
+            "\"a'b; id\"",
+        ] {
+            let command = substitute_args("git log $1", args, true).unwrap();
+            assert!(bash_permitted(&tools, &command), "{}", command);
+            assert_eq!(command_segments(&command).unwrap().len(), 1);
+            let all = substitute_args("git log $ARGUMENTS", args, true).unwrap();
+            assert!(bash_permitted(&tools, &all), "{}", all);
+        }
+        assert_eq!(
+            substitute_args("git log $1", "\"a'b\"", true).unwrap(),
+            "git log 'a'\\''b'"
+        );
+    }
+
+    #[test]
+    fn bash_permission_patterns() {
+        let tools = vec![
+            "Read".to_string(),
+            "Bash(git status:*)".to_string(),
+            "Bash(date)".to_string(),
+        ];
+        assert!(bash_permitted(&tools, "git status --short"));
+        assert!(bash_permitted(&tools, "date"));
+        assert!(!bash_permitted(&tools, "git statusx"));
+        assert!(!bash_permitted(&tools, "rm -rf /"));
+        assert!(bash_permitted(&["Bash".to_string()], "anything"));
+        assert!(!bash_permitted(&[], "date"));
+        // Every part of a compound command must be allowed.
+        assert!(bash_permitted(&tools, "git status && date"));
+        assert!(!bash_permitted(&tools, "git status; rm -rf ~"));
+        assert!(!bash_permitted(&tools, "git status && rm -rf ~"));
+        assert!(!bash_permitted(&tools, "git status | sh"));
+        assert!(!bash_permitted(&tools, "git status\nrm -rf ~"));
+        // Substitutions and redirections are never allowed by a pattern.
+        assert!(!bash_permitted(&tools, "git status $(rm -rf ~)"));
+        assert!(!bash_permitted(&tools, "git status \"`rm -rf ~`\""));
+        assert!(!bash_permitted(&tools, "git status > ~/.bashrc"));
+        // Quoted operators are plain text.
+        assert!(bash_permitted(&tools, "git status 'a; b' \"c && d\""));
+    }
+
+    #[test]
+    fn finds_snippets_and_includes() {
+        let text = "Status: !`git status`\nSee @src/main.rs and mail me@x.com, also (@docs/a.md).\n```\n@skip/me.rs\n```\n";
+        let snippets = find_shell_snippets(text);
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(&text[snippets[0].0..snippets[0].1], "!`git status`");
+
+        let includes: Vec<String> = find_includes(text).into_iter().map(|i| i.2).collect();
+        assert_eq!(includes, vec!["src/main.rs", "docs/a.md"]);
+    }
+
+    #[test]
+    fn resolves_namespaced_project_commands() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().join(".claude").join("commands").join("fe");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("lint.md"), "x").unwrap();
+        let cwd = tmp.path().to_str();
+        assert!(resolve_command_file("/fe:lint", cwd)
+            .unwrap()
+            .ends_with("fe/lint.md"));
+        assert!(resolve_command_file("/../etc", cwd).is_err());
+        assert!(resolve_command_file("/fe:..:x", cwd).is_err());
+    }
+
+    #[test]
+    fn strips_frontmatter_before_rendering() {
+        let (yaml, body) =
+            strip_frontmatter("---\nallowed-tools: Bash(date)\n---\nToday: !`date`\n");
+        assert_eq!(yaml, Some("\nallowed-tools: Bash(date)"));
+        assert_eq!(body, "Today: !`date`\n");
+    }
+}
@@ -0,0 +1,846 @@
+//! Files-touched index: which sessions modified which files.
+//!
+//! Every tracked session JSONL is scanned for mutating tool calls —
+//! Edit / Write / MultiEdit / NotebookEdit inputs plus file arguments of
+//! common Bash commands (`rm`, `mv`, `cp`, `touch`, `tee`, `sed -i`, output
+//! redirects) — and each hit is recorded as a [`FileTouch`] with the message
+//! uuid and timestamp it came from.
+//!
+//! The index is cached in `~/.tokenicode/file_index.json` keyed by session id
+//! and refreshed incrementally: a session is re-parsed only when its JSONL
+//! size or mtime changed, and sessions whose JSONL disappeared (deleted,
+//! compressed by retention, untracked) are dropped.
+//!
+//! Bash extraction is best-effort: commands are split on `&&`, `||`, `;` and
+//! `|`, `cd` is followed within one command, and arguments containing shell
+//! expansions (`$`, globs, backticks) are skipped rather than guessed.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::BufRead;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+const INDEX_VERSION: u32 = 1;
+/// Bash command text kept per touch — enough to recognise it in a list.
+const COMMAND_EXCERPT_CHARS: usize = 200;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FileTouch {
+    /// Absolute, lexically normalized path.
+    pub path: String,
+    /// Tool that touched the file (`Edit`, `Write`, `MultiEdit`, `NotebookEdit`, `Bash`).
+    pub tool: String,
+    /// `uuid` of the assistant JSONL line carrying the tool_use.
+    pub message_uuid: String,
+    /// RFC 3339 timestamp of that line (empty if missing).
+    pub timestamp: String,
+    /// Bash command excerpt, only for `tool == "Bash"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct IndexedSession {
+    jsonl_path: String,
+    project: String,
+    mtime_ms: u64,
+    size: u64,
+    touches: Vec<FileTouch>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct FileIndexCache {
+    version: u32,
+    sessions: BTreeMap<String, IndexedSession>,
+}
+
+/// Tauri managed state holding the in-memory copy of the index.
+#[derive(Default, Clone)]
+pub struct FileIndexState {
+    inner: Arc<Mutex<Option<FileIndexCache>>>,
+}
+
+impl FileIndexState {
+    /// Newest touch timestamp of each file below `dir`, keyed by its
+    /// `/`-separated path relative to `dir`. Quick-open uses it to rank
+    /// files the agent edited recently.
+    pub(crate) fn last_touched_under(&self, dir: &str) -> Result<HashMap<String, String>, String> {
+        with_index(&self.inner, false, |cache, _| {
+            let mut out: HashMap<String, String> = HashMap::new();
+            for t in cache.sessions.values().flat_map(|s| &s.touches) {
+                if !path_is_within(&t.path, dir) {
+                    continue;
+                }
+                let slot = out
+                    .entry(relative_to(&t.path, dir).replace('\\', "/"))
+                    .or_default();
+                if t.timestamp > *slot {
+                    slot.clone_from(&t.timestamp);
+                }
+            }
+            out
+        })
+    }
+}
+
+/// One session's touches of the queried file (or directory).
+#[derive(Debug, Serialize, Clone)]
+pub struct FileHistoryEntry {
+    pub session_id: String,
+    pub project: String,
+    pub jsonl_path: String,
+    /// Newest timestamp among `touches` — entries are sorted by this, newest first.
+    pub last_touched: String,
+    pub touches: Vec<FileTouch>,
+}
+
+/// One file changed by a session, with every touch in message order.
+#[derive(Debug, Serialize, Clone)]
+pub struct ChangedFile {
+    pub path: String,
+    /// Path relative to the session's project, or the absolute path if outside it.
+    pub relative_path: String,
+    pub touches: Vec<FileTouch>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FileIndexStats {
+    pub sessions: usize,
+    pub files: usize,
+    pub touches: usize,
+    /// Sessions re-parsed during this refresh.
+    pub reindexed: usize,
+}
+
+fn cache_path() -> Result<PathBuf, String> {
+    crate::tokenicode_data_path("file_index.json")
+}
+
+fn load_cache() -> FileIndexCache {
+    cache_path()
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str::<FileIndexCache>(&c).ok())
+        .filter(|c| c.version == INDEX_VERSION)
+        .unwrap_or(FileIndexCache {
+            version: INDEX_VERSION,
+            sessions: BTreeMap::new(),
+        })
+}
+
+fn save_cache(cache: &FileIndexCache) -> Result<(), String> {
+    let path = cache_path()?;
+    let content = serde_json::to_string(cache)
+        .map_err(|e| format!("Failed to serialize file index: {}", e))?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, content).map_err(|e| format!("Failed to write file index: {}", e))?;
+    std::fs::rename(&tmp, &path).map_err(|e| format!("Failed to write file index: {}", e))
+}
+
+/// Bring the cache in line with the tracked session JSONLs on disk.
+/// Returns the number of sessions that were (re-)parsed.
+fn refresh(cache: &mut FileIndexCache, force: bool) -> Result<usize, String> {
+    let home = dirs::home_dir().ok_or("Cannot find home dir")?;
+    let projects_dir = home.join(".claude").join("projects");
+    let tracked = crate::load_tracked_sessions();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut reindexed = 0usize;
+
+    if let Ok(entries) = std::fs::read_dir(&projects_dir) {
+        for entry in entries.flatten() {
+            if !entry.path().is_dir() {
+                continue;
+            }
+            let Ok(files) = std::fs::read_dir(entry.path()) else {
+                continue;
+            };
+            for file in files.flatten() {
+                let path = file.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+                    continue;
+                }
+                let Some(id) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                    continue;
+                };
+                if !tracked.contains(&id) {
+                    continue;
+                }
+                let Ok(meta) = std::fs::metadata(&path) else {
+                    continue;
+                };
+                let mtime_ms = meta
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0);
+                seen.insert(id.clone());
+
+                let jsonl_path = path.to_string_lossy().to_string();
+                let fresh = cache.sessions.get(&id).is_some_and(|s| {
+                    s.mtime_ms == mtime_ms && s.size == meta.len() && s.jsonl_path == jsonl_path
+                });
+                if fresh && !force {
+                    continue;
+                }
+
+                let (project, touches) = match index_session_file(&path) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        eprintln!("[TOKENICODE] file_index: skipping {}: {}", jsonl_path, e);
+                        continue;
+                    }
+                };
+                let project = if project.is_empty() {
+                    crate::decode_project_name(&entry.file_name().to_string_lossy())
+                } else {
+                    project
+                };
+                cache.sessions.insert(
+                    id,
+                    IndexedSession {
+                        jsonl_path,
+                        project,
+                        mtime_ms,
+                        size: meta.len(),
+                        touches,
+                    },
+                );
+                reindexed += 1;
+            }
+        }
+    }
+
+    let before = cache.sessions.len();
+    cache.sessions.retain(|id, _| seen.contains(id));
+    if reindexed > 0 || cache.sessions.len() != before {
+        save_cache(cache)?;
+    }
+    Ok(reindexed)
+}
+
+/// Run `f` against a refreshed index. Blocking — call from `spawn_blocking`.
+fn with_index<T>(
+    inner: &Mutex<Option<FileIndexCache>>,
+    force: bool,
+    f: impl FnOnce(&FileIndexCache, usize) -> T,
+) -> Result<T, String> {
+    let mut guard = inner
+        .lock()
+        .map_err(|e| format!("File index lock poisoned: {}", e))?;
+    let cache = guard.get_or_insert_with(load_cache);
+    let reindexed = refresh(cache, force)?;
+    Ok(f(cache, reindexed))
+}
+
+fn index_session_file(path: &Path) -> Result<(String, Vec<FileTouch>), String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Cannot open session: {}", e))?;
+    let lines = std::io::BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|l| serde_json::from_str::<Value>(&l).ok());
+    Ok(extract_touches(lines))
+}
+
+/// Walk JSONL line values and collect every file-mutating tool call.
+/// Returns the session's project cwd (first `cwd` seen) and the touches.
+fn extract_touches(lines: impl Iterator<Item = Value>) -> (String, Vec<FileTouch>) {
+    let mut project = String::new();
+    let mut cwd = String::new();
+    let mut touches = Vec::new();
+
+    for line in lines {
+        if let Some(c) = line["cwd"].as_str().filter(|c| !c.is_empty()) {
+            if project.is_empty() {
+                project = c.to_string();
+            }
+            cwd = c.to_string();
+        }
+        if line["type"].as_str() != Some("assistant") {
+            continue;
+        }
+        let Some(blocks) = line["message"]["content"].as_array() else {
+            continue;
+        };
+        let message_uuid = line["uuid"].as_str().unwrap_or("").to_string();
+        let timestamp = line["timestamp"].as_str().unwrap_or("").to_string();
+
+        for block in blocks {
+            if block["type"].as_str() != Some("tool_use") {
+                continue;
+            }
+            let tool = block["name"].as_str().unwrap_or("");
+            let input = &block["input"];
+            let (paths, command) = match tool {
+                "Edit" | "Write" | "MultiEdit" | "NotebookEdit" => {
+                    (super::session_diff::tool_file_paths(tool, input), None)
+                }
+                "Bash" => {
+                    let Some(cmd) = input["command"].as_str() else {
+                        continue;
+                    };
+                    (
+                        bash_touched_paths(cmd),
+                        Some(cmd.chars().take(COMMAND_EXCERPT_CHARS).collect()),
+                    )
+                }
+                _ => continue,
+            };
+            let mut dedup = HashSet::new();
+            for p in paths {
+                let abs = absolutize(&p, &cwd);
+                if !dedup.insert(abs.clone()) {
+                    continue;
+                }
+                touches.push(FileTouch {
+                    path: abs,
+                    tool: tool.to_string(),
+                    message_uuid: message_uuid.clone(),
+                    timestamp: timestamp.clone(),
+                    command: command.clone(),
+                });
+            }
+        }
+    }
+    (project, touches)
+}
+
+/// Resolve `path` against `cwd` and normalize `.` / `..` lexically — the
+/// file may no longer exist, so no canonicalization.
+fn absolutize(path: &str, cwd: &str) -> String {
+    let p = Path::new(path);
+    let joined = if p.is_absolute() || cwd.is_empty() {
+        p.to_path_buf()
+    } else {
+        Path::new(cwd).join(p)
+    };
+    normalize_lexically(&joined).to_string_lossy().to_string()
+}
+
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push("..");
+                }
+            }
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+/// Split a shell command into simple commands on `&&`, `||`, `;`, `|` and
+/// newlines, tokenizing words with basic quote handling. Redirect operators
+/// are emitted as their own tokens.
+fn shell_segments(cmd: &str) -> Vec<Vec<String>> {
+    let mut segments: Vec<Vec<String>> = vec![];
+    let mut words: Vec<String> = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = cmd.chars().peekable();
+
+    fn flush(word: &mut String, in_word: &mut bool, words: &mut Vec<String>) {
+        if *in_word {
+            words.push(std::mem::take(word));
+            *in_word = false;
+        }
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                for q in chars.by_ref() {
+                    if q == '\'' {
+                        break;
+                    }
+                    word.push(q);
+                }
+            }
+            '"' => {
+                in_word = true;
+                while let Some(q) = chars.next() {
+                    match q {
+                        '"' => break,
+                        '\\' => {
+                            if let Some(n) = chars.next() {
+                                word.push(n);
+                            }
+                        }
+                        _ => word.push(q),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(n) = chars.next() {
+                    if n != '\n' {
+                        word.push(n);
+                    }
+                }
+            }
+            ';' | '\n' | '|' | '&' => {
+                flush(&mut word, &mut in_word, &mut words);
+                // `&>` is a redirect, `2>&1` was consumed with its `>`
+                if c == '&' && chars.peek() == Some(&'>') {
+                    continue;
+                }
+                if (c == '|' || c == '&') && chars.peek() == Some(&c) {
+                    chars.next();
+                }
+                if !words.is_empty() {
+                    segments.push(std::mem::take(&mut words));
+                }
+            }
+            '>' => {
+                // Drop an fd prefix like `2` in `2>`.
+                if in_word && word.chars().all(|d| d.is_ascii_digit()) {
+                    word.clear();
+                    in_word = false;
+                }
+                flush(&mut word, &mut in_word, &mut words);
+                let mut op = String::from(">");
+                if chars.peek() == Some(&'>') {
+                    chars.next();
+                    op.push('>');
+                }
+                if chars.peek() == Some(&'&') {
+                    // `>&1` duplicates a descriptor — no file involved.
+                    chars.next();
+                    while chars
+                        .peek()
+                        .is_some_and(|d| d.is_ascii_digit() || *d == '-')
+                    {
+                        chars.next();
+                    }
+                    continue;
+                }
+                words.push(op);
+            }
+            c if c.is_whitespace() => flush(&mut word, &mut in_word, &mut words),
+            _ => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    flush(&mut word, &mut in_word, &mut words);
+    if !words.is_empty() {
+        segments.push(words);
+    }
+    segments
+}
+
+fn is_literal_path(word: &str) -> bool {
+    !word.is_empty()
+        && !word.starts_with('-')
+        && !word.contains(['$', '*', '?', '`', '{', '[', '~'])
+        && !word.starts_with("/dev/")
+}
+
+/// Best-effort extraction of files a Bash command writes, moves or deletes.
+/// Relative results stay relative to the command's working directory, with
+/// any `cd` inside the command folded in.
+fn bash_touched_paths(cmd: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    let mut dir = String::new();
+    let push = |dir: &str, p: &str, out: &mut Vec<String>| {
+        if !is_literal_path(p) {
+            return;
+        }
+        let full = if dir.is_empty() || Path::new(p).is_absolute() {
+            p.to_string()
+        } else {
+            Path::new(dir).join(p).to_string_lossy().to_string()
+        };
+        if !out.contains(&full) {
+            out.push(full);
+        }
+    };
+
+    for seg in shell_segments(cmd) {
+        // Redirect targets, then strip them from the argument list.
+        let mut args: Vec<&str> = Vec::new();
+        let mut it = seg.iter().peekable();
+        while let Some(w) = it.next() {
+            if w == ">" || w == ">>" {
+                if let Some(target) = it.next() {
+                    push(&dir, target, &mut out);
+                }
+            } else {
+                args.push(w);
+            }
+        }
+        // Skip env assignments and wrappers in front of the real program.
+        while let Some(first) = args.first() {
+            if first.contains('=') && !first.starts_with('-')
+                || matches!(*first, "sudo" | "command" | "env")
+            {
+                args.remove(0);
+            } else {
+                break;
+            }
+        }
+        let Some((&prog, rest)) = args.split_first() else {
+            continue;
+        };
+        let operands: Vec<&str> = rest
+            .iter()
+            .copied()
+            .filter(|a| !a.starts_with('-'))
+            .collect();
+        match prog {
+            "cd" => {
+                if let Some(target) = operands.first().filter(|t| is_literal_path(t)) {
+                    dir = if Path::new(target).is_absolute() || dir.is_empty() {
+                        target.to_string()
+                    } else {
+                        Path::new(&dir).join(target).to_string_lossy().to_string()
+                    };
+                }
+            }
+            "rm" | "mv" | "touch" | "tee" | "truncate" | "unlink" | "shred" => {
+                for p in operands {
+                    push(&dir, p, &mut out);
+                }
+            }
+            "cp" | "install" if operands.len() >= 2 => {
+                push(&dir, operands[operands.len() - 1], &mut out);
+            }
+            "sed" | "perl" => {
+                // The script is the argument of -e/-f (or a cluster ending
+                // in them, like `-ne`), --expression=/--file=, or else the
+                // first operand. The remaining operands are the files.
+                let mut in_place = false;
+                let mut script_given = false;
+                let mut files: Vec<&str> = Vec::new();
+                let mut it = rest.iter().copied();
+                while let Some(a) = it.next() {
+                    if a.starts_with("-i") || a.starts_with("--in-place") || a.starts_with("-pi") {
+                        in_place = true;
+                    } else if matches!(a, "-e" | "-f" | "--expression" | "--file")
+                        || (a.len() > 2
+                            && a.starts_with('-')
+                            && !a.starts_with("--")
+                            && a.ends_with(['e', 'f']))
+                    {
+                        script_given = true;
+                        it.next();
+                    } else if a.starts_with("--expression=") || a.starts_with("--file=") {
+                        script_given = true;
+                    } else if !a.starts_with('-') {
+                        files.push(a);
+                    }
+                }
+                if in_place {
+                    let skip = if script_given { 0 } else { 1 };
+                    for p in files.into_iter().skip(skip) {
+                        push(&dir, p, &mut out);
+                    }
+                }
+            }
+            "git" => {
+                let sub = operands.first().copied().unwrap_or("");
+                if matches!(sub, "rm" | "mv") {
+                    for p in operands.into_iter().skip(1) {
+                        push(&dir, p, &mut out);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+fn path_is_within(path: &str, dir: &str) -> bool {
+    let dir = dir.trim_end_matches(['/', '\\']);
+    path.strip_prefix(dir)
+        .is_some_and(|rest| rest.starts_with('/') || rest.starts_with('\\'))
+}
+
+fn relative_to(path: &str, base: &str) -> String {
+    if path_is_within(path, base) {
+        path[base.trim_end_matches(['/', '\\']).len() + 1..].to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+fn sessions_touching(
+    cache: &FileIndexCache,
+    target: &str,
+    recursive: bool,
+) -> Vec<FileHistoryEntry> {
+    let mut result: Vec<FileHistoryEntry> = cache
+        .sessions
+        .iter()
+        .filter_map(|(id, s)| {
+            let touches: Vec<FileTouch> = s
+                .touches
+                .iter()
+                .filter(|t| t.path == target || (recursive && path_is_within(&t.path, target)))
+                .cloned()
+                .collect();
+            if touches.is_empty() {
+                return None;
+            }
+            let last_touched = touches
+                .iter()
+                .map(|t| t.timestamp.clone())
+                .max()
+                .unwrap_or_default();
+            Some(FileHistoryEntry {
+                session_id: id.clone(),
+                project: s.project.clone(),
+                jsonl_path: s.jsonl_path.clone(),
+                last_touched,
+                touches,
+            })
+        })
+        .collect();
+    result.sort_by(|a, b| b.last_touched.cmp(&a.last_touched));
+    result
+}
+
+fn changed_files(session: &IndexedSession) -> Vec<ChangedFile> {
+    let mut by_path: BTreeMap<&str, Vec<FileTouch>> = BTreeMap::new();
+    for t in &session.touches {
+        by_path.entry(t.path.as_str()).or_default().push(t.clone());
+    }
+    by_path
+        .into_iter()
+        .map(|(path, touches)| ChangedFile {
+            path: path.to_string(),
+            relative_path: relative_to(path, &session.project),
+            touches,
+        })
+        .collect()
+}
+
+/// Sessions that modified `path`, newest first. With `recursive`, `path` is
+/// treated as a directory and touches of anything beneath it count.
+#[tauri::command]
+pub async fn find_sessions_touching_file(
+    state: State<'_, FileIndexState>,
+    path: String,
+    recursive: Option<bool>,
+) -> Result<Vec<FileHistoryEntry>, String> {
+    let inner = state.inner.clone();
+    let target = absolutize(&path, "");
+    let recursive = recursive.unwrap_or(false);
+    tokio::task::spawn_blocking(move || {
+        with_index(&inner, false, |cache, _| {
+            sessions_touching(cache, &target, recursive)
+        })
+    })
+    .await
+    .map_err(|e| format!("File index task failed: {}", e))?
+}
+
+/// Files a session modified, sorted by path, each with its touches in
+/// message order.
+#[tauri::command]
+pub async fn get_session_changed_files(
+    state: State<'_, FileIndexState>,
+    session_id: String,
+) -> Result<Vec<ChangedFile>, String> {
+    if uuid::Uuid::parse_str(&session_id).is_err() {
+        return Err(format!("Invalid session id: {}", session_id));
+    }
+    let inner = state.inner.clone();
+    tokio::task::spawn_blocking(move || {
+        with_index(&inner, false, |cache, _| {
+            cache
+                .sessions
+                .get(&session_id)
+                .map(changed_files)
+                .unwrap_or_default()
+        })
+    })
+    .await
+    .map_err(|e| format!("File index task failed: {}", e))?
+}
+
+/// Re-parse every tracked session (e.g. after upgrading extraction rules).
+#[tauri::command]
+pub async fn rebuild_file_index(
+    state: State<'_, FileIndexState>,
+) -> Result<FileIndexStats, String> {
+    let inner = state.inner.clone();
+    tokio::task::spawn_blocking(move || {
+        with_index(&inner, true, |cache, reindexed| {
+            let files: HashSet<&str> = cache
+                .sessions
+                .values()
+                .flat_map(|s| s.touches.iter().map(|t| t.path.as_str()))
+                .collect();
+            FileIndexStats {
+                sessions: cache.sessions.len(),
+                files: files.len(),
+                touches: cache.sessions.values().map(|s| s.touches.len()).sum(),
+                reindexed,
+            }
+        })
+    })
+    .await
+    .map_err(|e| format!("File index task failed: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tool_line(uuid: &str, cwd: &str, name: &str, input: Value) -> Value {
+        json!({
+            "type": "assistant",
+            "uuid": uuid,
+            "cwd": cwd,
+            "timestamp": "2026-01-02T03:04:05.000Z",
+            "message": {"content": [{"type": "tool_use", "name": name, "input": input}]}
+        })
+    }
+
+    #[test]
+    fn extracts_edit_tools_and_skips_reads() {
+        let lines = vec![
+            tool_line("a", "/p", "Read", json!({"file_path": "/p/x.rs"})),
+            tool_line("b", "/p", "Edit", json!({"file_path": "/p/src/../x.rs"})),
+            tool_line(
+                "c",
+                "/p",
+                "NotebookEdit",
+                json!({"notebook_path": "nb.ipynb"}),
+            ),
+            tool_line("d", "/p", "Bash", json!({"command": "cargo test"})),
+        ];
+        let (project, touches) = extract_touches(lines.into_iter());
+        assert_eq!(project, "/p");
+        let got: Vec<(&str, &str)> = touches
+            .iter()
+            .map(|t| (t.path.as_str(), t.tool.as_str()))
+            .collect();
+        assert_eq!(
+            got,
+            vec![("/p/x.rs", "Edit"), ("/p/nb.ipynb", "NotebookEdit")]
+        );
+        assert_eq!(touches[0].message_uuid, "b");
+    }
+
+    #[test]
+    fn bash_extraction_covers_common_writers() {
+        assert_eq!(
+            bash_touched_paths("rm -rf build dist"),
+            vec!["build", "dist"]
+        );
+        assert_eq!(bash_touched_paths("cp a.txt b/c.txt"), vec!["b/c.txt"]);
+        assert_eq!(
+            bash_touched_paths("echo hi > out.log 2>&1 && cat x | tee -a t.txt"),
+            vec!["out.log", "t.txt"]
+        );
+        assert_eq!(
+            bash_touched_paths("sed -i 's/a/b/' src/main.rs"),
+            vec!["src/main.rs"]
+        );
+        assert_eq!(
+            bash_touched_paths("cd sub && git mv old.rs new.rs"),
+            vec!["sub/old.rs", "sub/new.rs"]
+        );
+        assert!(bash_touched_paths("rm $TMP/*.o; ls > /dev/null").is_empty());
+        assert_eq!(
+            bash_touched_paths("touch \"with space.md\""),
+            vec!["with space.md"]
+        );
+    }
+
+    #[test]
+    fn sed_script_arguments_are_not_paths() {
+        assert_eq!(
+            bash_touched_paths("sed -i -e 's/a/b/' -e 's/c/d/' a.txt b.txt"),
+            vec!["a.txt", "b.txt"]
+        );
+        assert_eq!(
+            bash_touched_paths("sed -i -f fix.sed src/x.rs"),
+            vec!["src/x.rs"]
+        );
+        assert_eq!(
+            bash_touched_paths("sed --in-place=.bak --expression='s/a/b/' y.rs"),
+            vec!["y.rs"]
+        );
+        assert_eq!(
+            bash_touched_paths("perl -i -pe 's/a/b/' w.rs"),
+            vec!["w.rs"]
+        );
+        assert!(bash_touched_paths("sed -n -e p notes.txt").is_empty());
+    }
+
+    #[test]
+    fn bash_paths_resolve_against_line_cwd() {
+        let lines = vec![tool_line(
+            "a",
+            "/repo",
+            "Bash",
+            json!({"command": "mv ../notes.md docs/notes.md"}),
+        )];
+        let (_, touches) = extract_touches(lines.into_iter());
+        let paths: Vec<&str> = touches.iter().map(|t| t.path.as_str()).collect();
+        assert_eq!(paths, vec!["/notes.md", "/repo/docs/notes.md"]);
+        assert!(touches[0].command.as_deref().unwrap().starts_with("mv "));
+    }
+
+    #[test]
+    fn queries_group_by_session_and_file() {
+        let touch = |path: &str, ts: &str| FileTouch {
+            path: path.to_string(),
+            tool: "Edit".to_string(),
+            message_uuid: "m".to_string(),
+            timestamp: ts.to_string(),
+            command: None,
+        };
+        let mut cache = FileIndexCache::default();
+        cache.sessions.insert(
+            "s1".into(),
+            IndexedSession {
+                project: "/p".into(),
+                touches: vec![
+                    touch("/p/a.rs", "2026-01-01"),
+                    touch("/p/src/b.rs", "2026-01-03"),
+                ],
+                ..Default::default()
+            },
+        );
+        cache.sessions.insert(
+            "s2".into(),
+            IndexedSession {
+                project: "/p".into(),
+                touches: vec![
+                    touch("/p/a.rs", "2026-01-02"),
+                    touch("/p/a.rs", "2026-01-04"),
+                ],
+                ..Default::default()
+            },
+        );
+
+        let hist = sessions_touching(&cache, "/p/a.rs", false);
+        assert_eq!(
+            hist.iter()
+                .map(|h| h.session_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["s2", "s1"]
+        );
+        assert_eq!(hist[0].touches.len(), 2);
+        assert_eq!(sessions_touching(&cache, "/p/src", true).len(), 1);
+        assert!(sessions_touching(&cache, "/p/sr", true).is_empty());
+
+        let files = changed_files(&cache.sessions["s1"]);
+        assert_eq!(files[1].relative_path, "src/b.rs");
+    }
+}
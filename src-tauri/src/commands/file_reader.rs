@@ -0,0 +1,795 @@
+//! Ranged, line-oriented reads for files too big for `read_file_content`.
+//!
+//! The viewer pages through logs and generated files of any size:
+//! `read_file_lines` (lines N..N+count), `tail_file_lines` (the last lines,
+//! found by scanning backwards from the end), `search_in_file` (matches,
+//! resumable) and `count_file_lines`.
+//!
+//! Line positions come from a sparse index: the byte offset of every
+//! `LINE_STRIDE`th line, recorded as reads scan forward. It is cached per
+//! file and dropped when the file's size or mtime changes. So jumping to
+//! line 10M of a multi-GB log scans once, and later jumps nearby are cheap.
+//!
+//! The encoding is detected from a BOM, or sniffed from the first bytes:
+//! UTF-16 by its NUL pattern, then UTF-8, then GBK (common for our Chinese
+//! users). The caller can also force one. Files that look binary are refused.
+
+use encoding_rs::{Encoding, GBK, UTF_16BE, UTF_16LE, UTF_8};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tauri::State;
+
+use super::project_search::{build_regex, clip, match_line, SearchMatch, SearchOptions};
+use crate::path_access::{PathAccessManager, PathCapability};
+
+/// Bytes sniffed for encoding and binary detection.
+const SNIFF_BYTES: usize = 64 * 1024;
+/// Lines between two recorded offsets in the line index.
+const LINE_STRIDE: usize = 1000;
+const READ_BUF: usize = 64 * 1024;
+const DEFAULT_LINE_COUNT: usize = 500;
+const MAX_LINE_COUNT: usize = 10_000;
+/// Longest line kept; the rest is dropped and `clipped` set.
+const MAX_LINE_BYTES: usize = 16 * 1024;
+/// Bytes one `search_in_file` call scans before returning `next_line`.
+const MAX_SEARCH_BYTES: u64 = 256 * 1024 * 1024;
+const DEFAULT_SEARCH_RESULTS: usize = 500;
+/// Line indexes kept in memory.
+const MAX_CACHED_INDEXES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Newline {
+    Byte,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Newline {
+    fn width(self) -> usize {
+        match self {
+            Newline::Byte => 1,
+            _ => 2,
+        }
+    }
+
+    /// Index of the first newline unit in `data`, which starts aligned.
+    fn find(self, data: &[u8]) -> Option<usize> {
+        match self {
+            Newline::Byte => data.iter().position(|&b| b == b'\n'),
+            Newline::Utf16Le => (0..data.len() / 2 * 2)
+                .step_by(2)
+                .find(|&i| data[i] == b'\n' && data[i + 1] == 0),
+            Newline::Utf16Be => (0..data.len() / 2 * 2)
+                .step_by(2)
+                .find(|&i| data[i] == 0 && data[i + 1] == b'\n'),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Detected {
+    encoding: &'static Encoding,
+    /// Byte offset of line 1 (past the BOM).
+    data_start: u64,
+    newline: Newline,
+    binary: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FileEncodingInfo {
+    pub encoding: String,
+    pub bom: bool,
+    pub binary: bool,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct FileLines {
+    pub lines: Vec<String>,
+    /// 1-based number of the first line. A tail only knows it once the file
+    /// has been counted.
+    pub start_line: Option<usize>,
+    pub start_byte: u64,
+    pub end_byte: u64,
+    pub eof: bool,
+    /// Known once a read or `count_file_lines` reached the end.
+    pub total_lines: Option<usize>,
+    pub size: u64,
+    pub encoding: String,
+    /// Some lines exceeded the per-line limit and were cut.
+    pub clipped: bool,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct FileSearchResult {
+    pub matches: Vec<SearchMatch>,
+    /// `max_results` was reached.
+    pub truncated: bool,
+    /// Where to continue when the scan stopped before the end.
+    pub next_line: Option<usize>,
+    pub total_lines: Option<usize>,
+    pub encoding: String,
+}
+
+fn newline_for(encoding: &'static Encoding) -> Newline {
+    if encoding == UTF_16LE {
+        Newline::Utf16Le
+    } else if encoding == UTF_16BE {
+        Newline::Utf16Be
+    } else {
+        Newline::Byte
+    }
+}
+
+/// Valid UTF-8, allowing a sequence cut off by the end of the sample.
+fn looks_utf8(sample: &[u8]) -> bool {
+    match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+fn looks_gbk(sample: &[u8]) -> bool {
+    // Cut after the last ASCII byte so a split double-byte char at the end
+    // of the sample doesn't count as malformed.
+    let end = sample
+        .iter()
+        .rposition(|b| *b < 0x80)
+        .map_or(sample.len(), |i| i + 1);
+    GBK.decode_without_bom_handling_and_without_replacement(&sample[..end])
+        .is_some()
+}
+
+fn detect(sample: &[u8], forced: Option<&str>) -> Result<Detected, String> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(sample) {
+        return Ok(Detected {
+            encoding,
+            data_start: bom_len as u64,
+            newline: newline_for(encoding),
+            binary: false,
+        });
+    }
+    if let Some(label) = forced {
+        let encoding = Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| format!("Unknown encoding: {}", label))?;
+        return Ok(Detected {
+            encoding,
+            data_start: 0,
+            newline: newline_for(encoding),
+            binary: false,
+        });
+    }
+
+    // ASCII-heavy UTF-16 without a BOM has NULs in every other byte.
+    let probe = &sample[..sample.len().min(4096) / 2 * 2];
+    let pairs = probe.len() / 2;
+    let even_nuls = probe.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_nuls = probe.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+    let utf16 = if pairs >= 2 && odd_nuls * 10 >= pairs * 3 && even_nuls * 20 < pairs {
+        Some(UTF_16LE)
+    } else if pairs >= 2 && even_nuls * 10 >= pairs * 3 && odd_nuls * 20 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    };
+    let encoding = match utf16 {
+        Some(encoding) => encoding,
+        None if sample.contains(&0) => {
+            return Ok(Detected {
+                encoding: UTF_8,
+                data_start: 0,
+                newline: Newline::Byte,
+                binary: true,
+            })
+        }
+        None if looks_utf8(sample) => UTF_8,
+        None if looks_gbk(sample) => GBK,
+        // Undecodable either way: show it as UTF-8 with replacement chars.
+        None => UTF_8,
+    };
+    Ok(Detected {
+        encoding,
+        data_start: 0,
+        newline: newline_for(encoding),
+        binary: false,
+    })
+}
+
+fn detect_file(file: &mut File, forced: Option<&str>) -> Result<Detected, String> {
+    let mut sample = Vec::with_capacity(SNIFF_BYTES);
+    file.by_ref()
+        .take(SNIFF_BYTES as u64)
+        .read_to_end(&mut sample)
+        .map_err(|e| format!("Cannot read file: {}", e))?;
+    detect(&sample, forced)
+}
+
+/// Decode one raw line (without its newline), dropping a trailing `\r`.
+fn decode_line(encoding: &'static Encoding, raw: &[u8]) -> String {
+    let (text, _) = encoding.decode_without_bom_handling(raw);
+    let text = text.strip_suffix('\r').unwrap_or(&text);
+    text.to_string()
+}
+
+/// Append up to the per-line limit of `bytes`; true when some were dropped.
+fn keep(out: &mut Vec<u8>, bytes: &[u8]) -> bool {
+    let room = MAX_LINE_BYTES.saturating_sub(out.len());
+    out.extend_from_slice(&bytes[..bytes.len().min(room)]);
+    bytes.len() > room
+}
+
+/// Reads raw lines forward from an aligned byte offset.
+struct LineReader<'a> {
+    file: &'a mut File,
+    newline: Newline,
+    buf: Vec<u8>,
+    start: usize,
+    end: usize,
+    /// Absolute offset of `buf[start]`.
+    pos: u64,
+}
+
+impl<'a> LineReader<'a> {
+    fn new(file: &'a mut File, newline: Newline, offset: u64) -> std::io::Result<Self> {
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(LineReader {
+            file,
+            newline,
+            buf: vec![0; READ_BUF],
+            start: 0,
+            end: 0,
+            pos: offset,
+        })
+    }
+
+    /// The next line into `out` (at most `MAX_LINE_BYTES` of it), returning
+    /// whether it was cut. `None` at end of file.
+    fn next_line(&mut self, out: &mut Vec<u8>) -> std::io::Result<Option<bool>> {
+        out.clear();
+        let line_start = self.pos;
+        let width = self.newline.width();
+        let mut cut = false;
+        loop {
+            if self.end - self.start < width {
+                self.buf.copy_within(self.start..self.end, 0);
+                self.end -= self.start;
+                self.start = 0;
+                let n = self.file.read(&mut self.buf[self.end..])?;
+                if n == 0 {
+                    // Last line without a trailing newline (or a stray byte).
+                    let rest = self.end - self.start;
+                    cut |= keep(out, &self.buf[self.start..self.end]);
+                    self.start = self.end;
+                    self.pos += rest as u64;
+                    return Ok((self.pos > line_start).then_some(cut));
+                }
+                self.end += n;
+                continue;
+            }
+            let data = &self.buf[self.start..self.end];
+            match self.newline.find(data) {
+                Some(i) => {
+                    cut |= keep(out, &data[..i]);
+                    self.start += i + width;
+                    self.pos += (i + width) as u64;
+                    return Ok(Some(cut));
+                }
+                None => {
+                    let usable = data.len() / width * width;
+                    cut |= keep(out, &data[..usable]);
+                    self.start += usable;
+                    self.pos += usable as u64;
+                }
+            }
+        }
+    }
+}
+
+/// Sparse line offsets of one file version.
+struct LineIndex {
+    size: u64,
+    modified: Option<SystemTime>,
+    detected: Detected,
+    /// `checkpoints[k]` is the byte offset of line `k * LINE_STRIDE + 1`.
+    checkpoints: Vec<u64>,
+    total_lines: Option<usize>,
+}
+
+impl LineIndex {
+    fn new(size: u64, modified: Option<SystemTime>, detected: Detected) -> Self {
+        LineIndex {
+            size,
+            modified,
+            detected,
+            checkpoints: vec![detected.data_start],
+            total_lines: None,
+        }
+    }
+
+    /// The closest known line at or before `line`, with its offset.
+    fn seek(&self, line: usize) -> (usize, u64) {
+        let k = ((line.max(1) - 1) / LINE_STRIDE).min(self.checkpoints.len() - 1);
+        (k * LINE_STRIDE + 1, self.checkpoints[k])
+    }
+
+    /// Visit lines from `from_line` on, recording offsets along the way.
+    /// `visit` gets the line number, raw bytes, whether the line was cut and
+    /// the line's start and end offsets, and returns false to stop.
+    fn scan(
+        &mut self,
+        file: &mut File,
+        from_line: usize,
+        mut visit: impl FnMut(usize, &[u8], bool, (u64, u64)) -> bool,
+    ) -> Result<(), String> {
+        let (mut line_no, offset) = self.seek(from_line);
+        let mut reader = LineReader::new(file, self.detected.newline, offset)
+            .map_err(|e| format!("Cannot read file: {}", e))?;
+        let mut raw = Vec::new();
+        loop {
+            let line_offset = reader.pos;
+            if (line_no - 1) % LINE_STRIDE == 0
+                && (line_no - 1) / LINE_STRIDE == self.checkpoints.len()
+            {
+                self.checkpoints.push(line_offset);
+            }
+            let Some(cut) = reader
+                .next_line(&mut raw)
+                .map_err(|e| format!("Cannot read file: {}", e))?
+            else {
+                self.total_lines = Some(line_no - 1);
+                return Ok(());
+            };
+            if line_no >= from_line && !visit(line_no, &raw, cut, (line_offset, reader.pos)) {
+                return Ok(());
+            }
+            line_no += 1;
+        }
+    }
+}
+
+/// Cached line indexes by canonical path.
+#[derive(Default)]
+pub struct FileReaderState {
+    indexes: Mutex<HashMap<PathBuf, LineIndex>>,
+}
+
+impl FileReaderState {
+    /// The cached index of `path` if it still matches the file on disk, or
+    /// a fresh one. Callers put it back with `store` when done.
+    fn take(
+        &self,
+        path: &Path,
+        file: &mut File,
+        forced: Option<&str>,
+    ) -> Result<LineIndex, String> {
+        let meta = file
+            .metadata()
+            .map_err(|e| format!("Cannot read file: {}", e))?;
+        let modified = meta.modified().ok();
+        if forced.is_none() {
+            let cached = self.indexes.lock().ok().and_then(|mut m| m.remove(path));
+            if let Some(index) = cached.filter(|i| i.size == meta.len() && i.modified == modified) {
+                return Ok(index);
+            }
+        }
+        let detected = detect_file(file, forced)?;
+        if detected.binary {
+            return Err("File appears to be binary".to_string());
+        }
+        Ok(LineIndex::new(meta.len(), modified, detected))
+    }
+
+    fn store(&self, path: PathBuf, index: LineIndex) {
+        if let Ok(mut indexes) = self.indexes.lock() {
+            if indexes.len() >= MAX_CACHED_INDEXES {
+                indexes.clear();
+            }
+            indexes.insert(path, index);
+        }
+    }
+}
+
+fn read_lines(
+    index: &mut LineIndex,
+    file: &mut File,
+    start_line: usize,
+    count: usize,
+) -> Result<FileLines, String> {
+    let encoding = index.detected.encoding;
+    let mut out = FileLines {
+        start_line: Some(start_line),
+        size: index.size,
+        encoding: encoding.name().to_string(),
+        ..Default::default()
+    };
+    let mut first = true;
+    index.scan(file, start_line, |_, raw, cut, (start, end)| {
+        if first {
+            out.start_byte = start;
+            first = false;
+        }
+        out.lines.push(decode_line(encoding, raw));
+        out.clipped |= cut;
+        out.end_byte = end;
+        out.lines.len() < count
+    })?;
+    if first {
+        out.start_byte = index.size;
+        out.end_byte = index.size;
+    }
+    out.total_lines = index.total_lines;
+    out.eof = out.end_byte >= index.size;
+    Ok(out)
+}
+
+/// Offset where the last `count` lines of the file start, scanning back
+/// from the end in aligned chunks.
+fn tail_offset(
+    file: &mut File,
+    detected: &Detected,
+    size: u64,
+    count: usize,
+) -> Result<u64, String> {
+    let width = detected.newline.width() as u64;
+    let data_start = detected.data_start;
+    let mut end = size - (size - data_start) % width;
+    // A newline ending the file does not start another line.
+    let mut skip_trailing = true;
+    let mut seen = 0usize;
+    let mut buf = vec![0u8; READ_BUF];
+    while end > data_start {
+        let chunk = (end - data_start).min(READ_BUF as u64);
+        let start = end - chunk;
+        file.seek(SeekFrom::Start(start))
+            .and_then(|_| file.read_exact(&mut buf[..chunk as usize]))
+            .map_err(|e| format!("Cannot read file: {}", e))?;
+        let data = &buf[..chunk as usize];
+        let mut i = data.len();
+        while i >= width as usize {
+            i -= width as usize;
+            if detected.newline.find(&data[i..i + width as usize]) != Some(0) {
+                continue;
+            }
+            if skip_trailing && start + i as u64 + width == size {
+                skip_trailing = false;
+                continue;
+            }
+            seen += 1;
+            if seen == count {
+                return Ok(start + i as u64 + width);
+            }
+        }
+        skip_trailing = false;
+        end = start;
+    }
+    Ok(data_start)
+}
+
+fn search_lines(
+    index: &mut LineIndex,
+    file: &mut File,
+    opts: &SearchOptions,
+    start_line: usize,
+) -> Result<FileSearchResult, String> {
+    let re = build_regex(opts)?;
+    let encoding = index.detected.encoding;
+    let max_results = opts.max_results.unwrap_or(DEFAULT_SEARCH_RESULTS).max(1);
+    let context = opts.context_lines.unwrap_or(0).min(10);
+    let mut result = FileSearchResult {
+        encoding: encoding.name().to_string(),
+        ..Default::default()
+    };
+    let mut before: VecDeque<String> = VecDeque::new();
+    // Matches still collecting `after` lines: (index, lines missing).
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut scanned = 0u64;
+
+    index.scan(file, start_line, |line_no, raw, _, (start, end)| {
+        let line = decode_line(encoding, raw);
+        for (i, missing) in open.iter_mut() {
+            result.matches[*i].after.push(clip(&line).to_string());
+            *missing -= 1;
+        }
+        open.retain(|(_, missing)| *missing > 0);
+
+        if let Some((text, ranges)) = match_line(&re, &line) {
+            if result.matches.len() == max_results {
+                result.truncated = true;
+                result.next_line = Some(line_no);
+                return false;
+            }
+            result.matches.push(SearchMatch {
+                line: line_no,
+                text,
+                ranges,
+                before: before.iter().cloned().collect(),
+                after: Vec::new(),
+            });
+            if context > 0 {
+                open.push((result.matches.len() - 1, context));
+            }
+        }
+        if context > 0 {
+            if before.len() == context {
+                before.pop_front();
+            }
+            before.push_back(clip(&line).to_string());
+        }
+        scanned += end - start;
+        if scanned >= MAX_SEARCH_BYTES {
+            result.next_line = Some(line_no + 1);
+            return false;
+        }
+        true
+    })?;
+    result.total_lines = index.total_lines;
+    Ok(result)
+}
+
+/// Validate `path`, open it and run `f` with its line index on a blocking
+/// thread.
+async fn with_file<T: Send + 'static>(
+    path_access: &PathAccessManager,
+    state: &FileReaderState,
+    path: &str,
+    tab_id: Option<&str>,
+    encoding: Option<String>,
+    f: impl FnOnce(&mut LineIndex, &mut File) -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    let p = path_access
+        .validate(Path::new(path), tab_id, PathCapability::Read)
+        .await?;
+    let mut file = File::open(&p).map_err(|e| format!("Cannot open file: {}", e))?;
+    let mut index = state.take(&p, &mut file, encoding.as_deref())?;
+    let (index, result) = tokio::task::spawn_blocking(move || {
+        let result = f(&mut index, &mut file);
+        (index, result)
+    })
+    .await
+    .map_err(|e| format!("File read failed: {}", e))?;
+    state.store(p, index);
+    result
+}
+
+/// Encoding, BOM and binary detection for `path`.
+#[tauri::command]
+pub async fn detect_file_encoding(
+    path_access: State<'_, PathAccessManager>,
+    path: String,
+    tab_id: Option<String>,
+) -> Result<FileEncodingInfo, String> {
+    let p = path_access
+        .validate(Path::new(&path), tab_id.as_deref(), PathCapability::Read)
+        .await?;
+    let mut file = File::open(&p).map_err(|e| format!("Cannot open file: {}", e))?;
+    let size = file
+        .metadata()
+        .map_err(|e| format!("Cannot read file: {}", e))?
+        .len();
+    let detected = detect_file(&mut file, None)?;
+    Ok(FileEncodingInfo {
+        encoding: detected.encoding.name().to_string(),
+        bom: detected.data_start > 0,
+        binary: detected.binary,
+        size,
+    })
+}
+
+/// `line_count` lines (default 500, max 10k) starting at 1-based
+/// `start_line`. `encoding` forces a WHATWG label such as `gbk`.
+#[tauri::command]
+pub async fn read_file_lines(
+    path_access: State<'_, PathAccessManager>,
+    state: State<'_, FileReaderState>,
+    path: String,
+    start_line: usize,
+    line_count: Option<usize>,
+    encoding: Option<String>,
+    tab_id: Option<String>,
+) -> Result<FileLines, String> {
+    let count = line_count
+        .unwrap_or(DEFAULT_LINE_COUNT)
+        .clamp(1, MAX_LINE_COUNT);
+    with_file(
+        &path_access,
+        &state,
+        &path,
+        tab_id.as_deref(),
+        encoding,
+        move |index, file| read_lines(index, file, start_line.max(1), count),
+    )
+    .await
+}
+
+/// The last `line_count` lines (default 500, max 10k).
+#[tauri::command]
+pub async fn tail_file_lines(
+    path_access: State<'_, PathAccessManager>,
+    state: State<'_, FileReaderState>,
+    path: String,
+    line_count: Option<usize>,
+    encoding: Option<String>,
+    tab_id: Option<String>,
+) -> Result<FileLines, String> {
+    let count = line_count
+        .unwrap_or(DEFAULT_LINE_COUNT)
+        .clamp(1, MAX_LINE_COUNT);
+    with_file(
+        &path_access,
+        &state,
+        &path,
+        tab_id.as_deref(),
+        encoding,
+        move |index, file| {
+            let offset = tail_offset(file, &index.detected, index.size, count)?;
+            let encoding = index.detected.encoding;
+            let mut out = FileLines {
+                start_byte: offset,
+                end_byte: offset,
+                size: index.size,
+                encoding: encoding.name().to_string(),
+                total_lines: index.total_lines,
+                eof: true,
+                ..Default::default()
+            };
+            let mut reader = LineReader::new(file, index.detected.newline, offset)
+                .map_err(|e| format!("Cannot read file: {}", e))?;
+            let mut raw = Vec::new();
+            while let Some(cut) = reader
+                .next_line(&mut raw)
+                .map_err(|e| format!("Cannot read file: {}", e))?
+            {
+                out.lines.push(decode_line(encoding, &raw));
+                out.clipped |= cut;
+            }
+            out.end_byte = reader.pos;
+            out.start_line = index.total_lines.map(|t| t + 1 - out.lines.len());
+            Ok(out)
+        },
+    )
+    .await
+}
+
+/// Search one file line by line from `start_line` (default 1). Stops after
+/// `options.max_results` matches (default 500) or 256 MB scanned; continue
+/// from `next_line`.
+#[tauri::command]
+pub async fn search_in_file(
+    path_access: State<'_, PathAccessManager>,
+    state: State<'_, FileReaderState>,
+    path: String,
+    options: SearchOptions,
+    start_line: Option<usize>,
+    encoding: Option<String>,
+    tab_id: Option<String>,
+) -> Result<FileSearchResult, String> {
+    with_file(
+        &path_access,
+        &state,
+        &path,
+        tab_id.as_deref(),
+        encoding,
+        move |index, file| search_lines(index, file, &options, start_line.unwrap_or(1).max(1)),
+    )
+    .await
+}
+
+/// Count all lines, completing the line index so paging knows the end.
+#[tauri::command]
+pub async fn count_file_lines(
+    path_access: State<'_, PathAccessManager>,
+    state: State<'_, FileReaderState>,
+    path: String,
+    encoding: Option<String>,
+    tab_id: Option<String>,
+) -> Result<usize, String> {
+    with_file(
+        &path_access,
+        &state,
+        &path,
+        tab_id.as_deref(),
+        encoding,
+        |index, file| {
+            if let Some(total) = index.total_lines {
+                return Ok(total);
+            }
+            let from = (index.checkpoints.len() - 1) * LINE_STRIDE + 1;
+            index.scan(file, from, |_, _, _, _| true)?;
+            Ok(index.total_lines.unwrap_or(0))
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::project_search::SearchMode;
+    use super::*;
+
+    fn open_index(path: &Path, forced: Option<&str>) -> (LineIndex, File) {
+        let mut file = File::open(path).unwrap();
+        let index = FileReaderState::default()
+            .take(path, &mut file, forced)
+            .unwrap();
+        (index, file)
+    }
+
+    #[test]
+    fn detects_encodings_and_binary() {
+        let utf16le: Vec<u8> = "hello\nworld\n"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        assert_eq!(detect(&utf16le, None).unwrap().encoding, UTF_16LE);
+        let mut bom = vec![0xFE, 0xFF];
+        bom.extend("hi".encode_utf16().flat_map(|u| u.to_be_bytes()));
+        let d = detect(&bom, None).unwrap();
+        assert_eq!((d.encoding, d.data_start), (UTF_16BE, 2));
+
+        let (gbk, _, _) = GBK.encode("中文日志：构建成功\n");
+        assert_eq!(detect(&gbk, None).unwrap().encoding, GBK);
+        assert_eq!(detect("中文".as_bytes(), None).unwrap().encoding, UTF_8);
+        assert!(detect(b"\x7fELF\x02\x01\x01\0\0\0\0", None).unwrap().binary);
+        assert_eq!(detect(b"abc", Some("gbk")).unwrap().encoding, GBK);
+        assert!(detect(b"abc", Some("nope")).is_err());
+    }
+
+    #[test]
+    fn pages_tails_and_searches_large_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("big.log");
+        let text: String = (1..=2500).map(|i| format!("line {}\r\n", i)).collect();
+        std::fs::write(&path, &text).unwrap();
+
+        let (mut index, mut file) = open_index(&path, None);
+        let page = read_lines(&mut index, &mut file, 1999, 3).unwrap();
+        assert_eq!(page.lines, ["line 1999", "line 2000", "line 2001"]);
+        assert!(!page.eof && page.total_lines.is_none());
+        assert_eq!(index.checkpoints.len(), 3);
+        assert_eq!(
+            index.seek(2001),
+            (2001, text.find("line 2001").unwrap() as u64)
+        );
+
+        let end = read_lines(&mut index, &mut file, 2499, 10).unwrap();
+        assert_eq!(end.lines, ["line 2499", "line 2500"]);
+        assert!(end.eof);
+        assert_eq!(end.total_lines, Some(2500));
+
+        let offset = tail_offset(&mut file, &index.detected, index.size, 2).unwrap();
+        assert_eq!(offset, text.find("line 2499").unwrap() as u64);
+
+        let opts = SearchOptions {
+            query: "^line 2.?5$".into(),
+            mode: SearchMode::Regex,
+            context_lines: Some(1),
+            max_results: Some(2),
+            ..Default::default()
+        };
+        let found = search_lines(&mut index, &mut file, &opts, 1).unwrap();
+        let lines: Vec<usize> = found.matches.iter().map(|m| m.line).collect();
+        assert_eq!(lines, [25, 205]);
+        assert_eq!(found.matches[0].before, ["line 24"]);
+        assert_eq!(found.matches[0].after, ["line 26"]);
+        assert!(found.truncated);
+        assert_eq!(found.next_line, Some(215));
+
+        // UTF-16 without BOM, no trailing newline.
+        let wide = tmp.path().join("wide.txt");
+        let bytes: Vec<u8> = "第一行\n第二行\n第三行"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        std::fs::write(&wide, &bytes).unwrap();
+        let (mut index, mut file) = open_index(&wide, Some("utf-16le"));
+        let page = read_lines(&mut index, &mut file, 2, 5).unwrap();
+        assert_eq!(page.lines, ["第二行", "第三行"]);
+        let offset = tail_offset(&mut file, &index.detected, index.size, 1).unwrap();
+        assert_eq!(offset, 16);
+    }
+}
@@ -0,0 +1,503 @@
+//! Lazy, gitignore-aware file tree for the explorer.
+//!
+//! The explorer asks for one directory at a time (`list_directory`) or, for
+//! a first paint of a large repository, starts a breadth-first walk that
+//! streams directory listings as `file-tree:chunk` events
+//! (`stream_file_tree`). Both honor `.gitignore`/`.ignore` via
+//! `ignore_rules`, plus per-project settings in `~/.tokenicode/file_tree.json`:
+//!
+//!   - `exclude` — gitignore-style globs that are always hidden
+//!   - `include` — globs shown even when ignored (e.g. `.env`); an included
+//!     file inside an ignored directory needs the directory included too
+//!   - `max_entries_per_dir` — listing cap; the rest is paged with `offset`
+//!
+//! Directories no ignore file applies to fall back to `FALLBACK_SKIP`, so a
+//! project without a `.gitignore` still hides `node_modules` and friends.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+use super::ignore_rules::{rel_path, GlobList, IgnoreMatcher};
+use crate::events::emit_to_frontend;
+use crate::path_access::{PathAccessManager, PathCapability};
+
+const CONFIG_FILE: &str = "file_tree.json";
+const DEFAULT_MAX_ENTRIES_PER_DIR: usize = 1000;
+const DEFAULT_STREAM_DEPTH: u32 = 3;
+/// A streamed walk stops after this many entries in total.
+const MAX_STREAM_ENTRIES: usize = 50_000;
+/// Entries per `file-tree:chunk` event.
+const CHUNK_ENTRIES: usize = 500;
+/// Hidden when no ignore rules apply (`.git` and OS clutter are always
+/// hidden by `ignore_rules`). The fs watcher skips the same directories.
+pub(crate) const FALLBACK_SKIP: &[&str] = &[
+    "node_modules",
+    "target",
+    "__pycache__",
+    ".venv",
+    "venv",
+    ".env",
+    "dist",
+    "build",
+    ".next",
+    ".nuxt",
+    ".parcel-cache",
+    "coverage",
+    ".turbo",
+    ".svelte-kit",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct FileTreeConfig {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_entries_per_dir: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct TreeEntry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DirListing {
+    pub dir: String,
+    pub entries: Vec<TreeEntry>,
+    /// Visible entries in the directory, before paging.
+    pub total: usize,
+    /// More entries exist past this page.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct FileTreeChunk {
+    request_id: String,
+    dirs: Vec<DirListing>,
+    done: bool,
+    /// The walk hit the total entry cap (only on the final chunk).
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    capped: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cancelled: bool,
+}
+
+/// Cancellation flags of running `stream_file_tree` walks.
+#[derive(Default)]
+pub struct FileTreeState {
+    streams: std::sync::Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+/// Project settings resolved into matchers.
+pub(crate) struct TreeFilter {
+    root: PathBuf,
+    include: GlobList,
+    exclude: GlobList,
+    max_entries_per_dir: usize,
+}
+
+impl TreeFilter {
+    pub(crate) fn new(root: &Path, config: &FileTreeConfig) -> Self {
+        TreeFilter {
+            root: root.to_path_buf(),
+            include: GlobList::new(&config.include),
+            exclude: GlobList::new(&config.exclude),
+            max_entries_per_dir: config
+                .max_entries_per_dir
+                .unwrap_or(DEFAULT_MAX_ENTRIES_PER_DIR)
+                .max(1),
+        }
+    }
+
+    pub(crate) fn for_root(root: &Path) -> Self {
+        Self::new(root, &load_config(root))
+    }
+
+    fn visible(&self, ignore: &IgnoreMatcher, path: &Path, is_dir: bool) -> bool {
+        let rel = rel_path(&self.root, path).unwrap_or_default();
+        if self.exclude.matches(&rel, is_dir) {
+            return false;
+        }
+        let ignored = ignore.matched(path, is_dir)
+            || (!ignore.has_rules()
+                && path
+                    .file_name()
+                    .is_some_and(|n| FALLBACK_SKIP.iter().any(|s| n == *s)));
+        !ignored || self.include.matches(&rel, is_dir)
+    }
+}
+
+fn load_all_configs() -> HashMap<String, FileTreeConfig> {
+    crate::tokenicode_data_path(CONFIG_FILE)
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+pub(crate) fn load_config(root: &Path) -> FileTreeConfig {
+    load_all_configs()
+        .remove(root.to_string_lossy().as_ref())
+        .unwrap_or_default()
+}
+
+/// One page of a directory's visible entries, directories first, then by
+/// case-insensitive name.
+pub(crate) fn list_dir(
+    dir: &Path,
+    filter: &TreeFilter,
+    ignore: &IgnoreMatcher,
+    offset: usize,
+    limit: Option<usize>,
+) -> DirListing {
+    let limit = limit.unwrap_or(filter.max_entries_per_dir);
+    let mut listing = DirListing {
+        dir: dir.to_string_lossy().to_string(),
+        entries: Vec::new(),
+        total: 0,
+        truncated: false,
+    };
+    let Ok(read) = std::fs::read_dir(dir) else {
+        return listing;
+    };
+
+    // PATCH C (v0.10.5): snapshot is_dir() + lowercase name BEFORE sort_by.
+    //
+    // Calling `path().is_dir()` inside the comparator is a filesystem
+    // syscall on every comparison. While the sort runs, the Claude CLI
+    // concurrently writes SDK checkpoint / temp files into the workspace,
+    // so the same entry can return `true` on one call and `false` on the next.
+    // Rust 1.81+'s strict total-order check detects this violation and panics
+    // the tokio worker thread, tearing down all async tauri commands (CLI
+    // detection, file scan, chat) → "CLI env / file manager disappears after
+    // first response" + flood of "Couldn't find callback id" warnings.
+    let mut entries: Vec<(TreeEntry, String)> = read
+        .flatten()
+        .filter_map(|e| {
+            let path = e.path();
+            let is_symlink = e.file_type().is_ok_and(|t| t.is_symlink());
+            let is_dir = path.is_dir();
+            if !filter.visible(ignore, &path, is_dir) {
+                return None;
+            }
+            let name = e.file_name().to_string_lossy().to_string();
+            let lower = name.to_lowercase();
+            Some((
+                TreeEntry {
+                    name,
+                    path: path.to_string_lossy().to_string(),
+                    is_dir,
+                    is_symlink,
+                },
+                lower,
+            ))
+        })
+        .collect();
+    entries.sort_by(|a, b| b.0.is_dir.cmp(&a.0.is_dir).then_with(|| a.1.cmp(&b.1)));
+
+    listing.total = entries.len();
+    listing.entries = entries
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(|(e, _)| e)
+        .collect();
+    listing.truncated = offset + listing.entries.len() < listing.total;
+    listing
+}
+
+/// Breadth-first walk below `root`, calling `emit` with batches of
+/// directory listings. Symlinked directories are listed but not entered.
+fn walk_streaming(
+    root: &Path,
+    filter: &TreeFilter,
+    depth: u32,
+    cancel: &AtomicBool,
+    mut emit: impl FnMut(Vec<DirListing>),
+) -> (bool, bool) {
+    let mut queue = VecDeque::from([(root.to_path_buf(), IgnoreMatcher::for_dir(root), 0u32)]);
+    let mut batch: Vec<DirListing> = Vec::new();
+    let (mut batch_entries, mut total) = (0usize, 0usize);
+    let mut capped = false;
+
+    while let Some((dir, ignore, level)) = queue.pop_front() {
+        if cancel.load(Ordering::Relaxed) {
+            return (false, true);
+        }
+        let listing = list_dir(&dir, filter, &ignore, 0, None);
+        if level < depth {
+            for entry in listing.entries.iter().filter(|e| e.is_dir && !e.is_symlink) {
+                let child = PathBuf::from(&entry.path);
+                let child_ignore = ignore.child(&child);
+                queue.push_back((child, child_ignore, level + 1));
+            }
+        }
+        total += listing.entries.len();
+        batch_entries += listing.entries.len();
+        batch.push(listing);
+        if total >= MAX_STREAM_ENTRIES {
+            capped = true;
+            break;
+        }
+        if batch_entries >= CHUNK_ENTRIES {
+            emit(std::mem::take(&mut batch));
+            batch_entries = 0;
+        }
+    }
+    if !batch.is_empty() {
+        emit(batch);
+    }
+    (capped, false)
+}
+
+/// One directory of the explorer, paged by `offset`/`limit` (limit defaults
+/// to the project's `max_entries_per_dir`). `root` is the project root the
+/// include/exclude settings belong to.
+#[tauri::command]
+pub async fn list_directory(
+    path_access: State<'_, PathAccessManager>,
+    root: String,
+    dir: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    tab_id: Option<String>,
+) -> Result<DirListing, String> {
+    let dir_path = path_access
+        .validate(Path::new(&dir), tab_id.as_deref(), PathCapability::Read)
+        .await?;
+    if !dir_path.is_dir() {
+        return Err(format!("Not a directory: {}", dir));
+    }
+    let root = PathBuf::from(root);
+    tokio::task::spawn_blocking(move || {
+        let filter = TreeFilter::for_root(&root);
+        let ignore = IgnoreMatcher::for_dir_under(&root, Path::new(&dir));
+        list_dir(
+            Path::new(&dir),
+            &filter,
+            &ignore,
+            offset.unwrap_or(0),
+            limit,
+        )
+    })
+    .await
+    .map_err(|e| format!("Failed to list directory: {}", e))
+}
+
+/// Walk `root` breadth-first to `depth` (default 3), emitting
+/// `file-tree:chunk` events tagged with `request_id`. Resolves when the walk
+/// ends; the last chunk has `done: true`.
+#[tauri::command]
+pub async fn stream_file_tree(
+    app: AppHandle,
+    path_access: State<'_, PathAccessManager>,
+    state: State<'_, FileTreeState>,
+    root: String,
+    request_id: String,
+    depth: Option<u32>,
+) -> Result<(), String> {
+    let root_path = PathBuf::from(&root);
+    if !root_path.is_dir() {
+        return Err("Directory does not exist".to_string());
+    }
+    // Same as read_file_tree: the browsed directory becomes a fixed root.
+    path_access.register_cwd(&root_path).await;
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    state
+        .streams
+        .lock()
+        .map_err(|e| format!("File tree state poisoned: {}", e))?
+        .insert(request_id.clone(), cancel.clone());
+
+    let depth = depth.unwrap_or(DEFAULT_STREAM_DEPTH);
+    let id = request_id.clone();
+    let app_for_walk = app.clone();
+    let flag = cancel.clone();
+    let walk = tokio::task::spawn_blocking(move || {
+        let filter = TreeFilter::for_root(&root_path);
+        let (capped, cancelled) = walk_streaming(&root_path, &filter, depth, &flag, |dirs| {
+            let _ = emit_to_frontend(
+                &app_for_walk,
+                "file-tree:chunk",
+                FileTreeChunk {
+                    request_id: id.clone(),
+                    dirs,
+                    done: false,
+                    capped: false,
+                    cancelled: false,
+                },
+            );
+        });
+        let _ = emit_to_frontend(
+            &app_for_walk,
+            "file-tree:chunk",
+            FileTreeChunk {
+                request_id: id,
+                dirs: Vec::new(),
+                done: true,
+                capped,
+                cancelled,
+            },
+        );
+    })
+    .await;
+
+    if let Ok(mut streams) = state.streams.lock() {
+        streams.remove(&request_id);
+    }
+    walk.map_err(|e| format!("File tree walk failed: {}", e))
+}
+
+/// Stop a running `stream_file_tree` walk.
+#[tauri::command]
+pub async fn cancel_file_tree(
+    state: State<'_, FileTreeState>,
+    request_id: String,
+) -> Result<(), String> {
+    if let Some(flag) = state
+        .streams
+        .lock()
+        .map_err(|e| format!("File tree state poisoned: {}", e))?
+        .get(&request_id)
+    {
+        flag.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_file_tree_config(root: String) -> Result<FileTreeConfig, String> {
+    Ok(load_config(Path::new(&root)))
+}
+
+/// Save include/exclude settings for `root`; an empty config removes the entry.
+#[tauri::command]
+pub async fn set_file_tree_config(root: String, config: FileTreeConfig) -> Result<(), String> {
+    let mut all = load_all_configs();
+    if config == FileTreeConfig::default() {
+        all.remove(&root);
+    } else {
+        all.insert(root, config);
+    }
+    let path = crate::tokenicode_data_path(CONFIG_FILE)?;
+    let text = serde_json::to_string_pretty(&all)
+        .map_err(|e| format!("Failed to serialize file tree settings: {}", e))?;
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write file tree settings: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(listing: &DirListing) -> Vec<&str> {
+        listing.entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn lists_with_gitignore_include_exclude_and_paging() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(root.join(".gitignore"), "dist/\n.env\n*.log\n").unwrap();
+        for d in ["dist", "src", "Docs"] {
+            std::fs::create_dir_all(root.join(d)).unwrap();
+        }
+        for f in [".env", "a.log", "b.txt", "README.md", "notes.tmp"] {
+            std::fs::write(root.join(f), "x").unwrap();
+        }
+
+        let ignore = IgnoreMatcher::for_dir(root);
+        let plain = TreeFilter::new(root, &FileTreeConfig::default());
+        let listing = list_dir(root, &plain, &ignore, 0, None);
+        assert_eq!(
+            names(&listing),
+            [
+                "Docs",
+                "src",
+                ".gitignore",
+                "b.txt",
+                "notes.tmp",
+                "README.md"
+            ]
+        );
+
+        let config = FileTreeConfig {
+            include: vec![".env".into()],
+            exclude: vec!["*.tmp".into()],
+            max_entries_per_dir: Some(3),
+        };
+        let filter = TreeFilter::new(root, &config);
+        let first = list_dir(root, &filter, &ignore, 0, None);
+        assert_eq!(first.total, 6);
+        assert!(first.truncated);
+        assert_eq!(names(&first), ["Docs", "src", ".env"]);
+        let rest = list_dir(root, &filter, &ignore, 3, None);
+        assert_eq!(names(&rest), [".gitignore", "b.txt", "README.md"]);
+        assert!(!rest.truncated);
+    }
+
+    #[test]
+    fn fallback_skip_list_and_root_rules_below_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        for d in ["node_modules", "dist", "src/build", "src/lib"] {
+            std::fs::create_dir_all(root.join(d)).unwrap();
+        }
+        std::fs::write(root.join("src/a.log"), "x").unwrap();
+        let filter = TreeFilter::new(root, &FileTreeConfig::default());
+
+        // No ignore files anywhere: the fallback list applies.
+        let listing = list_dir(root, &filter, &IgnoreMatcher::for_dir(root), 0, None);
+        assert_eq!(names(&listing), ["src"]);
+
+        // An ignore file at the (non-repository) root replaces the fallback
+        // and still applies when listing a subdirectory.
+        std::fs::write(root.join(".ignore"), "*.log\n").unwrap();
+        let src = root.join("src");
+        let ignore = IgnoreMatcher::for_dir_under(root, &src);
+        assert_eq!(
+            names(&list_dir(&src, &filter, &ignore, 0, None)),
+            ["build", "lib"]
+        );
+        let listing = list_dir(root, &filter, &IgnoreMatcher::for_dir(root), 0, None);
+        assert_eq!(names(&listing), ["dist", "node_modules", "src", ".ignore"]);
+    }
+
+    #[test]
+    fn streaming_walk_batches_and_respects_depth() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::write(root.join(".ignore"), "skipped/\n").unwrap();
+        std::fs::create_dir_all(root.join("a/b/c")).unwrap();
+        std::fs::create_dir_all(root.join("skipped/inner")).unwrap();
+        for i in 0..(CHUNK_ENTRIES + 10) {
+            std::fs::write(root.join("a").join(format!("f{}.txt", i)), "").unwrap();
+        }
+
+        let filter = TreeFilter::new(root, &FileTreeConfig::default());
+        let cancel = AtomicBool::new(false);
+        let mut chunks: Vec<Vec<DirListing>> = Vec::new();
+        let (capped, cancelled) = walk_streaming(root, &filter, 1, &cancel, |d| chunks.push(d));
+        assert!(!capped && !cancelled);
+        assert_eq!(chunks.len(), 1);
+        let dirs: Vec<&str> = chunks[0].iter().map(|d| d.dir.as_str()).collect();
+        // Depth 1: root and `a` are listed, `a/b` is not entered.
+        assert_eq!(dirs.len(), 2);
+        assert!(dirs[1].ends_with("a"));
+        assert!(chunks[0][0].entries.iter().all(|e| e.name != "skipped"));
+
+        cancel.store(true, Ordering::Relaxed);
+        let (_, cancelled) = walk_streaming(root, &filter, 3, &cancel, |_| {});
+        assert!(cancelled);
+    }
+}
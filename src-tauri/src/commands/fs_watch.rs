@@ -0,0 +1,522 @@
+//! Coalescing layer between the notify watcher and `fs:change`.
+//!
+//! Agent edits and builds produce floods of create/modify/remove events for
+//! the same paths. `watch_directory` feeds every raw event of a root into an
+//! [`FsCoalescer`]. The coalescer:
+//!
+//!   - drops paths ignored by `.gitignore`/`.ignore` (plus `.git`, the CLI's
+//!     `.claude` dir and our own `.tokenicode` scratch dir), and the file
+//!     tree's `FALLBACK_SKIP` dirs where no ignore rules apply
+//!   - folds repeated events per path (create + modify = create, create +
+//!     remove = nothing, remove + create = modify)
+//!   - pairs renames into `{from, to}` using notify's tracker or the
+//!     From/To order
+//!   - emits one batch after a quiet period (`DEBOUNCE`), or after
+//!     `MAX_LATENCY` under a constant stream of events
+//!   - emits at most one batch per `MIN_EMIT_INTERVAL` per root, and sends
+//!     `overflow` instead of paths past `MAX_BATCH_PATHS`
+//!
+//! A flusher thread per watcher emits the batches and feeds them to the
+//! quick-open index. It exits when the watcher, and with it the coalescer,
+//! is dropped.
+
+use notify::event::{CreateKind, ModifyKind, RemoveKind, RenameMode};
+use notify::{Event, EventKind};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use super::file_tree::FALLBACK_SKIP;
+use super::ignore_rules::{IgnoreMatcher, IGNORE_FILES};
+use super::path_index::PathIndexState;
+use crate::events::emit_to_frontend;
+
+/// Quiet period after the last event before a batch is emitted.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+/// Longest a change waits while events keep arriving.
+const MAX_LATENCY: Duration = Duration::from_secs(1);
+const MIN_EMIT_INTERVAL: Duration = Duration::from_millis(250);
+/// Paths listed per batch; beyond this the batch only reports `overflow`.
+const MAX_BATCH_PATHS: usize = 1000;
+const FLUSH_TICK: Duration = Duration::from_millis(50);
+/// Never reported, whatever the ignore files say.
+const ALWAYS_SKIPPED: &[&str] = &[".git", ".claude", ".tokenicode"];
+/// Cached per-directory matchers before the cache is reset.
+const MAX_CACHED_MATCHERS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    Created,
+    Modified,
+    Removed,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct FsRename {
+    pub from: String,
+    pub to: String,
+}
+
+/// Payload of `fs:change`. Paths are sorted and appear in one list only.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct FsChangeBatch {
+    pub root: String,
+    pub created: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+    pub renamed: Vec<FsRename>,
+    /// More changes than could be listed, or the OS dropped events: the
+    /// frontend should reload the tree.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub overflow: bool,
+}
+
+impl FsChangeBatch {
+    fn is_empty(&self) -> bool {
+        self.created.is_empty()
+            && self.modified.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+            && !self.overflow
+    }
+
+    /// Every path in the batch, rename sources and targets included.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.created
+            .iter()
+            .chain(&self.modified)
+            .chain(&self.removed)
+            .chain(self.renamed.iter().flat_map(|r| [&r.from, &r.to]))
+            .map(PathBuf::from)
+            .collect()
+    }
+}
+
+/// Pending changes of one watched root.
+pub struct FsCoalescer {
+    root: PathBuf,
+    pending: HashMap<PathBuf, Change>,
+    renames: Vec<(PathBuf, PathBuf)>,
+    /// Rename sources waiting for their target, with notify's tracker id.
+    rename_from: Vec<(Option<usize>, PathBuf)>,
+    overflow: bool,
+    first_at: Option<Instant>,
+    last_at: Option<Instant>,
+    last_emit: Option<Instant>,
+    matchers: HashMap<PathBuf, IgnoreMatcher>,
+}
+
+/// Fold a new event into the pending change of a path; `None` means the
+/// path ends up unchanged (e.g. a temp file created and removed again).
+fn fold(prev: Option<Change>, next: Change) -> Option<Change> {
+    use Change::*;
+    match (prev, next) {
+        (None, next) => Some(next),
+        (Some(Created), Removed) => None,
+        (Some(Created), _) => Some(Created),
+        (Some(Removed), Created | Modified) => Some(Modified),
+        (Some(_), Removed) => Some(Removed),
+        (Some(Modified), _) => Some(Modified),
+    }
+}
+
+impl FsCoalescer {
+    pub fn new(root: &Path) -> Self {
+        FsCoalescer {
+            root: root.to_path_buf(),
+            pending: HashMap::new(),
+            renames: Vec::new(),
+            rename_from: Vec::new(),
+            overflow: false,
+            first_at: None,
+            last_at: None,
+            last_emit: None,
+            matchers: HashMap::new(),
+        }
+    }
+
+    fn skipped(&mut self, path: &Path, is_dir: bool) -> bool {
+        let Ok(rel) = path.strip_prefix(&self.root) else {
+            return true;
+        };
+        if rel
+            .components()
+            .any(|c| ALWAYS_SKIPPED.iter().any(|s| c.as_os_str() == *s))
+        {
+            return true;
+        }
+        let parent = path.parent().unwrap_or(&self.root).to_path_buf();
+        if self.matchers.len() >= MAX_CACHED_MATCHERS {
+            self.matchers.clear();
+        }
+        let root = &self.root;
+        let matcher = self
+            .matchers
+            .entry(parent)
+            .or_insert_with_key(|dir| IgnoreMatcher::for_dir_under(root, dir));
+        if !matcher.has_rules()
+            && rel
+                .components()
+                .any(|c| FALLBACK_SKIP.iter().any(|s| c.as_os_str() == *s))
+        {
+            return true;
+        }
+        matcher.is_ignored(path, is_dir)
+    }
+
+    fn record(&mut self, path: PathBuf, change: Change) {
+        if path
+            .file_name()
+            .is_some_and(|n| IGNORE_FILES.iter().any(|f| n == *f))
+        {
+            self.matchers.clear();
+        }
+        let prev = self.pending.get(&path).copied();
+        if prev.is_none() && self.pending.len() >= MAX_BATCH_PATHS {
+            self.overflow = true;
+            return;
+        }
+        match fold(prev, change) {
+            Some(c) => {
+                self.pending.insert(path, c);
+            }
+            None => {
+                self.pending.remove(&path);
+            }
+        }
+    }
+
+    fn record_rename(&mut self, from: PathBuf, to: PathBuf) {
+        let to_dir = to.is_dir();
+        match (self.skipped(&from, to_dir), self.skipped(&to, to_dir)) {
+            (true, true) => return,
+            // Moved out of or into view: a plain remove or create.
+            (false, true) => return self.record(from, Change::Removed),
+            (true, false) => return self.record(to, Change::Created),
+            (false, false) => {}
+        }
+        self.pending.remove(&to);
+        if self.pending.get(&from) == Some(&Change::Created) {
+            // Created and renamed in one window (write temp, rename into
+            // place): the target is simply new.
+            self.pending.remove(&from);
+            return self.record(to, Change::Created);
+        }
+        self.pending.remove(&from);
+        // Chains (a -> b -> c) collapse; renaming back is a modify.
+        if let Some(i) = self.renames.iter().position(|(_, t)| *t == from) {
+            let (origin, _) = self.renames.remove(i);
+            if origin == to {
+                return self.record(to, Change::Modified);
+            }
+            self.renames.push((origin, to));
+        } else {
+            self.renames.push((from, to));
+        }
+    }
+
+    /// Take one raw notify event.
+    pub fn push(&mut self, event: &Event, now: Instant) {
+        if event.need_rescan() {
+            self.overflow = true;
+        }
+        let dir_hint = matches!(
+            event.kind,
+            EventKind::Create(CreateKind::Folder) | EventKind::Remove(RemoveKind::Folder)
+        );
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                self.record_rename(event.paths[0].clone(), event.paths[1].clone());
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                for path in &event.paths {
+                    self.rename_from.push((event.attrs.tracker(), path.clone()));
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                for path in &event.paths {
+                    let tracker = event.attrs.tracker();
+                    let from = self
+                        .rename_from
+                        .iter()
+                        .rposition(|(t, _)| *t == tracker)
+                        .map(|i| self.rename_from.remove(i).1);
+                    match from {
+                        Some(from) => self.record_rename(from, path.clone()),
+                        None if !self.skipped(path, path.is_dir()) => {
+                            self.record(path.clone(), Change::Created)
+                        }
+                        None => {}
+                    }
+                }
+            }
+            // Unpaired renames (macOS reports each side on its own): the
+            // disk tells which side this is.
+            EventKind::Modify(ModifyKind::Name(_)) => {
+                for path in &event.paths {
+                    let change = if path.exists() {
+                        Change::Created
+                    } else {
+                        Change::Removed
+                    };
+                    if !self.skipped(path, path.is_dir()) {
+                        self.record(path.clone(), change);
+                    }
+                }
+            }
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {
+                let change = match event.kind {
+                    EventKind::Create(_) => Change::Created,
+                    EventKind::Remove(_) => Change::Removed,
+                    _ => Change::Modified,
+                };
+                for path in &event.paths {
+                    if !self.skipped(path, dir_hint || path.is_dir()) {
+                        self.record(path.clone(), change);
+                    }
+                }
+            }
+            _ => return,
+        }
+        self.first_at.get_or_insert(now);
+        self.last_at = Some(now);
+    }
+
+    /// The pending batch, if its debounce window closed and the rate limit
+    /// allows an emit now.
+    pub fn take_ready(&mut self, now: Instant) -> Option<FsChangeBatch> {
+        let first = self.first_at?;
+        let quiet = self
+            .last_at
+            .is_some_and(|t| now.duration_since(t) >= DEBOUNCE);
+        if !quiet && now.duration_since(first) < MAX_LATENCY {
+            return None;
+        }
+        if self
+            .last_emit
+            .is_some_and(|t| now.duration_since(t) < MIN_EMIT_INTERVAL)
+        {
+            return None;
+        }
+
+        // Rename sources that never saw their target left the tree.
+        for (_, from) in std::mem::take(&mut self.rename_from) {
+            if !self.skipped(&from, false) {
+                self.record(from, Change::Removed);
+            }
+        }
+        let mut batch = FsChangeBatch {
+            root: self.root.to_string_lossy().to_string(),
+            overflow: std::mem::take(&mut self.overflow),
+            ..Default::default()
+        };
+        for (path, change) in self.pending.drain() {
+            let path = path.to_string_lossy().to_string();
+            match change {
+                Change::Created => batch.created.push(path),
+                Change::Modified => batch.modified.push(path),
+                Change::Removed => batch.removed.push(path),
+            }
+        }
+        batch.renamed = self
+            .renames
+            .drain(..)
+            .map(|(from, to)| FsRename {
+                from: from.to_string_lossy().to_string(),
+                to: to.to_string_lossy().to_string(),
+            })
+            .collect();
+        batch.created.sort();
+        batch.modified.sort();
+        batch.removed.sort();
+        batch.renamed.sort_by(|a, b| a.from.cmp(&b.from));
+        self.first_at = None;
+        self.last_at = None;
+
+        if batch.is_empty() {
+            return None;
+        }
+        self.last_emit = Some(now);
+        Some(batch)
+    }
+}
+
+/// Emit the coalesced batches of `coalescer` until it is dropped.
+pub fn spawn_flusher(app: AppHandle, watch_root: String, coalescer: &Arc<Mutex<FsCoalescer>>) {
+    let weak = Arc::downgrade(coalescer);
+    std::thread::spawn(move || loop {
+        std::thread::sleep(FLUSH_TICK);
+        let Some(coalescer) = weak.upgrade() else {
+            return;
+        };
+        let batch = match coalescer.lock() {
+            Ok(mut c) => c.take_ready(Instant::now()),
+            Err(_) => return,
+        };
+        drop(coalescer);
+        let Some(batch) = batch else {
+            continue;
+        };
+        if let Some(index) = app.try_state::<PathIndexState>() {
+            if batch.overflow {
+                index.mark_stale(&watch_root);
+            } else {
+                index.apply_fs_event(&watch_root, &batch.paths());
+            }
+        }
+        let _ = emit_to_frontend(&app, "fs:change", batch);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::DataChange;
+
+    fn ev(kind: EventKind, paths: &[&Path]) -> Event {
+        paths
+            .iter()
+            .fold(Event::new(kind), |e, p| e.add_path(p.to_path_buf()))
+    }
+
+    #[test]
+    fn folds_dedups_filters_and_pairs_renames() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        for f in ["src/a.rs", "src/new.rs", "src/b2.rs", "src/d.rs"] {
+            std::fs::write(root.join(f), "").unwrap();
+        }
+        let p = |rel: &str| root.join(rel);
+        let modify = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        let mut c = FsCoalescer::new(root);
+        let t0 = Instant::now();
+
+        for _ in 0..5 {
+            c.push(&ev(modify, &[&p("src/a.rs")]), t0);
+        }
+        c.push(
+            &ev(EventKind::Create(CreateKind::File), &[&p("src/new.rs")]),
+            t0,
+        );
+        c.push(&ev(modify, &[&p("src/new.rs")]), t0);
+        // Transient temp file.
+        c.push(
+            &ev(EventKind::Create(CreateKind::File), &[&p("src/x.tmp")]),
+            t0,
+        );
+        c.push(
+            &ev(EventKind::Remove(RemoveKind::File), &[&p("src/x.tmp")]),
+            t0,
+        );
+        c.push(
+            &ev(EventKind::Create(CreateKind::File), &[&p("target/out.o")]),
+            t0,
+        );
+        c.push(&ev(modify, &[&p(".git/index")]), t0);
+        c.push(
+            &ev(
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)),
+                &[&p("src/b.rs")],
+            )
+            .set_tracker(7),
+            t0,
+        );
+        c.push(
+            &ev(
+                EventKind::Modify(ModifyKind::Name(RenameMode::To)),
+                &[&p("src/b2.rs")],
+            )
+            .set_tracker(7),
+            t0,
+        );
+        c.push(
+            &ev(
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+                &[&p("src/c.rs"), &p("src/d.rs")],
+            ),
+            t0,
+        );
+
+        assert!(
+            c.take_ready(t0).is_none(),
+            "still inside the debounce window"
+        );
+        let batch = c.take_ready(t0 + DEBOUNCE).unwrap();
+        let s = |rel: &str| p(rel).to_string_lossy().to_string();
+        assert_eq!(batch.modified, [s("src/a.rs")]);
+        assert_eq!(batch.created, [s("src/new.rs")]);
+        assert!(batch.removed.is_empty());
+        assert_eq!(
+            batch.renamed,
+            [
+                FsRename {
+                    from: s("src/b.rs"),
+                    to: s("src/b2.rs")
+                },
+                FsRename {
+                    from: s("src/c.rs"),
+                    to: s("src/d.rs")
+                },
+            ]
+        );
+        assert!(!batch.overflow);
+        assert!(c.take_ready(t0 + DEBOUNCE * 2).is_none());
+    }
+
+    #[test]
+    fn fallback_and_cli_dirs_are_skipped_without_ignore_rules() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let p = |rel: &str| root.join(rel);
+        let create = EventKind::Create(CreateKind::File);
+        let mut c = FsCoalescer::new(root);
+        let t0 = Instant::now();
+        for rel in [
+            "node_modules/pkg/index.js",
+            "target/debug/app",
+            ".claude/settings.local.json",
+            "src/a.rs",
+        ] {
+            c.push(&ev(create, &[&p(rel)]), t0);
+        }
+        let batch = c.take_ready(t0 + DEBOUNCE).unwrap();
+        assert_eq!(batch.created, [p("src/a.rs").to_string_lossy()]);
+    }
+
+    #[test]
+    fn latency_cap_rate_limit_and_overflow() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let modify = EventKind::Modify(ModifyKind::Any);
+        let mut c = FsCoalescer::new(root);
+        let t0 = Instant::now();
+
+        // A steady stream never goes quiet, but MAX_LATENCY forces a flush.
+        let mut t = t0;
+        while t < t0 + MAX_LATENCY {
+            c.push(&ev(modify, &[&root.join("log.txt")]), t);
+            assert!(c.take_ready(t).is_none());
+            t += DEBOUNCE / 2;
+        }
+        c.push(&ev(modify, &[&root.join("log.txt")]), t);
+        assert_eq!(c.take_ready(t).unwrap().modified.len(), 1);
+
+        // Right after an emit, the rate limit holds the next batch back.
+        c.push(&ev(modify, &[&root.join("other.txt")]), t);
+        assert!(c.take_ready(t + DEBOUNCE).is_none());
+        assert!(c.take_ready(t + MIN_EMIT_INTERVAL).is_some());
+
+        let t = t + MIN_EMIT_INTERVAL * 2;
+        for i in 0..(MAX_BATCH_PATHS + 10) {
+            c.push(&ev(modify, &[&root.join(format!("f{}", i))]), t);
+        }
+        let batch = c.take_ready(t + DEBOUNCE).unwrap();
+        assert!(batch.overflow);
+        assert_eq!(batch.modified.len(), MAX_BATCH_PATHS);
+    }
+}
@@ -0,0 +1,830 @@
+//! Typed git queries for the diff viewer and file explorer.
+//!
+//! `run_git_command` hands raw stdout to the frontend, which then has to
+//! re-parse git's formats. These commands run git with machine-readable
+//! options and return structured data instead:
+//!
+//!   - `git_status` — `status --porcelain=v2 -z`, split into staged /
+//!     unstaged / untracked / conflicted with rename sources
+//!   - `git_diff`   — unified diff parsed into files → hunks → lines, with an
+//!     optional word-level (`--word-diff=porcelain`) mode
+//!   - `git_log`    — commits with parents and refs, paginated by skip/limit
+//!   - `git_blame`  — `blame --porcelain` for a line range
+//!
+//! Revisions supplied by the UI are rejected when they look like options, and
+//! paths always follow `--`.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::git_output;
+
+const DEFAULT_LOG_LIMIT: usize = 50;
+const MAX_LOG_LIMIT: usize = 500;
+/// Log fields are separated by US (0x1f) and records by RS (0x1e).
+const LOG_FORMAT: &str = "--format=%H%x1f%h%x1f%P%x1f%an%x1f%ae%x1f%at%x1f%D%x1f%s%x1f%b%x1e";
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct GitFileStatus {
+    pub path: String,
+    /// Source path of a rename or copy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orig_path: Option<String>,
+    /// "added" | "modified" | "deleted" | "renamed" | "copied" |
+    /// "type-changed" | "unmerged"
+    pub status: String,
+    /// Rename/copy similarity score (0-100).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<u8>,
+}
+
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct GitStatus {
+    /// Current branch; `None` on a detached HEAD.
+    pub branch: Option<String>,
+    /// `None` before the first commit.
+    pub head: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    pub staged: Vec<GitFileStatus>,
+    pub unstaged: Vec<GitFileStatus>,
+    pub untracked: Vec<String>,
+    pub conflicted: Vec<GitFileStatus>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DiffSegment {
+    /// "context" | "add" | "del"
+    pub kind: String,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DiffLine {
+    /// "context" | "add" | "del" | "change" (word diff with both sides)
+    pub kind: String,
+    pub content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_line: Option<u32>,
+    /// Word-level pieces; only in word-diff mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<DiffSegment>>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DiffHunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// Text after the second `@@` (usually the enclosing function).
+    pub header: String,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DiffFile {
+    /// `None` for added files.
+    pub old_path: Option<String>,
+    /// `None` for deleted files.
+    pub new_path: Option<String>,
+    /// "added" | "deleted" | "modified" | "renamed" | "copied"
+    pub status: String,
+    pub binary: bool,
+    pub additions: u32,
+    pub deletions: u32,
+    pub hunks: Vec<DiffHunk>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct GitCommit {
+    pub hash: String,
+    pub short_hash: String,
+    pub parents: Vec<String>,
+    pub author_name: String,
+    pub author_email: String,
+    /// Unix seconds.
+    pub author_time: i64,
+    /// Branch/tag decorations (`HEAD -> main`, `tag: v1.0`, ...).
+    pub refs: Vec<String>,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct GitLogPage {
+    pub commits: Vec<GitCommit>,
+    pub skip: usize,
+    pub has_more: bool,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct BlameCommit {
+    pub author: String,
+    pub author_email: String,
+    pub author_time: i64,
+    pub summary: String,
+    /// Uncommitted lines carry the all-zero hash.
+    pub uncommitted: bool,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct BlameLine {
+    pub line: u32,
+    pub commit: String,
+    /// Line number in `commit`'s version of the file.
+    pub orig_line: u32,
+    pub content: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct GitBlame {
+    pub lines: Vec<BlameLine>,
+    pub commits: BTreeMap<String, BlameCommit>,
+}
+
+fn repo_dir(cwd: &str) -> Result<&Path, String> {
+    let path = Path::new(cwd);
+    if !path.is_dir() {
+        return Err(format!("Working directory does not exist: {}", cwd));
+    }
+    Ok(path)
+}
+
+/// Revisions come from the UI; one starting with `-` would be read as an option.
+fn check_rev(rev: &str) -> Result<(), String> {
+    if rev.is_empty() || rev.starts_with('-') || rev.contains('\0') {
+        return Err(format!("Invalid revision: '{}'", rev));
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Parsers
+// ---------------------------------------------------------------------------
+
+fn status_word(code: char) -> &'static str {
+    match code {
+        'A' => "added",
+        'D' => "deleted",
+        'R' => "renamed",
+        'C' => "copied",
+        'T' => "type-changed",
+        'U' => "unmerged",
+        _ => "modified",
+    }
+}
+
+/// Parse `git status --porcelain=v2 --branch -z`.
+fn parse_status_v2(out: &str) -> GitStatus {
+    let mut status = GitStatus::default();
+    let mut records = out.split('\0').filter(|r| !r.is_empty());
+    while let Some(rec) = records.next() {
+        if let Some(header) = rec.strip_prefix("# ") {
+            let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+            match key {
+                "branch.oid" if value != "(initial)" => status.head = Some(value.to_string()),
+                "branch.head" if value != "(detached)" => status.branch = Some(value.to_string()),
+                "branch.upstream" => status.upstream = Some(value.to_string()),
+                "branch.ab" => {
+                    for part in value.split_whitespace() {
+                        if let Some(n) = part.strip_prefix('+') {
+                            status.ahead = n.parse().unwrap_or(0);
+                        } else if let Some(n) = part.strip_prefix('-') {
+                            status.behind = n.parse().unwrap_or(0);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        let kind = rec.chars().next().unwrap_or(' ');
+        match kind {
+            '?' => status.untracked.push(rec[2..].to_string()),
+            '1' | '2' => {
+                // 1 XY sub mH mI mW hH hI path
+                // 2 XY sub mH mI mW hH hI Xscore path \0 origPath
+                let fields = if kind == '1' { 9 } else { 10 };
+                let parts: Vec<&str> = rec.splitn(fields, ' ').collect();
+                if parts.len() < fields {
+                    continue;
+                }
+                let xy: Vec<char> = parts[1].chars().collect();
+                let path = parts[fields - 1].to_string();
+                let (orig_path, similarity) = if kind == '2' {
+                    let score = parts[8].get(1..).and_then(|s| s.parse().ok());
+                    (records.next().map(str::to_string), score)
+                } else {
+                    (None, None)
+                };
+                let entry = |code: char| GitFileStatus {
+                    path: path.clone(),
+                    orig_path: if matches!(code, 'R' | 'C') {
+                        orig_path.clone()
+                    } else {
+                        None
+                    },
+                    status: status_word(code).to_string(),
+                    similarity: if matches!(code, 'R' | 'C') {
+                        similarity
+                    } else {
+                        None
+                    },
+                };
+                if let Some(&x) = xy.first().filter(|c| **c != '.') {
+                    status.staged.push(entry(x));
+                }
+                if let Some(&y) = xy.get(1).filter(|c| **c != '.') {
+                    status.unstaged.push(entry(y));
+                }
+            }
+            'u' => {
+                // u XY sub m1 m2 m3 mW h1 h2 h3 path
+                let parts: Vec<&str> = rec.splitn(11, ' ').collect();
+                if let Some(path) = parts.get(10) {
+                    status.conflicted.push(GitFileStatus {
+                        path: path.to_string(),
+                        orig_path: None,
+                        status: "unmerged".to_string(),
+                        similarity: None,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    status
+}
+
+/// Undo git's C-style quoting of unusual paths (`"a\tb"`, `"\303\251"`).
+fn unquote_path(raw: &str) -> String {
+    let Some(inner) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) else {
+        return raw.to_string();
+    };
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut chars = inner.bytes().peekable();
+    while let Some(b) = chars.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        match chars.next() {
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b't') => bytes.push(b'\t'),
+            Some(b'r') => bytes.push(b'\r'),
+            Some(d @ b'0'..=b'7') => {
+                let mut value = (d - b'0') as u32;
+                for _ in 0..2 {
+                    match chars.peek() {
+                        Some(n @ b'0'..=b'7') => {
+                            value = value * 8 + (n - b'0') as u32;
+                            chars.next();
+                        }
+                        _ => break,
+                    }
+                }
+                bytes.push(value as u8);
+            }
+            Some(other) => bytes.push(other),
+            None => bytes.push(b'\\'),
+        }
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+/// Path from a `---`/`+++` line: `None` for /dev/null, prefix stripped.
+fn side_path(raw: &str, prefix: &str) -> Option<String> {
+    let raw = raw.trim_end_matches('\t');
+    if raw == "/dev/null" {
+        return None;
+    }
+    let path = unquote_path(raw);
+    Some(
+        path.strip_prefix(prefix)
+            .map(str::to_string)
+            .unwrap_or(path),
+    )
+}
+
+/// `@@ -a,b +c,d @@ header`
+fn parse_hunk_header(line: &str) -> Option<DiffHunk> {
+    let rest = line.strip_prefix("@@ ")?;
+    let (ranges, header) = rest.split_once(" @@")?;
+    let mut parts = ranges.split_whitespace();
+    let range = |s: &str| -> Option<(u32, u32)> {
+        match s.split_once(',') {
+            Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+            None => Some((s.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_lines) = range(parts.next()?.strip_prefix('-')?)?;
+    let (new_start, new_lines) = range(parts.next()?.strip_prefix('+')?)?;
+    Some(DiffHunk {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        header: header.trim().to_string(),
+        lines: Vec::new(),
+    })
+}
+
+fn new_diff_file() -> DiffFile {
+    DiffFile {
+        old_path: None,
+        new_path: None,
+        status: "modified".to_string(),
+        binary: false,
+        additions: 0,
+        deletions: 0,
+        hunks: Vec::new(),
+    }
+}
+
+/// Parse `git diff` output (plain or `--word-diff=porcelain`).
+pub(crate) fn parse_unified_diff(out: &str) -> Vec<DiffFile> {
+    let mut files: Vec<DiffFile> = Vec::new();
+    // Line counters of the current hunk.
+    let (mut old_no, mut new_no) = (0u32, 0u32);
+    let mut segments: Vec<DiffSegment> = Vec::new();
+    let word_mode = out.lines().any(|l| l == "~");
+
+    for line in out.lines() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            let mut file = new_diff_file();
+            // Fallback paths when there are no ---/+++ lines (binary, mode-only).
+            if let Some((a, b)) = rest.split_once(" b/") {
+                let a = unquote_path(a);
+                file.old_path = Some(a.strip_prefix("a/").unwrap_or(&a).to_string());
+                file.new_path = Some(unquote_path(b));
+            }
+            files.push(file);
+            segments.clear();
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        let in_hunk = !file.hunks.is_empty();
+
+        if !in_hunk {
+            if line.starts_with("new file mode") {
+                file.status = "added".to_string();
+                file.old_path = None;
+            } else if line.starts_with("deleted file mode") {
+                file.status = "deleted".to_string();
+                file.new_path = None;
+            } else if let Some(p) = line.strip_prefix("rename from ") {
+                file.status = "renamed".to_string();
+                file.old_path = Some(unquote_path(p));
+            } else if let Some(p) = line.strip_prefix("rename to ") {
+                file.new_path = Some(unquote_path(p));
+            } else if let Some(p) = line.strip_prefix("copy from ") {
+                file.status = "copied".to_string();
+                file.old_path = Some(unquote_path(p));
+            } else if let Some(p) = line.strip_prefix("copy to ") {
+                file.new_path = Some(unquote_path(p));
+            } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+                file.binary = true;
+            } else if let Some(p) = line.strip_prefix("--- ") {
+                file.old_path = side_path(p, "a/");
+            } else if let Some(p) = line.strip_prefix("+++ ") {
+                file.new_path = side_path(p, "b/");
+            }
+        }
+
+        if line.starts_with("@@ ") {
+            if let Some(hunk) = parse_hunk_header(line) {
+                old_no = hunk.old_start;
+                new_no = hunk.new_start;
+                file.hunks.push(hunk);
+                segments.clear();
+            }
+            continue;
+        }
+        let Some(hunk) = file.hunks.last_mut() else {
+            continue;
+        };
+        let (marker, text) = match line.char_indices().nth(1) {
+            Some((i, _)) => (&line[..i], &line[i..]),
+            None => (line, ""),
+        };
+        match marker {
+            // Word diff: `~` ends a line made of the segments seen so far.
+            "~" => {
+                let has_add = segments.iter().any(|s| s.kind == "add");
+                let has_del = segments.iter().any(|s| s.kind == "del");
+                let has_ctx = segments.iter().any(|s| s.kind == "context");
+                let kind = match (has_add, has_del, has_ctx) {
+                    (true, false, false) => "add",
+                    (false, true, false) => "del",
+                    (false, false, _) => "context",
+                    _ => "change",
+                };
+                let content: String = segments
+                    .iter()
+                    .filter(|s| s.kind != "del")
+                    .map(|s| s.text.as_str())
+                    .collect();
+                let (old_line, new_line) = match kind {
+                    "add" => (None, Some(new_no)),
+                    "del" => (Some(old_no), None),
+                    _ => (Some(old_no), Some(new_no)),
+                };
+                if kind != "add" {
+                    old_no += 1;
+                }
+                if kind != "del" {
+                    new_no += 1;
+                }
+                if has_add {
+                    file.additions += 1;
+                }
+                if has_del {
+                    file.deletions += 1;
+                }
+                hunk.lines.push(DiffLine {
+                    kind: kind.to_string(),
+                    content,
+                    old_line,
+                    new_line,
+                    segments: Some(std::mem::take(&mut segments)),
+                });
+            }
+            " " | "+" | "-" => {
+                let kind = match marker {
+                    "+" => "add",
+                    "-" => "del",
+                    _ => "context",
+                };
+                if word_mode {
+                    segments.push(DiffSegment {
+                        kind: kind.to_string(),
+                        text: text.to_string(),
+                    });
+                    continue;
+                }
+                let (old_line, new_line) = match kind {
+                    "add" => {
+                        file.additions += 1;
+                        new_no += 1;
+                        (None, Some(new_no - 1))
+                    }
+                    "del" => {
+                        file.deletions += 1;
+                        old_no += 1;
+                        (Some(old_no - 1), None)
+                    }
+                    _ => {
+                        old_no += 1;
+                        new_no += 1;
+                        (Some(old_no - 1), Some(new_no - 1))
+                    }
+                };
+                hunk.lines.push(DiffLine {
+                    kind: kind.to_string(),
+                    content: text.to_string(),
+                    old_line,
+                    new_line,
+                    segments: None,
+                });
+            }
+            _ => {}
+        }
+    }
+    files
+}
+
+fn parse_log(out: &str) -> Vec<GitCommit> {
+    out.split('\x1e')
+        .map(|r| r.trim_start_matches('\n'))
+        .filter(|r| !r.is_empty())
+        .filter_map(|record| {
+            let f: Vec<&str> = record.split('\x1f').collect();
+            if f.len() < 9 {
+                return None;
+            }
+            Some(GitCommit {
+                hash: f[0].to_string(),
+                short_hash: f[1].to_string(),
+                parents: f[2].split_whitespace().map(str::to_string).collect(),
+                author_name: f[3].to_string(),
+                author_email: f[4].to_string(),
+                author_time: f[5].parse().unwrap_or(0),
+                refs: f[6]
+                    .split(", ")
+                    .filter(|r| !r.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                subject: f[7].to_string(),
+                body: f[8].trim_end().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse `git blame --porcelain`.
+fn parse_blame(out: &str) -> GitBlame {
+    let mut blame = GitBlame::default();
+    let mut current: Option<(String, u32, u32)> = None;
+    let mut pending = BlameCommit {
+        author: String::new(),
+        author_email: String::new(),
+        author_time: 0,
+        summary: String::new(),
+        uncommitted: false,
+    };
+    for line in out.lines() {
+        if let Some(content) = line.strip_prefix('\t') {
+            if let Some((commit, orig_line, final_line)) = current.take() {
+                if !blame.commits.contains_key(&commit) {
+                    let mut info = pending.clone();
+                    info.uncommitted = commit.bytes().all(|b| b == b'0');
+                    blame.commits.insert(commit.clone(), info);
+                }
+                blame.lines.push(BlameLine {
+                    line: final_line,
+                    commit,
+                    orig_line,
+                    content: content.to_string(),
+                });
+            }
+            continue;
+        }
+        let mut parts = line.split(' ');
+        let first = parts.next().unwrap_or("");
+        if first.len() == 40 && first.bytes().all(|b| b.is_ascii_hexdigit()) {
+            let orig: u32 = parts.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+            let fin: u32 = parts.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+            if !blame.commits.contains_key(first) {
+                pending = BlameCommit {
+                    author: String::new(),
+                    author_email: String::new(),
+                    author_time: 0,
+                    summary: String::new(),
+                    uncommitted: false,
+                };
+            }
+            current = Some((first.to_string(), orig, fin));
+            continue;
+        }
+        let value = line.split_once(' ').map(|(_, v)| v).unwrap_or("");
+        match first {
+            "author" => pending.author = value.to_string(),
+            "author-mail" => {
+                pending.author_email = value.trim_matches(['<', '>']).to_string();
+            }
+            "author-time" => pending.author_time = value.parse().unwrap_or(0),
+            "summary" => pending.summary = value.to_string(),
+            _ => {}
+        }
+    }
+    blame
+}
+
+// ---------------------------------------------------------------------------
+// Commands
+// ---------------------------------------------------------------------------
+
+/// Working tree status, parsed from porcelain v2.
+#[tauri::command]
+pub async fn git_status(cwd: String) -> Result<GitStatus, String> {
+    let dir = repo_dir(&cwd)?;
+    let out = git_output(
+        dir,
+        &[
+            "status",
+            "--porcelain=v2",
+            "--branch",
+            "-z",
+            "--untracked-files=all",
+        ],
+    )
+    .await?;
+    Ok(parse_status_v2(&out))
+}
+
+/// Parsed diff. `staged` diffs the index against HEAD; `base` diffs the
+/// working tree (or index when staged) against a revision.
+#[tauri::command]
+pub async fn git_diff(
+    cwd: String,
+    staged: Option<bool>,
+    base: Option<String>,
+    paths: Option<Vec<String>>,
+    context_lines: Option<u32>,
+    word_diff: Option<bool>,
+) -> Result<Vec<DiffFile>, String> {
+    let dir = repo_dir(&cwd)?;
+    let context = format!("-U{}", context_lines.unwrap_or(3).min(1000));
+    let mut args: Vec<&str> = vec!["diff", "--no-color", "--no-ext-diff", "-M", &context];
+    if staged.unwrap_or(false) {
+        args.push("--cached");
+    }
+    if word_diff.unwrap_or(false) {
+        args.push("--word-diff=porcelain");
+    }
+    if let Some(rev) = base.as_deref() {
+        check_rev(rev)?;
+        args.push(rev);
+    }
+    args.push("--");
+    let paths = paths.unwrap_or_default();
+    args.extend(paths.iter().map(String::as_str));
+    let out = git_output(dir, &args).await?;
+    Ok(parse_unified_diff(&out))
+}
+
+/// One page of history, newest first. `rev` defaults to HEAD; `path` limits
+/// to commits touching that file or directory.
+#[tauri::command]
+pub async fn git_log(
+    cwd: String,
+    skip: Option<usize>,
+    limit: Option<usize>,
+    rev: Option<String>,
+    path: Option<String>,
+) -> Result<GitLogPage, String> {
+    let dir = repo_dir(&cwd)?;
+    let skip = skip.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_LOG_LIMIT).clamp(1, MAX_LOG_LIMIT);
+    let skip_arg = format!("--skip={}", skip);
+    // One extra commit tells us whether another page exists.
+    let count_arg = format!("--max-count={}", limit + 1);
+    let mut args: Vec<&str> = vec!["log", LOG_FORMAT, &skip_arg, &count_arg];
+    if let Some(rev) = rev.as_deref() {
+        check_rev(rev)?;
+        args.push(rev);
+    }
+    args.push("--");
+    if let Some(p) = path.as_deref() {
+        args.push(p);
+    }
+    let mut commits = parse_log(&git_output(dir, &args).await?);
+    let has_more = commits.len() > limit;
+    commits.truncate(limit);
+    Ok(GitLogPage {
+        commits,
+        skip,
+        has_more,
+    })
+}
+
+/// Blame lines `start_line..=end_line` (1-based) of `path`, optionally at `rev`.
+#[tauri::command]
+pub async fn git_blame(
+    cwd: String,
+    path: String,
+    start_line: u32,
+    end_line: u32,
+    rev: Option<String>,
+) -> Result<GitBlame, String> {
+    let dir = repo_dir(&cwd)?;
+    if start_line == 0 || end_line < start_line {
+        return Err(format!("Invalid line range {}..{}", start_line, end_line));
+    }
+    let range = format!("{},{}", start_line, end_line);
+    let mut args: Vec<&str> = vec!["blame", "--porcelain", "-L", &range];
+    if let Some(rev) = rev.as_deref() {
+        check_rev(rev)?;
+        args.push(rev);
+    }
+    args.push("--");
+    args.push(&path);
+    Ok(parse_blame(&git_output(dir, &args).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_porcelain_v2_status() {
+        let out = "# branch.oid 1234abcd\0# branch.head main\0# branch.upstream origin/main\0# branch.ab +2 -1\0\
+1 M. N... 100644 100644 100644 aaa bbb src/a.rs\0\
+1 .M N... 100644 100644 100644 aaa aaa src/b rs\0\
+2 R. N... 100644 100644 100644 aaa aaa R92 new.rs\0old.rs\0\
+u UU N... 100644 100644 100644 100644 a b c conflict.txt\0\
+? notes/todo.md\0";
+        let s = parse_status_v2(out);
+        assert_eq!(s.branch.as_deref(), Some("main"));
+        assert_eq!(s.head.as_deref(), Some("1234abcd"));
+        assert_eq!((s.ahead, s.behind), (2, 1));
+        assert_eq!(s.staged.len(), 2);
+        assert_eq!(s.staged[1].status, "renamed");
+        assert_eq!(s.staged[1].orig_path.as_deref(), Some("old.rs"));
+        assert_eq!(s.staged[1].similarity, Some(92));
+        assert_eq!(s.unstaged[0].path, "src/b rs");
+        assert_eq!(s.conflicted[0].path, "conflict.txt");
+        assert_eq!(s.untracked, ["notes/todo.md"]);
+
+        let detached = parse_status_v2("# branch.oid (initial)\0# branch.head (detached)\0");
+        assert_eq!(detached.branch, None);
+        assert_eq!(detached.head, None);
+    }
+
+    #[test]
+    fn parses_unified_diff() {
+        let out = "diff --git a/src/a.rs b/src/a.rs
+index 1..2 100644
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,3 +1,3 @@ fn main() {
+ keep
+-old
++new
+ tail
+diff --git a/old.txt b/new.txt
+similarity index 90%
+rename from old.txt
+rename to new.txt
+diff --git a/img.png b/img.png
+new file mode 100644
+index 0000000..3
+Binary files /dev/null and b/img.png differ
+diff --git \"a/sp\\303\\251c.txt\" \"b/sp\\303\\251c.txt\"
+deleted file mode 100644
+--- \"a/sp\\303\\251c.txt\"
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+";
+        let files = parse_unified_diff(out);
+        assert_eq!(files.len(), 4);
+        let a = &files[0];
+        assert_eq!(a.new_path.as_deref(), Some("src/a.rs"));
+        assert_eq!((a.additions, a.deletions), (1, 1));
+        let h = &a.hunks[0];
+        assert_eq!(h.header, "fn main() {");
+        assert_eq!(h.lines[1].kind, "del");
+        assert_eq!(h.lines[1].old_line, Some(2));
+        assert_eq!(h.lines[2].new_line, Some(2));
+        assert_eq!(h.lines[3].old_line, Some(3));
+
+        assert_eq!(files[1].status, "renamed");
+        assert_eq!(files[1].old_path.as_deref(), Some("old.txt"));
+        assert_eq!(files[1].new_path.as_deref(), Some("new.txt"));
+        assert!(files[2].binary && files[2].status == "added");
+        assert_eq!(files[3].status, "deleted");
+        assert_eq!(files[3].old_path.as_deref(), Some("spéc.txt"));
+        assert_eq!(files[3].new_path, None);
+    }
+
+    #[test]
+    fn parses_word_diff() {
+        let out = "diff --git a/t.txt b/t.txt
+--- a/t.txt
++++ b/t.txt
+@@ -1,2 +1,3 @@
+ hello 
+-world
++there
+~
+ same
+~
++added line
+~
+";
+        let files = parse_unified_diff(out);
+        let lines = &files[0].hunks[0].lines;
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].kind, "change");
+        assert_eq!(lines[0].content, "hello there");
+        assert_eq!(lines[0].segments.as_ref().unwrap().len(), 3);
+        assert_eq!(lines[1].kind, "context");
+        assert_eq!((lines[1].old_line, lines[1].new_line), (Some(2), Some(2)));
+        assert_eq!(lines[2].kind, "add");
+        assert_eq!(lines[2].new_line, Some(3));
+    }
+
+    #[test]
+    fn parses_log_and_blame() {
+        let log = "aaaa\x1fa\x1fp1 p2\x1fAnn\x1fann@x.io\x1f1700000000\x1fHEAD -> main, tag: v1\x1fMerge it\x1fBody line\n\n\x1e\nbbbb\x1fb\x1f\x1fBob\x1fb@x.io\x1f1600000000\x1f\x1fInit\x1f\x1e\n";
+        let commits = parse_log(log);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].parents, ["p1", "p2"]);
+        assert_eq!(commits[0].refs, ["HEAD -> main", "tag: v1"]);
+        assert_eq!(commits[0].body, "Body line");
+        assert!(commits[1].parents.is_empty() && commits[1].refs.is_empty());
+
+        let sha = "1".repeat(40);
+        let zero = "0".repeat(40);
+        let blame = format!(
+            "{sha} 3 10 2\nauthor Ann\nauthor-mail <ann@x.io>\nauthor-time 1700000000\nsummary Fix\nfilename a.rs\n\tline ten\n{sha} 4 11\n\tline eleven\n{zero} 12 12 1\nauthor Not Committed Yet\nauthor-mail <not.committed.yet>\nauthor-time 1800000000\nsummary Version of a.rs from a.rs\nfilename a.rs\n\tlocal edit\n"
+        );
+        let b = parse_blame(&blame);
+        assert_eq!(b.lines.len(), 3);
+        assert_eq!(b.lines[1].line, 11);
+        assert_eq!(b.lines[1].orig_line, 4);
+        assert_eq!(b.lines[1].content, "line eleven");
+        assert_eq!(b.commits[&sha].author_email, "ann@x.io");
+        assert!(b.commits[&zero].uncommitted);
+        assert!(check_rev("--output=/tmp/x").is_err());
+        assert!(check_rev("HEAD~2").is_ok());
+    }
+}
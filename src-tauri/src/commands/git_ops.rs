@@ -0,0 +1,430 @@
+//! Guarded git write operations: stage, unstage, commit, amend, branch.
+//!
+//! `run_git_command` only allows read-only subcommands, so these are the only
+//! paths through which the UI changes a repository. Every operation is a
+//! plain git invocation with fixed arguments and the same protections:
+//!
+//!   - nothing forceful: no `-f`, `--force`, `reset`, `--discard-changes` or
+//!     push; git's own refusals (ignored files, dirty switch) surface as errors
+//!   - hooks always run (`--no-verify` is never passed) and a failing hook
+//!     aborts the commit with its output in the error
+//!   - commit, amend and switch refuse on a detached HEAD unless the caller
+//!     passes `allow_detached` after confirming with the user
+//!   - amend refuses once HEAD has reached a remote branch, since publishing
+//!     the rewrite would need a force push
+//!
+//! `generate_commit_message` drafts a message from the staged diff through
+//! the same isolated one-shot CLI spawn used for session titles; it never
+//! commits by itself.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::git_output;
+
+/// Staged diff sent to the message generator is cut at this many bytes.
+const MESSAGE_DIFF_BUDGET: usize = 12_000;
+const MESSAGE_GEN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Clone)]
+pub struct GitCommitResult {
+    pub commit: String,
+    /// `None` when committed on a detached HEAD.
+    pub branch: Option<String>,
+    pub summary: String,
+    /// Git's stdout, e.g. `[main 1a2b3c4] subject`.
+    pub output: String,
+}
+
+fn repo_dir(cwd: &str) -> Result<&Path, String> {
+    let path = Path::new(cwd);
+    if !path.is_dir() {
+        return Err(format!("Working directory does not exist: {}", cwd));
+    }
+    Ok(path)
+}
+
+fn check_paths(paths: &[String]) -> Result<(), String> {
+    if paths.is_empty() {
+        return Err("No paths given".to_string());
+    }
+    if let Some(bad) = paths.iter().find(|p| p.is_empty() || p.contains('\0')) {
+        return Err(format!("Invalid path: '{}'", bad));
+    }
+    Ok(())
+}
+
+/// Current branch name, or `None` on a detached HEAD.
+async fn current_branch(dir: &Path) -> Result<Option<String>, String> {
+    match git_output(dir, &["symbolic-ref", "--quiet", "--short", "HEAD"]).await {
+        Ok(name) => Ok(Some(name.trim().to_string())),
+        // symbolic-ref fails only when HEAD is not a symref; make sure this
+        // really is a repository before calling it detached.
+        Err(_) => {
+            git_output(dir, &["rev-parse", "--git-dir"]).await?;
+            Ok(None)
+        }
+    }
+}
+
+async fn ensure_attached(dir: &Path, allow_detached: bool, action: &str) -> Result<(), String> {
+    if !allow_detached && current_branch(dir).await?.is_none() {
+        return Err(format!(
+            "HEAD is detached; refusing to {} without confirmation",
+            action
+        ));
+    }
+    Ok(())
+}
+
+async fn has_head(dir: &Path) -> bool {
+    git_output(dir, &["rev-parse", "--verify", "--quiet", "HEAD"])
+        .await
+        .is_ok()
+}
+
+/// Branch names go through git's own validation, and a leading `-` would
+/// be taken as an option.
+async fn check_branch_name(dir: &Path, name: &str) -> Result<(), String> {
+    if name.is_empty() || name.starts_with('-') {
+        return Err(format!("Invalid branch name: '{}'", name));
+    }
+    git_output(dir, &["check-ref-format", "--branch", name])
+        .await
+        .map(|_| ())
+        .map_err(|_| format!("Invalid branch name: '{}'", name))
+}
+
+/// Stage `paths` (relative to `cwd`). Ignored files are refused by git.
+#[tauri::command]
+pub async fn git_stage(cwd: String, paths: Vec<String>) -> Result<(), String> {
+    let dir = repo_dir(&cwd)?;
+    check_paths(&paths)?;
+    let mut args = vec!["add", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    git_output(dir, &args).await.map(|_| ())
+}
+
+/// Remove `paths` from the index, leaving the working tree untouched.
+#[tauri::command]
+pub async fn git_unstage(cwd: String, paths: Vec<String>) -> Result<(), String> {
+    let dir = repo_dir(&cwd)?;
+    check_paths(&paths)?;
+    // Before the first commit there is no HEAD to restore the index from.
+    let mut args = if has_head(dir).await {
+        vec!["restore", "--staged", "--"]
+    } else {
+        vec!["rm", "--cached", "-r", "--quiet", "--"]
+    };
+    args.extend(paths.iter().map(String::as_str));
+    git_output(dir, &args).await.map(|_| ())
+}
+
+/// Commit the index. With `amend`, rewrite HEAD instead (message may then
+/// be omitted to keep the old one).
+#[tauri::command]
+pub async fn git_commit(
+    cwd: String,
+    message: Option<String>,
+    amend: Option<bool>,
+    allow_detached: Option<bool>,
+) -> Result<GitCommitResult, String> {
+    let dir = repo_dir(&cwd)?;
+    let amend = amend.unwrap_or(false);
+    let message = message
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty());
+    ensure_attached(
+        dir,
+        allow_detached.unwrap_or(false),
+        if amend { "amend" } else { "commit" },
+    )
+    .await?;
+
+    let mut args = vec!["commit"];
+    if amend {
+        if !has_head(dir).await {
+            return Err("Nothing to amend: the branch has no commits yet".to_string());
+        }
+        let remotes = git_output(dir, &["branch", "-r", "--contains", "HEAD"]).await?;
+        if let Some(remote) = remotes.lines().map(str::trim).find(|l| !l.is_empty()) {
+            return Err(format!(
+                "HEAD is already on {}; amending it would require a force push",
+                remote
+            ));
+        }
+        args.push("--amend");
+        if message.is_none() {
+            args.push("--no-edit");
+        }
+    } else {
+        if message.is_none() {
+            return Err("Commit message is empty".to_string());
+        }
+        let staged = git_output(dir, &["diff", "--cached", "--name-only"]).await?;
+        if staged.trim().is_empty() {
+            return Err("Nothing staged to commit".to_string());
+        }
+    }
+    if let Some(m) = message.as_deref() {
+        args.extend(["-m", m]);
+    }
+
+    let output = git_output(dir, &args).await?;
+    let commit = git_output(dir, &["rev-parse", "HEAD"])
+        .await?
+        .trim()
+        .to_string();
+    let summary = git_output(dir, &["log", "-1", "--format=%s"])
+        .await?
+        .trim()
+        .to_string();
+    Ok(GitCommitResult {
+        commit,
+        branch: current_branch(dir).await?,
+        summary,
+        output: output.trim().to_string(),
+    })
+}
+
+/// Create branch `name` at `start_point` (default HEAD) and optionally
+/// switch to it. Existing branches are never overwritten; switching away
+/// from a detached HEAD needs `allow_detached`, as in `git_switch_branch`.
+#[tauri::command]
+pub async fn git_create_branch(
+    cwd: String,
+    name: String,
+    start_point: Option<String>,
+    checkout: Option<bool>,
+    allow_detached: Option<bool>,
+) -> Result<(), String> {
+    let dir = repo_dir(&cwd)?;
+    check_branch_name(dir, &name).await?;
+    if let Some(start) = start_point.as_deref() {
+        if start.is_empty() || start.starts_with('-') {
+            return Err(format!("Invalid start point: '{}'", start));
+        }
+    }
+    if checkout.unwrap_or(false) {
+        ensure_attached(dir, allow_detached.unwrap_or(false), "switch away").await?;
+        let mut args = vec!["switch", "--create", &name];
+        args.extend(start_point.as_deref());
+        git_output(dir, &args).await?;
+    } else {
+        let mut args = vec!["branch", &name];
+        args.extend(start_point.as_deref());
+        git_output(dir, &args).await?;
+    }
+    Ok(())
+}
+
+/// Switch to an existing branch. Git refuses if local changes would be
+/// overwritten; leaving a detached HEAD needs `allow_detached`, since its
+/// commits may become unreachable.
+#[tauri::command]
+pub async fn git_switch_branch(
+    cwd: String,
+    name: String,
+    allow_detached: Option<bool>,
+) -> Result<(), String> {
+    let dir = repo_dir(&cwd)?;
+    check_branch_name(dir, &name).await?;
+    ensure_attached(dir, allow_detached.unwrap_or(false), "switch away").await?;
+    git_output(dir, &["switch", "--no-guess", &name])
+        .await
+        .map(|_| ())
+}
+
+fn truncate_utf8(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while end > 0 && !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Pull the message out of the CLI's JSON output and drop any code fence
+/// the model wrapped it in.
+fn extract_message(stdout: &str) -> Option<String> {
+    let text = match serde_json::from_str::<Value>(stdout.trim()) {
+        Ok(json) => json.get("result")?.as_str()?.to_string(),
+        Err(_) => stdout.to_string(),
+    };
+    let mut lines: Vec<&str> = text.trim().lines().collect();
+    if lines.first().is_some_and(|l| l.starts_with("```")) {
+        lines.remove(0);
+        if lines.last().is_some_and(|l| l.trim() == "```") {
+            lines.pop();
+        }
+    }
+    let message = lines.join("\n").trim().trim_matches('"').trim().to_string();
+    (!message.is_empty()).then_some(message)
+}
+
+/// Draft a commit message for the staged changes. `Ok(None)` when the
+/// generator is unavailable or timed out; the user then writes one.
+#[tauri::command]
+pub async fn generate_commit_message(
+    cwd: String,
+    provider_id: Option<String>,
+) -> Result<Option<String>, String> {
+    let dir = repo_dir(&cwd)?;
+    let stat = git_output(dir, &["diff", "--cached", "--stat"]).await?;
+    if stat.trim().is_empty() {
+        return Err("Nothing staged to commit".to_string());
+    }
+    let diff = git_output(dir, &["diff", "--cached", "--no-color", "--no-ext-diff"]).await?;
+    let recent = git_output(dir, &["log", "-10", "--format=%s"])
+        .await
+        .unwrap_or_default();
+
+    let prompt = format!(
+        "Write a git commit message for the staged changes below. Use an imperative subject line of at most 72 characters; add a blank line and a short body only if the change needs explanation. Follow the style of the recent subjects if there are any. Reply with ONLY the commit message, no quotes, no code fences.\n\nRecent subjects:\n{}\n\nSummary:\n{}\n\nDiff:\n{}",
+        recent.trim(),
+        stat.trim(),
+        truncate_utf8(&diff, MESSAGE_DIFF_BUDGET)
+    );
+    let stdout = crate::run_isolated_prompt(
+        prompt,
+        provider_id.as_deref(),
+        MESSAGE_GEN_TIMEOUT,
+        "commit-msg",
+    )
+    .await?;
+    Ok(stdout.as_deref().and_then(extract_message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn git(dir: &Path, args: &[&str]) -> String {
+        git_output(dir, args).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn stage_commit_amend_and_branch() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let cwd = dir.to_string_lossy().to_string();
+        git(dir, &["init", "-q", "-b", "main"]).await;
+        git(dir, &["config", "user.name", "T"]).await;
+        git(dir, &["config", "user.email", "t@example.com"]).await;
+        std::fs::write(dir.join("a.txt"), "one\n").unwrap();
+
+        // Unstaging works before the first commit.
+        git_stage(cwd.clone(), vec!["a.txt".into()]).await.unwrap();
+        git_unstage(cwd.clone(), vec!["a.txt".into()])
+            .await
+            .unwrap();
+        let err = git_commit(cwd.clone(), Some("init".into()), None, None)
+            .await
+            .unwrap_err();
+        assert!(err.contains("Nothing staged"));
+
+        git_stage(cwd.clone(), vec!["a.txt".into()]).await.unwrap();
+        let first = git_commit(cwd.clone(), Some(" init \n".into()), None, None)
+            .await
+            .unwrap();
+        assert_eq!(first.summary, "init");
+        assert_eq!(first.branch.as_deref(), Some("main"));
+
+        let amended = git_commit(cwd.clone(), Some("initial".into()), Some(true), None)
+            .await
+            .unwrap();
+        assert_ne!(amended.commit, first.commit);
+        assert_eq!(amended.summary, "initial");
+
+        git_create_branch(cwd.clone(), "feature/x".into(), None, Some(true), None)
+            .await
+            .unwrap();
+        assert!(
+            git_create_branch(cwd.clone(), "feature/x".into(), None, None, None)
+                .await
+                .is_err()
+        );
+        assert!(
+            git_create_branch(cwd.clone(), "-f".into(), None, None, None)
+                .await
+                .is_err()
+        );
+        assert!(
+            git_create_branch(cwd.clone(), "bad..name".into(), None, None, None)
+                .await
+                .is_err()
+        );
+        git_switch_branch(cwd.clone(), "main".into(), None)
+            .await
+            .unwrap();
+
+        // Detached HEAD needs confirmation.
+        git(dir, &["switch", "--detach", "-q", "HEAD"]).await;
+        std::fs::write(dir.join("a.txt"), "two\n").unwrap();
+        git_stage(cwd.clone(), vec!["a.txt".into()]).await.unwrap();
+        let err = git_commit(cwd.clone(), Some("x".into()), None, None)
+            .await
+            .unwrap_err();
+        assert!(err.contains("detached"));
+        let detached = git_commit(cwd.clone(), Some("x".into()), None, Some(true))
+            .await
+            .unwrap();
+        assert_eq!(detached.branch, None);
+        assert!(git_switch_branch(cwd.clone(), "main".into(), None)
+            .await
+            .is_err());
+        let err = git_create_branch(
+            cwd.clone(),
+            "elsewhere".into(),
+            Some("main".into()),
+            Some(true),
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("detached"));
+        git_create_branch(cwd.clone(), "rescue".into(), None, Some(true), Some(true))
+            .await
+            .unwrap();
+        assert_eq!(
+            current_branch(dir).await.unwrap().as_deref(),
+            Some("rescue")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn failing_hook_aborts_commit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let cwd = dir.to_string_lossy().to_string();
+        git(dir, &["init", "-q"]).await;
+        git(dir, &["config", "user.name", "T"]).await;
+        git(dir, &["config", "user.email", "t@example.com"]).await;
+        let hook = dir.join(".git/hooks/pre-commit");
+        std::fs::write(&hook, "#!/bin/sh\necho 'lint failed' >&2\nexit 1\n").unwrap();
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(dir.join("a.txt"), "one\n").unwrap();
+        git_stage(cwd.clone(), vec!["a.txt".into()]).await.unwrap();
+        let err = git_commit(cwd, Some("init".into()), None, None)
+            .await
+            .unwrap_err();
+        assert!(err.contains("lint failed"));
+    }
+
+    #[test]
+    fn extracts_generated_message() {
+        let json = r#"{"type":"result","result":"```\nAdd parser\n\nHandles quoted paths.\n```"}"#;
+        assert_eq!(
+            extract_message(json).as_deref(),
+            Some("Add parser\n\nHandles quoted paths.")
+        );
+        assert_eq!(extract_message(r#"{"result":"  "}"#), None);
+        assert_eq!(extract_message("Fix typo\n").as_deref(), Some("Fix typo"));
+    }
+}
@@ -0,0 +1,396 @@
+//! `.gitignore`-style matching shared by the explorer, search and watcher.
+//!
+//! Covers the parts of gitignore that matter in practice: `*`, `?`, `[...]`
+//! classes, `**` across directories, leading-`/` anchoring, trailing-`/`
+//! directory rules, `!` negation (last match wins) and per-directory files
+//! where deeper files override shallower ones. Rules are read from
+//! `.gitignore` and `.ignore` in each directory from the repository root
+//! down, plus `.git/info/exclude`. Outside a repository the walk starts at
+//! the directory itself.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+pub(crate) const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+/// Hidden regardless of ignore files.
+pub(crate) const ALWAYS_IGNORED: &[&str] = &[".git", ".DS_Store", "Thumbs.db"];
+
+/// One gitignore line.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Rule {
+    pattern: Vec<char>,
+    negate: bool,
+    dir_only: bool,
+    /// Contains a `/`, so it matches the whole relative path rather than
+    /// just the file name.
+    anchored: bool,
+}
+
+impl Rule {
+    pub(crate) fn parse(line: &str) -> Option<Rule> {
+        let mut text = line.trim_end_matches(['\r', '\n']).to_string();
+        while text.ends_with(' ') && !text.ends_with("\\ ") {
+            text.pop();
+        }
+        if text.is_empty() || text.starts_with('#') {
+            return None;
+        }
+        let (negate, body) = match text.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, text.as_str()),
+        };
+        let body = body
+            .strip_prefix('\\')
+            .filter(|b| b.starts_with(['!', '#']))
+            .unwrap_or(body);
+        let dir_only = body.ends_with('/');
+        let body = body.trim_end_matches('/');
+        if body.is_empty() {
+            return None;
+        }
+        let anchored = body.contains('/');
+        let body = body.strip_prefix('/').unwrap_or(body);
+        Some(Rule {
+            pattern: body.chars().collect(),
+            negate,
+            dir_only,
+            anchored,
+        })
+    }
+
+    /// `rel` is `/`-separated and relative to the directory the rule came from.
+    fn matches(&self, rel: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let subject = if self.anchored {
+            rel
+        } else {
+            rel.rsplit('/').next().unwrap_or(rel)
+        };
+        glob_match(&self.pattern, &subject.chars().collect::<Vec<_>>())
+    }
+}
+
+/// Glob match over a `/`-separated path. `*` and `?` stay within one
+/// segment; `**/`, `/**/` and a trailing `/**` span directories.
+fn glob_match(p: &[char], s: &[char]) -> bool {
+    match p.first() {
+        None => s.is_empty(),
+        Some('*') if p.get(1) == Some(&'*') => {
+            let rest = &p[2..];
+            if let Some(after) = rest.strip_prefix(&['/']) {
+                // `**/`: zero or more whole directories.
+                glob_match(after, s)
+                    || (0..s.len()).any(|i| s[i] == '/' && glob_match(after, &s[i + 1..]))
+            } else {
+                (0..=s.len()).any(|i| glob_match(rest, &s[i..]))
+            }
+        }
+        Some('*') => {
+            for i in 0..=s.len() {
+                if glob_match(&p[1..], &s[i..]) {
+                    return true;
+                }
+                if i < s.len() && s[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => !s.is_empty() && s[0] != '/' && glob_match(&p[1..], &s[1..]),
+        Some('[') => match class_match(&p[1..], s.first().copied()) {
+            Some((true, consumed)) => glob_match(&p[1 + consumed..], &s[1..]),
+            Some((false, _)) => false,
+            // Unterminated class: a literal `[`.
+            None => s.first() == Some(&'[') && glob_match(&p[1..], &s[1..]),
+        },
+        Some('\\') if p.len() > 1 => s.first() == Some(&p[1]) && glob_match(&p[2..], &s[1..]),
+        Some(c) => s.first() == Some(c) && glob_match(&p[1..], &s[1..]),
+    }
+}
+
+/// Match `c` against a class body (after `[`). Returns whether it matched
+/// and how many pattern chars the class used, including `]`.
+fn class_match(p: &[char], c: Option<char>) -> Option<(bool, usize)> {
+    let mut i = 0;
+    let negated = matches!(p.first(), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    while i < p.len() {
+        let ch = p[i];
+        if ch == ']' && !first {
+            let hit = c.is_some_and(|c| c != '/') && matched != negated;
+            return Some((hit, i + 1));
+        }
+        first = false;
+        if i + 2 < p.len() && p[i + 1] == '-' && p[i + 2] != ']' {
+            if c.is_some_and(|c| ch <= c && c <= p[i + 2]) {
+                matched = true;
+            }
+            i += 3;
+        } else {
+            if c == Some(ch) {
+                matched = true;
+            }
+            i += 1;
+        }
+    }
+    None
+}
+
+/// `/`-separated path of `path` below `base`, `None` when outside it.
+pub(crate) fn rel_path(base: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(base).ok()?;
+    let parts: Vec<String> = rel
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(s) => Some(s.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+    Some(parts.join("/"))
+}
+
+/// An ordered list of gitignore-style globs, e.g. user include/exclude
+/// settings. The last matching line decides, so `!` lines carve exceptions.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GlobList {
+    rules: Vec<Rule>,
+}
+
+impl GlobList {
+    pub(crate) fn new<S: AsRef<str>>(globs: &[S]) -> Self {
+        GlobList {
+            rules: globs
+                .iter()
+                .filter_map(|g| Rule::parse(g.as_ref()))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub(crate) fn matches(&self, rel: &str, is_dir: bool) -> bool {
+        last_match(&self.rules, rel, is_dir).unwrap_or(false)
+    }
+}
+
+fn last_match(rules: &[Rule], rel: &str, is_dir: bool) -> Option<bool> {
+    rules
+        .iter()
+        .rev()
+        .find(|r| r.matches(rel, is_dir))
+        .map(|r| !r.negate)
+}
+
+#[derive(Debug)]
+struct RuleSet {
+    /// Directory the rules came from, relative to the matcher root.
+    base: String,
+    rules: Vec<Rule>,
+}
+
+/// Ignore rules in effect for one directory and, via `child`, below it.
+#[derive(Debug, Clone)]
+pub(crate) struct IgnoreMatcher {
+    root: PathBuf,
+    sets: Vec<Arc<RuleSet>>,
+}
+
+fn find_repo_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|a| a.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+impl IgnoreMatcher {
+    /// Rules that apply inside `dir`, loaded from the enclosing repository's
+    /// root (or `dir` itself outside a repository) down to `dir`.
+    pub(crate) fn for_dir(dir: &Path) -> Self {
+        let repo = find_repo_root(dir);
+        let root = repo.clone().unwrap_or_else(|| dir.to_path_buf());
+        let mut matcher = IgnoreMatcher {
+            root: root.clone(),
+            sets: Vec::new(),
+        };
+        if let Some(repo) = repo {
+            matcher.load_file(&repo.join(".git").join("info").join("exclude"), "");
+        }
+        matcher.load_dir(&root);
+        let mut current = root.clone();
+        if let Ok(rest) = dir.strip_prefix(&root) {
+            for part in rest.components() {
+                current.push(part);
+                matcher.load_dir(&current);
+            }
+        }
+        matcher
+    }
+
+    /// Rules in effect inside `dir`, loaded from `root` down. Unlike
+    /// `for_dir`, the ignore files of `root` apply even outside a repository.
+    pub(crate) fn for_dir_under(root: &Path, dir: &Path) -> Self {
+        let mut matcher = Self::for_dir(root);
+        let mut current = root.to_path_buf();
+        if let Ok(rest) = dir.strip_prefix(root) {
+            for part in rest.components() {
+                current.push(part);
+                matcher.load_dir(&current);
+            }
+        }
+        matcher
+    }
+
+    /// Matcher for `dir`, a subdirectory of this matcher's directory: adds
+    /// the ignore files found in `dir`.
+    pub(crate) fn child(&self, dir: &Path) -> Self {
+        let mut matcher = self.clone();
+        matcher.load_dir(dir);
+        matcher
+    }
+
+    fn load_dir(&mut self, dir: &Path) {
+        let Some(base) = rel_path(&self.root, dir) else {
+            return;
+        };
+        for name in IGNORE_FILES {
+            self.load_file(&dir.join(name), &base);
+        }
+    }
+
+    fn load_file(&mut self, file: &Path, base: &str) {
+        let Ok(text) = std::fs::read_to_string(file) else {
+            return;
+        };
+        let rules: Vec<Rule> = text.lines().filter_map(Rule::parse).collect();
+        if !rules.is_empty() {
+            self.sets.push(Arc::new(RuleSet {
+                base: base.to_string(),
+                rules,
+            }));
+        }
+    }
+
+    /// Whether any ignore file contributed rules (beyond `ALWAYS_IGNORED`).
+    pub(crate) fn has_rules(&self) -> bool {
+        !self.sets.is_empty()
+    }
+
+    /// Whether the entry itself is ignored. Walkers that never descend into
+    /// ignored directories only need this.
+    pub(crate) fn matched(&self, path: &Path, is_dir: bool) -> bool {
+        if path
+            .file_name()
+            .is_some_and(|n| ALWAYS_IGNORED.iter().any(|a| n == *a))
+        {
+            return true;
+        }
+        let Some(rel) = rel_path(&self.root, path) else {
+            return false;
+        };
+        self.matched_rel(&rel, is_dir)
+    }
+
+    fn matched_rel(&self, rel: &str, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for set in &self.sets {
+            let local = if set.base.is_empty() {
+                Some(rel)
+            } else {
+                rel.strip_prefix(set.base.as_str())
+                    .and_then(|r| r.strip_prefix('/'))
+            };
+            if let Some(decision) = local.and_then(|l| last_match(&set.rules, l, is_dir)) {
+                ignored = decision;
+            }
+        }
+        ignored
+    }
+
+    /// Whether `path` is ignored itself or sits inside an ignored directory
+    /// (git never re-includes files below an excluded directory). For paths
+    /// that arrive out of order, e.g. from the file watcher.
+    pub(crate) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(rel) = rel_path(&self.root, path) else {
+            return false;
+        };
+        let parts: Vec<&str> = rel.split('/').filter(|p| !p.is_empty()).collect();
+        if parts.iter().any(|p| ALWAYS_IGNORED.contains(p)) {
+            return true;
+        }
+        (1..=parts.len()).any(|n| {
+            let last = n == parts.len();
+            self.matched_rel(&parts[..n].join("/"), !last || is_dir)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn m(pattern: &str, path: &str, is_dir: bool) -> bool {
+        Rule::parse(pattern).unwrap().matches(path, is_dir)
+    }
+
+    #[test]
+    fn gitignore_pattern_semantics() {
+        assert!(m("*.log", "a/b/debug.log", false));
+        assert!(!m("*.log", "a/b/debug.log.txt", false));
+        assert!(m("/build", "build", true));
+        assert!(!m("/build", "src/build", true));
+        assert!(m("build/", "src/build", true));
+        assert!(!m("build/", "src/build", false));
+        assert!(m("docs/*.md", "docs/a.md", false));
+        assert!(!m("docs/*.md", "docs/x/a.md", false));
+        assert!(m("**/cache", "a/b/cache", true));
+        assert!(m("**/cache", "cache", true));
+        assert!(m("a/**/z", "a/z", false));
+        assert!(m("a/**/z", "a/b/c/z", false));
+        assert!(m("out/**", "out/x/y", false));
+        assert!(m("file[0-9].txt", "file7.txt", false));
+        assert!(!m("file[!0-9].txt", "file7.txt", false));
+        assert!(m("\\#notes", "#notes", false));
+        assert!(m("what?", "whatx", false));
+        assert_eq!(Rule::parse("# comment"), None);
+        assert_eq!(Rule::parse("   "), None);
+        assert!(Rule::parse("!keep.log").unwrap().negate);
+
+        let list = GlobList::new(&["*.rs", "!generated.rs"]);
+        assert!(list.matches("src/lib.rs", false));
+        assert!(!list.matches("src/generated.rs", false));
+    }
+
+    #[test]
+    fn nested_ignore_files_and_negation() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join(".git/info")).unwrap();
+        std::fs::write(root.join(".git/info/exclude"), "secret.txt\n").unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log\nnode_modules/\n").unwrap();
+        std::fs::create_dir_all(root.join("app/logs")).unwrap();
+        std::fs::write(root.join("app/.gitignore"), "!keep.log\n").unwrap();
+
+        let matcher = IgnoreMatcher::for_dir(root);
+        assert!(matcher.matched(&root.join("x.log"), false));
+        assert!(matcher.matched(&root.join("secret.txt"), false));
+        assert!(matcher.matched(&root.join(".git"), true));
+        assert!(!matcher.matched(&root.join("app"), true));
+
+        let app = matcher.child(&root.join("app"));
+        assert!(!app.matched(&root.join("app/keep.log"), false));
+        assert!(app.matched(&root.join("app/other.log"), false));
+
+        // Built directly for a subdirectory, the root's rules still apply.
+        let sub = IgnoreMatcher::for_dir(&root.join("app/logs"));
+        assert!(sub.matched(&root.join("app/logs/a.log"), false));
+        assert!(sub.is_ignored(&root.join("node_modules/pkg/index.js"), false));
+        assert!(!sub.is_ignored(&root.join("app/logs/readme.md"), false));
+    }
+}
@@ -0,0 +1,1248 @@
+//! MCP server configuration, per-session selection and health checks.
+//!
+//! Servers live in two places, both in the CLI's own format:
+//!
+//!   - global:  `~/.claude.json` → `mcpServers`
+//!   - project: `{cwd}/.mcp.json` → `mcpServers` (wins on a name clash)
+//!
+//! Sessions run with `--strict-mcp-config`, so the CLI only sees what
+//! `build_mcp_scratch_config` writes. `session_servers` merges both scopes
+//! and applies the session's `McpSelection` (disabled names and an optional
+//! allow-list). A project server is only carried over once the user approved
+//! it the way the CLI records it (`enabledMcpjsonServers` /
+//! `enableAllProjectMcpServers`) or listed it in the selection's `only`.
+//!
+//! Selections passed to `start_claude_session` are remembered under the
+//! desk id and copied to the CLI session id once the CLI reports it, so a
+//! resume (new desk id, same CLI id) keeps the same set.
+//!
+//! `check_mcp_server` performs the MCP handshake (`initialize`,
+//! `notifications/initialized`, `tools/list`) over stdio, streamable HTTP or
+//! legacy SSE and reports the server's tools or the first error.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+const SELECTIONS_FILE: &str = "mcp_selections.json";
+const PROTOCOL_VERSION: &str = "2025-06-18";
+const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+/// Keep only the tail of a stdio server's stderr for the report.
+const MAX_STDERR: usize = 4 * 1024;
+/// Upper bound on `tools/list` pages followed via `nextCursor`.
+const MAX_TOOL_PAGES: usize = 10;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct McpServerEntry {
+    pub name: String,
+    /// "global" | "project"
+    pub scope: String,
+    /// "stdio" | "http" | "sse"
+    pub transport: String,
+    /// The raw entry as stored, so unknown keys survive a round trip.
+    pub config: Value,
+    /// A project entry with the same name replaces this one in sessions.
+    pub overridden: bool,
+    /// Validation problems; a server with issues is still listed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<String>,
+}
+
+/// Which configured servers a session gets.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct McpSelection {
+    /// Server names left out of the session.
+    #[serde(default)]
+    pub disabled: Vec<String>,
+    /// When set, only these servers are included (before `disabled`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub only: Option<Vec<String>>,
+}
+
+impl McpSelection {
+    pub(crate) fn allows(&self, name: &str) -> bool {
+        let listed = self
+            .only
+            .as_ref()
+            .is_none_or(|only| only.iter().any(|n| n == name));
+        listed && !self.disabled.iter().any(|n| n == name)
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct McpToolInfo {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct McpHealthReport {
+    pub ok: bool,
+    pub transport: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<String>,
+    pub tools: Vec<McpToolInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Tail of a stdio server's stderr — usually where the real error is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+// ---------------------------------------------------------------------------
+// Config files
+// ---------------------------------------------------------------------------
+
+fn config_file(scope: &str, cwd: Option<&str>) -> Result<PathBuf, String> {
+    match scope {
+        "global" => dirs::home_dir()
+            .map(|h| h.join(".claude.json"))
+            .ok_or_else(|| "Cannot resolve home directory".to_string()),
+        "project" => cwd
+            .filter(|c| !c.is_empty())
+            .map(|c| Path::new(c).join(".mcp.json"))
+            .ok_or_else(|| "Project scope requires a working directory".to_string()),
+        other => Err(format!("Unknown MCP scope: {}", other)),
+    }
+}
+
+/// Missing file → empty object. A file that exists but does not parse is an
+/// error: rewriting it would destroy whatever else the user keeps there.
+fn read_config_json(path: &Path) -> Result<Value, String> {
+    let raw = match std::fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(json!({})),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    if raw.trim().is_empty() {
+        return Ok(json!({}));
+    }
+    let value: Value = serde_json::from_str(&raw)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    if !value.is_object() {
+        return Err(format!("{} is not a JSON object", path.display()));
+    }
+    Ok(value)
+}
+
+/// Write through a temp file + rename so the CLI never reads a half-written
+/// `~/.claude.json`.
+fn write_config_json(path: &Path, value: &Value) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize MCP config: {}", e))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directories: {}", e))?;
+    }
+    let tmp = path.with_extension(format!("json.tmp-{}", std::process::id()));
+    std::fs::write(&tmp, text).map_err(|e| format!("Failed to write MCP config: {}", e))?;
+    std::fs::rename(&tmp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        format!("Failed to replace {}: {}", path.display(), e)
+    })
+}
+
+/// The `mcpServers` map of a config document, tolerating the double-nested
+/// `mcpServers.mcpServers` shape older builds wrote.
+pub(crate) fn servers_of(doc: &Value) -> Map<String, Value> {
+    let mut servers = doc
+        .get("mcpServers")
+        .and_then(|v| v.as_object())
+        .cloned()
+        .unwrap_or_default();
+    if let Some(inner) = servers.get("mcpServers").and_then(|v| v.as_object()) {
+        servers = inner.clone();
+    }
+    servers
+}
+
+fn valid_server_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Transport of a server entry: explicit `type`, else `url` → http, else stdio.
+fn transport_of(config: &Value) -> String {
+    match config.get("type").and_then(|t| t.as_str()) {
+        Some(t) => t.to_string(),
+        None if config.get("url").is_some() => "http".to_string(),
+        None => "stdio".to_string(),
+    }
+}
+
+fn string_map_issue(config: &Value, key: &str) -> Option<String> {
+    match config.get(key) {
+        None => None,
+        Some(Value::Object(m)) if m.values().all(|v| v.is_string()) => None,
+        Some(_) => Some(format!("`{}` must be an object of string values", key)),
+    }
+}
+
+/// Problems that would stop the CLI from starting this server.
+pub(crate) fn validate_server_config(config: &Value) -> Vec<String> {
+    let mut issues = Vec::new();
+    if !config.is_object() {
+        return vec!["Server entry must be a JSON object".to_string()];
+    }
+    match transport_of(config).as_str() {
+        "stdio" => {
+            match config.get("command").and_then(|c| c.as_str()) {
+                Some(c) if !c.trim().is_empty() => {}
+                _ => issues.push("stdio servers need a non-empty `command`".to_string()),
+            }
+            match config.get("args") {
+                None => {}
+                Some(Value::Array(a)) if a.iter().all(|v| v.is_string()) => {}
+                Some(_) => issues.push("`args` must be an array of strings".to_string()),
+            }
+            issues.extend(string_map_issue(config, "env"));
+        }
+        "http" | "sse" => {
+            match config.get("url").and_then(|u| u.as_str()) {
+                Some(u) if u.starts_with("http://") || u.starts_with("https://") => {}
+                Some(u) if u.contains("${") => {}
+                _ => issues.push("http/sse servers need an http(s):// `url`".to_string()),
+            }
+            issues.extend(string_map_issue(config, "headers"));
+        }
+        other => issues.push(format!(
+            "Unknown transport type '{}' (expected stdio, http or sse)",
+            other
+        )),
+    }
+    issues
+}
+
+/// Expand `${VAR}` and `${VAR:-default}` the way the CLI does for `.mcp.json`.
+/// Unset variables without a default expand to an empty string.
+fn expand_env(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        let expr = &after[..end];
+        let (var, default) = match expr.split_once(":-") {
+            Some((v, d)) => (v, Some(d)),
+            None => (expr, None),
+        };
+        match std::env::var(var) {
+            Ok(val) if !val.is_empty() => out.push_str(&val),
+            _ => out.push_str(default.unwrap_or("")),
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn string_map(config: &Value, key: &str) -> Vec<(String, String)> {
+    config
+        .get(key)
+        .and_then(|v| v.as_object())
+        .map(|m| {
+            m.iter()
+                .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), expand_env(s))))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn load_scope(scope: &str, cwd: Option<&str>) -> Result<Map<String, Value>, String> {
+    Ok(servers_of(&read_config_json(&config_file(scope, cwd)?)?))
+}
+
+/// The CLI's approval state for a project's `.mcp.json` servers.
+#[derive(Debug, Default, PartialEq)]
+struct ProjectApprovals {
+    all: bool,
+    enabled: Vec<String>,
+    disabled: Vec<String>,
+}
+
+impl ProjectApprovals {
+    /// Fold the approval keys of `docs`, most specific first: the first
+    /// document setting `enableAllProjectMcpServers` decides it, lists add up.
+    fn from_docs(docs: &[Value]) -> Self {
+        let names = |doc: &Value, key: &str| -> Vec<String> {
+            doc.get(key)
+                .and_then(|v| v.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|n| n.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let mut approvals = Self {
+            all: docs
+                .iter()
+                .find_map(|d| {
+                    d.get("enableAllProjectMcpServers")
+                        .and_then(|v| v.as_bool())
+                })
+                .unwrap_or(false),
+            ..Default::default()
+        };
+        for doc in docs {
+            approvals
+                .enabled
+                .extend(names(doc, "enabledMcpjsonServers"));
+            approvals
+                .disabled
+                .extend(names(doc, "disabledMcpjsonServers"));
+        }
+        approvals
+    }
+
+    /// Where the CLI keeps approvals for `cwd`: the project's local and
+    /// shared settings, the user settings and `~/.claude.json` → `projects`.
+    fn load(cwd: &str) -> Self {
+        let mut docs = Vec::new();
+        let project = Path::new(cwd).join(".claude");
+        docs.push(project.join("settings.local.json"));
+        docs.push(project.join("settings.json"));
+        if let Some(home) = dirs::home_dir() {
+            docs.push(home.join(".claude").join("settings.json"));
+        }
+        let mut values: Vec<Value> = docs
+            .iter()
+            .filter_map(|p| read_config_json(p).ok())
+            .collect();
+        if let Ok(global) = config_file("global", None).and_then(|p| read_config_json(&p)) {
+            if let Some(entry) = global.get("projects").and_then(|p| p.get(cwd)) {
+                values.insert(0, entry.clone());
+            }
+        }
+        Self::from_docs(&values)
+    }
+
+    fn approved(&self, name: &str) -> bool {
+        !self.disabled.iter().any(|n| n == name)
+            && (self.all || self.enabled.iter().any(|n| n == name))
+    }
+}
+
+/// Global servers plus the approved (or explicitly selected) project ones,
+/// project entries replacing global ones, with `selection` applied.
+fn merge_servers(
+    global: Map<String, Value>,
+    project: Map<String, Value>,
+    approvals: &ProjectApprovals,
+    selection: Option<&McpSelection>,
+) -> Map<String, Value> {
+    let mut merged = global;
+    for (name, config) in project {
+        let selected = selection
+            .and_then(|s| s.only.as_ref())
+            .is_some_and(|only| only.contains(&name));
+        if selected || approvals.approved(&name) {
+            merged.insert(name, config);
+        } else {
+            eprintln!(
+                "[TOKENICODE] MCP project server {} not approved, skipped",
+                name
+            );
+        }
+    }
+    if let Some(sel) = selection {
+        merged.retain(|name, _| sel.allows(name));
+    }
+    merged
+}
+
+/// Servers a session in `cwd` gets. Unreadable files contribute nothing.
+pub(crate) fn session_servers(
+    cwd: Option<&str>,
+    selection: Option<&McpSelection>,
+) -> Map<String, Value> {
+    let cwd = cwd.filter(|c| !c.is_empty());
+    let load = |scope: &str| {
+        load_scope(scope, cwd).unwrap_or_else(|e| {
+            eprintln!("[TOKENICODE] MCP {} config skipped: {}", scope, e);
+            Map::new()
+        })
+    };
+    let global = load("global");
+    let (project, approvals) = match cwd {
+        Some(dir) => (load("project"), ProjectApprovals::load(dir)),
+        None => (Map::new(), ProjectApprovals::default()),
+    };
+    merge_servers(global, project, &approvals, selection)
+}
+
+// ---------------------------------------------------------------------------
+// Per-session selections
+// ---------------------------------------------------------------------------
+
+fn load_selections() -> BTreeMap<String, McpSelection> {
+    crate::tokenicode_data_path(SELECTIONS_FILE)
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_selections(map: &BTreeMap<String, McpSelection>) -> Result<(), String> {
+    let path = crate::tokenicode_data_path(SELECTIONS_FILE)?;
+    let text = serde_json::to_string_pretty(map)
+        .map_err(|e| format!("Failed to serialize MCP selections: {}", e))?;
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write MCP selections: {}", e))
+}
+
+/// Remember `selection` for `session_id`; an empty selection forgets it.
+pub(crate) fn store_selection(session_id: &str, selection: &McpSelection) -> Result<(), String> {
+    let mut map = load_selections();
+    if *selection == McpSelection::default() {
+        if map.remove(session_id).is_none() {
+            return Ok(());
+        }
+    } else {
+        map.insert(session_id.to_string(), selection.clone());
+    }
+    save_selections(&map)
+}
+
+/// Copy the selection stored under `from` to `to`. True when `map` changed.
+fn link_in(map: &mut BTreeMap<String, McpSelection>, from: &str, to: &str) -> bool {
+    match map.get(from).cloned() {
+        Some(sel) if from != to && map.get(to) != Some(&sel) => {
+            map.insert(to.to_string(), sel);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Carry the selection of desk session `stdin_id` over to the CLI session
+/// id it runs, which is what a later resume looks it up by.
+pub(crate) fn link_selection(stdin_id: &str, cli_session_id: &str) {
+    let mut map = load_selections();
+    if link_in(&mut map, stdin_id, cli_session_id) {
+        if let Err(e) = save_selections(&map) {
+            eprintln!("[TOKENICODE] Failed to store MCP selection: {}", e);
+        }
+    }
+}
+
+/// Selection remembered for the first of `ids` that has one.
+pub(crate) fn stored_selection(ids: &[&str]) -> Option<McpSelection> {
+    let map = load_selections();
+    ids.iter().find_map(|id| map.get(*id).cloned())
+}
+
+// ---------------------------------------------------------------------------
+// Health check
+// ---------------------------------------------------------------------------
+
+/// Incremental `text/event-stream` parser yielding (event, data) pairs.
+#[derive(Default)]
+struct SseParser {
+    buf: String,
+    event: String,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    fn push(&mut self, chunk: &str) -> Vec<(String, String)> {
+        self.buf.push_str(chunk);
+        let mut out = Vec::new();
+        while let Some(nl) = self.buf.find('\n') {
+            let line: String = self.buf.drain(..=nl).collect();
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    let event = if self.event.is_empty() {
+                        "message".to_string()
+                    } else {
+                        std::mem::take(&mut self.event)
+                    };
+                    out.push((event, self.data.join("\n")));
+                    self.data.clear();
+                }
+                self.event.clear();
+            } else if let Some(v) = line.strip_prefix("data:") {
+                self.data.push(v.strip_prefix(' ').unwrap_or(v).to_string());
+            } else if let Some(v) = line.strip_prefix("event:") {
+                self.event = v.trim().to_string();
+            }
+        }
+        out
+    }
+}
+
+enum McpClient {
+    Stdio {
+        child: Box<tokio::process::Child>,
+        stdin: tokio::process::ChildStdin,
+        stdout: tokio::io::Lines<BufReader<tokio::process::ChildStdout>>,
+    },
+    Http {
+        client: reqwest::Client,
+        url: String,
+        headers: Vec<(String, String)>,
+        session: Option<String>,
+    },
+    Sse {
+        client: reqwest::Client,
+        endpoint: String,
+        headers: Vec<(String, String)>,
+        messages: tokio::sync::mpsc::UnboundedReceiver<Value>,
+    },
+}
+
+fn rpc(id: Option<u64>, method: &str, params: Value) -> Value {
+    let mut msg = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+    if let Some(id) = id {
+        msg["id"] = json!(id);
+    }
+    msg
+}
+
+/// The JSON-RPC result for `id`, or its error as a string.
+fn take_result(msg: Value, id: u64) -> Option<Result<Value, String>> {
+    if msg.get("id").and_then(|v| v.as_u64()) != Some(id) || msg.get("method").is_some() {
+        return None;
+    }
+    if let Some(err) = msg.get("error") {
+        let text = err
+            .get("message")
+            .and_then(|m| m.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| err.to_string());
+        return Some(Err(text));
+    }
+    Some(Ok(msg.get("result").cloned().unwrap_or(Value::Null)))
+}
+
+impl McpClient {
+    async fn connect_stdio(
+        config: &Value,
+        cwd: Option<&str>,
+        stderr_tail: Arc<Mutex<String>>,
+    ) -> Result<Self, String> {
+        let command = expand_env(config.get("command").and_then(|c| c.as_str()).unwrap_or(""));
+        let args: Vec<String> = config
+            .get("args")
+            .and_then(|a| a.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|v| v.as_str())
+                    .map(expand_env)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // npx/uvx and friends are .cmd shims on Windows; go through cmd.
+        #[cfg(not(target_os = "windows"))]
+        let mut cmd = {
+            let mut c = tokio::process::Command::new(&command);
+            c.args(&args);
+            c
+        };
+        #[cfg(target_os = "windows")]
+        let mut cmd = {
+            let mut c = tokio::process::Command::new("cmd");
+            c.arg("/C").arg(&command).args(&args);
+            c.creation_flags(0x08000000); // CREATE_NO_WINDOW
+            c
+        };
+        if let Some(dir) = cwd.filter(|c| !c.is_empty()) {
+            cmd.current_dir(dir);
+        }
+        cmd.env("PATH", crate::build_enriched_path());
+        for (k, v) in string_map(config, "env") {
+            cmd.env(k, v);
+        }
+        cmd.stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| format!("Failed to spawn `{}`: {}", command, e))?;
+        let stdin = child.stdin.take().ok_or("Failed to open server stdin")?;
+        let stdout = child.stdout.take().ok_or("Failed to open server stdout")?;
+        if let Some(mut stderr) = child.stderr.take() {
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                while let Ok(n) = stderr.read(&mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                    let mut tail = stderr_tail.lock().unwrap();
+                    tail.push_str(&String::from_utf8_lossy(&buf[..n]));
+                    if tail.len() > MAX_STDERR {
+                        let mut cut = tail.len() - MAX_STDERR;
+                        while !tail.is_char_boundary(cut) {
+                            cut += 1;
+                        }
+                        tail.drain(..cut);
+                    }
+                }
+            });
+        }
+        Ok(McpClient::Stdio {
+            child: Box::new(child),
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+        })
+    }
+
+    async fn connect_sse(
+        client: reqwest::Client,
+        url: String,
+        headers: Vec<(String, String)>,
+    ) -> Result<Self, String> {
+        let mut req = client.get(&url).header("Accept", "text/event-stream");
+        for (k, v) in &headers {
+            req = req.header(k, v);
+        }
+        let mut resp = req
+            .send()
+            .await
+            .map_err(|e| format!("Failed to connect to {}: {}", url, e))?;
+        if !resp.status().is_success() {
+            return Err(format!("SSE connect failed: HTTP {}", resp.status()));
+        }
+
+        // The first `endpoint` event names the URL to POST messages to.
+        let mut parser = SseParser::default();
+        let endpoint = loop {
+            let chunk = resp
+                .chunk()
+                .await
+                .map_err(|e| format!("SSE stream error: {}", e))?
+                .ok_or("SSE stream closed before the endpoint event")?;
+            let found = parser
+                .push(&String::from_utf8_lossy(&chunk))
+                .into_iter()
+                .find(|(event, _)| event == "endpoint");
+            if let Some((_, data)) = found {
+                break reqwest::Url::parse(&url)
+                    .and_then(|base| base.join(data.trim()))
+                    .map_err(|e| format!("Invalid SSE endpoint '{}': {}", data, e))?
+                    .to_string();
+            }
+        };
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok(Some(chunk)) = resp.chunk().await {
+                for (event, data) in parser.push(&String::from_utf8_lossy(&chunk)) {
+                    if event != "message" {
+                        continue;
+                    }
+                    if let Ok(v) = serde_json::from_str::<Value>(&data) {
+                        if tx.send(v).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+        Ok(McpClient::Sse {
+            client,
+            endpoint,
+            headers,
+            messages: rx,
+        })
+    }
+
+    /// POST one message over streamable HTTP; returns the messages in the
+    /// response (a JSON body or an SSE stream of them).
+    async fn http_post(
+        client: &reqwest::Client,
+        url: &str,
+        headers: &[(String, String)],
+        session: &mut Option<String>,
+        msg: &Value,
+        wanted_id: Option<u64>,
+    ) -> Result<Vec<Value>, String> {
+        let mut req = client
+            .post(url)
+            .header("Accept", "application/json, text/event-stream")
+            .header("MCP-Protocol-Version", PROTOCOL_VERSION)
+            .json(msg);
+        for (k, v) in headers {
+            req = req.header(k, v);
+        }
+        if let Some(sid) = session.as_deref() {
+            req = req.header("Mcp-Session-Id", sid);
+        }
+        let mut resp = req
+            .send()
+            .await
+            .map_err(|e| format!("Request to {} failed: {}", url, e))?;
+        if let Some(sid) = resp
+            .headers()
+            .get("mcp-session-id")
+            .and_then(|v| v.to_str().ok())
+        {
+            *session = Some(sid.to_string());
+        }
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("HTTP {}: {}", status, body.trim()));
+        }
+        if wanted_id.is_none() {
+            return Ok(vec![]);
+        }
+        let is_sse = resp
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("text/event-stream"));
+        if !is_sse {
+            let body: Value = resp
+                .json()
+                .await
+                .map_err(|e| format!("Invalid JSON response: {}", e))?;
+            return Ok(match body {
+                Value::Array(batch) => batch,
+                other => vec![other],
+            });
+        }
+        let mut parser = SseParser::default();
+        let mut out = Vec::new();
+        while let Some(chunk) = resp
+            .chunk()
+            .await
+            .map_err(|e| format!("SSE stream error: {}", e))?
+        {
+            for (_, data) in parser.push(&String::from_utf8_lossy(&chunk)) {
+                if let Ok(v) = serde_json::from_str::<Value>(&data) {
+                    let done = v.get("id").and_then(|i| i.as_u64()) == wanted_id;
+                    out.push(v);
+                    if done {
+                        return Ok(out);
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    async fn send(&mut self, msg: &Value, wanted_id: Option<u64>) -> Result<Vec<Value>, String> {
+        match self {
+            McpClient::Stdio { stdin, .. } => {
+                let mut line = msg.to_string();
+                line.push('\n');
+                stdin
+                    .write_all(line.as_bytes())
+                    .await
+                    .map_err(|e| format!("Failed to write to server: {}", e))?;
+                stdin
+                    .flush()
+                    .await
+                    .map_err(|e| format!("Failed to write to server: {}", e))?;
+                Ok(vec![])
+            }
+            McpClient::Http {
+                client,
+                url,
+                headers,
+                session,
+            } => Self::http_post(client, url, headers, session, msg, wanted_id).await,
+            McpClient::Sse {
+                client,
+                endpoint,
+                headers,
+                ..
+            } => {
+                let mut req = client.post(endpoint.as_str()).json(msg);
+                for (k, v) in headers.iter() {
+                    req = req.header(k, v);
+                }
+                let resp = req
+                    .send()
+                    .await
+                    .map_err(|e| format!("Request to {} failed: {}", endpoint, e))?;
+                if !resp.status().is_success() {
+                    return Err(format!("HTTP {}", resp.status()));
+                }
+                Ok(vec![])
+            }
+        }
+    }
+
+    /// Next server→client message on a streaming transport.
+    async fn next_message(&mut self) -> Result<Value, String> {
+        match self {
+            McpClient::Stdio { stdout, stdin, .. } => loop {
+                let line = stdout
+                    .next_line()
+                    .await
+                    .map_err(|e| format!("Failed to read from server: {}", e))?
+                    .ok_or("Server exited before answering")?;
+                // Servers sometimes log to stdout; skip anything that is not JSON.
+                let Ok(msg) = serde_json::from_str::<Value>(line.trim()) else {
+                    continue;
+                };
+                // Decline server→client requests (roots/list, sampling…).
+                if let (Some(id), Some(_)) = (msg.get("id"), msg.get("method")) {
+                    let reply = json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32601, "message": "Method not found" },
+                    });
+                    let _ = stdin.write_all(format!("{}\n", reply).as_bytes()).await;
+                    continue;
+                }
+                return Ok(msg);
+            },
+            McpClient::Sse { messages, .. } => messages
+                .recv()
+                .await
+                .ok_or_else(|| "SSE stream closed before answering".to_string()),
+            McpClient::Http { .. } => Err("HTTP responses arrive with the request".to_string()),
+        }
+    }
+
+    async fn request(&mut self, id: u64, method: &str, params: Value) -> Result<Value, String> {
+        let replies = self.send(&rpc(Some(id), method, params), Some(id)).await?;
+        if let McpClient::Http { .. } = self {
+            return replies
+                .into_iter()
+                .find_map(|m| take_result(m, id))
+                .unwrap_or_else(|| Err(format!("No response to `{}`", method)));
+        }
+        loop {
+            if let Some(result) = take_result(self.next_message().await?, id) {
+                return result;
+            }
+        }
+    }
+
+    async fn notify(&mut self, method: &str) -> Result<(), String> {
+        self.send(&rpc(None, method, json!({})), None).await?;
+        Ok(())
+    }
+
+    async fn shutdown(self) {
+        if let McpClient::Stdio {
+            mut child, stdin, ..
+        } = self
+        {
+            drop(stdin);
+            let _ = child.kill().await;
+        }
+    }
+}
+
+async fn handshake(client: &mut McpClient, report: &mut McpHealthReport) -> Result<(), String> {
+    let init = client
+        .request(
+            1,
+            "initialize",
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": "tokenicode", "version": env!("CARGO_PKG_VERSION") },
+            }),
+        )
+        .await
+        .map_err(|e| format!("initialize failed: {}", e))?;
+    report.protocol_version = init
+        .get("protocolVersion")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    report.server_name = init
+        .pointer("/serverInfo/name")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    report.server_version = init
+        .pointer("/serverInfo/version")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+
+    client
+        .notify("notifications/initialized")
+        .await
+        .map_err(|e| format!("initialized notification failed: {}", e))?;
+
+    if init.pointer("/capabilities/tools").is_none() {
+        // Prompt- or resource-only server: nothing to list, still healthy.
+        return Ok(());
+    }
+    let mut cursor: Option<String> = None;
+    for page in 0..MAX_TOOL_PAGES {
+        let params = match &cursor {
+            Some(c) => json!({ "cursor": c }),
+            None => json!({}),
+        };
+        let result = client
+            .request(2 + page as u64, "tools/list", params)
+            .await
+            .map_err(|e| format!("tools/list failed: {}", e))?;
+        for tool in result
+            .get("tools")
+            .and_then(|t| t.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(name) = tool.get("name").and_then(|n| n.as_str()) {
+                report.tools.push(McpToolInfo {
+                    name: name.to_string(),
+                    description: tool
+                        .get("description")
+                        .and_then(|d| d.as_str())
+                        .map(str::to_string),
+                });
+            }
+        }
+        cursor = result
+            .get("nextCursor")
+            .and_then(|c| c.as_str())
+            .map(str::to_string);
+        if cursor.is_none() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Connect to `config`, run the handshake and list tools. Never fails: the
+/// outcome is in `ok` / `error`.
+pub(crate) async fn check_server(
+    config: &Value,
+    cwd: Option<&str>,
+    timeout: Duration,
+) -> McpHealthReport {
+    let started = Instant::now();
+    let transport = transport_of(config);
+    let mut report = McpHealthReport {
+        transport: transport.clone(),
+        ..Default::default()
+    };
+    let issues = validate_server_config(config);
+    if !issues.is_empty() {
+        report.error = Some(issues.join("; "));
+        return report;
+    }
+
+    let stderr_tail = Arc::new(Mutex::new(String::new()));
+    let outcome = tokio::time::timeout(timeout, async {
+        let mut client = match transport.as_str() {
+            "stdio" => McpClient::connect_stdio(config, cwd, stderr_tail.clone()).await?,
+            _ => {
+                let url = expand_env(config.get("url").and_then(|u| u.as_str()).unwrap_or(""));
+                let headers = string_map(config, "headers");
+                let http = crate::build_smart_http_client(Duration::from_secs(10), timeout).await;
+                if transport == "sse" {
+                    McpClient::connect_sse(http, url, headers).await?
+                } else {
+                    McpClient::Http {
+                        client: http,
+                        url,
+                        headers,
+                        session: None,
+                    }
+                }
+            }
+        };
+        let result = handshake(&mut client, &mut report).await;
+        client.shutdown().await;
+        result
+    })
+    .await;
+
+    match outcome {
+        Ok(Ok(())) => report.ok = true,
+        Ok(Err(e)) => report.error = Some(e),
+        Err(_) => {
+            report.error = Some(format!(
+                "No response within {}s (is the server waiting for input or credentials?)",
+                timeout.as_secs()
+            ))
+        }
+    }
+    let stderr = stderr_tail.lock().unwrap().trim().to_string();
+    if !stderr.is_empty() {
+        report.stderr = Some(stderr);
+    }
+    report.elapsed_ms = started.elapsed().as_millis() as u64;
+    report
+}
+
+// ---------------------------------------------------------------------------
+// Commands
+// ---------------------------------------------------------------------------
+
+/// All configured MCP servers: global (`~/.claude.json`) then project
+/// (`{cwd}/.mcp.json`).
+#[tauri::command]
+pub async fn list_mcp_servers(cwd: Option<String>) -> Result<Vec<McpServerEntry>, String> {
+    let global = load_scope("global", None)?;
+    let project = match cwd.as_deref().filter(|c| !c.is_empty()) {
+        Some(c) => load_scope("project", Some(c))?,
+        None => Map::new(),
+    };
+    let mut entries = Vec::new();
+    for (scope, servers) in [("global", &global), ("project", &project)] {
+        for (name, config) in servers {
+            entries.push(McpServerEntry {
+                name: name.clone(),
+                scope: scope.to_string(),
+                transport: transport_of(config),
+                config: config.clone(),
+                overridden: scope == "global" && project.contains_key(name),
+                issues: validate_server_config(config),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// Create or replace a server entry. Pass `previous_name` to rename.
+#[tauri::command]
+pub async fn save_mcp_server(
+    scope: String,
+    cwd: Option<String>,
+    name: String,
+    config: Value,
+    previous_name: Option<String>,
+) -> Result<McpServerEntry, String> {
+    if !valid_server_name(&name) {
+        return Err(format!(
+            "Invalid server name '{}': use letters, digits, '-', '_' or '.'",
+            name
+        ));
+    }
+    let issues = validate_server_config(&config);
+    if !issues.is_empty() {
+        return Err(issues.join("; "));
+    }
+    let path = config_file(&scope, cwd.as_deref())?;
+    let mut doc = read_config_json(&path)?;
+    let mut servers = servers_of(&doc);
+    if let Some(prev) = previous_name.as_deref().filter(|p| *p != name) {
+        if servers.contains_key(&name) {
+            return Err(format!("A server named '{}' already exists", name));
+        }
+        servers.remove(prev);
+    }
+    servers.insert(name.clone(), config.clone());
+    doc["mcpServers"] = Value::Object(servers);
+    write_config_json(&path, &doc)?;
+    eprintln!("[TOKENICODE] MCP server '{}' saved to {:?}", name, path);
+
+    Ok(McpServerEntry {
+        name,
+        scope,
+        transport: transport_of(&config),
+        config,
+        overridden: false,
+        issues: vec![],
+    })
+}
+
+#[tauri::command]
+pub async fn delete_mcp_server(
+    scope: String,
+    cwd: Option<String>,
+    name: String,
+) -> Result<(), String> {
+    let path = config_file(&scope, cwd.as_deref())?;
+    let mut doc = read_config_json(&path)?;
+    let mut servers = servers_of(&doc);
+    if servers.remove(&name).is_none() {
+        return Err(format!("No {} MCP server named '{}'", scope, name));
+    }
+    doc["mcpServers"] = Value::Object(servers);
+    write_config_json(&path, &doc)
+}
+
+/// Health-check a server entry (saved or not) by running the MCP handshake.
+#[tauri::command]
+pub async fn check_mcp_server(
+    config: Value,
+    cwd: Option<String>,
+    timeout_secs: Option<u64>,
+) -> Result<McpHealthReport, String> {
+    let timeout = timeout_secs
+        .map(|s| Duration::from_secs(s.clamp(1, 300)))
+        .unwrap_or(DEFAULT_CHECK_TIMEOUT);
+    Ok(check_server(&config, cwd.as_deref(), timeout).await)
+}
+
+/// Selection remembered for a session (empty when none was set).
+#[tauri::command]
+pub async fn get_session_mcp_selection(session_id: String) -> Result<McpSelection, String> {
+    Ok(stored_selection(&[&session_id]).unwrap_or_default())
+}
+
+/// Remember which servers `session_id` gets. Takes effect the next time the
+/// session's CLI process starts (the CLI reads `--mcp-config` once).
+#[tauri::command]
+pub async fn set_session_mcp_selection(
+    session_id: String,
+    selection: McpSelection,
+) -> Result<(), String> {
+    store_selection(&session_id, &selection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_entries_and_infers_transport() {
+        assert!(
+            validate_server_config(&json!({ "command": "npx", "args": ["-y", "srv"] })).is_empty()
+        );
+        assert!(
+            validate_server_config(&json!({ "type": "http", "url": "https://x/mcp" })).is_empty()
+        );
+        assert_eq!(transport_of(&json!({ "url": "https://x/mcp" })), "http");
+        assert_eq!(transport_of(&json!({ "command": "x" })), "stdio");
+        assert_eq!(validate_server_config(&json!({ "args": "-y" })).len(), 2);
+        assert_eq!(
+            validate_server_config(&json!({ "type": "sse", "url": "ftp://x" })).len(),
+            1
+        );
+        assert_eq!(
+            validate_server_config(&json!({ "type": "ws", "url": "ws://x" })).len(),
+            1
+        );
+        assert!(!valid_server_name("has space"));
+        assert!(valid_server_name("github.enterprise_1"));
+    }
+
+    #[test]
+    fn selection_and_nesting() {
+        let doc = json!({ "mcpServers": { "mcpServers": { "a": {}, "b": {} } }, "other": 1 });
+        let mut servers = servers_of(&doc);
+        assert_eq!(servers.len(), 2);
+
+        let sel = McpSelection {
+            disabled: vec!["b".into()],
+            only: None,
+        };
+        servers.retain(|n, _| sel.allows(n));
+        assert!(servers.contains_key("a") && !servers.contains_key("b"));
+
+        let only = McpSelection {
+            disabled: vec![],
+            only: Some(vec!["b".into()]),
+        };
+        assert!(!only.allows("a") && only.allows("b"));
+    }
+
+    #[test]
+    fn project_servers_need_approval_and_selection_follows_resume() {
+        let global = servers_of(&json!({ "mcpServers": { "g": {} } }));
+        let project = servers_of(&json!({ "mcpServers": { "p1": {}, "p2": {}, "p3": {} } }));
+        let approvals = ProjectApprovals::from_docs(&[
+            json!({ "enabledMcpjsonServers": ["p1"], "disabledMcpjsonServers": ["p3"] }),
+            json!({ "enableAllProjectMcpServers": false }),
+        ]);
+        let names = |m: Map<String, Value>| m.keys().cloned().collect::<Vec<_>>();
+        assert_eq!(
+            names(merge_servers(
+                global.clone(),
+                project.clone(),
+                &approvals,
+                None
+            )),
+            vec!["g", "p1"]
+        );
+        let picked = McpSelection {
+            disabled: vec![],
+            only: Some(vec!["p2".into(), "g".into()]),
+        };
+        assert_eq!(
+            names(merge_servers(global, project, &approvals, Some(&picked))),
+            vec!["g", "p2"]
+        );
+        assert!(
+            ProjectApprovals::from_docs(&[json!({ "enableAllProjectMcpServers": true })])
+                .approved("any")
+        );
+
+        // Stored under the desk id, found again by a resume that only knows
+        // the CLI session id.
+        let mut map = BTreeMap::new();
+        map.insert("desk_1".to_string(), picked.clone());
+        assert!(link_in(&mut map, "desk_1", "cli-uuid"));
+        assert!(!link_in(&mut map, "desk_1", "cli-uuid"));
+        let ids = ["desk_2", "cli-uuid"];
+        assert_eq!(ids.iter().find_map(|id| map.get(*id)), Some(&picked));
+    }
+
+    #[test]
+    fn env_expansion_and_sse_parsing() {
+        std::env::set_var("TOKENICODE_MCP_TEST", "tok");
+        assert_eq!(expand_env("Bearer ${TOKENICODE_MCP_TEST}"), "Bearer tok");
+        assert_eq!(expand_env("${TOKENICODE_MCP_UNSET:-dflt}/x"), "dflt/x");
+        assert_eq!(expand_env("${broken"), "${broken");
+
+        let mut p = SseParser::default();
+        assert!(p.push("event: endpoint\r\ndata: /messages?s=1").is_empty());
+        let events = p.push("\r\n\r\ndata: {\"id\":1}\n\n");
+        assert_eq!(
+            events,
+            vec![
+                ("endpoint".to_string(), "/messages?s=1".to_string()),
+                ("message".to_string(), "{\"id\":1}".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn config_writes_preserve_other_keys() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(".mcp.json");
+        assert_eq!(read_config_json(&path).unwrap(), json!({}));
+
+        std::fs::write(&path, r#"{"projects":{"x":1},"mcpServers":{}}"#).unwrap();
+        let mut doc = read_config_json(&path).unwrap();
+        let mut servers = servers_of(&doc);
+        servers.insert("s".into(), json!({ "command": "srv" }));
+        doc["mcpServers"] = Value::Object(servers);
+        write_config_json(&path, &doc).unwrap();
+
+        let back = read_config_json(&path).unwrap();
+        assert_eq!(back["projects"]["x"], 1);
+        assert_eq!(back["mcpServers"]["s"]["command"], "srv");
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(read_config_json(&path).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stdio_check_reports_tools() {
+        // A tiny shell "server": answers initialize and tools/list in order.
+        let script = r#"read l; echo 'not json'; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}},"serverInfo":{"name":"fake","version":"1.0"}}}'; read l; read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"ping","description":"Ping"}]}}'"#;
+        let config = json!({ "command": "sh", "args": ["-c", script] });
+        let report = check_server(&config, None, Duration::from_secs(10)).await;
+        assert!(report.ok, "{:?}", report.error);
+        assert_eq!(report.server_name.as_deref(), Some("fake"));
+        assert_eq!(report.tools.len(), 1);
+        assert_eq!(report.tools[0].name, "ping");
+
+        let failing = json!({ "command": "sh", "args": ["-c", "echo boom >&2; exit 1"] });
+        let report = check_server(&failing, None, Duration::from_secs(10)).await;
+        assert!(!report.ok);
+        assert!(report.error.is_some());
+    }
+}
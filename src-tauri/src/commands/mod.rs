@@ -1,5 +1,29 @@
+pub mod agents;
+pub mod attachment_gc;
+pub mod attachments;
+pub mod builtin_mcp;
 pub mod claude_process;
 pub mod cli_resolver;
+pub mod command_templates;
 pub mod feedback;
+pub mod file_index;
+pub mod file_reader;
+pub mod file_tree;
+pub mod fs_watch;
+pub mod git_api;
+pub mod git_ops;
+pub mod ignore_rules;
+pub mod mcp_manager;
+pub mod path_index;
+pub mod project_search;
+pub mod safe_write;
+pub mod session_diff;
+pub mod session_import;
+pub mod session_retention;
+pub mod session_windows;
+pub mod skill_lint;
+pub mod skill_packages;
+pub mod turn_snapshots;
+pub mod worktrees;
 
 pub use claude_process::*;
@@ -0,0 +1,596 @@
+//! Flat per-workspace path index for quick-open (`fuzzy_find_files`).
+//!
+//! The first query for a root walks it once (gitignore-aware, like the
+//! explorer) into a sorted set of relative file paths. The `watch_directory`
+//! flusher feeds each coalesced change batch into `apply_fs_event`, so a
+//! watched root stays current without re-walking. An unwatched root is
+//! rebuilt when its index is older than `UNWATCHED_MAX_AGE`. A changed
+//! `.gitignore`/`.ignore` marks the index stale.
+//!
+//! Ranking is done here rather than in the frontend, so it stays fast with
+//! 100k-file repositories. Matches score higher when they are at path
+//! segment boundaries, consecutive, or inside the file name. Files the agent
+//! edited recently, according to the files-touched index, get a boost.
+
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+use super::file_index::FileIndexState;
+use super::ignore_rules::{rel_path, IgnoreMatcher, IGNORE_FILES};
+use crate::path_access::{PathAccessManager, PathCapability};
+
+/// Files indexed per root; larger trees are indexed partially.
+const MAX_INDEXED_FILES: usize = 300_000;
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 1000;
+const UNWATCHED_MAX_AGE: Duration = Duration::from_secs(30);
+/// How often the agent-edit ranking is reloaded from the files-touched index.
+const RECENCY_TTL: Duration = Duration::from_secs(30);
+/// Recently edited files that get a ranking boost, newest first.
+const RECENT_FILES: usize = 100;
+
+const BOUNDARY_BONUS: i64 = 8;
+const CONSECUTIVE_BONUS: i64 = 5;
+const FILE_NAME_BONUS: i64 = 10;
+const EXACT_NAME_BONUS: i64 = 50;
+const NAME_PREFIX_BONUS: i64 = 20;
+const RECENT_BONUS: i64 = 30;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FuzzyMatch {
+    pub path: String,
+    pub rel_path: String,
+    pub score: i64,
+    /// Matched characters in `rel_path` as UTF-16 offsets, for highlighting.
+    pub positions: Vec<usize>,
+    /// Among the files the agent edited most recently.
+    pub recent: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FuzzyFindResult {
+    pub matches: Vec<FuzzyMatch>,
+    pub total_files: usize,
+    /// The root has more files than the index holds.
+    pub capped: bool,
+}
+
+struct PathIndex {
+    root: PathBuf,
+    paths: BTreeSet<String>,
+    capped: bool,
+    stale: bool,
+    built_at: Instant,
+    /// Relative path -> rank among recently edited files (0 = newest).
+    recent: HashMap<String, usize>,
+    recent_loaded: Option<Instant>,
+}
+
+/// Path indexes by canonical root, plus the watched directories feeding them.
+#[derive(Default)]
+pub struct PathIndexState {
+    indexes: Mutex<HashMap<PathBuf, Arc<Mutex<PathIndex>>>>,
+    /// Watched path as given to `watch_directory` -> canonical form.
+    watched: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl PathIndex {
+    /// An unbuilt index; the first query walks the root.
+    fn new(root: &Path) -> Self {
+        PathIndex {
+            root: root.to_path_buf(),
+            paths: BTreeSet::new(),
+            capped: false,
+            stale: true,
+            built_at: Instant::now(),
+            recent: HashMap::new(),
+            recent_loaded: None,
+        }
+    }
+
+    fn rebuild(&mut self) {
+        self.paths.clear();
+        self.capped = false;
+        self.stale = false;
+        let root = self.root.clone();
+        self.add_tree(&root, IgnoreMatcher::for_dir(&root));
+        self.built_at = Instant::now();
+    }
+
+    /// Add the visible files below `dir`. Symlinked directories are not
+    /// entered.
+    fn add_tree(&mut self, dir: &Path, ignore: IgnoreMatcher) {
+        let mut stack = vec![(dir.to_path_buf(), ignore)];
+        while let Some((dir, ignore)) = stack.pop() {
+            let Ok(read) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in read.flatten() {
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+                let path = entry.path();
+                let is_dir = file_type.is_dir();
+                if ignore.matched(&path, is_dir) {
+                    continue;
+                }
+                if is_dir {
+                    let child = ignore.child(&path);
+                    stack.push((path, child));
+                } else if !self.insert(&path) {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// False once the index is full.
+    fn insert(&mut self, path: &Path) -> bool {
+        if self.paths.len() >= MAX_INDEXED_FILES {
+            self.capped = true;
+            return false;
+        }
+        if let Some(rel) = rel_path(&self.root, path).filter(|r| !r.is_empty()) {
+            self.paths.insert(rel);
+        }
+        true
+    }
+
+    fn has_under(&self, rel: &str) -> bool {
+        let prefix = format!("{}/", rel);
+        self.paths
+            .range(prefix.clone()..)
+            .next()
+            .is_some_and(|p| p.starts_with(&prefix))
+    }
+
+    fn remove_tree(&mut self, rel: &str) {
+        self.paths.remove(rel);
+        // '0' sorts right after '/', so this range is exactly `rel/...`.
+        let mut below = self.paths.split_off(&format!("{}/", rel));
+        let mut after = below.split_off(&format!("{}0", rel));
+        self.paths.append(&mut after);
+    }
+
+    /// Reconcile one changed path with the disk. Event kinds are not
+    /// trusted (renames arrive as create, modify or remove depending on the
+    /// platform), so the path is simply looked up again.
+    fn apply_change(&mut self, path: &Path) {
+        let Some(rel) = rel_path(&self.root, path).filter(|r| !r.is_empty()) else {
+            return;
+        };
+        if path
+            .file_name()
+            .is_some_and(|n| IGNORE_FILES.iter().any(|f| n == *f))
+        {
+            self.stale = true;
+        }
+        let Ok(meta) = std::fs::symlink_metadata(path) else {
+            self.remove_tree(&rel);
+            return;
+        };
+        let is_dir = meta.is_dir();
+        let ignore = IgnoreMatcher::for_dir_under(&self.root, path.parent().unwrap_or(&self.root));
+        if ignore.is_ignored(path, is_dir) {
+            self.remove_tree(&rel);
+        } else if !is_dir {
+            self.insert(path);
+        } else if !self.has_under(&rel) {
+            // A directory created or moved in; known directories only see
+            // metadata changes here and their files arrive as own events.
+            self.paths.remove(&rel);
+            self.add_tree(path, ignore.child(path));
+        }
+    }
+
+    fn load_recent(&mut self, touched: HashMap<String, String>) {
+        let mut by_time: Vec<(String, String)> = touched.into_iter().collect();
+        by_time.sort_by(|a, b| b.1.cmp(&a.1));
+        self.recent = by_time
+            .into_iter()
+            .take(RECENT_FILES)
+            .enumerate()
+            .map(|(rank, (rel, _))| (rel, rank))
+            .collect();
+        self.recent_loaded = Some(Instant::now());
+    }
+
+    fn recent_boost(&self, rel: &str) -> Option<i64> {
+        self.recent
+            .get(rel)
+            .map(|rank| RECENT_BONUS * (RECENT_FILES - rank) as i64 / RECENT_FILES as i64)
+    }
+
+    fn find(&self, query: &str, limit: usize) -> Vec<FuzzyMatch> {
+        let query: Vec<char> = query
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .flat_map(char::to_lowercase)
+            .collect();
+        let mut matches: Vec<FuzzyMatch> = if query.is_empty() {
+            // No query: recently edited files, newest first.
+            let mut recent: Vec<(&String, &usize)> = self
+                .recent
+                .iter()
+                .filter(|(rel, _)| self.paths.contains(*rel))
+                .collect();
+            recent.sort_by_key(|(_, rank)| **rank);
+            recent
+                .into_iter()
+                .map(|(rel, _)| self.to_match(rel, 0, Vec::new()))
+                .collect()
+        } else {
+            self.paths
+                .iter()
+                .filter_map(|rel| {
+                    let (score, positions) = fuzzy_score(rel, &query)?;
+                    let score = score + self.recent_boost(rel).unwrap_or(0);
+                    Some(self.to_match(rel, score, positions))
+                })
+                .collect()
+        };
+        matches.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.rel_path.len().cmp(&b.rel_path.len()))
+                .then_with(|| a.rel_path.cmp(&b.rel_path))
+        });
+        matches.truncate(limit);
+        matches
+    }
+
+    fn to_match(&self, rel: &str, score: i64, positions: Vec<usize>) -> FuzzyMatch {
+        FuzzyMatch {
+            path: self.root.join(rel).to_string_lossy().to_string(),
+            rel_path: rel.to_string(),
+            score,
+            positions,
+            recent: self.recent.contains_key(rel),
+        }
+    }
+}
+
+fn is_boundary(chars: &[char], i: usize) -> bool {
+    i == 0
+        || matches!(chars[i - 1], '/' | '\\' | '_' | '-' | '.' | ' ')
+        || (chars[i].is_uppercase() && chars[i - 1].is_lowercase())
+}
+
+fn folds_to(c: char, q: char) -> bool {
+    c == q || c.to_lowercase().eq(std::iter::once(q))
+}
+
+/// Greedy subsequence match of `query` from `start`, with the char indices
+/// it used.
+fn match_from(chars: &[char], query: &[char], start: usize) -> Option<(i64, Vec<usize>)> {
+    let mut score = 0i64;
+    let mut positions: Vec<usize> = Vec::with_capacity(query.len());
+    let mut i = start;
+    for &q in query {
+        while i < chars.len() && !folds_to(chars[i], q) {
+            i += 1;
+        }
+        if i == chars.len() {
+            return None;
+        }
+        score += 1;
+        if is_boundary(chars, i) {
+            score += BOUNDARY_BONUS;
+        }
+        match positions.last() {
+            Some(&prev) if prev + 1 == i => score += CONSECUTIVE_BONUS,
+            Some(&prev) => score -= (i - prev - 1).min(3) as i64,
+            None => {}
+        }
+        positions.push(i);
+        i += 1;
+    }
+    Some((score, positions))
+}
+
+/// Best score of `query` (lowercase, no whitespace) against `rel`, trying a
+/// plain greedy match plus one starting at every segment boundary that
+/// matches the first query char. Positions are UTF-16 offsets.
+fn fuzzy_score(rel: &str, query: &[char]) -> Option<(i64, Vec<usize>)> {
+    // Cheap subsequence check before allocating anything.
+    let mut rest = query.iter().peekable();
+    for c in rel.chars() {
+        if rest.peek().is_some_and(|q| folds_to(c, **q)) {
+            rest.next();
+        }
+    }
+    if rest.peek().is_some() {
+        return None;
+    }
+
+    let chars: Vec<char> = rel.chars().collect();
+    let name_start = chars.iter().rposition(|c| *c == '/').map_or(0, |i| i + 1);
+    let mut best = match_from(&chars, query, 0)?;
+    for start in 1..chars.len() {
+        if !is_boundary(&chars, start) || !folds_to(chars[start], query[0]) {
+            continue;
+        }
+        let Some((mut score, positions)) = match_from(&chars, query, start) else {
+            break;
+        };
+        if start >= name_start {
+            score += FILE_NAME_BONUS;
+        }
+        if score > best.0 {
+            best = (score, positions);
+        }
+    }
+
+    let name: String = chars[name_start..]
+        .iter()
+        .flat_map(|c| c.to_lowercase())
+        .collect();
+    let query: String = query.iter().collect();
+    let stem = name.split('.').next().unwrap_or("");
+    if name == query || stem == query {
+        best.0 += EXACT_NAME_BONUS;
+    } else if name.starts_with(&query) {
+        best.0 += NAME_PREFIX_BONUS;
+    }
+    // Shorter paths win ties between otherwise similar matches.
+    best.0 -= chars.len() as i64 / 16;
+
+    let mut utf16 = Vec::with_capacity(chars.len() + 1);
+    let mut offset = 0;
+    for c in &chars {
+        utf16.push(offset);
+        offset += c.len_utf16();
+    }
+    let positions = best.1.iter().map(|&i| utf16[i]).collect();
+    Some((best.0, positions))
+}
+
+impl PathIndexState {
+    /// Called by `watch_directory`: events under `path` now keep matching
+    /// indexes current.
+    pub(crate) fn attach(&self, path: &str) {
+        let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        if let Ok(mut watched) = self.watched.lock() {
+            watched.insert(path.to_string(), canonical);
+        }
+    }
+
+    /// Called by `unwatch_directory`.
+    pub(crate) fn detach(&self, path: &str) {
+        if let Ok(mut watched) = self.watched.lock() {
+            watched.remove(path);
+        }
+    }
+
+    fn is_watched(&self, root: &Path) -> bool {
+        self.watched
+            .lock()
+            .is_ok_and(|w| w.values().any(|dir| root.starts_with(dir)))
+    }
+
+    /// Apply the paths of one change batch from the watcher on `watch_root`.
+    pub(crate) fn apply_fs_event(&self, watch_root: &str, paths: &[PathBuf]) {
+        let Some(canonical_root) = self
+            .watched
+            .lock()
+            .ok()
+            .and_then(|w| w.get(watch_root).cloned())
+        else {
+            return;
+        };
+        let indexes: Vec<Arc<Mutex<PathIndex>>> = match self.indexes.lock() {
+            Ok(map) => map.values().cloned().collect(),
+            Err(_) => return,
+        };
+        for path in paths {
+            // notify reports paths under the watched path as given; indexes
+            // are keyed by canonical roots.
+            let path = match path.strip_prefix(watch_root) {
+                Ok(rest) => canonical_root.join(rest),
+                Err(_) => path.clone(),
+            };
+            for index in &indexes {
+                let Ok(mut index) = index.lock() else {
+                    continue;
+                };
+                if path.starts_with(&index.root) {
+                    index.apply_change(&path);
+                }
+            }
+        }
+    }
+
+    /// The watcher under `watch_root` lost events; rebuild on next query.
+    pub(crate) fn mark_stale(&self, watch_root: &str) {
+        let Some(canonical_root) = self
+            .watched
+            .lock()
+            .ok()
+            .and_then(|w| w.get(watch_root).cloned())
+        else {
+            return;
+        };
+        if let Ok(indexes) = self.indexes.lock() {
+            for (root, index) in indexes.iter() {
+                if root.starts_with(&canonical_root) || canonical_root.starts_with(root) {
+                    if let Ok(mut index) = index.lock() {
+                        index.stale = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn index_for(&self, root: &Path) -> Result<Arc<Mutex<PathIndex>>, String> {
+        let mut indexes = self
+            .indexes
+            .lock()
+            .map_err(|e| format!("Path index state poisoned: {}", e))?;
+        Ok(indexes
+            .entry(root.to_path_buf())
+            .or_insert_with(|| Arc::new(Mutex::new(PathIndex::new(root))))
+            .clone())
+    }
+}
+
+/// Rank the files below `root` against `query` (best first, up to `limit`,
+/// default 50). An empty query returns the files the agent edited most
+/// recently.
+#[tauri::command]
+pub async fn fuzzy_find_files(
+    path_access: State<'_, PathAccessManager>,
+    state: State<'_, PathIndexState>,
+    file_index: State<'_, FileIndexState>,
+    root: String,
+    query: String,
+    limit: Option<usize>,
+    tab_id: Option<String>,
+) -> Result<FuzzyFindResult, String> {
+    let root = path_access
+        .validate(Path::new(&root), tab_id.as_deref(), PathCapability::Read)
+        .await?;
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", root.display()));
+    }
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let watched = state.is_watched(&root);
+    let index = state.index_for(&root)?;
+    let file_index = file_index.inner().clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut index = index
+            .lock()
+            .map_err(|e| format!("Path index poisoned: {}", e))?;
+        if index.stale || (!watched && index.built_at.elapsed() > UNWATCHED_MAX_AGE) {
+            index.rebuild();
+        }
+        if index
+            .recent_loaded
+            .is_none_or(|t| t.elapsed() > RECENCY_TTL)
+        {
+            match file_index.last_touched_under(&root.to_string_lossy()) {
+                Ok(touched) => index.load_recent(touched),
+                Err(e) => eprintln!("[TOKENICODE] quick-open recency unavailable: {}", e),
+            }
+        }
+        Ok(FuzzyFindResult {
+            matches: index.find(&query, limit),
+            total_files: index.paths.len(),
+            capped: index.capped,
+        })
+    })
+    .await
+    .map_err(|e| format!("Fuzzy find failed: {}", e))?
+}
+
+/// Drop the cached index of `root`; the next query re-walks it.
+#[tauri::command]
+pub async fn reset_path_index(
+    state: State<'_, PathIndexState>,
+    root: String,
+) -> Result<(), String> {
+    let canonical = std::fs::canonicalize(&root).unwrap_or_else(|_| PathBuf::from(&root));
+    state
+        .indexes
+        .lock()
+        .map_err(|e| format!("Path index state poisoned: {}", e))?
+        .remove(&canonical);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rels(matches: &[FuzzyMatch]) -> Vec<&str> {
+        matches.iter().map(|m| m.rel_path.as_str()).collect()
+    }
+
+    #[test]
+    fn ranks_boundaries_file_names_and_recent_edits() {
+        let q: Vec<char> = "fb".chars().collect();
+        let (boundary, _) = fuzzy_score("src/foo_bar.rs", &q).unwrap();
+        let (inner, _) = fuzzy_score("src/xfxxbx.rs", &q).unwrap();
+        assert!(boundary > inner);
+        assert!(fuzzy_score("src/abc.rs", &q).is_none());
+
+        // The emoji is two UTF-16 units.
+        let (_, positions) = fuzzy_score("😀/FooBar.md", &q).unwrap();
+        assert_eq!(positions, [3, 6]);
+
+        let mut index = PathIndex::new(Path::new("/p"));
+        index.paths = [
+            "src/components/ChatPanel.tsx",
+            "src/lib/chat.ts",
+            "docs/changelog/archive.md",
+            "src/chat/panel/index.ts",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        assert_eq!(
+            index.find("chatp", 10)[0].rel_path,
+            "src/components/ChatPanel.tsx"
+        );
+        assert_eq!(index.find("chat", 10)[0].rel_path, "src/lib/chat.ts");
+        assert_eq!(
+            index.find("panel", 10)[0].rel_path,
+            "src/components/ChatPanel.tsx"
+        );
+
+        index.load_recent(HashMap::from([
+            (
+                "src/chat/panel/index.ts".to_string(),
+                "2026-03-02T00:00:00Z".to_string(),
+            ),
+            (
+                "docs/changelog/archive.md".to_string(),
+                "2026-03-01T00:00:00Z".to_string(),
+            ),
+        ]));
+        let found = index.find("panel", 10);
+        assert_eq!(found[0].rel_path, "src/chat/panel/index.ts");
+        assert!(found[0].recent);
+        assert_eq!(
+            rels(&index.find("", 10)),
+            ["src/chat/panel/index.ts", "docs/changelog/archive.md"]
+        );
+    }
+
+    #[test]
+    fn builds_and_applies_watcher_changes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("build")).unwrap();
+        std::fs::write(root.join(".gitignore"), "build/\n").unwrap();
+        std::fs::write(root.join("src/a.rs"), "").unwrap();
+        std::fs::write(root.join("build/out.o"), "").unwrap();
+
+        let mut index = PathIndex::new(&root);
+        index.rebuild();
+        let all: Vec<&str> = index.paths.iter().map(String::as_str).collect();
+        assert_eq!(all, [".gitignore", "src/a.rs"]);
+
+        std::fs::create_dir_all(root.join("src/new/deep")).unwrap();
+        std::fs::write(root.join("src/new/deep/b.rs"), "").unwrap();
+        std::fs::write(root.join("build/more.o"), "").unwrap();
+        index.apply_change(&root.join("src/new"));
+        index.apply_change(&root.join("build/more.o"));
+        assert!(index.paths.contains("src/new/deep/b.rs"));
+        assert!(!index.paths.contains("build/more.o"));
+
+        std::fs::remove_dir_all(root.join("src")).unwrap();
+        index.apply_change(&root.join("src"));
+        let all: Vec<&str> = index.paths.iter().map(String::as_str).collect();
+        assert_eq!(all, [".gitignore"]);
+
+        std::fs::write(root.join(".gitignore"), "").unwrap();
+        index.apply_change(&root.join(".gitignore"));
+        assert!(index.stale);
+    }
+}
@@ -0,0 +1,625 @@
+//! Project-wide content search (`search_in_project`).
+//!
+//! A walker thread lists files under the root, using the same gitignore rules
+//! as the explorer. A small pool of workers searches them and the results
+//! stream to the frontend as `search:chunk` events: per-file matches with
+//! their surrounding lines. The root must pass `PathAccessManager`, and the
+//! walk never follows symlinks, so nothing outside the root is read.
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, State};
+
+use super::ignore_rules::{rel_path, GlobList, IgnoreMatcher};
+use crate::events::emit_to_frontend;
+use crate::path_access::{PathAccessManager, PathCapability};
+
+const DEFAULT_MAX_RESULTS: usize = 2_000;
+const MAX_RESULTS_LIMIT: usize = 20_000;
+const DEFAULT_CONTEXT_LINES: usize = 2;
+const MAX_CONTEXT_LINES: usize = 10;
+const DEFAULT_MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+/// Bytes of a line sent to the frontend; minified files have huge lines.
+const MAX_LINE_BYTES: usize = 1000;
+/// A NUL byte in this many leading bytes marks a file as binary.
+const BINARY_PROBE_BYTES: usize = 8192;
+const MAX_WORKERS: usize = 8;
+/// A chunk is emitted once it holds this many files or is this old.
+const CHUNK_FILES: usize = 50;
+const CHUNK_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    #[default]
+    Literal,
+    Regex,
+}
+
+/// `smart` is case-insensitive unless the query contains an uppercase letter.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseMode {
+    #[default]
+    Smart,
+    Sensitive,
+    Insensitive,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct SearchOptions {
+    pub query: String,
+    #[serde(default)]
+    pub mode: SearchMode,
+    #[serde(default)]
+    pub case: CaseMode,
+    #[serde(default)]
+    pub whole_word: bool,
+    /// Gitignore-style globs; when set, only matching files are searched.
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Skip gitignored files (default true). `.git` is always skipped.
+    pub respect_gitignore: Option<bool>,
+    pub max_results: Option<usize>,
+    pub context_lines: Option<usize>,
+    /// Larger files are skipped (default 4 MB).
+    pub max_file_size: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SearchMatch {
+    /// 1-based line number.
+    pub line: usize,
+    pub text: String,
+    /// Match spans in `text` as UTF-16 offsets, ready for JS string slicing.
+    pub ranges: Vec<[usize; 2]>,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FileMatches {
+    pub path: String,
+    pub matches: Vec<SearchMatch>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct SearchChunk {
+    request_id: String,
+    files: Vec<FileMatches>,
+    done: bool,
+    /// `max_results` was reached (only on the final chunk).
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cancelled: bool,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct SearchSummary {
+    pub files_searched: usize,
+    pub files_matched: usize,
+    pub matches: usize,
+    pub truncated: bool,
+    pub cancelled: bool,
+    pub elapsed_ms: u64,
+}
+
+/// Cancellation flags of running searches.
+#[derive(Default)]
+pub struct SearchState {
+    searches: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+struct Limits {
+    max_results: usize,
+    context: usize,
+    max_file_size: u64,
+}
+
+impl Limits {
+    fn new(opts: &SearchOptions) -> Self {
+        Limits {
+            max_results: opts
+                .max_results
+                .unwrap_or(DEFAULT_MAX_RESULTS)
+                .clamp(1, MAX_RESULTS_LIMIT),
+            context: opts
+                .context_lines
+                .unwrap_or(DEFAULT_CONTEXT_LINES)
+                .min(MAX_CONTEXT_LINES),
+            max_file_size: opts.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE),
+        }
+    }
+}
+
+/// Which entries the walker visits.
+struct WalkFilter {
+    root: PathBuf,
+    include: GlobList,
+    exclude: GlobList,
+    gitignore: bool,
+}
+
+impl WalkFilter {
+    fn new(root: &Path, opts: &SearchOptions) -> Self {
+        WalkFilter {
+            root: root.to_path_buf(),
+            include: GlobList::new(&opts.include),
+            exclude: GlobList::new(&opts.exclude),
+            gitignore: opts.respect_gitignore.unwrap_or(true),
+        }
+    }
+
+    fn wanted(&self, path: &Path, is_dir: bool, ignore: Option<&IgnoreMatcher>) -> bool {
+        if path.file_name().is_some_and(|n| n == ".git") {
+            return false;
+        }
+        if ignore.is_some_and(|m| m.matched(path, is_dir)) {
+            return false;
+        }
+        let rel = rel_path(&self.root, path).unwrap_or_default();
+        if self.exclude.matches(&rel, is_dir) {
+            return false;
+        }
+        is_dir || self.include.is_empty() || self.include.matches(&rel, false)
+    }
+}
+
+pub(crate) fn build_regex(opts: &SearchOptions) -> Result<Regex, String> {
+    if opts.query.is_empty() {
+        return Err("Search query is empty".to_string());
+    }
+    let mut pattern = match opts.mode {
+        SearchMode::Literal => regex::escape(&opts.query),
+        SearchMode::Regex => opts.query.clone(),
+    };
+    if opts.whole_word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+    let insensitive = match opts.case {
+        CaseMode::Sensitive => false,
+        CaseMode::Insensitive => true,
+        CaseMode::Smart => !opts.query.chars().any(char::is_uppercase),
+    };
+    // multi_line + crlf so `^`/`$` behave the same in the whole-file
+    // pre-check as in the per-line pass.
+    RegexBuilder::new(&pattern)
+        .case_insensitive(insensitive)
+        .multi_line(true)
+        .crlf(true)
+        .build()
+        .map_err(|e| format!("Invalid search pattern: {}", e))
+}
+
+pub(crate) fn clip(line: &str) -> &str {
+    if line.len() <= MAX_LINE_BYTES {
+        return line;
+    }
+    let mut end = MAX_LINE_BYTES;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+/// Byte spans converted to UTF-16 offsets, dropping what falls past the clip.
+fn utf16_ranges(line: &str, spans: &[(usize, usize)]) -> Vec<[usize; 2]> {
+    let cut = clip(line).len();
+    spans
+        .iter()
+        .filter(|(start, _)| *start < cut)
+        .map(|&(start, end)| [utf16_len(&line[..start]), utf16_len(&line[..end.min(cut)])])
+        .collect()
+}
+
+/// Clipped text of `line` with its match spans, or `None` when `re` has no
+/// non-empty match in it.
+pub(crate) fn match_line(re: &Regex, line: &str) -> Option<(String, Vec<[usize; 2]>)> {
+    let spans: Vec<(usize, usize)> = re
+        .find_iter(line)
+        .filter(|m| m.start() < m.end())
+        .map(|m| (m.start(), m.end()))
+        .collect();
+    if spans.is_empty() {
+        return None;
+    }
+    Some((clip(line).to_string(), utf16_ranges(line, &spans)))
+}
+
+/// Matches in one file, or `None` when it was not searched (too large,
+/// binary or unreadable). Each matching line takes one slot of `found`;
+/// the line that would exceed `max_results` is dropped.
+fn search_file(
+    path: &Path,
+    re: &Regex,
+    limits: &Limits,
+    found: &AtomicUsize,
+) -> Option<Vec<SearchMatch>> {
+    let meta = std::fs::metadata(path).ok()?;
+    if meta.len() > limits.max_file_size {
+        return None;
+    }
+    let bytes = std::fs::read(path).ok()?;
+    if bytes[..bytes.len().min(BINARY_PROBE_BYTES)].contains(&0) {
+        return None;
+    }
+    let content = String::from_utf8_lossy(&bytes);
+    let mut matches = Vec::new();
+    if !re.is_match(&content) {
+        return Some(matches);
+    }
+
+    let lines: Vec<&str> = content.lines().collect();
+    for (i, line) in lines.iter().enumerate() {
+        let Some((text, ranges)) = match_line(re, line) else {
+            continue;
+        };
+        if found.fetch_add(1, Ordering::Relaxed) >= limits.max_results {
+            break;
+        }
+        let from = i.saturating_sub(limits.context);
+        let to = (i + 1 + limits.context).min(lines.len());
+        matches.push(SearchMatch {
+            line: i + 1,
+            text,
+            ranges,
+            before: lines[from..i].iter().map(|l| clip(l).to_string()).collect(),
+            after: lines[i + 1..to]
+                .iter()
+                .map(|l| clip(l).to_string())
+                .collect(),
+        });
+    }
+    Some(matches)
+}
+
+/// Depth-first walk handing each wanted file to `visit` until it returns
+/// false or `stop` says so. Symlinks are skipped entirely.
+fn walk_files(
+    root: &Path,
+    filter: &WalkFilter,
+    stop: impl Fn() -> bool,
+    mut visit: impl FnMut(PathBuf) -> bool,
+) {
+    let root_ignore = filter.gitignore.then(|| IgnoreMatcher::for_dir(root));
+    let mut stack = vec![(root.to_path_buf(), root_ignore)];
+    while let Some((dir, ignore)) = stack.pop() {
+        if stop() {
+            return;
+        }
+        let Ok(read) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in read.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_symlink() {
+                continue;
+            }
+            let path = entry.path();
+            let is_dir = file_type.is_dir();
+            if !filter.wanted(&path, is_dir, ignore.as_ref()) {
+                continue;
+            }
+            if is_dir {
+                let child = ignore.as_ref().map(|m| m.child(&path));
+                stack.push((path, child));
+            } else if file_type.is_file() && !visit(path) {
+                return;
+            }
+        }
+    }
+}
+
+/// Search below `root`, calling `emit` on the calling thread with batches
+/// of files that matched.
+fn run_search(
+    root: &Path,
+    opts: &SearchOptions,
+    re: &Regex,
+    cancel: &AtomicBool,
+    mut emit: impl FnMut(Vec<FileMatches>),
+) -> SearchSummary {
+    let started = Instant::now();
+    let limits = Limits::new(opts);
+    let filter = WalkFilter::new(root, opts);
+    let found = AtomicUsize::new(0);
+    let searched = AtomicUsize::new(0);
+    let workers = std::thread::available_parallelism()
+        .map_or(2, |n| n.get())
+        .min(MAX_WORKERS);
+    let mut summary = SearchSummary::default();
+
+    let (path_tx, path_rx) = mpsc::sync_channel::<PathBuf>(1024);
+    let (hit_tx, hit_rx) = mpsc::channel::<FileMatches>();
+    let path_rx = Mutex::new(path_rx);
+    let stop =
+        || cancel.load(Ordering::Relaxed) || found.load(Ordering::Relaxed) > limits.max_results;
+    let (path_rx, found_ref, searched_ref, stop, limits, filter) =
+        (&path_rx, &found, &searched, &stop, &limits, &filter);
+
+    std::thread::scope(|s| {
+        s.spawn(move || walk_files(root, filter, stop, |p| path_tx.send(p).is_ok()));
+        for _ in 0..workers {
+            let hit_tx = hit_tx.clone();
+            s.spawn(move || loop {
+                let next = path_rx.lock().ok().and_then(|rx| rx.recv().ok());
+                let Some(path) = next else {
+                    break;
+                };
+                // Keep draining after a stop so the walker is never blocked.
+                if stop() {
+                    continue;
+                }
+                let Some(matches) = search_file(&path, re, limits, found_ref) else {
+                    continue;
+                };
+                searched_ref.fetch_add(1, Ordering::Relaxed);
+                if !matches.is_empty() {
+                    let _ = hit_tx.send(FileMatches {
+                        path: path.to_string_lossy().to_string(),
+                        matches,
+                    });
+                }
+            });
+        }
+        drop(hit_tx);
+
+        let mut batch: Vec<FileMatches> = Vec::new();
+        let mut last_emit = Instant::now();
+        loop {
+            match hit_rx.recv_timeout(CHUNK_INTERVAL) {
+                Ok(file) => {
+                    summary.files_matched += 1;
+                    summary.matches += file.matches.len();
+                    batch.push(file);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if !batch.is_empty()
+                && (batch.len() >= CHUNK_FILES || last_emit.elapsed() >= CHUNK_INTERVAL)
+            {
+                emit(std::mem::take(&mut batch));
+                last_emit = Instant::now();
+            }
+        }
+        if !batch.is_empty() {
+            emit(batch);
+        }
+    });
+
+    summary.files_searched = searched.load(Ordering::Relaxed);
+    summary.truncated = found.load(Ordering::Relaxed) > limits.max_results;
+    summary.cancelled = cancel.load(Ordering::Relaxed);
+    summary.elapsed_ms = started.elapsed().as_millis() as u64;
+    summary
+}
+
+/// Search file contents below `root`, streaming `search:chunk` events tagged
+/// with `request_id`. Resolves with totals when the search ends; the last
+/// chunk has `done: true`.
+#[tauri::command]
+pub async fn search_in_project(
+    app: AppHandle,
+    path_access: State<'_, PathAccessManager>,
+    state: State<'_, SearchState>,
+    root: String,
+    request_id: String,
+    options: SearchOptions,
+    tab_id: Option<String>,
+) -> Result<SearchSummary, String> {
+    let root_path = path_access
+        .validate(Path::new(&root), tab_id.as_deref(), PathCapability::Read)
+        .await?;
+    if !root_path.is_dir() {
+        return Err(format!("Not a directory: {}", root));
+    }
+    let re = build_regex(&options)?;
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    state
+        .searches
+        .lock()
+        .map_err(|e| format!("Search state poisoned: {}", e))?
+        .insert(request_id.clone(), cancel.clone());
+
+    let id = request_id.clone();
+    let flag = cancel.clone();
+    let search = tokio::task::spawn_blocking(move || {
+        let summary = run_search(&root_path, &options, &re, &flag, |files| {
+            let _ = emit_to_frontend(
+                &app,
+                "search:chunk",
+                SearchChunk {
+                    request_id: id.clone(),
+                    files,
+                    done: false,
+                    truncated: false,
+                    cancelled: false,
+                },
+            );
+        });
+        let _ = emit_to_frontend(
+            &app,
+            "search:chunk",
+            SearchChunk {
+                request_id: id,
+                files: Vec::new(),
+                done: true,
+                truncated: summary.truncated,
+                cancelled: summary.cancelled,
+            },
+        );
+        summary
+    })
+    .await;
+
+    if let Ok(mut searches) = state.searches.lock() {
+        searches.remove(&request_id);
+    }
+    search.map_err(|e| format!("Search failed: {}", e))
+}
+
+/// Stop a running `search_in_project`.
+#[tauri::command]
+pub async fn cancel_project_search(
+    state: State<'_, SearchState>,
+    request_id: String,
+) -> Result<(), String> {
+    if let Some(flag) = state
+        .searches
+        .lock()
+        .map_err(|e| format!("Search state poisoned: {}", e))?
+        .get(&request_id)
+    {
+        flag.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(query: &str) -> SearchOptions {
+        SearchOptions {
+            query: query.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn lines_of(path: &Path, o: &SearchOptions) -> Vec<usize> {
+        let re = build_regex(o).unwrap();
+        let found = AtomicUsize::new(0);
+        search_file(path, &re, &Limits::new(o), &found)
+            .unwrap()
+            .iter()
+            .map(|m| m.line)
+            .collect()
+    }
+
+    #[test]
+    fn modes_case_whole_word_and_context() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("a.txt");
+        std::fs::write(
+            &file,
+            "Foo bar\nfoo.bar()\nfoobar\n名字 foo 😀 foo\r\nlast\n",
+        )
+        .unwrap();
+
+        assert_eq!(lines_of(&file, &opts("foo")), [1, 2, 3, 4]);
+        assert_eq!(lines_of(&file, &opts("Foo")), [1]);
+        let literal_dot = SearchOptions {
+            whole_word: true,
+            ..opts("foo.bar")
+        };
+        assert_eq!(lines_of(&file, &literal_dot), [2]);
+        let regex = SearchOptions {
+            mode: SearchMode::Regex,
+            case: CaseMode::Sensitive,
+            ..opts(r"^foo\w*$")
+        };
+        assert_eq!(lines_of(&file, &regex), [3]);
+
+        let o = SearchOptions {
+            case: CaseMode::Sensitive,
+            context_lines: Some(1),
+            ..opts("foo")
+        };
+        let re = build_regex(&o).unwrap();
+        let found = AtomicUsize::new(0);
+        let hits = search_file(&file, &re, &Limits::new(&o), &found).unwrap();
+        let cjk = hits.iter().find(|m| m.line == 4).unwrap();
+        // "名字 " is 3 UTF-16 units; the emoji takes two.
+        assert_eq!(cjk.ranges, [[3, 6], [10, 13]]);
+        assert_eq!(cjk.before, ["foobar"]);
+        assert_eq!(cjk.after, ["last"]);
+
+        assert!(build_regex(&SearchOptions {
+            mode: SearchMode::Regex,
+            ..opts("(")
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn walks_with_gitignore_globs_and_limits() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("src/deep")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(root.join("src/lib.rs"), "needle\nneedle\n").unwrap();
+        std::fs::write(root.join("src/deep/mod.rs"), "needle\n").unwrap();
+        std::fs::write(root.join("src/notes.md"), "needle\n").unwrap();
+        std::fs::write(root.join("target/out.rs"), "needle\n").unwrap();
+        std::fs::write(root.join("src/blob.rs"), b"needle\0\x01").unwrap();
+
+        let search = |o: &SearchOptions, cancel: bool| {
+            let re = build_regex(o).unwrap();
+            let flag = AtomicBool::new(cancel);
+            let mut files = Vec::new();
+            let summary = run_search(root, o, &re, &flag, |batch| files.extend(batch));
+            let mut paths: Vec<String> = files
+                .iter()
+                .map(|f| rel_path(root, Path::new(&f.path)).unwrap())
+                .collect();
+            paths.sort();
+            (summary, paths)
+        };
+
+        let (summary, paths) = search(
+            &SearchOptions {
+                include: vec!["*.rs".into()],
+                exclude: vec!["deep/".into()],
+                ..opts("needle")
+            },
+            false,
+        );
+        assert_eq!(paths, ["src/lib.rs"]);
+        // blob.rs is binary and not counted as searched.
+        assert_eq!((summary.matches, summary.files_searched), (2, 1));
+        assert!(!summary.truncated);
+
+        let (summary, paths) = search(
+            &SearchOptions {
+                respect_gitignore: Some(false),
+                ..opts("needle")
+            },
+            false,
+        );
+        assert_eq!(paths.len(), 4);
+        assert!(paths.contains(&"target/out.rs".to_string()));
+        assert_eq!(summary.matches, 5);
+
+        let (summary, _) = search(
+            &SearchOptions {
+                max_results: Some(2),
+                ..opts("needle")
+            },
+            false,
+        );
+        assert_eq!(summary.matches, 2);
+        assert!(summary.truncated);
+
+        let (summary, paths) = search(&opts("needle"), true);
+        assert!(summary.cancelled && paths.is_empty());
+    }
+}
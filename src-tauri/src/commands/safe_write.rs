@@ -0,0 +1,427 @@
+//! Conflict-checked, atomic file writes.
+//!
+//! The user and the agent edit the same files at the same time. A save from
+//! the editor carries the version it was loaded from (a SHA-256 of the
+//! content, or just the mtime), and the write is refused with a `Conflict`
+//! outcome if the file on disk no longer matches. The conflict payload holds
+//! the current content, so the frontend can run `merge_file_versions`
+//! (a line-based three-way merge) and either save the clean result or show
+//! the conflict markers to the user.
+//!
+//! Writes go to a temp file in the same directory, are synced, and are then
+//! renamed over the target, so readers never see a half-written file.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use tauri::State;
+
+use crate::path_access::{open_checked, PathAccessManager, PathCapability};
+
+/// Largest file returned inline as text (same limit as `read_file_content`).
+const MAX_INLINE_BYTES: u64 = 1_048_576;
+/// Cap on the LCS table in `merge3`; bigger inputs merge without line matching.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// Identity of a file's content at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileVersion {
+    /// Hex SHA-256 of the content.
+    pub hash: String,
+    pub mtime_ms: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum WriteOutcome {
+    Written {
+        version: FileVersion,
+    },
+    /// The file changed since the caller read it; nothing was written.
+    Conflict {
+        /// `None` if the file was deleted.
+        current: Option<FileVersion>,
+        /// Present when the file is UTF-8 text and at most 1MB.
+        current_content: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionedContent {
+    pub content: String,
+    pub version: FileVersion,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeResult {
+    pub merged: String,
+    /// Number of conflicting hunks, each wrapped in conflict markers.
+    pub conflicts: usize,
+    pub clean: bool,
+}
+
+fn version_of(bytes: &[u8], meta: &std::fs::Metadata) -> FileVersion {
+    FileVersion {
+        hash: format!("{:x}", Sha256::digest(bytes)),
+        mtime_ms: mtime_ms(meta),
+        size: meta.len(),
+    }
+}
+
+fn mtime_ms(meta: &std::fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Read the file and its version. `Ok(None)` if it does not exist.
+fn current_state(path: &Path) -> Result<Option<(Vec<u8>, FileVersion)>, String> {
+    let bytes = match std::fs::read(path) {
+        Ok(b) => b,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Cannot read file: {}", e)),
+    };
+    let meta = std::fs::metadata(path).map_err(|e| format!("Cannot read file: {}", e))?;
+    let version = version_of(&bytes, &meta);
+    Ok(Some((bytes, version)))
+}
+
+/// Whether `current` still matches what the caller expected. The hash wins
+/// when given; the mtime is only compared when it is the sole expectation.
+fn matches_expected(
+    current: Option<&FileVersion>,
+    expected_hash: Option<&str>,
+    expected_mtime_ms: Option<u64>,
+) -> bool {
+    match (expected_hash, expected_mtime_ms) {
+        (None, None) => true,
+        (Some(hash), _) => current.is_some_and(|v| v.hash.eq_ignore_ascii_case(hash)),
+        (None, Some(mtime)) => current.is_some_and(|v| v.mtime_ms == mtime),
+    }
+}
+
+/// Write `bytes` to `path` via a synced temp file in the same directory and
+/// a rename. The target's existing permissions are kept.
+pub(crate) fn atomic_write(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| "Cannot write file: no parent directory".to_string())?;
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp = dir.join(format!(
+        ".{}.tokenicode-{}.tmp",
+        name,
+        uuid::Uuid::new_v4().simple()
+    ));
+
+    let result = (|| {
+        // create_new: never reuse (or follow) something already at the temp name.
+        let mut file = open_checked(&tmp, OpenOptions::new().write(true).create_new(true))?;
+        file.write_all(bytes)
+            .map_err(|e| format!("Cannot write file: {}", e))?;
+        file.sync_all()
+            .map_err(|e| format!("Cannot write file: {}", e))?;
+        if let Ok(meta) = std::fs::metadata(path) {
+            let _ = std::fs::set_permissions(&tmp, meta.permissions());
+        }
+        std::fs::rename(&tmp, path).map_err(|e| format!("Cannot write file: {}", e))
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Write `content` unless the file changed away from the expected version.
+/// With no expectation this always writes, like a plain save.
+pub(crate) fn write_checked(
+    path: &Path,
+    content: &str,
+    expected_hash: Option<&str>,
+    expected_mtime_ms: Option<u64>,
+) -> Result<WriteOutcome, String> {
+    let checked = expected_hash.is_some() || expected_mtime_ms.is_some();
+    if checked {
+        let current = current_state(path)?;
+        if !matches_expected(
+            current.as_ref().map(|(_, v)| v),
+            expected_hash,
+            expected_mtime_ms,
+        ) {
+            return Ok(conflict(current));
+        }
+    }
+
+    atomic_write(path, content.as_bytes())?;
+
+    let meta = std::fs::metadata(path).map_err(|e| format!("Cannot read file: {}", e))?;
+    Ok(WriteOutcome::Written {
+        version: version_of(content.as_bytes(), &meta),
+    })
+}
+
+fn conflict(current: Option<(Vec<u8>, FileVersion)>) -> WriteOutcome {
+    match current {
+        Some((bytes, version)) => {
+            let current_content = if version.size <= MAX_INLINE_BYTES {
+                String::from_utf8(bytes).ok()
+            } else {
+                None
+            };
+            WriteOutcome::Conflict {
+                current: Some(version),
+                current_content,
+            }
+        }
+        None => WriteOutcome::Conflict {
+            current: None,
+            current_content: None,
+        },
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Three-way merge
+// ---------------------------------------------------------------------------
+
+/// For each line of `base`, the index of the line of `other` it is matched
+/// to by the longest common subsequence, if any.
+fn match_lines(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+    let prefix = base.iter().zip(other).take_while(|(a, b)| a == b).count();
+    for (i, m) in matches.iter_mut().enumerate().take(prefix) {
+        *m = Some(i);
+    }
+    let suffix = base[prefix..]
+        .iter()
+        .rev()
+        .zip(other[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    for k in 0..suffix {
+        matches[base.len() - 1 - k] = Some(other.len() - 1 - k);
+    }
+
+    let a = &base[prefix..base.len() - suffix];
+    let b = &other[prefix..other.len() - suffix];
+    let (n, m) = (a.len(), b.len());
+    if n == 0 || m == 0 || (n + 1) * (m + 1) > MAX_LCS_CELLS {
+        return matches;
+    }
+
+    // lcs[i][j] = LCS length of a[i..] and b[j..].
+    let width = m + 1;
+    let mut lcs = vec![0u32; (n + 1) * width];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * width + j] = if a[i] == b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[i] == b[j] {
+            matches[prefix + i] = Some(prefix + j);
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matches
+}
+
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+    }
+}
+
+fn push_marker(out: &mut String, marker: &str) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(marker);
+    out.push('\n');
+}
+
+/// Line-based three-way merge of `ours` and `theirs` against their common
+/// ancestor `base` (diff3). Hunks changed on only one side, or identically
+/// on both, merge cleanly; others are emitted between conflict markers.
+pub(crate) fn merge3(base: &str, ours: &str, theirs: &str) -> MergeResult {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let our_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+    let their_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let to_ours = match_lines(&base_lines, &our_lines);
+    let to_theirs = match_lines(&base_lines, &their_lines);
+
+    let mut merged = String::with_capacity(ours.len().max(theirs.len()));
+    let mut conflicts = 0;
+    let (mut i, mut a, mut b) = (0, 0, 0);
+    loop {
+        // Next base line kept by both sides: the end of the unstable hunk.
+        let stable = (i..base_lines.len()).find_map(|k| match (to_ours[k], to_theirs[k]) {
+            (Some(x), Some(y)) if x >= a && y >= b => Some((k, x, y)),
+            _ => None,
+        });
+        let (k, x, y) = stable.unwrap_or((base_lines.len(), our_lines.len(), their_lines.len()));
+
+        let base_hunk = &base_lines[i..k];
+        let our_hunk = &our_lines[a..x];
+        let their_hunk = &their_lines[b..y];
+        if our_hunk == base_hunk || our_hunk == their_hunk {
+            push_lines(&mut merged, their_hunk);
+        } else if their_hunk == base_hunk {
+            push_lines(&mut merged, our_hunk);
+        } else {
+            conflicts += 1;
+            push_marker(&mut merged, "<<<<<<< yours");
+            push_lines(&mut merged, our_hunk);
+            push_marker(&mut merged, "||||||| original");
+            push_lines(&mut merged, base_hunk);
+            push_marker(&mut merged, "=======");
+            push_lines(&mut merged, their_hunk);
+            push_marker(&mut merged, ">>>>>>> on disk");
+        }
+
+        if stable.is_none() {
+            break;
+        }
+        merged.push_str(base_lines[k]);
+        (i, a, b) = (k + 1, x + 1, y + 1);
+    }
+
+    MergeResult {
+        merged,
+        conflicts,
+        clean: conflicts == 0,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Commands
+// ---------------------------------------------------------------------------
+
+/// Like `read_file_content`, but also returns the version to pass back to
+/// `write_file_content` on save.
+#[tauri::command]
+pub async fn read_file_versioned(
+    path_access: State<'_, PathAccessManager>,
+    path: String,
+    tab_id: Option<String>,
+) -> Result<VersionedContent, String> {
+    let p = path_access
+        .validate(Path::new(&path), tab_id.as_deref(), PathCapability::Read)
+        .await?;
+    let meta = std::fs::metadata(&p).map_err(|e| format!("Cannot read file: {}", e))?;
+    if meta.len() > MAX_INLINE_BYTES {
+        return Err("File too large (>1MB)".to_string());
+    }
+    let (bytes, version) =
+        current_state(&p)?.ok_or_else(|| "Cannot read file: not found".to_string())?;
+    let content = String::from_utf8(bytes).map_err(|e| format!("Cannot read file: {}", e))?;
+    Ok(VersionedContent { content, version })
+}
+
+/// Current version of a file, or `None` if it does not exist.
+#[tauri::command]
+pub async fn get_file_version(
+    path_access: State<'_, PathAccessManager>,
+    path: String,
+    tab_id: Option<String>,
+) -> Result<Option<FileVersion>, String> {
+    let p = path_access
+        .validate(Path::new(&path), tab_id.as_deref(), PathCapability::Read)
+        .await?;
+    tokio::task::spawn_blocking(move || Ok(current_state(&p)?.map(|(_, v)| v)))
+        .await
+        .map_err(|e| format!("Failed to read file version: {}", e))?
+}
+
+#[tauri::command]
+pub async fn merge_file_versions(
+    base: String,
+    ours: String,
+    theirs: String,
+) -> Result<MergeResult, String> {
+    tokio::task::spawn_blocking(move || merge3(&base, &ours, &theirs))
+        .await
+        .map_err(|e| format!("Failed to merge files: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_write_refuses_stale_version() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("notes.md");
+        std::fs::write(&path, "one\n").unwrap();
+        let (_, loaded) = current_state(&path).unwrap().unwrap();
+
+        // The agent edits the file after the user opened it.
+        std::fs::write(&path, "one\nagent\n").unwrap();
+        match write_checked(&path, "one\nuser\n", Some(&loaded.hash), None).unwrap() {
+            WriteOutcome::Conflict {
+                current,
+                current_content,
+            } => {
+                assert_ne!(current.unwrap().hash, loaded.hash);
+                assert_eq!(current_content.as_deref(), Some("one\nagent\n"));
+            }
+            other => panic!("expected conflict, got {:?}", other),
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\nagent\n");
+
+        // Saving against the current version goes through, leaving no temp files.
+        let (_, current) = current_state(&path).unwrap().unwrap();
+        let outcome = write_checked(&path, "one\nboth\n", Some(&current.hash), None).unwrap();
+        assert!(matches!(outcome, WriteOutcome::Written { .. }));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\nboth\n");
+        assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 1);
+
+        // A deleted file is a conflict too; an unchecked write recreates it.
+        std::fs::remove_file(&path).unwrap();
+        let outcome = write_checked(&path, "x", None, Some(current.mtime_ms)).unwrap();
+        assert!(matches!(
+            outcome,
+            WriteOutcome::Conflict { current: None, .. }
+        ));
+        let outcome = write_checked(&path, "x", None, None).unwrap();
+        assert!(matches!(outcome, WriteOutcome::Written { .. }));
+    }
+
+    #[test]
+    fn merge3_combines_disjoint_edits_and_marks_overlaps() {
+        let base = "a\nb\nc\nd\ne\n";
+        let ours = "a\nB\nc\nd\ne\n";
+        let theirs = "a\nb\nc\nd\nE\nf\n";
+        let result = merge3(base, ours, theirs);
+        assert!(result.clean);
+        assert_eq!(result.merged, "a\nB\nc\nd\nE\nf\n");
+
+        // Identical changes on both sides are not a conflict.
+        assert!(merge3(base, ours, ours).clean);
+
+        let result = merge3(base, "a\nmine\nc\nd\ne\n", "a\ntheirs\nc\nd\ne");
+        assert_eq!(result.conflicts, 1);
+        assert_eq!(
+            result.merged,
+            "a\n<<<<<<< yours\nmine\n||||||| original\nb\n=======\ntheirs\n>>>>>>> on disk\nc\nd\ne"
+        );
+    }
+}
@@ -0,0 +1,665 @@
+//! Turn-by-turn comparison of two session JSONLs.
+//!
+//! Used to evaluate the same task run against different models/providers
+//! (e.g. DeepSeek vs native Claude). Each session is folded into turns — a
+//! real user prompt plus every assistant/tool_result line up to the next
+//! prompt — and the two turn lists are aligned by prompt text (LCS), with
+//! unmatched turns between anchors paired positionally.
+//!
+//! JSONL quirks handled here:
+//!   - The CLI writes one line per content block of an assistant message,
+//!     repeating `message.usage` on each. Usage is deduplicated by
+//!     `message.id` (last line wins, it carries the final counts).
+//!   - Sidechain (sub-agent) and meta lines are skipped; they'd skew the
+//!     alignment and are not part of the main conversation.
+//!   - tool_result lines have `type: "user"` but are not prompts.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+#[derive(Debug, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+}
+
+impl TokenUsage {
+    fn from_value(usage: &Value) -> Self {
+        Self {
+            input_tokens: usage["input_tokens"].as_u64().unwrap_or(0),
+            output_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+            cache_creation_input_tokens: usage["cache_creation_input_tokens"].as_u64().unwrap_or(0),
+            cache_read_input_tokens: usage["cache_read_input_tokens"].as_u64().unwrap_or(0),
+        }
+    }
+
+    fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+    }
+}
+
+/// Signed `right - left` token difference.
+#[derive(Debug, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct TokenDelta {
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_creation_input_tokens: i64,
+    pub cache_read_input_tokens: i64,
+}
+
+impl TokenDelta {
+    fn between(left: &TokenUsage, right: &TokenUsage) -> Self {
+        Self {
+            input_tokens: right.input_tokens as i64 - left.input_tokens as i64,
+            output_tokens: right.output_tokens as i64 - left.output_tokens as i64,
+            cache_creation_input_tokens: right.cache_creation_input_tokens as i64
+                - left.cache_creation_input_tokens as i64,
+            cache_read_input_tokens: right.cache_read_input_tokens as i64
+                - left.cache_read_input_tokens as i64,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct TurnSummary {
+    /// 0-based turn index within its own session.
+    pub index: usize,
+    /// First 200 chars of the user prompt.
+    pub prompt: String,
+    /// Tool names in call order (duplicates kept).
+    pub tools: Vec<String>,
+    /// Files targeted by file tools, relative to the session cwd when inside it.
+    pub files_touched: Vec<String>,
+    pub usage: TokenUsage,
+    /// Prompt timestamp → last line of the turn. None when timestamps are missing.
+    pub duration_ms: Option<u64>,
+    /// Last assistant text block of the turn.
+    pub final_text: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub path: String,
+    pub cwd: String,
+    /// Distinct `message.model` values seen, in first-seen order.
+    pub models: Vec<String>,
+    pub turn_count: usize,
+    pub usage: TokenUsage,
+    pub duration_ms: u64,
+    pub tool_counts: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TurnDiff {
+    pub left: Option<TurnSummary>,
+    pub right: Option<TurnSummary>,
+    /// Both sides present and prompts identical after whitespace normalization.
+    pub prompt_matches: bool,
+    /// Tool calls present on one side only (multiset difference).
+    pub tools_only_left: Vec<String>,
+    pub tools_only_right: Vec<String>,
+    pub files_only_left: Vec<String>,
+    pub files_only_right: Vec<String>,
+    pub token_delta: TokenDelta,
+    pub duration_delta_ms: Option<i64>,
+    pub final_text_identical: bool,
+    /// Word-level Jaccard similarity of the final texts, 0.0–1.0.
+    pub final_text_similarity: f64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SessionComparison {
+    pub left: SessionSummary,
+    pub right: SessionSummary,
+    pub turns: Vec<TurnDiff>,
+    pub token_delta: TokenDelta,
+    pub duration_delta_ms: i64,
+}
+
+/// Compare two session JSONL files turn by turn.
+#[tauri::command]
+pub async fn compare_sessions(
+    left_path: String,
+    right_path: String,
+) -> Result<SessionComparison, String> {
+    let (left, left_turns) = summarize_session_file(Path::new(&left_path))?;
+    let (right, right_turns) = summarize_session_file(Path::new(&right_path))?;
+    Ok(compare_summaries(left, left_turns, right, right_turns))
+}
+
+fn summarize_session_file(path: &Path) -> Result<(SessionSummary, Vec<TurnSummary>), String> {
+    use std::io::BufRead;
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open session {}: {}", path.display(), e))?;
+    // Lines are decoded lossily so one bad byte costs one line, not the rest
+    // of the session; read errors abort rather than diff a partial file.
+    let mut reader = std::io::BufReader::new(file);
+    let mut lines = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let read = reader
+            .read_until(b'\n', &mut buf)
+            .map_err(|e| format!("Failed to read session {}: {}", path.display(), e))?;
+        if read == 0 {
+            break;
+        }
+        if let Ok(value) = serde_json::from_str::<Value>(&String::from_utf8_lossy(&buf)) {
+            lines.push(value);
+        }
+    }
+    let (mut summary, turns) = summarize_lines(&lines);
+    summary.path = path.to_string_lossy().to_string();
+    if summary.session_id.is_empty() {
+        summary.session_id = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+    }
+    Ok((summary, turns))
+}
+
+/// In-progress turn state while folding JSONL lines.
+#[derive(Default)]
+struct TurnBuilder {
+    summary: TurnSummary,
+    files: BTreeSet<String>,
+    /// Usage per assistant message id — last line for an id wins.
+    usage_by_msg: HashMap<String, TokenUsage>,
+    start_ms: Option<u64>,
+    end_ms: Option<u64>,
+}
+
+impl TurnBuilder {
+    fn finish(mut self) -> TurnSummary {
+        for usage in self.usage_by_msg.values() {
+            self.summary.usage.add(usage);
+        }
+        self.summary.files_touched = self.files.into_iter().collect();
+        self.summary.duration_ms = match (self.start_ms, self.end_ms) {
+            (Some(s), Some(e)) if e >= s => Some(e - s),
+            _ => None,
+        };
+        self.summary
+    }
+}
+
+fn summarize_lines(lines: &[Value]) -> (SessionSummary, Vec<TurnSummary>) {
+    let mut summary = SessionSummary::default();
+    let mut turns: Vec<TurnSummary> = Vec::new();
+    let mut current: Option<TurnBuilder> = None;
+
+    for line in lines {
+        if line["isSidechain"].as_bool() == Some(true) || line["isMeta"].as_bool() == Some(true) {
+            continue;
+        }
+        if summary.session_id.is_empty() {
+            if let Some(id) = line["sessionId"].as_str() {
+                summary.session_id = id.to_string();
+            }
+        }
+        if summary.cwd.is_empty() {
+            if let Some(cwd) = line["cwd"].as_str() {
+                summary.cwd = cwd.to_string();
+            }
+        }
+        let ts = line["timestamp"].as_str().and_then(parse_rfc3339_ms);
+        let kind = line["type"].as_str().unwrap_or("");
+
+        if kind == "user" || kind == "human" {
+            if let Some(prompt) = user_prompt_text(line) {
+                if let Some(done) = current.take() {
+                    turns.push(done.finish());
+                }
+                let mut builder = TurnBuilder::default();
+                builder.summary.index = turns.len();
+                builder.summary.prompt = prompt.chars().take(200).collect();
+                builder.start_ms = ts;
+                builder.end_ms = ts;
+                current = Some(builder);
+                continue;
+            }
+        }
+
+        let Some(turn) = current.as_mut() else {
+            continue;
+        };
+        if ts.is_some() {
+            turn.end_ms = ts;
+        }
+        if kind != "assistant" {
+            continue;
+        }
+
+        let message = &line["message"];
+        if let Some(model) = message["model"].as_str() {
+            if !model.starts_with('<') && !summary.models.iter().any(|m| m == model) {
+                summary.models.push(model.to_string());
+            }
+        }
+        if message["usage"].is_object() {
+            let id = message["id"]
+                .as_str()
+                .map(String::from)
+                .unwrap_or_else(|| format!("line-{}", turn.usage_by_msg.len()));
+            turn.usage_by_msg
+                .insert(id, TokenUsage::from_value(&message["usage"]));
+        }
+        if let Some(blocks) = message["content"].as_array() {
+            for block in blocks {
+                match block["type"].as_str() {
+                    Some("tool_use") => {
+                        let name = block["name"].as_str().unwrap_or("unknown").to_string();
+                        for file in tool_file_paths(&name, &block["input"]) {
+                            turn.files.insert(relativize(&file, &summary.cwd));
+                        }
+                        *summary.tool_counts.entry(name.clone()).or_insert(0) += 1;
+                        turn.summary.tools.push(name);
+                    }
+                    Some("text") => {
+                        if let Some(text) = block["text"].as_str() {
+                            if !text.trim().is_empty() {
+                                turn.summary.final_text = text.to_string();
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    if let Some(done) = current.take() {
+        turns.push(done.finish());
+    }
+
+    summary.turn_count = turns.len();
+    for turn in &turns {
+        summary.usage.add(&turn.usage);
+        summary.duration_ms += turn.duration_ms.unwrap_or(0);
+    }
+    (summary, turns)
+}
+
+/// Text of a real user prompt, or None for tool_result carrier lines.
+fn user_prompt_text(line: &Value) -> Option<String> {
+    let content = &line["message"]["content"];
+    if let Some(text) = content.as_str() {
+        return Some(text.trim().to_string());
+    }
+    let blocks = content.as_array()?;
+    if blocks
+        .iter()
+        .any(|b| b["type"].as_str() == Some("tool_result"))
+    {
+        return None;
+    }
+    let text: Vec<&str> = blocks
+        .iter()
+        .filter(|b| b["type"].as_str() == Some("text"))
+        .filter_map(|b| b["text"].as_str())
+        .collect();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.join("\n").trim().to_string())
+    }
+}
+
+/// File paths a tool call targets, taken from its input. Covers the CLI's
+/// file tools; Bash/Glob/Grep are not included because their inputs are
+/// commands or patterns rather than concrete files.
+pub(crate) fn tool_file_paths(tool_name: &str, input: &Value) -> Vec<String> {
+    let key = match tool_name {
+        "Read" | "Edit" | "Write" | "MultiEdit" => "file_path",
+        "NotebookEdit" | "NotebookRead" => "notebook_path",
+        _ => return vec![],
+    };
+    input[key]
+        .as_str()
+        .filter(|p| !p.is_empty())
+        .map(|p| vec![p.to_string()])
+        .unwrap_or_default()
+}
+
+/// Make `path` relative to `cwd` when it lives inside it so runs in
+/// different checkouts of the same repo compare equal.
+fn relativize(path: &str, cwd: &str) -> String {
+    if cwd.is_empty() {
+        return path.to_string();
+    }
+    match path.strip_prefix(cwd) {
+        Some(rest) if rest.starts_with('/') || rest.starts_with('\\') => {
+            rest[1..].replace('\\', "/")
+        }
+        _ => path.to_string(),
+    }
+}
+
+fn normalize_prompt(prompt: &str) -> String {
+    prompt.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Pair turns: LCS over normalized prompts gives anchor pairs; turns between
+/// two anchors are paired positionally, leftovers are one-sided.
+fn align_turns(left: &[TurnSummary], right: &[TurnSummary]) -> Vec<(Option<usize>, Option<usize>)> {
+    let l: Vec<String> = left.iter().map(|t| normalize_prompt(&t.prompt)).collect();
+    let r: Vec<String> = right.iter().map(|t| normalize_prompt(&t.prompt)).collect();
+    let (n, m) = (l.len(), r.len());
+
+    let mut dp = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            dp[i][j] = if l[i] == r[j] {
+                dp[i + 1][j + 1] + 1
+            } else {
+                dp[i + 1][j].max(dp[i][j + 1])
+            };
+        }
+    }
+    let mut anchors = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if l[i] == r[j] {
+            anchors.push((i, j));
+            i += 1;
+            j += 1;
+        } else if dp[i + 1][j] >= dp[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    anchors.push((n, m)); // sentinel flushes the tail
+
+    let mut pairs = Vec::new();
+    let (mut li, mut rj) = (0, 0);
+    for (ai, aj) in anchors {
+        while li < ai || rj < aj {
+            let lp = (li < ai).then_some(li);
+            let rp = (rj < aj).then_some(rj);
+            pairs.push((lp, rp));
+            if lp.is_some() {
+                li += 1;
+            }
+            if rp.is_some() {
+                rj += 1;
+            }
+        }
+        if ai < n && aj < m {
+            pairs.push((Some(ai), Some(aj)));
+            li = ai + 1;
+            rj = aj + 1;
+        }
+    }
+    pairs
+}
+
+fn multiset_difference(a: &[String], b: &[String]) -> Vec<String> {
+    let mut counts: HashMap<&str, i64> = HashMap::new();
+    for x in b {
+        *counts.entry(x.as_str()).or_insert(0) += 1;
+    }
+    let mut out = Vec::new();
+    for x in a {
+        let c = counts.entry(x.as_str()).or_insert(0);
+        if *c > 0 {
+            *c -= 1;
+        } else {
+            out.push(x.clone());
+        }
+    }
+    out
+}
+
+fn text_similarity(a: &str, b: &str) -> f64 {
+    let wa: BTreeSet<&str> = a.split_whitespace().collect();
+    let wb: BTreeSet<&str> = b.split_whitespace().collect();
+    if wa.is_empty() && wb.is_empty() {
+        return 1.0;
+    }
+    let inter = wa.intersection(&wb).count() as f64;
+    let union = wa.union(&wb).count() as f64;
+    ((inter / union) * 1000.0).round() / 1000.0
+}
+
+fn diff_turns(left: Option<&TurnSummary>, right: Option<&TurnSummary>) -> TurnDiff {
+    let empty = TurnSummary::default();
+    let l = left.unwrap_or(&empty);
+    let r = right.unwrap_or(&empty);
+    let duration_delta_ms = match (
+        left.and_then(|t| t.duration_ms),
+        right.and_then(|t| t.duration_ms),
+    ) {
+        (Some(a), Some(b)) => Some(b as i64 - a as i64),
+        _ => None,
+    };
+    TurnDiff {
+        left: left.cloned(),
+        right: right.cloned(),
+        prompt_matches: left.is_some()
+            && right.is_some()
+            && normalize_prompt(&l.prompt) == normalize_prompt(&r.prompt),
+        tools_only_left: multiset_difference(&l.tools, &r.tools),
+        tools_only_right: multiset_difference(&r.tools, &l.tools),
+        files_only_left: multiset_difference(&l.files_touched, &r.files_touched),
+        files_only_right: multiset_difference(&r.files_touched, &l.files_touched),
+        token_delta: TokenDelta::between(&l.usage, &r.usage),
+        duration_delta_ms,
+        final_text_identical: l.final_text.trim() == r.final_text.trim(),
+        final_text_similarity: text_similarity(&l.final_text, &r.final_text),
+    }
+}
+
+fn compare_summaries(
+    left: SessionSummary,
+    left_turns: Vec<TurnSummary>,
+    right: SessionSummary,
+    right_turns: Vec<TurnSummary>,
+) -> SessionComparison {
+    let turns = align_turns(&left_turns, &right_turns)
+        .into_iter()
+        .map(|(li, ri)| diff_turns(li.map(|i| &left_turns[i]), ri.map(|j| &right_turns[j])))
+        .collect();
+    SessionComparison {
+        token_delta: TokenDelta::between(&left.usage, &right.usage),
+        duration_delta_ms: right.duration_ms as i64 - left.duration_ms as i64,
+        left,
+        right,
+        turns,
+    }
+}
+
+/// Digits only: `str::parse` would also accept a sign.
+fn ascii_number(s: &str, range: std::ops::Range<usize>) -> Option<i64> {
+    let field = s.get(range)?;
+    if !field.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    field.parse().ok()
+}
+
+/// Parse an RFC 3339 UTC timestamp (`2025-06-01T12:34:56.789Z`, as written
+/// by the CLI) into epoch milliseconds. Offsets other than `Z`/`+00:00` are
+/// applied; anything malformed yields None.
+pub(crate) fn parse_rfc3339_ms(s: &str) -> Option<u64> {
+    let b = s.as_bytes();
+    if b.len() < 19 || b[4] != b'-' || b[7] != b'-' || (b[10] != b'T' && b[10] != b' ') {
+        return None;
+    }
+    let num = |r| ascii_number(s, r);
+    let (year, month, day) = (num(0..4)?, num(5..7)?, num(8..10)?);
+    let (hour, min, sec) = (num(11..13)?, num(14..16)?, num(17..19)?);
+
+    let mut rest = s.get(19..)?;
+    let mut millis = 0i64;
+    if let Some(frac) = rest.strip_prefix('.') {
+        let digits: String = frac.chars().take_while(|c| c.is_ascii_digit()).collect();
+        let ms: String = digits.chars().chain("000".chars()).take(3).collect();
+        millis = ms.parse().ok()?;
+        rest = &frac[digits.len()..];
+    }
+    let offset_secs = match rest {
+        "" | "Z" | "z" => 0,
+        // Exactly `±HH:MM`.
+        tz if tz.len() == 6
+            && matches!(tz.as_bytes()[0], b'+' | b'-')
+            && tz.as_bytes()[3] == b':' =>
+        {
+            let sign = if tz.starts_with('-') { -1 } else { 1 };
+            sign * (ascii_number(tz, 1..3)? * 3600 + ascii_number(tz, 4..6)? * 60)
+        }
+        _ => return None,
+    };
+
+    // Days from civil (Howard Hinnant's algorithm).
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs = days * 86400 + hour * 3600 + min * 60 + sec - offset_secs;
+    u64::try_from(secs * 1000 + millis).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn session(prompt_b: &str, tool: &str, file: &str, out: u64) -> Vec<Value> {
+        vec![
+            json!({"type":"user","sessionId":"s1","cwd":"/repo","timestamp":"2025-06-01T10:00:00.000Z",
+                   "message":{"role":"user","content":"fix the bug"}}),
+            json!({"type":"assistant","timestamp":"2025-06-01T10:00:02.000Z",
+                   "message":{"id":"m1","model":"claude-x","content":[{"type":"tool_use","name":tool,"input":{"file_path":file}}],
+                              "usage":{"input_tokens":10,"output_tokens":1}}}),
+            json!({"type":"assistant","timestamp":"2025-06-01T10:00:03.000Z",
+                   "message":{"id":"m1","model":"claude-x","content":[{"type":"text","text":"done"}],
+                              "usage":{"input_tokens":10,"output_tokens":out}}}),
+            json!({"type":"user","timestamp":"2025-06-01T10:00:04.000Z",
+                   "message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t","content":"ok"}]}}),
+            json!({"type":"user","timestamp":"2025-06-01T10:01:00.000Z",
+                   "message":{"role":"user","content":[{"type":"text","text":prompt_b}]}}),
+            json!({"type":"assistant","isSidechain":true,"message":{"content":[{"type":"tool_use","name":"Bash","input":{}}]}}),
+            json!({"type":"assistant","timestamp":"2025-06-01T10:01:05.500Z",
+                   "message":{"id":"m2","content":[{"type":"text","text":"all good now"}],"usage":{"input_tokens":5,"output_tokens":2}}}),
+        ]
+    }
+
+    #[test]
+    fn folds_lines_into_turns() {
+        let (summary, turns) = summarize_lines(&session("and tests", "Edit", "/repo/src/a.rs", 7));
+        assert_eq!(summary.session_id, "s1");
+        assert_eq!(summary.models, vec!["claude-x"]);
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].tools, vec!["Edit"]);
+        assert_eq!(turns[0].files_touched, vec!["src/a.rs"]);
+        // Usage for m1 is counted once, from its last line.
+        assert_eq!(turns[0].usage.output_tokens, 7);
+        assert_eq!(turns[0].duration_ms, Some(4000));
+        assert_eq!(turns[0].final_text, "done");
+        // Sidechain Bash call is ignored.
+        assert!(turns[1].tools.is_empty());
+        assert_eq!(turns[1].duration_ms, Some(5500));
+        assert_eq!(summary.usage.output_tokens, 9);
+    }
+
+    #[test]
+    fn invalid_utf8_line_does_not_truncate_the_session() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("s1.jsonl");
+        let mut raw = Vec::new();
+        for (i, line) in session("and tests", "Edit", "/repo/src/a.rs", 7)
+            .iter()
+            .enumerate()
+        {
+            if i == 2 {
+                raw.extend_from_slice(b"{\"type\":\"system\",\"text\":\"\xff\xfe\"}\n");
+            }
+            raw.extend_from_slice(line.to_string().as_bytes());
+            raw.push(b'\n');
+        }
+        std::fs::write(&path, raw).unwrap();
+        let (_, turns) = summarize_session_file(&path).unwrap();
+        assert_eq!(turns.len(), 2);
+    }
+
+    #[test]
+    fn compares_aligned_turns() {
+        let (ls, lt) = summarize_lines(&session("and tests", "Edit", "/repo/src/a.rs", 7));
+        let (rs, rt) = summarize_lines(&session("and tests", "Write", "/repo/src/b.rs", 3));
+        let cmp = compare_summaries(ls, lt, rs, rt);
+        assert_eq!(cmp.turns.len(), 2);
+        let t0 = &cmp.turns[0];
+        assert!(t0.prompt_matches);
+        assert_eq!(t0.tools_only_left, vec!["Edit"]);
+        assert_eq!(t0.tools_only_right, vec!["Write"]);
+        assert_eq!(t0.files_only_right, vec!["src/b.rs"]);
+        assert_eq!(t0.token_delta.output_tokens, -4);
+        assert!(cmp.turns[1].final_text_identical);
+    }
+
+    #[test]
+    fn alignment_handles_inserted_turns() {
+        let mk = |p: &str| TurnSummary {
+            prompt: p.to_string(),
+            ..Default::default()
+        };
+        let left = vec![mk("a"), mk("b"), mk("c")];
+        let right = vec![mk("a"), mk("x"), mk("y"), mk("c")];
+        let pairs = align_turns(&left, &right);
+        assert_eq!(
+            pairs,
+            vec![
+                (Some(0), Some(0)),
+                (Some(1), Some(1)),
+                (None, Some(2)),
+                (Some(2), Some(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_cli_timestamps() {
+        assert_eq!(parse_rfc3339_ms("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_rfc3339_ms("2025-06-01T10:00:00.250Z"),
+            Some(1_748_772_000_250)
+        );
+        assert_eq!(
+            parse_rfc3339_ms("2025-06-01T12:00:00+02:00"),
+            Some(1_748_772_000_000)
+        );
+        assert_eq!(parse_rfc3339_ms("yesterday"), None);
+    }
+
+    #[test]
+    fn malformed_timestamps_are_rejected_without_panicking() {
+        for bad in [
+            "2025-06-01T12:00:00+0é:0",
+            "2025-06-01T12:00:00+é0:00",
+            "2025-06-01T12:00:0é",
+            "2025-06-01T12:00:00é",
+            "2025-06-01T12:00:00.5é",
+            "2025-06-01T12:00:00+02",
+            "2025-06-01T12:00:00+02:0",
+            "2025-06-01T12:00:00+05x30",
+            "2025-06-01T12:00:00+-1:30",
+            "2025-+6-01T12:00:00Z",
+            "２０２５-06-01T12:00:00Z",
+        ] {
+            assert_eq!(parse_rfc3339_ms(bad), None, "{bad}");
+        }
+    }
+}
@@ -0,0 +1,350 @@
+//! Session import from other machines and agents.
+//!
+//! `export_session_json` writes a pretty-printed JSON array of the session's
+//! JSONL lines; teammates can also hand over the raw `<uuid>.jsonl` the CLI
+//! writes under `~/.claude/projects/`. Both shapes are accepted here.
+//!
+//! Importing a session means:
+//!   1. Parse the source (JSON array or JSONL) into line values.
+//!   2. Pick a session id — the one recorded in the file unless it is not a
+//!      UUID or already exists locally, in which case a fresh one is minted.
+//!   3. Rebase every `cwd` field onto the local project path and rewrite
+//!      every `sessionId` field to the chosen id.
+//!   4. Check the conversation is resumable (has user/assistant turns and an
+//!      intact `parentUuid` chain).
+//!   5. Write `<id>.jsonl` into the encoded project dir the CLI expects for
+//!      that cwd and register the id in tracked_sessions.txt.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::Path;
+use tauri::AppHandle;
+
+use crate::events::emit_to_frontend;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportedSession {
+    /// Session UUID the imported file was written under (use for --resume).
+    pub session_id: String,
+    /// Absolute path of the written JSONL.
+    pub path: String,
+    /// Local project path the session was rebased onto.
+    pub project: String,
+    /// Encoded directory name under ~/.claude/projects/.
+    pub project_dir: String,
+    pub message_count: usize,
+    /// First `cwd` recorded in the source before rewriting (empty if none).
+    pub original_cwd: String,
+    /// True when the source id was unusable or already present locally and a
+    /// fresh UUID was assigned instead.
+    pub renamed: bool,
+    /// Non-fatal issues found while importing (skipped lines, chain gaps).
+    pub warnings: Vec<String>,
+}
+
+/// Import an exported session JSON or raw CLI JSONL so it can be resumed
+/// from `project_path` on this machine.
+#[tauri::command]
+pub async fn import_session(
+    app: AppHandle,
+    source_path: String,
+    project_path: String,
+) -> Result<ImportedSession, String> {
+    let project = project_path.trim_end_matches(['/', '\\']).to_string();
+    if project.is_empty() || !Path::new(&project).is_dir() {
+        return Err(format!(
+            "Project directory does not exist: {}",
+            project_path
+        ));
+    }
+
+    let raw = std::fs::read_to_string(&source_path)
+        .map_err(|e| format!("Failed to read session file: {}", e))?;
+    let (mut lines, mut warnings) = parse_session_source(&raw)?;
+
+    // Session id: prefer the one recorded in the file, fall back to the file
+    // stem (raw JSONL exports are named <uuid>.jsonl), then a fresh UUID.
+    let recorded_id = lines
+        .iter()
+        .find_map(|l| l["sessionId"].as_str().map(String::from))
+        .or_else(|| {
+            Path::new(&source_path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+        })
+        .filter(|id| uuid::Uuid::parse_str(id).is_ok());
+    let (session_id, renamed) = match recorded_id {
+        Some(id) if crate::find_session_jsonl(&id).is_none() => (id, false),
+        _ => (uuid::Uuid::new_v4().to_string(), true),
+    };
+
+    let original_cwd = rewrite_session_lines(&mut lines, &session_id, &project);
+    warnings.extend(validate_resumable(&lines)?);
+
+    let home = dirs::home_dir().ok_or("Cannot find home dir")?;
+    let project_dir = crate::encode_project_name(&project);
+    let target_dir = home.join(".claude").join("projects").join(&project_dir);
+    std::fs::create_dir_all(&target_dir)
+        .map_err(|e| format!("Failed to create project dir: {}", e))?;
+    let target = target_dir.join(format!("{}.jsonl", session_id));
+
+    let mut body = String::new();
+    for line in &lines {
+        let s = serde_json::to_string(line)
+            .map_err(|e| format!("Failed to serialize session line: {}", e))?;
+        body.push_str(&s);
+        body.push('\n');
+    }
+    // Atomic write: temp file + rename so a crash never leaves a truncated
+    // JSONL that the CLI would choke on during --resume.
+    let tmp = target.with_extension("jsonl.tmp");
+    std::fs::write(&tmp, body).map_err(|e| format!("Failed to write session: {}", e))?;
+    std::fs::rename(&tmp, &target).map_err(|e| format!("Failed to place session: {}", e))?;
+
+    crate::append_tracked_session(&session_id)?;
+    let _ = emit_to_frontend(&app, "sessions:changed", serde_json::json!(null));
+
+    eprintln!(
+        "[TOKENICODE] import_session: {} → {:?} ({} lines, renamed={})",
+        source_path,
+        target,
+        lines.len(),
+        renamed
+    );
+
+    Ok(ImportedSession {
+        session_id,
+        path: target.to_string_lossy().to_string(),
+        project,
+        project_dir,
+        message_count: lines.len(),
+        original_cwd,
+        renamed,
+        warnings,
+    })
+}
+
+/// Parse either a JSON array (`export_session_json` output) or JSONL.
+/// Returns the line values plus warnings for entries that were skipped.
+fn parse_session_source(raw: &str) -> Result<(Vec<Value>, Vec<String>), String> {
+    let trimmed = raw.trim_start_matches('\u{feff}').trim();
+    if trimmed.is_empty() {
+        return Err("Session file is empty".to_string());
+    }
+
+    if trimmed.starts_with('[') {
+        let values: Vec<Value> = serde_json::from_str(trimmed)
+            .map_err(|e| format!("Invalid exported session JSON: {}", e))?;
+        let total = values.len();
+        let objects: Vec<Value> = values.into_iter().filter(|v| v.is_object()).collect();
+        if objects.is_empty() {
+            return Err("No session messages found in file".to_string());
+        }
+        let mut warnings = Vec::new();
+        if objects.len() < total {
+            warnings.push(format!(
+                "{} array entries are not JSON objects, skipped",
+                total - objects.len()
+            ));
+        }
+        return Ok((objects, warnings));
+    }
+
+    let mut values = Vec::new();
+    let mut warnings = Vec::new();
+    for (idx, line) in trimmed.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Value>(line) {
+            Ok(v) if v.is_object() => values.push(v),
+            _ => warnings.push(format!("Line {} is not a JSON object, skipped", idx + 1)),
+        }
+    }
+    if values.is_empty() {
+        return Err("No session messages found in file".to_string());
+    }
+    Ok((values, warnings))
+}
+
+/// Rewrite `sessionId` to `session_id` and rebase every `cwd` onto
+/// `local_cwd`. Subdirectories of the original project root keep their
+/// relative suffix; unrelated cwds collapse to the project root.
+/// Returns the original project root (first `cwd` seen, empty if none).
+fn rewrite_session_lines(lines: &mut [Value], session_id: &str, local_cwd: &str) -> String {
+    let original_root = lines
+        .iter()
+        .find_map(|l| {
+            l["cwd"]
+                .as_str()
+                .filter(|c| !c.is_empty())
+                .map(String::from)
+        })
+        .unwrap_or_default();
+    let local_sep = if local_cwd.contains('\\') && !local_cwd.contains('/') {
+        '\\'
+    } else {
+        '/'
+    };
+
+    for line in lines.iter_mut() {
+        let Some(obj) = line.as_object_mut() else {
+            continue;
+        };
+        if obj.contains_key("sessionId") {
+            obj.insert("sessionId".into(), Value::String(session_id.to_string()));
+        }
+        if let Some(cwd) = obj.get("cwd").and_then(|c| c.as_str()).map(String::from) {
+            let rebased = rebase_cwd(&cwd, &original_root, local_cwd, local_sep);
+            obj.insert("cwd".into(), Value::String(rebased));
+        }
+    }
+    original_root
+}
+
+fn rebase_cwd(cwd: &str, original_root: &str, local_cwd: &str, local_sep: char) -> String {
+    if original_root.is_empty() || cwd == original_root {
+        return local_cwd.to_string();
+    }
+    let suffix = cwd
+        .strip_prefix(original_root)
+        .filter(|rest| rest.starts_with('/') || rest.starts_with('\\'));
+    match suffix {
+        Some(rest) => {
+            let rest: String = rest
+                .chars()
+                .map(|c| if c == '/' || c == '\\' { local_sep } else { c })
+                .collect();
+            format!("{}{}", local_cwd, rest)
+        }
+        None => local_cwd.to_string(),
+    }
+}
+
+/// Check that the CLI can pick the conversation back up with `--resume`.
+/// Hard failures (nothing to resume) are `Err`; chain gaps are warnings
+/// because the CLI tolerates them by starting a new branch from the gap.
+fn validate_resumable(lines: &[Value]) -> Result<Vec<String>, String> {
+    let mut warnings = Vec::new();
+    // Parents may be any line with a uuid (system, summary, ...), not just turns.
+    let seen: HashSet<&str> = lines.iter().filter_map(|l| l["uuid"].as_str()).collect();
+    let mut turns = 0usize;
+    let mut broken = 0usize;
+
+    for line in lines {
+        let kind = line["type"].as_str().unwrap_or("");
+        if !matches!(kind, "user" | "assistant") {
+            continue;
+        }
+        turns += 1;
+        if let Some(parent) = line["parentUuid"].as_str() {
+            if !seen.contains(parent) {
+                broken += 1;
+            }
+        }
+        if line["uuid"].as_str().is_none() {
+            return Err(
+                "Session messages have no `uuid` field — not a Claude CLI transcript".to_string(),
+            );
+        }
+    }
+
+    if turns == 0 {
+        return Err("Session has no user or assistant messages to resume".to_string());
+    }
+    if broken > 0 {
+        warnings.push(format!(
+            "{} message(s) reference a parent that is not in the file; \
+             the CLI will resume from the last intact branch",
+            broken
+        ));
+    }
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_exported_json_array() {
+        let raw = r#"[{"type":"user","uuid":"a"},{"type":"assistant","uuid":"b"}]"#;
+        let (lines, warnings) = parse_session_source(raw).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(warnings.is_empty());
+
+        let raw = r#"[{"type":"user","uuid":"a"},"stray",42]"#;
+        let (lines, warnings) = parse_session_source(raw).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(warnings, ["2 array entries are not JSON objects, skipped"]);
+        assert!(parse_session_source("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn parses_jsonl_and_skips_garbage() {
+        let raw = "{\"type\":\"user\",\"uuid\":\"a\"}\nnot json\n\n{\"type\":\"assistant\"}\n";
+        let (lines, warnings) = parse_session_source(raw).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("Line 2"));
+    }
+
+    #[test]
+    fn rewrites_cwd_and_session_id() {
+        let mut lines = vec![
+            json!({"type":"summary","summary":"x"}),
+            json!({"type":"user","sessionId":"old","cwd":"/home/bob/proj"}),
+            json!({"type":"assistant","sessionId":"old","cwd":"/home/bob/proj/src/ui"}),
+            json!({"type":"assistant","sessionId":"old","cwd":"/tmp/elsewhere"}),
+        ];
+        let root = rewrite_session_lines(&mut lines, "new-id", "/Users/alice/work/proj");
+        assert_eq!(root, "/home/bob/proj");
+        assert!(lines[0].get("sessionId").is_none());
+        assert_eq!(lines[1]["sessionId"], "new-id");
+        assert_eq!(lines[1]["cwd"], "/Users/alice/work/proj");
+        assert_eq!(lines[2]["cwd"], "/Users/alice/work/proj/src/ui");
+        assert_eq!(lines[3]["cwd"], "/Users/alice/work/proj");
+    }
+
+    #[test]
+    fn rebases_windows_source_onto_unix() {
+        assert_eq!(
+            rebase_cwd("C:\\dev\\proj\\src", "C:\\dev\\proj", "/home/a/proj", '/'),
+            "/home/a/proj/src"
+        );
+        // Sibling dir sharing a name prefix must not be treated as a subdir.
+        assert_eq!(
+            rebase_cwd("/dev/proj-old", "/dev/proj", "/home/a/proj", '/'),
+            "/home/a/proj"
+        );
+    }
+
+    #[test]
+    fn validate_requires_turns_and_uuids() {
+        assert!(validate_resumable(&[json!({"type":"summary"})]).is_err());
+        assert!(validate_resumable(&[json!({"type":"user"})]).is_err());
+
+        let ok = vec![
+            json!({"type":"user","uuid":"a","parentUuid":null}),
+            json!({"type":"assistant","uuid":"b","parentUuid":"a"}),
+        ];
+        assert!(validate_resumable(&ok).unwrap().is_empty());
+
+        // Parents may be system lines, which are not turns themselves.
+        let via_system = vec![
+            json!({"type":"user","uuid":"a"}),
+            json!({"type":"system","uuid":"s","parentUuid":"a"}),
+            json!({"type":"assistant","uuid":"b","parentUuid":"s"}),
+        ];
+        assert!(validate_resumable(&via_system).unwrap().is_empty());
+
+        let gap = vec![
+            json!({"type":"user","uuid":"a"}),
+            json!({"type":"assistant","uuid":"b","parentUuid":"missing"}),
+        ];
+        assert_eq!(validate_resumable(&gap).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,730 @@
+//! Backend retention rules for TOKENICODE-tracked sessions.
+//!
+//! `archived.json` / `pinned.json` are id lists owned by the sidebar, and
+//! `delete_session` removes one session at a time. This module adds policy
+//! driven housekeeping on top of them, configured in
+//! `~/.tokenicode/retention.json`:
+//!
+//!   - **Archive** sessions idle for `archive_after_days` (idle = JSONL mtime).
+//!     Archiving reports the id to the sidebar, which adds it to
+//!     `archived.json` (this module never writes the file); with
+//!     `compress_archived` the
+//!     JSONL is also gzipped into `~/.tokenicode/archive/<project_dir>/` and
+//!     removed from `~/.claude/projects/` (restorable via
+//!     `restore_archived_session`).
+//!   - **Delete** sessions (live or compressed) idle for `delete_after_days`,
+//!     through the system trash when `use_trash` is set.
+//!
+//! Rules resolve per project (exact cwd match in `projects`, else `default`).
+//! Retention is opt-in: the default rule is disabled. Pinned sessions are
+//! never touched. The pass runs once at startup next to
+//! `cleanup_tracked_sessions`, and on demand via `apply_retention_policy`.
+//! Archive-list changes go out on `sessions:changed` and are also kept
+//! until the sidebar collects them with `take_retention_changes`, since the
+//! startup pass can finish before the sidebar is listening.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+use crate::events::emit_to_frontend;
+
+const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct RetentionRule {
+    pub enabled: bool,
+    /// Archive after this many idle days. `None` = never archive.
+    pub archive_after_days: Option<u32>,
+    /// Gzip archived JSONL out of ~/.claude/projects/ into ~/.tokenicode/archive/.
+    pub compress_archived: bool,
+    /// Hard-delete after this many idle days. `None` = never delete.
+    pub delete_after_days: Option<u32>,
+    /// Move deleted sessions to the system trash instead of unlinking them.
+    pub use_trash: bool,
+}
+
+impl Default for RetentionRule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            archive_after_days: Some(30),
+            compress_archived: true,
+            delete_after_days: None,
+            use_trash: true,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    pub version: u32,
+    pub default: RetentionRule,
+    /// Per-project overrides keyed by project cwd.
+    pub projects: BTreeMap<String, RetentionRule>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            version: 1,
+            default: RetentionRule::default(),
+            projects: BTreeMap::new(),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    fn rule_for(&self, project: &str) -> &RetentionRule {
+        let key = project.trim_end_matches(['/', '\\']);
+        self.projects
+            .iter()
+            .find(|(k, _)| k.trim_end_matches(['/', '\\']) == key)
+            .map(|(_, r)| r)
+            .unwrap_or(&self.default)
+    }
+
+    fn any_enabled(&self) -> bool {
+        self.default.enabled || self.projects.values().any(|r| r.enabled)
+    }
+}
+
+/// One gzipped session under ~/.tokenicode/archive/.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CompressedSession {
+    pub session_id: String,
+    pub project: String,
+    pub project_dir: String,
+    pub archive_path: String,
+    /// JSONL mtime at archive time — idle time keeps counting from here.
+    pub last_active_ms: u64,
+    pub archived_at_ms: u64,
+    pub original_bytes: u64,
+    pub compressed_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    /// Ids newly archived; the sidebar adds them to archived.json.
+    pub archived: Vec<String>,
+    /// Ids whose JSONL was gzipped out of ~/.claude/projects/.
+    pub compressed: Vec<String>,
+    pub deleted: Vec<String>,
+    /// Bytes freed under ~/.claude/projects/ and ~/.tokenicode/archive/.
+    pub bytes_reclaimed: u64,
+    pub errors: Vec<String>,
+}
+
+impl RetentionReport {
+    fn changed(&self) -> bool {
+        !self.archived.is_empty() || !self.compressed.is_empty() || !self.deleted.is_empty()
+    }
+}
+
+/// Edits to the sidebar's archived list, sent with `sessions:changed`.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct ArchiveChanges {
+    pub archived: Vec<String>,
+    /// Ids to drop from the list (deleted sessions).
+    pub unarchived: Vec<String>,
+}
+
+/// Changes from non-dry passes the sidebar has not collected yet.
+static PENDING_CHANGES: Mutex<ArchiveChanges> = Mutex::new(ArchiveChanges {
+    archived: Vec::new(),
+    unarchived: Vec::new(),
+});
+
+#[derive(Debug, PartialEq, Eq)]
+enum RetentionAction {
+    Keep,
+    Archive { compress: bool },
+    Delete,
+}
+
+fn decide_action(
+    rule: &RetentionRule,
+    idle_days: u64,
+    pinned: bool,
+    archived: bool,
+) -> RetentionAction {
+    if !rule.enabled || pinned {
+        return RetentionAction::Keep;
+    }
+    if let Some(days) = rule.delete_after_days {
+        if idle_days >= days as u64 {
+            return RetentionAction::Delete;
+        }
+    }
+    match rule.archive_after_days {
+        Some(days) if idle_days >= days as u64 => {
+            if rule.compress_archived {
+                RetentionAction::Archive { compress: true }
+            } else if !archived {
+                RetentionAction::Archive { compress: false }
+            } else {
+                RetentionAction::Keep
+            }
+        }
+        _ => RetentionAction::Keep,
+    }
+}
+
+fn policy_path() -> Result<PathBuf, String> {
+    crate::tokenicode_data_path("retention.json")
+}
+
+pub(crate) fn archive_root() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Cannot find home dir")?;
+    Ok(home.join(".tokenicode").join("archive"))
+}
+
+fn archive_index_path() -> Result<PathBuf, String> {
+    Ok(archive_root()?.join("index.json"))
+}
+
+fn read_policy() -> Result<RetentionPolicy, String> {
+    let path = policy_path()?;
+    if !path.exists() {
+        return Ok(RetentionPolicy::default());
+    }
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read retention policy: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse retention policy: {}", e))
+}
+
+fn read_archive_index() -> BTreeMap<String, CompressedSession> {
+    archive_index_path()
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+fn write_archive_index(index: &BTreeMap<String, CompressedSession>) -> Result<(), String> {
+    let path = archive_index_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create archive dir: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(index)
+        .map_err(|e| format!("Failed to serialize archive index: {}", e))?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, content).map_err(|e| format!("Failed to write archive index: {}", e))?;
+    std::fs::rename(&tmp, &path).map_err(|e| format!("Failed to write archive index: {}", e))
+}
+
+/// Read one of the frontend-owned id lists (pinned.json / archived.json).
+fn read_id_list(filename: &str) -> Vec<String> {
+    crate::tokenicode_data_path(filename)
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str::<Vec<Value>>(&c).ok())
+        .map(|v| {
+            v.into_iter()
+                .filter_map(|x| x.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn mtime_ms(path: &Path) -> Option<u64> {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+}
+
+fn remove_path(path: &Path, use_trash: bool) -> Result<(), String> {
+    if use_trash {
+        trash::delete(path).map_err(|e| format!("Cannot move to trash: {}", e))
+    } else {
+        std::fs::remove_file(path).map_err(|e| format!("Cannot delete file: {}", e))
+    }
+}
+
+/// Gzip `src` into `dest` and verify the byte count before the caller
+/// removes the original. Returns the compressed size.
+fn compress_jsonl(src: &Path, dest: &Path) -> Result<u64, String> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create archive dir: {}", e))?;
+    }
+    let expected = std::fs::metadata(src)
+        .map_err(|e| format!("Cannot stat session: {}", e))?
+        .len();
+    let mut input = std::fs::File::open(src).map_err(|e| format!("Cannot open session: {}", e))?;
+    let tmp = dest.with_extension("gz.tmp");
+    let out = std::fs::File::create(&tmp).map_err(|e| format!("Cannot create archive: {}", e))?;
+    let mut encoder = flate2::write::GzEncoder::new(out, flate2::Compression::default());
+    let copied = std::io::copy(&mut input, &mut encoder)
+        .map_err(|e| format!("Failed to compress session: {}", e))?;
+    let mut out = encoder
+        .finish()
+        .map_err(|e| format!("Failed to finish archive: {}", e))?;
+    out.flush()
+        .map_err(|e| format!("Failed to flush archive: {}", e))?;
+    drop(out);
+    if copied != expected {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(format!(
+            "Session changed while compressing ({} of {} bytes)",
+            copied, expected
+        ));
+    }
+    std::fs::rename(&tmp, dest).map_err(|e| format!("Failed to place archive: {}", e))?;
+    std::fs::metadata(dest)
+        .map(|m| m.len())
+        .map_err(|e| format!("Cannot stat archive: {}", e))
+}
+
+fn decompress_jsonl(src: &Path, dest: &Path) -> Result<(), String> {
+    let input = std::fs::File::open(src).map_err(|e| format!("Cannot open archive: {}", e))?;
+    let mut decoder = flate2::read::GzDecoder::new(input);
+    let tmp = dest.with_extension("jsonl.tmp");
+    let mut out =
+        std::fs::File::create(&tmp).map_err(|e| format!("Cannot create session file: {}", e))?;
+    std::io::copy(&mut decoder, &mut out)
+        .map_err(|e| format!("Failed to decompress archive: {}", e))?;
+    drop(out);
+    std::fs::rename(&tmp, dest).map_err(|e| format!("Failed to place session file: {}", e))
+}
+
+/// One retention pass over tracked sessions and compressed archives.
+fn apply_retention(dry_run: bool) -> Result<RetentionReport, String> {
+    let mut report = RetentionReport {
+        dry_run,
+        ..Default::default()
+    };
+    let policy = read_policy()?;
+    if !policy.any_enabled() {
+        return Ok(report);
+    }
+
+    let home = dirs::home_dir().ok_or("Cannot find home dir")?;
+    let projects_dir = home.join(".claude").join("projects");
+    let archive_dir = archive_root()?;
+    let tracked = crate::load_tracked_sessions();
+    let pinned: HashSet<String> = read_id_list("pinned.json").into_iter().collect();
+    let archived: HashSet<String> = read_id_list("archived.json").into_iter().collect();
+    let mut index = read_archive_index();
+    let now = now_ms();
+    let mut untrack: Vec<String> = Vec::new();
+
+    // 1. Live JSONLs under ~/.claude/projects/
+    if let Ok(entries) = std::fs::read_dir(&projects_dir) {
+        for entry in entries.flatten() {
+            if !entry.path().is_dir() {
+                continue;
+            }
+            let project_dir = entry.file_name().to_string_lossy().to_string();
+            let Ok(files) = std::fs::read_dir(entry.path()) else {
+                continue;
+            };
+            for file in files.flatten() {
+                let path = file.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+                    continue;
+                }
+                let id = match path.file_stem() {
+                    Some(s) => s.to_string_lossy().to_string(),
+                    None => continue,
+                };
+                if !tracked.contains(&id) {
+                    continue;
+                }
+                let Some(last_active) = mtime_ms(&path) else {
+                    continue;
+                };
+                let (_, cwd) = crate::extract_session_info(&path);
+                let project = if cwd.is_empty() {
+                    crate::decode_project_name(&project_dir)
+                } else {
+                    cwd
+                };
+                let rule = policy.rule_for(&project);
+                let idle_days = now.saturating_sub(last_active) / MS_PER_DAY;
+                let is_archived = archived.contains(&id);
+                let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+
+                match decide_action(rule, idle_days, pinned.contains(&id), is_archived) {
+                    RetentionAction::Keep => {}
+                    RetentionAction::Delete => {
+                        if !dry_run {
+                            if let Err(e) = remove_path(&path, rule.use_trash) {
+                                report.errors.push(format!("{}: {}", id, e));
+                                continue;
+                            }
+                            untrack.push(id.clone());
+                        }
+                        report.bytes_reclaimed += size;
+                        report.deleted.push(id);
+                    }
+                    RetentionAction::Archive { compress } => {
+                        if !is_archived {
+                            report.archived.push(id.clone());
+                        }
+                        if !compress {
+                            continue;
+                        }
+                        let dest = archive_dir
+                            .join(&project_dir)
+                            .join(format!("{}.jsonl.gz", id));
+                        if dry_run {
+                            report.bytes_reclaimed += size;
+                            report.compressed.push(id);
+                            continue;
+                        }
+                        match compress_jsonl(&path, &dest) {
+                            Ok(compressed_bytes) => {
+                                if let Err(e) = std::fs::remove_file(&path) {
+                                    let _ = std::fs::remove_file(&dest);
+                                    report.errors.push(format!("{}: {}", id, e));
+                                    continue;
+                                }
+                                report.bytes_reclaimed += size.saturating_sub(compressed_bytes);
+                                index.insert(
+                                    id.clone(),
+                                    CompressedSession {
+                                        session_id: id.clone(),
+                                        project: project.clone(),
+                                        project_dir: project_dir.clone(),
+                                        archive_path: dest.to_string_lossy().to_string(),
+                                        last_active_ms: last_active,
+                                        archived_at_ms: now,
+                                        original_bytes: size,
+                                        compressed_bytes,
+                                    },
+                                );
+                                report.compressed.push(id);
+                            }
+                            Err(e) => report.errors.push(format!("{}: {}", id, e)),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // 2. Compressed archives — only deletion applies.
+    let expired: Vec<CompressedSession> = index
+        .values()
+        .filter(|entry| {
+            let rule = policy.rule_for(&entry.project);
+            let idle_days = now.saturating_sub(entry.last_active_ms) / MS_PER_DAY;
+            decide_action(rule, idle_days, pinned.contains(&entry.session_id), true)
+                == RetentionAction::Delete
+        })
+        .cloned()
+        .collect();
+    for entry in expired {
+        if !dry_run {
+            let path = PathBuf::from(&entry.archive_path);
+            if path.exists() {
+                let use_trash = policy.rule_for(&entry.project).use_trash;
+                if let Err(e) = remove_path(&path, use_trash) {
+                    report.errors.push(format!("{}: {}", entry.session_id, e));
+                    continue;
+                }
+            }
+            index.remove(&entry.session_id);
+            untrack.push(entry.session_id.clone());
+        }
+        report.bytes_reclaimed += entry.compressed_bytes;
+        report.deleted.push(entry.session_id);
+    }
+
+    if !dry_run && report.changed() {
+        for id in &untrack {
+            if let Err(e) = crate::remove_tracked_session(id) {
+                report.errors.push(format!("{}: {}", id, e));
+            }
+        }
+        write_archive_index(&index)?;
+    }
+    Ok(report)
+}
+
+/// Queue the report's archive-list edits until the sidebar takes them.
+fn queue_changes(report: &RetentionReport) -> ArchiveChanges {
+    let changes = ArchiveChanges {
+        archived: report.archived.clone(),
+        unarchived: report.deleted.clone(),
+    };
+    if let Ok(mut pending) = PENDING_CHANGES.lock() {
+        pending.archived.extend(changes.archived.iter().cloned());
+        pending
+            .unarchived
+            .extend(changes.unarchived.iter().cloned());
+    }
+    changes
+}
+
+fn publish_changes(app: &AppHandle, report: &RetentionReport) {
+    let _ = emit_to_frontend(app, "sessions:changed", queue_changes(report));
+}
+
+/// Startup hook: run one retention pass off the main thread so a large
+/// `~/.claude/projects/` never delays window creation.
+pub fn run_retention_at_startup(app: AppHandle) {
+    std::thread::spawn(move || match apply_retention(false) {
+        Ok(report) if report.changed() => {
+            eprintln!(
+                "[TOKENICODE] retention: archived={} compressed={} deleted={} reclaimed={}B errors={}",
+                report.archived.len(),
+                report.compressed.len(),
+                report.deleted.len(),
+                report.bytes_reclaimed,
+                report.errors.len()
+            );
+            publish_changes(&app, &report);
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("[TOKENICODE] retention pass failed: {}", e),
+    });
+}
+
+/// Load retention rules (defaults when the file does not exist yet).
+#[tauri::command]
+pub async fn load_retention_policy() -> Result<RetentionPolicy, String> {
+    read_policy()
+}
+
+/// Save retention rules. Day thresholds must be ≥ 1 and deletion must not
+/// precede archiving, so a typo cannot wipe sessions that are in use.
+#[tauri::command]
+pub async fn save_retention_policy(policy: RetentionPolicy) -> Result<(), String> {
+    for (scope, rule) in std::iter::once(("default", &policy.default))
+        .chain(policy.projects.iter().map(|(k, r)| (k.as_str(), r)))
+    {
+        if rule.archive_after_days == Some(0) || rule.delete_after_days == Some(0) {
+            return Err(format!("Retention days for '{}' must be at least 1", scope));
+        }
+        if let (Some(a), Some(d)) = (rule.archive_after_days, rule.delete_after_days) {
+            if d < a {
+                return Err(format!(
+                    "Retention for '{}': delete_after_days ({}) is shorter than archive_after_days ({})",
+                    scope, d, a
+                ));
+            }
+        }
+    }
+    let path = policy_path()?;
+    let content = serde_json::to_string_pretty(&policy)
+        .map_err(|e| format!("Failed to serialize retention policy: {}", e))?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write retention policy: {}", e))
+}
+
+/// Run the retention pass now. With `dry_run`, report what would happen
+/// without touching any file.
+#[tauri::command]
+pub async fn apply_retention_policy(
+    app: AppHandle,
+    dry_run: Option<bool>,
+) -> Result<RetentionReport, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    let report = tokio::task::spawn_blocking(move || apply_retention(dry_run))
+        .await
+        .map_err(|e| format!("Retention task failed: {}", e))??;
+    if !dry_run && report.changed() {
+        publish_changes(&app, &report);
+    }
+    Ok(report)
+}
+
+/// Archive-list edits made by retention since the last call. The sidebar
+/// calls this after loading archived.json, so edits whose `sessions:changed`
+/// event it missed are still applied.
+#[tauri::command]
+pub async fn take_retention_changes() -> Result<ArchiveChanges, String> {
+    PENDING_CHANGES
+        .lock()
+        .map(|mut pending| std::mem::take(&mut *pending))
+        .map_err(|e| format!("Failed to read retention changes: {}", e))
+}
+
+/// List sessions gzipped out of ~/.claude/projects/ by retention.
+#[tauri::command]
+pub async fn list_compressed_sessions() -> Result<Vec<CompressedSession>, String> {
+    let mut entries: Vec<CompressedSession> = read_archive_index().into_values().collect();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.last_active_ms));
+    Ok(entries)
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RestoredSession {
+    pub session_id: String,
+    /// Restored JSONL under ~/.claude/projects/.
+    pub path: String,
+    /// Whether the sidebar should drop the id from its archived list.
+    pub was_archived: bool,
+}
+
+/// Decompress an archived session back into ~/.claude/projects/. The JSONL
+/// keeps the fresh mtime of the restore, so idle time starts over and the
+/// next retention pass leaves it alone. `archived.json` is not touched here:
+/// the sidebar owns it and drops the id itself (see `sessions:changed`).
+#[tauri::command]
+pub async fn restore_archived_session(
+    app: AppHandle,
+    session_id: String,
+) -> Result<RestoredSession, String> {
+    if uuid::Uuid::parse_str(&session_id).is_err() {
+        return Err(format!("Invalid session id: {}", session_id));
+    }
+    let mut index = read_archive_index();
+    let entry = index
+        .get(&session_id)
+        .cloned()
+        .ok_or_else(|| format!("Session {} is not in the archive", session_id))?;
+
+    let home = dirs::home_dir().ok_or("Cannot find home dir")?;
+    let dest_dir = home
+        .join(".claude")
+        .join("projects")
+        .join(&entry.project_dir);
+    std::fs::create_dir_all(&dest_dir)
+        .map_err(|e| format!("Failed to create project dir: {}", e))?;
+    let dest = dest_dir.join(format!("{}.jsonl", session_id));
+    if dest.exists() {
+        return Err(format!("Session file already exists: {}", dest.display()));
+    }
+    let archive_path = PathBuf::from(&entry.archive_path);
+    decompress_jsonl(&archive_path, &dest)?;
+
+    let _ = std::fs::remove_file(&archive_path);
+    index.remove(&session_id);
+    write_archive_index(&index)?;
+    if !crate::load_tracked_sessions().contains(&session_id) {
+        crate::append_tracked_session(&session_id)?;
+    }
+    let was_archived = read_id_list("archived.json").contains(&session_id);
+    let changes = ArchiveChanges {
+        unarchived: if was_archived {
+            vec![session_id.clone()]
+        } else {
+            Vec::new()
+        },
+        ..Default::default()
+    };
+    let _ = emit_to_frontend(&app, "sessions:changed", changes);
+    Ok(RestoredSession {
+        path: dest.to_string_lossy().to_string(),
+        session_id,
+        was_archived,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(archive: Option<u32>, delete: Option<u32>, compress: bool) -> RetentionRule {
+        RetentionRule {
+            enabled: true,
+            archive_after_days: archive,
+            compress_archived: compress,
+            delete_after_days: delete,
+            use_trash: false,
+        }
+    }
+
+    #[test]
+    fn disabled_or_pinned_sessions_are_kept() {
+        let mut r = rule(Some(1), Some(2), true);
+        assert_eq!(decide_action(&r, 10, true, false), RetentionAction::Keep);
+        r.enabled = false;
+        assert_eq!(decide_action(&r, 10, false, false), RetentionAction::Keep);
+    }
+
+    #[test]
+    fn thresholds_pick_archive_then_delete() {
+        let r = rule(Some(7), Some(30), false);
+        assert_eq!(decide_action(&r, 6, false, false), RetentionAction::Keep);
+        assert_eq!(
+            decide_action(&r, 7, false, false),
+            RetentionAction::Archive { compress: false }
+        );
+        // Already archived and no compression requested → nothing to do.
+        assert_eq!(decide_action(&r, 8, false, true), RetentionAction::Keep);
+        assert_eq!(decide_action(&r, 30, false, true), RetentionAction::Delete);
+
+        let c = rule(Some(7), None, true);
+        assert_eq!(
+            decide_action(&c, 400, false, true),
+            RetentionAction::Archive { compress: true }
+        );
+    }
+
+    #[test]
+    fn project_rules_override_default() {
+        let mut policy = RetentionPolicy::default();
+        policy
+            .projects
+            .insert("/work/app/".to_string(), rule(Some(3), None, false));
+        assert!(policy.rule_for("/work/app").enabled);
+        assert!(!policy.rule_for("/work/other").enabled);
+        assert!(policy.any_enabled());
+    }
+
+    #[tokio::test]
+    async fn archive_changes_wait_for_the_sidebar() {
+        let report = RetentionReport {
+            archived: vec!["a".to_string()],
+            deleted: vec!["b".to_string()],
+            ..Default::default()
+        };
+        queue_changes(&report);
+        queue_changes(&RetentionReport {
+            archived: vec!["c".to_string()],
+            ..Default::default()
+        });
+        let taken = take_retention_changes().await.unwrap();
+        assert_eq!(taken.archived, ["a", "c"]);
+        assert_eq!(taken.unarchived, ["b"]);
+        assert_eq!(
+            take_retention_changes().await.unwrap(),
+            ArchiveChanges::default()
+        );
+    }
+
+    #[test]
+    fn gzip_roundtrip_preserves_content() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let src = tmp.path().join("a.jsonl");
+        let body = "{\"type\":\"user\"}\n".repeat(200);
+        std::fs::write(&src, &body).unwrap();
+
+        let gz = tmp.path().join("archive").join("a.jsonl.gz");
+        let compressed = compress_jsonl(&src, &gz).unwrap();
+        assert!(compressed < body.len() as u64);
+
+        let back = tmp.path().join("b.jsonl");
+        decompress_jsonl(&gz, &back).unwrap();
+        assert_eq!(std::fs::read_to_string(&back).unwrap(), body);
+        // A restored session starts idle time over instead of inheriting the
+        // archived mtime, so it is not picked up again by the next pass.
+        let idle_days = now_ms().saturating_sub(mtime_ms(&back).unwrap()) / MS_PER_DAY;
+        assert_eq!(idle_days, 0);
+        assert_eq!(
+            decide_action(&rule(Some(7), Some(30), true), idle_days, false, false),
+            RetentionAction::Keep
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Detach session tabs into their own windows and move them back.
+//!
+//! Ownership lives in `events::WindowRouter`; this module only creates and
+//! closes windows and keeps the router in sync. A detach is two-phase so no
+//! stream line is lost while the new webview boots:
+//!
+//!   1. `detach_session_window` opens (or focuses) `session-<id>`; the main
+//!      window keeps receiving the session's events meanwhile.
+//!   2. Once the new window has loaded the session it calls
+//!      `claim_session`, which binds the session to the caller's label and
+//!      broadcasts `window:session-moved` so the old owner drops the tab.
+//!      The detached window also claims the stdinId of every CLI process it
+//!      starts, so that process's stream is routed to it.
+//!
+//! `attach_session_to_main` is the reverse; closing a detached window hands
+//! its sessions back to main via `handle_window_destroyed`.
+//!
+//! Moves do not carry a running CLI process across windows: the window
+//! giving up a session stops its process first, and the new owner resumes
+//! the session from its transcript on the next message. The frontend
+//! therefore only offers detaching for sessions that are not running.
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State, WebviewWindow};
+
+use crate::events::{emit_to_frontend, WindowRouter, MAIN_WINDOW_LABEL};
+
+const SESSION_WINDOW_PREFIX: &str = "session-";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SessionWindowBinding {
+    pub session_id: String,
+    /// Window that shows the session tab.
+    pub owner: String,
+    /// Every window receiving the session's events (owner first).
+    pub subscribers: Vec<String>,
+}
+
+/// Window labels only allow `[A-Za-z0-9-/:_]`.
+fn session_window_label(session_id: &str) -> String {
+    let safe: String = session_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}", SESSION_WINDOW_PREFIX, safe)
+}
+
+fn notify_moved(app: &AppHandle, session_id: &str, label: &str) {
+    let _ = emit_to_frontend(
+        app,
+        "window:session-moved",
+        serde_json::json!({ "session_id": session_id, "label": label }),
+    );
+}
+
+/// Open a dedicated window for `session_id` (or focus it if already open).
+/// The window loads the app with `?detachedSession=<id>` and calls
+/// `claim_session` once the session is loaded. Returns the label. The
+/// session's CLI process, if any, is stopped by the window giving it up.
+#[tauri::command]
+pub async fn detach_session_window(
+    app: AppHandle,
+    session_id: String,
+    title: Option<String>,
+) -> Result<String, String> {
+    if session_id.is_empty() {
+        return Err("Session id is empty".to_string());
+    }
+    let label = session_window_label(&session_id);
+    if let Some(existing) = app.get_webview_window(&label) {
+        existing
+            .set_focus()
+            .map_err(|e| format!("Failed to focus window: {}", e))?;
+        return Ok(label);
+    }
+
+    let query: String = session_id
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect();
+    let url = tauri::WebviewUrl::App(format!("index.html?detachedSession={}", query).into());
+    tauri::WebviewWindowBuilder::new(&app, &label, url)
+        .title(title.unwrap_or_else(|| "TOKENICODE".to_string()))
+        .inner_size(1000.0, 760.0)
+        .min_inner_size(600.0, 480.0)
+        .build()
+        .map_err(|e| format!("Failed to create window: {}", e))?;
+
+    eprintln!(
+        "[TOKENICODE] detach_session_window: {} → {}",
+        session_id, label
+    );
+    Ok(label)
+}
+
+/// Bind `session_id` to the calling window. Called by a detached window once
+/// it is ready, or by main to take a session back without closing anything.
+#[tauri::command]
+pub async fn claim_session(
+    app: AppHandle,
+    window: WebviewWindow,
+    router: State<'_, WindowRouter>,
+    session_id: String,
+) -> Result<(), String> {
+    let label = window.label().to_string();
+    router.bind(&session_id, &label);
+    notify_moved(&app, &session_id, &label);
+    Ok(())
+}
+
+/// Move `session_id` back to the main window and close its detached window
+/// if that window no longer owns any session. The caller stops the
+/// session's CLI process first; main resumes it from the transcript.
+#[tauri::command]
+pub async fn attach_session_to_main(
+    app: AppHandle,
+    router: State<'_, WindowRouter>,
+    session_id: String,
+) -> Result<(), String> {
+    let previous = router.owner_of(&session_id);
+    router.bind(&session_id, MAIN_WINDOW_LABEL);
+    notify_moved(&app, &session_id, MAIN_WINDOW_LABEL);
+
+    if previous != MAIN_WINDOW_LABEL && router.sessions_owned_by(&previous).is_empty() {
+        if let Some(win) = app.get_webview_window(&previous) {
+            let _ = win.close();
+        }
+    }
+    if let Some(main) = app.get_webview_window(MAIN_WINDOW_LABEL) {
+        let _ = main.set_focus();
+    }
+    Ok(())
+}
+
+/// Also deliver `session_id`'s events to the calling window without taking
+/// ownership (e.g. a read-only mirror on a second monitor).
+#[tauri::command]
+pub async fn subscribe_session_events(
+    window: WebviewWindow,
+    router: State<'_, WindowRouter>,
+    session_id: String,
+) -> Result<(), String> {
+    router.subscribe(&session_id, window.label());
+    Ok(())
+}
+
+/// Stop delivering `session_id`'s events to the calling window.
+#[tauri::command]
+pub async fn unsubscribe_session_events(
+    window: WebviewWindow,
+    router: State<'_, WindowRouter>,
+    session_id: String,
+) -> Result<(), String> {
+    router.unsubscribe(&session_id, window.label());
+    Ok(())
+}
+
+/// Every explicit session→window binding. Sessions not listed are owned by main.
+#[tauri::command]
+pub async fn list_session_windows(
+    router: State<'_, WindowRouter>,
+) -> Result<Vec<SessionWindowBinding>, String> {
+    Ok(router
+        .snapshot()
+        .into_iter()
+        .map(|(session_id, subscribers)| SessionWindowBinding {
+            owner: subscribers
+                .first()
+                .cloned()
+                .unwrap_or_else(|| MAIN_WINDOW_LABEL.to_string()),
+            session_id,
+            subscribers,
+        })
+        .collect())
+}
+
+/// Window-destroyed hook: hand the window's sessions back to main so their
+/// streams keep flowing somewhere visible.
+pub fn handle_window_destroyed(app: &AppHandle, label: &str) {
+    if label == MAIN_WINDOW_LABEL {
+        return;
+    }
+    let Some(router) = app.try_state::<WindowRouter>() else {
+        return;
+    };
+    for session_id in router.release_window(label) {
+        eprintln!(
+            "[TOKENICODE] window {} closed — session {} returns to main",
+            label, session_id
+        );
+        notify_moved(app, &session_id, MAIN_WINDOW_LABEL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_labels_are_sanitized() {
+        assert_eq!(session_window_label("desk_abc-1"), "session-desk_abc-1");
+        assert_eq!(session_window_label("a.b/c d"), "session-a_b_c_d");
+    }
+}
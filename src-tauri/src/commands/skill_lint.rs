@@ -0,0 +1,515 @@
+//! Diagnostics for SKILL.md files.
+//!
+//! `parse_skill_frontmatter` deliberately never fails — a broken skill still
+//! shows up in the list — but that means a YAML typo silently drops the
+//! description and tool restrictions. The linter surfaces what went wrong:
+//!
+//!   - YAML syntax and schema errors, with the line number in SKILL.md
+//!   - unknown frontmatter keys (usually misspellings)
+//!   - `allowed-tools` entries that are not Claude Code tools
+//!   - a missing `description` (the model cannot decide when to use the skill)
+//!   - the same skill name in both global and project scope
+//!   - relative links / `./` paths in the body that point at missing files
+//!
+//! `list_skills` attaches these diagnostics to every `SkillInfo`; `lint_skills`
+//! returns them on their own for a dedicated problems view.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Frontmatter keys Claude Code understands for skills.
+const KNOWN_KEYS: &[&str] = &[
+    "name",
+    "description",
+    "version",
+    "license",
+    "metadata",
+    "allowed-tools",
+    "disable-model-invocation",
+    "user-invocable",
+    "argument-hint",
+    "model",
+    "context",
+    "agent",
+];
+
+/// Built-in tool names accepted in `allowed-tools`. MCP tools (`mcp__*`) are
+/// accepted without checking since servers vary per machine.
+const KNOWN_TOOLS: &[&str] = &[
+    "AskUserQuestion",
+    "Bash",
+    "BashOutput",
+    "Edit",
+    "ExitPlanMode",
+    "Glob",
+    "Grep",
+    "KillShell",
+    "LS",
+    "MultiEdit",
+    "NotebookEdit",
+    "NotebookRead",
+    "Read",
+    "Skill",
+    "SlashCommand",
+    "Task",
+    "TodoWrite",
+    "WebFetch",
+    "WebSearch",
+    "Write",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SkillDiagnostic {
+    /// "error" | "warning"
+    pub severity: String,
+    /// Stable rule id: yaml-syntax, yaml-schema, unknown-key, unknown-tool,
+    /// missing-description, duplicate-name, missing-file. Agent files add
+    /// missing-name, invalid-name, unknown-model and empty-prompt.
+    pub code: String,
+    pub message: String,
+    /// 1-based line in SKILL.md, when the problem has a location.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+impl SkillDiagnostic {
+    pub(crate) fn new(severity: &str, code: &str, message: String, line: Option<usize>) -> Self {
+        Self {
+            severity: severity.to_string(),
+            code: code.to_string(),
+            message,
+            line,
+        }
+    }
+}
+
+/// Lint report for one skill, as returned by `lint_skills`.
+#[derive(Debug, Serialize, Clone)]
+pub struct SkillLintReport {
+    pub name: String,
+    pub scope: String,
+    pub path: String,
+    pub diagnostics: Vec<SkillDiagnostic>,
+}
+
+/// Locate the frontmatter block. Returns the YAML text, the 1-based SKILL.md
+/// line its first line sits on, and the body with its starting line.
+pub(crate) fn split_with_lines(content: &str) -> Option<(&str, usize, &str, usize)> {
+    let leading = content.len() - content.trim_start().len();
+    let leading_lines = content[..leading].matches('\n').count();
+    let trimmed = &content[leading..];
+    let after_open = trimmed.strip_prefix("---")?;
+    let close = after_open.find("\n---")?;
+    let yaml = &after_open[..close];
+    // `---` sits on line leading_lines + 1; YAML starts on the next line
+    // (the text right after `---` up to the newline is part of that line).
+    let yaml_first_line = leading_lines + 1;
+    let rest = &after_open[close + 4..];
+    let body_first_line = yaml_first_line + yaml.matches('\n').count() + 1;
+    let (body, body_first_line) = match rest.find('\n') {
+        Some(nl) => (&rest[nl + 1..], body_first_line + 1),
+        None => ("", body_first_line),
+    };
+    Some((yaml, yaml_first_line, body, body_first_line))
+}
+
+/// 1-based SKILL.md line of the top-level `key:` in the frontmatter.
+fn key_line(yaml: &str, yaml_first_line: usize, key: &str) -> Option<usize> {
+    yaml.lines()
+        .position(|l| {
+            l.strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with(':'))
+        })
+        .map(|idx| yaml_first_line + idx)
+}
+
+/// Tool name without its permission pattern: `Bash(git diff:*)` → `Bash`.
+fn base_tool_name(entry: &str) -> &str {
+    entry.split('(').next().unwrap_or(entry).trim()
+}
+
+fn allowed_tool_entries(value: &serde_yaml::Value) -> Vec<String> {
+    match value {
+        serde_yaml::Value::String(s) => s
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect(),
+        serde_yaml::Value::Sequence(seq) => seq
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.trim().to_string()))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// A frontmatter mapping and where it sits in the file, for the checks
+/// skills and agents share.
+pub(crate) struct LintFrontmatter<'a> {
+    yaml: &'a str,
+    first_line: usize,
+    map: serde_yaml::Mapping,
+}
+
+impl<'a> LintFrontmatter<'a> {
+    /// Parse `yaml` as a mapping. Otherwise pushes `yaml-syntax` or
+    /// `yaml-schema`; `effect` says what the error costs the file.
+    pub(crate) fn parse(
+        yaml: &'a str,
+        first_line: usize,
+        effect: &str,
+        diags: &mut Vec<SkillDiagnostic>,
+    ) -> Option<Self> {
+        let map = match serde_yaml::from_str::<serde_yaml::Value>(yaml) {
+            Ok(serde_yaml::Value::Mapping(m)) => m,
+            Ok(serde_yaml::Value::Null) => serde_yaml::Mapping::new(),
+            Ok(_) => {
+                diags.push(SkillDiagnostic::new(
+                    "error",
+                    "yaml-schema",
+                    "Frontmatter must be a YAML mapping of `key: value` pairs".to_string(),
+                    Some(first_line),
+                ));
+                return None;
+            }
+            Err(e) => {
+                let line = e.location().map(|l| first_line + l.line() - 1);
+                diags.push(SkillDiagnostic::new(
+                    "error",
+                    "yaml-syntax",
+                    format!("Frontmatter YAML error — {}: {}", effect, e),
+                    line,
+                ));
+                return None;
+            }
+        };
+        Some(Self {
+            yaml,
+            first_line,
+            map,
+        })
+    }
+
+    /// `yaml-schema` when the typed parse the scanner uses fails, which
+    /// makes the whole frontmatter fall back to defaults.
+    pub(crate) fn check_schema<T: serde::de::DeserializeOwned>(
+        &self,
+        effect: &str,
+        diags: &mut Vec<SkillDiagnostic>,
+    ) {
+        if let Err(e) = serde_yaml::from_str::<T>(self.yaml) {
+            let line = e.location().map(|l| self.first_line + l.line() - 1);
+            diags.push(SkillDiagnostic::new(
+                "error",
+                "yaml-schema",
+                format!("Frontmatter has a wrong value type — {}: {}", effect, e),
+                line,
+            ));
+        }
+    }
+
+    pub(crate) fn first_line(&self) -> usize {
+        self.first_line
+    }
+
+    /// 1-based line of the top-level `key:`.
+    pub(crate) fn line_of(&self, key: &str) -> Option<usize> {
+        key_line(self.yaml, self.first_line, key)
+    }
+
+    /// Trimmed string value of `key`; `None` when missing or blank.
+    pub(crate) fn get_str(&self, key: &str) -> Option<&str> {
+        self.map
+            .get(serde_yaml::Value::String(key.into()))
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    }
+
+    /// `unknown-key` for keys outside `known`; `hint` may add a suggestion.
+    pub(crate) fn check_keys(
+        &self,
+        known: &[&str],
+        hint: impl Fn(&str) -> Option<String>,
+        diags: &mut Vec<SkillDiagnostic>,
+    ) {
+        for k in self.map.keys().filter_map(|k| k.as_str()) {
+            if !known.contains(&k) {
+                diags.push(SkillDiagnostic::new(
+                    "warning",
+                    "unknown-key",
+                    format!(
+                        "Unknown frontmatter key `{}`{}",
+                        k,
+                        hint(k).map(|h| format!(" ({})", h)).unwrap_or_default()
+                    ),
+                    self.line_of(k),
+                ));
+            }
+        }
+    }
+
+    /// `unknown-tool` for entries of the tool list under `key` that are not
+    /// Claude Code tools (MCP tools are not checked).
+    pub(crate) fn check_tools(&self, key: &str, diags: &mut Vec<SkillDiagnostic>) {
+        let Some(tools) = self.map.get(serde_yaml::Value::String(key.into())) else {
+            return;
+        };
+        let line = self.line_of(key);
+        for entry in allowed_tool_entries(tools) {
+            let base = base_tool_name(&entry);
+            if base.starts_with("mcp__") || KNOWN_TOOLS.contains(&base) {
+                continue;
+            }
+            let hint = KNOWN_TOOLS
+                .iter()
+                .find(|t| t.eq_ignore_ascii_case(base))
+                .map(|t| format!(" (did you mean `{}`?)", t))
+                .unwrap_or_default();
+            diags.push(SkillDiagnostic::new(
+                "warning",
+                "unknown-tool",
+                format!("`{}` entry `{}` is not a known tool{}", key, entry, hint),
+                line,
+            ));
+        }
+    }
+}
+
+/// Relative file references in the body: markdown link targets and code
+/// spans starting with `./`. URLs, anchors and absolute paths are ignored.
+fn body_references(body: &str, body_first_line: usize) -> Vec<(String, usize)> {
+    let mut refs = Vec::new();
+    let mut in_fence = false;
+    for (idx, line) in body.lines().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let line_no = body_first_line + idx;
+        let mut rest = line;
+        while let Some(pos) = rest.find("](") {
+            let after = &rest[pos + 2..];
+            let Some(end) = after.find(')') else {
+                break;
+            };
+            let target = after[..end].split_whitespace().next().unwrap_or("");
+            let target = target.split('#').next().unwrap_or("");
+            refs.push((target.to_string(), line_no));
+            rest = &after[end..];
+        }
+        for (i, span) in line.split('`').enumerate() {
+            if i % 2 == 1 && span.starts_with("./") && !span.contains(char::is_whitespace) {
+                refs.push((span.to_string(), line_no));
+            }
+        }
+    }
+    refs.retain(|(t, _)| {
+        !t.is_empty()
+            && !t.contains("://")
+            && !t.starts_with("mailto:")
+            && !t.starts_with('/')
+            && !t.starts_with('~')
+            && !t.contains(['$', '{', '<', '*'])
+    });
+    refs
+}
+
+/// Lint one SKILL.md. `skill_dir` resolves relative file references.
+pub(crate) fn lint_skill_content(content: &str, skill_dir: &Path) -> Vec<SkillDiagnostic> {
+    const EFFECT: &str = "description and tool restrictions are ignored";
+    let mut diags = Vec::new();
+
+    let Some((yaml, yaml_first_line, body, body_first_line)) = split_with_lines(content) else {
+        diags.push(SkillDiagnostic::new(
+            "warning",
+            "missing-description",
+            "No YAML frontmatter — add a `description` so the model knows when to use this skill"
+                .to_string(),
+            Some(1),
+        ));
+        diags.extend(missing_file_diagnostics(content, 1, skill_dir));
+        return diags;
+    };
+
+    let Some(fm) = LintFrontmatter::parse(yaml, yaml_first_line, EFFECT, &mut diags) else {
+        diags.extend(missing_file_diagnostics(body, body_first_line, skill_dir));
+        return diags;
+    };
+    fm.check_schema::<crate::SkillFrontmatter>(EFFECT, &mut diags);
+    fm.check_keys(
+        KNOWN_KEYS,
+        |k| {
+            KNOWN_KEYS
+                .iter()
+                .find(|known| {
+                    known.replace('-', "_") == k
+                        || known.replace('-', "") == k.replace(['-', '_'], "")
+                })
+                .map(|known| format!("did you mean `{}`?", known))
+        },
+        &mut diags,
+    );
+    fm.check_tools("allowed-tools", &mut diags);
+
+    if fm.get_str("description").is_none() {
+        diags.push(SkillDiagnostic::new(
+            "warning",
+            "missing-description",
+            "Missing `description` — the model uses it to decide when to invoke the skill"
+                .to_string(),
+            Some(fm.first_line()),
+        ));
+    }
+
+    diags.extend(missing_file_diagnostics(body, body_first_line, skill_dir));
+    diags
+}
+
+fn missing_file_diagnostics(
+    body: &str,
+    body_first_line: usize,
+    skill_dir: &Path,
+) -> Vec<SkillDiagnostic> {
+    body_references(body, body_first_line)
+        .into_iter()
+        .filter(|(target, _)| !skill_dir.join(target).exists())
+        .map(|(target, line)| {
+            SkillDiagnostic::new(
+                "warning",
+                "missing-file",
+                format!("Referenced file `{}` does not exist", target),
+                Some(line),
+            )
+        })
+        .collect()
+}
+
+/// Add `duplicate-name` diagnostics for names present in more than one
+/// scope. `skills` yields (name, scope, diagnostics) for every item; `kind`
+/// ("Skill", "Agent") starts the message.
+pub(crate) fn add_duplicate_diagnostics<'a>(
+    kind: &str,
+    skills: impl Iterator<Item = (&'a str, &'a str, &'a mut Vec<SkillDiagnostic>)>,
+) {
+    let mut by_name: HashMap<&str, Vec<(&str, &mut Vec<SkillDiagnostic>)>> = HashMap::new();
+    for (name, scope, diags) in skills {
+        by_name.entry(name).or_default().push((scope, diags));
+    }
+    for (name, entries) in by_name {
+        if entries.len() < 2 {
+            continue;
+        }
+        let scopes: Vec<&str> = entries.iter().map(|(s, _)| *s).collect();
+        for (scope, diags) in entries {
+            let note = if scope == "project" {
+                "this project copy takes precedence"
+            } else {
+                "a project copy overrides this one"
+            };
+            diags.push(SkillDiagnostic::new(
+                "warning",
+                "duplicate-name",
+                format!(
+                    "{} `{}` exists in several scopes ({}); {}",
+                    kind,
+                    name,
+                    scopes.join(", "),
+                    note
+                ),
+                None,
+            ));
+        }
+    }
+}
+
+/// Lint every global and project skill.
+#[tauri::command]
+pub async fn lint_skills(cwd: Option<String>) -> Result<Vec<SkillLintReport>, String> {
+    Ok(crate::list_skills(cwd)
+        .await?
+        .into_iter()
+        .map(|s| SkillLintReport {
+            name: s.name,
+            scope: s.scope,
+            path: s.path,
+            diagnostics: s.diagnostics,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn codes(diags: &[SkillDiagnostic]) -> Vec<(&str, Option<usize>)> {
+        diags.iter().map(|d| (d.code.as_str(), d.line)).collect()
+    }
+
+    #[test]
+    fn clean_skill_has_no_diagnostics() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::write(tmp.path().join("guide.md"), "x").unwrap();
+        let content = "---\ndescription: Does things\nallowed-tools: Read, Bash(git diff:*), mcp__gh__pr\n---\nSee [guide](guide.md#top) and `./guide.md`.\n";
+        assert!(lint_skill_content(content, tmp.path()).is_empty());
+    }
+
+    #[test]
+    fn yaml_errors_point_at_skill_md_lines() {
+        let tmp = TempDir::new().unwrap();
+        let content = "\n---\ndescription: ok\nallowed-tools: [Read\n---\nbody\n";
+        let diags = lint_skill_content(content, tmp.path());
+        assert_eq!(diags[0].code, "yaml-syntax");
+        assert!(diags[0].line.unwrap() >= 4);
+
+        let schema = "---\ndescription: ok\nuser-invocable: maybe\n---\n";
+        let diags = lint_skill_content(schema, tmp.path());
+        assert_eq!(codes(&diags), vec![("yaml-schema", Some(3))]);
+    }
+
+    #[test]
+    fn unknown_keys_tools_and_missing_bits() {
+        let tmp = TempDir::new().unwrap();
+        let content = "---\nallowed_tools: [Read]\nallowed-tools: [read, Fetch]\n---\n# T\n\n[spec](docs/spec.md) [site](https://x.dev)\n```\n[not](checked.md)\n```\n";
+        let diags = lint_skill_content(content, tmp.path());
+        assert_eq!(
+            codes(&diags),
+            vec![
+                ("unknown-key", Some(2)),
+                ("unknown-tool", Some(3)),
+                ("unknown-tool", Some(3)),
+                ("missing-description", Some(1)),
+                ("missing-file", Some(7)),
+            ]
+        );
+        assert!(diags[0].message.contains("allowed-tools"));
+        assert!(diags[1].message.contains("did you mean `Read`"));
+    }
+
+    #[test]
+    fn duplicates_flag_both_scopes() {
+        let mut a = vec![];
+        let mut b = vec![];
+        let mut c = vec![];
+        add_duplicate_diagnostics(
+            "Skill",
+            vec![
+                ("pdf", "global", &mut a),
+                ("pdf", "project", &mut b),
+                ("other", "project", &mut c),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(a.len(), 1);
+        assert!(b[0]
+            .message
+            .starts_with("Skill `pdf` exists in several scopes"));
+        assert!(b[0].message.contains("takes precedence"));
+        assert!(c.is_empty());
+    }
+}
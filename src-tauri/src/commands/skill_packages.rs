@@ -0,0 +1,985 @@
+//! Skill package manager: install skill directories from archives or local
+//! git checkouts, and export them back to archives for sharing.
+//!
+//! A skill package is a directory containing `SKILL.md` plus any files it
+//! references. Sources:
+//!   - `.zip`, `.tar.gz` / `.tgz` — extracted into a private staging dir
+//!     first; entries escaping the archive root, symlinks and oversized
+//!     archives are rejected.
+//!   - a local directory (typically a git checkout) — copied without `.git`;
+//!     the checked-out commit is reported when one can be read from `.git`.
+//!
+//! The package root is the directory holding `SKILL.md`: the source root, its
+//! single top-level directory (the usual archive layout), or `subdir` when a
+//! repository ships several skills.
+//!
+//! Before anything is written the frontmatter is checked (`version`,
+//! `allowed-tools`, `model`) and conflicts with existing skills are reported.
+//! An existing skill of the same name in the target scope blocks the install
+//! unless `overwrite` is set; the report is returned either way so the UI can
+//! ask the user.
+
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tauri::State;
+
+use crate::path_access::{PathAccessManager, PathCapability};
+
+/// Upper bounds for a single package — skills are prompt files, not datasets.
+const MAX_PACKAGE_FILES: usize = 2_000;
+const MAX_PACKAGE_BYTES: u64 = 50 * 1024 * 1024;
+/// Entries never copied into or out of a package.
+const SKIPPED_ENTRIES: &[&str] = &[".git", ".DS_Store", "__MACOSX", "Thumbs.db"];
+pub(crate) const MODEL_ALIASES: &[&str] = &["inherit", "sonnet", "opus", "haiku", "opusplan"];
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PackageIssue {
+    /// "error" blocks the install; "warning" is informational.
+    pub severity: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SkillConflict {
+    /// "exists" — same name in the target scope (blocks without `overwrite`);
+    /// "shadows" — project skill hides a global one of the same name;
+    /// "shadowed" — global skill hidden by a project one.
+    pub kind: String,
+    pub scope: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub existing_version: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SkillInstallReport {
+    pub name: String,
+    pub scope: String,
+    /// Directory the skill was (or would be) installed into.
+    pub target_dir: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Commit of the source checkout, when installing from a git directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_revision: Option<String>,
+    pub files: usize,
+    pub bytes: u64,
+    pub conflicts: Vec<SkillConflict>,
+    pub issues: Vec<PackageIssue>,
+    pub installed: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SkillExportReport {
+    pub path: String,
+    pub files: usize,
+    pub bytes: u64,
+    pub issues: Vec<PackageIssue>,
+}
+
+/// Temp directory removed on drop.
+struct StagingDir(PathBuf);
+
+impl StagingDir {
+    fn new() -> Result<Self, String> {
+        let dir = std::env::temp_dir()
+            .join("tokenicode")
+            .join(format!("skill-stage-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create staging dir: {}", e))?;
+        Ok(Self(dir))
+    }
+}
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ArchiveKind {
+    Zip,
+    TarGz,
+}
+
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else {
+        None
+    }
+}
+
+/// Turn an archive entry path into a relative path that stays inside the
+/// extraction root, or `None` for absolute / `..` entries.
+fn safe_relative(path: &Path) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::Normal(c) => out.push(c),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!out.as_os_str().is_empty()).then_some(out)
+}
+
+fn is_skipped(rel: &Path) -> bool {
+    rel.components().any(|c| match c {
+        Component::Normal(n) => SKIPPED_ENTRIES.iter().any(|s| n == *s),
+        _ => false,
+    })
+}
+
+/// Running totals enforcing the package size limits.
+#[derive(Default)]
+struct Budget {
+    files: usize,
+    bytes: u64,
+}
+
+impl Budget {
+    fn charge(&mut self, bytes: u64) -> Result<(), String> {
+        self.files += 1;
+        self.bytes += bytes;
+        if self.files > MAX_PACKAGE_FILES {
+            return Err(format!("Package has more than {} files", MAX_PACKAGE_FILES));
+        }
+        if self.bytes > MAX_PACKAGE_BYTES {
+            return Err(format!(
+                "Package is larger than {} MB",
+                MAX_PACKAGE_BYTES / 1024 / 1024
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn extract_archive(
+    archive: &Path,
+    kind: ArchiveKind,
+    dest: &Path,
+    issues: &mut Vec<PackageIssue>,
+) -> Result<(), String> {
+    let file = std::fs::File::open(archive).map_err(|e| format!("Cannot open archive: {}", e))?;
+    let mut budget = Budget::default();
+    let mut skipped_links = 0usize;
+
+    match kind {
+        ArchiveKind::TarGz => {
+            let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(file));
+            for entry in tar.entries().map_err(|e| format!("tar error: {}", e))? {
+                let mut entry = entry.map_err(|e| format!("tar entry error: {}", e))?;
+                let raw = entry
+                    .path()
+                    .map_err(|e| format!("path error: {}", e))?
+                    .into_owned();
+                let Some(rel) = safe_relative(&raw) else {
+                    return Err(format!(
+                        "Archive entry escapes the package: {}",
+                        raw.display()
+                    ));
+                };
+                if is_skipped(&rel) {
+                    continue;
+                }
+                let kind = entry.header().entry_type();
+                if kind.is_dir() {
+                    std::fs::create_dir_all(dest.join(&rel))
+                        .map_err(|e| format!("Failed to create dir: {}", e))?;
+                } else if kind.is_file() {
+                    budget.charge(entry.size())?;
+                    let target = dest.join(&rel);
+                    if let Some(parent) = target.parent() {
+                        std::fs::create_dir_all(parent)
+                            .map_err(|e| format!("Failed to create dir: {}", e))?;
+                    }
+                    let mut out = std::fs::File::create(&target)
+                        .map_err(|e| format!("create file error: {}", e))?;
+                    std::io::copy(&mut (&mut entry).take(MAX_PACKAGE_BYTES), &mut out)
+                        .map_err(|e| format!("write error: {}", e))?;
+                } else {
+                    skipped_links += 1;
+                }
+            }
+        }
+        ArchiveKind::Zip => {
+            let mut zip =
+                zip::ZipArchive::new(file).map_err(|e| format!("zip open error: {}", e))?;
+            for i in 0..zip.len() {
+                let mut entry = zip
+                    .by_index(i)
+                    .map_err(|e| format!("zip entry error: {}", e))?;
+                let Some(rel) = entry.enclosed_name().and_then(|p| safe_relative(&p)) else {
+                    return Err(format!(
+                        "Archive entry escapes the package: {}",
+                        entry.name()
+                    ));
+                };
+                if is_skipped(&rel) {
+                    continue;
+                }
+                if entry.is_symlink() {
+                    skipped_links += 1;
+                    continue;
+                }
+                let target = dest.join(&rel);
+                if entry.is_dir() {
+                    std::fs::create_dir_all(&target)
+                        .map_err(|e| format!("Failed to create dir: {}", e))?;
+                    continue;
+                }
+                budget.charge(entry.size())?;
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create dir: {}", e))?;
+                }
+                let mut out = std::fs::File::create(&target)
+                    .map_err(|e| format!("create file error: {}", e))?;
+                std::io::copy(&mut (&mut entry).take(MAX_PACKAGE_BYTES), &mut out)
+                    .map_err(|e| format!("write error: {}", e))?;
+            }
+        }
+    }
+
+    if skipped_links > 0 {
+        issues.push(PackageIssue {
+            severity: "warning".into(),
+            message: format!("{} symlink/special entries were skipped", skipped_links),
+        });
+    }
+    Ok(())
+}
+
+/// Find the directory holding SKILL.md inside an extracted/checked-out source.
+fn locate_package_root(source: &Path, subdir: Option<&str>) -> Result<PathBuf, String> {
+    if let Some(sub) = subdir.filter(|s| !s.trim().is_empty()) {
+        let rel = safe_relative(Path::new(sub))
+            .ok_or_else(|| format!("Invalid package subdirectory: {}", sub))?;
+        let root = source.join(rel);
+        if !root.join("SKILL.md").is_file() {
+            return Err(format!("No SKILL.md in package subdirectory '{}'", sub));
+        }
+        return Ok(root);
+    }
+    if source.join("SKILL.md").is_file() {
+        return Ok(source.to_path_buf());
+    }
+
+    // Archives usually wrap everything in one top-level directory; repos may
+    // keep skills under skills/<name>/. Search two levels deep.
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    let children = |dir: &Path| -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .map(|rd| {
+                rd.flatten()
+                    .map(|e| e.path())
+                    .filter(|p| {
+                        p.is_dir() && !is_skipped(Path::new(p.file_name().unwrap_or_default()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    for child in children(source) {
+        if child.join("SKILL.md").is_file() {
+            candidates.push(child.clone());
+            continue;
+        }
+        for grandchild in children(&child) {
+            if grandchild.join("SKILL.md").is_file() {
+                candidates.push(grandchild);
+            }
+        }
+    }
+    match candidates.len() {
+        0 => Err("No SKILL.md found in package".to_string()),
+        1 => Ok(candidates.remove(0)),
+        _ => {
+            candidates.sort();
+            let names: Vec<String> = candidates
+                .iter()
+                .filter_map(|c| c.strip_prefix(source).ok())
+                .map(|c| c.to_string_lossy().replace('\\', "/"))
+                .collect();
+            Err(format!(
+                "Package contains several skills; pick one with subdir: {}",
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+/// Commit checked out in a git working tree, read from `.git` directly so
+/// no git process (or macOS CLT popup) is involved.
+fn read_git_head(dir: &Path) -> Option<String> {
+    let git_dir = dir.join(".git");
+    let git_dir = if git_dir.is_file() {
+        // Worktree / submodule: ".git" is a file pointing at the real dir.
+        let content = std::fs::read_to_string(&git_dir).ok()?;
+        let target = content.trim().strip_prefix("gitdir:")?.trim();
+        let p = Path::new(target);
+        if p.is_absolute() {
+            p.to_path_buf()
+        } else {
+            dir.join(p)
+        }
+    } else {
+        git_dir
+    };
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let Some(reference) = head.strip_prefix("ref:").map(str::trim) else {
+        return Some(head.to_string());
+    };
+    if let Ok(sha) = std::fs::read_to_string(git_dir.join(reference)) {
+        return Some(sha.trim().to_string());
+    }
+    let packed = std::fs::read_to_string(git_dir.join("packed-refs")).ok()?;
+    packed.lines().find_map(|line| {
+        let (sha, name) = line.split_once(' ')?;
+        (name == reference).then(|| sha.to_string())
+    })
+}
+
+fn split_frontmatter(content: &str) -> Option<&str> {
+    let trimmed = content.trim_start_matches('\u{feff}').trim_start();
+    let after_open = trimmed.strip_prefix("---")?;
+    let close = after_open.find("\n---")?;
+    Some(&after_open[..close])
+}
+
+fn looks_like_version(v: &str) -> bool {
+    let core = v.trim_start_matches('v');
+    let core = core.split(['-', '+']).next().unwrap_or("");
+    let parts: Vec<&str> = core.split('.').collect();
+    (1..=3).contains(&parts.len())
+        && parts
+            .iter()
+            .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Check the fields the package manager relies on. Returns the declared
+/// name and version plus any issues found.
+fn check_package_frontmatter(content: &str) -> (Option<String>, Option<String>, Vec<PackageIssue>) {
+    let mut issues = Vec::new();
+    let issue = |severity: &str, message: String| PackageIssue {
+        severity: severity.to_string(),
+        message,
+    };
+
+    let Some(yaml) = split_frontmatter(content) else {
+        issues.push(issue(
+            "warning",
+            "SKILL.md has no YAML frontmatter — description and version will be missing".into(),
+        ));
+        return (None, None, issues);
+    };
+    let map = match serde_yaml::from_str::<serde_yaml::Value>(yaml) {
+        Ok(serde_yaml::Value::Mapping(m)) => m,
+        Ok(serde_yaml::Value::Null) => serde_yaml::Mapping::new(),
+        Ok(_) => {
+            issues.push(issue("error", "Frontmatter is not a YAML mapping".into()));
+            return (None, None, issues);
+        }
+        Err(e) => {
+            issues.push(issue("error", format!("Frontmatter YAML error: {}", e)));
+            return (None, None, issues);
+        }
+    };
+    let get = |key: &str| map.get(serde_yaml::Value::String(key.to_string()));
+
+    let name = get("name").and_then(|v| v.as_str()).map(String::from);
+
+    let version = match get("version") {
+        None => {
+            issues.push(issue(
+                "warning",
+                "No `version` field — updates cannot be compared".into(),
+            ));
+            None
+        }
+        Some(v) => {
+            // Unquoted `1.0` parses as a float; accept it as text.
+            let text = match v {
+                serde_yaml::Value::String(s) => Some(s.clone()),
+                serde_yaml::Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            };
+            match text {
+                Some(t) if looks_like_version(&t) => Some(t),
+                Some(t) => {
+                    issues.push(issue(
+                        "warning",
+                        format!("`version` '{}' is not a semantic version", t),
+                    ));
+                    Some(t)
+                }
+                None => {
+                    issues.push(issue("error", "`version` must be a string".into()));
+                    None
+                }
+            }
+        }
+    };
+
+    if let Some(tools) = get("allowed-tools") {
+        let ok = match tools {
+            serde_yaml::Value::String(_) => true,
+            serde_yaml::Value::Sequence(seq) => seq.iter().all(|t| t.is_string()),
+            _ => false,
+        };
+        if !ok {
+            issues.push(issue(
+                "error",
+                "`allowed-tools` must be a list of tool names or a comma-separated string".into(),
+            ));
+        }
+    }
+
+    if let Some(model) = get("model") {
+        match model.as_str() {
+            Some(m) if MODEL_ALIASES.contains(&m) || m.starts_with("claude-") => {}
+            Some(m) => issues.push(issue(
+                "warning",
+                format!(
+                    "`model` '{}' is not a known alias ({}) or claude-* model id",
+                    m,
+                    MODEL_ALIASES.join(", ")
+                ),
+            )),
+            None => issues.push(issue("error", "`model` must be a string".into())),
+        }
+    }
+
+    (name, version, issues)
+}
+
+fn valid_skill_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+fn scope_skills_dir(scope: &str, cwd: Option<&str>) -> Result<PathBuf, String> {
+    match scope {
+        "global" => {
+            let home = dirs::home_dir().ok_or("Cannot find home dir")?;
+            Ok(home.join(".claude").join("skills"))
+        }
+        "project" => {
+            let cwd = cwd
+                .filter(|c| !c.is_empty())
+                .ok_or("Project scope requires a working directory")?;
+            Ok(Path::new(cwd).join(".claude").join("skills"))
+        }
+        other => Err(format!("Unknown skill scope: {}", other)),
+    }
+}
+
+fn installed_version(skill_dir: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(skill_dir.join("SKILL.md")).ok()?;
+    check_package_frontmatter(&content).1
+}
+
+fn find_conflicts(name: &str, scope: &str, cwd: Option<&str>) -> Vec<SkillConflict> {
+    let mut conflicts = Vec::new();
+    for other in ["global", "project"] {
+        let Ok(dir) = scope_skills_dir(other, cwd) else {
+            continue;
+        };
+        let existing = dir.join(name);
+        if !existing.join("SKILL.md").is_file() {
+            continue;
+        }
+        let kind = match (scope, other) {
+            (a, b) if a == b => "exists",
+            ("project", "global") => "shadows",
+            _ => "shadowed",
+        };
+        conflicts.push(SkillConflict {
+            kind: kind.to_string(),
+            scope: other.to_string(),
+            path: existing.to_string_lossy().to_string(),
+            existing_version: installed_version(&existing),
+        });
+    }
+    conflicts
+}
+
+/// Recursively copy `src` into `dest`, skipping VCS/OS clutter and symlinks.
+fn copy_package_dir(
+    src: &Path,
+    dest: &Path,
+    budget: &mut Budget,
+    issues: &mut Vec<PackageIssue>,
+) -> Result<(), String> {
+    std::fs::create_dir_all(dest).map_err(|e| format!("Failed to create dir: {}", e))?;
+    let entries = std::fs::read_dir(src).map_err(|e| format!("Cannot read directory: {}", e))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if is_skipped(Path::new(&name)) {
+            continue;
+        }
+        let ft = entry
+            .file_type()
+            .map_err(|e| format!("Cannot stat {}: {}", entry.path().display(), e))?;
+        let target = dest.join(&name);
+        if ft.is_symlink() {
+            issues.push(PackageIssue {
+                severity: "warning".into(),
+                message: format!("Symlink skipped: {}", entry.path().display()),
+            });
+        } else if ft.is_dir() {
+            copy_package_dir(&entry.path(), &target, budget, issues)?;
+        } else if ft.is_file() {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            budget.charge(size)?;
+            std::fs::copy(entry.path(), &target)
+                .map_err(|e| format!("Failed to copy {}: {}", entry.path().display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Swap `staged` into `target`, keeping the previous version until the new
+/// one is in place so a failed rename never loses the installed skill.
+fn replace_dir(staged: &Path, target: &Path) -> Result<(), String> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create skills dir: {}", e))?;
+    }
+    let backup = target.with_extension(format!("bak-{}", uuid::Uuid::new_v4().simple()));
+    let had_previous = target.exists();
+    if had_previous {
+        std::fs::rename(target, &backup)
+            .map_err(|e| format!("Failed to move existing skill aside: {}", e))?;
+    }
+    if let Err(e) = std::fs::rename(staged, target) {
+        if had_previous {
+            let _ = std::fs::rename(&backup, target);
+        }
+        return Err(format!("Failed to install skill: {}", e));
+    }
+    if had_previous {
+        let _ = std::fs::remove_dir_all(&backup);
+    }
+    Ok(())
+}
+
+/// Install a skill package into `scope` ("global" | "project").
+///
+/// `source` is a `.zip`, `.tar.gz`/`.tgz` or a directory (e.g. a git
+/// checkout). `name` overrides the directory name the skill is installed
+/// under (default: frontmatter `name`, then the package directory name).
+/// With `dry_run`, only validation and conflict detection run.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn install_skill_package(
+    path_access: State<'_, PathAccessManager>,
+    source: String,
+    scope: String,
+    cwd: Option<String>,
+    name: Option<String>,
+    subdir: Option<String>,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+    tab_id: Option<String>,
+) -> Result<SkillInstallReport, String> {
+    let source_path = path_access
+        .validate(Path::new(&source), tab_id.as_deref(), PathCapability::Read)
+        .await?;
+    let skills_dir = scope_skills_dir(&scope, cwd.as_deref())?;
+    let skills_dir = path_access
+        .validate(&skills_dir, tab_id.as_deref(), PathCapability::Write)
+        .await?;
+
+    let mut issues: Vec<PackageIssue> = Vec::new();
+    let staging = StagingDir::new()?;
+    let (unpacked_root, source_revision) = if source_path.is_dir() {
+        (source_path.clone(), read_git_head(&source_path))
+    } else {
+        let kind = archive_kind(&source_path).ok_or_else(|| {
+            format!(
+                "Unsupported package format (expected .zip, .tar.gz or a directory): {}",
+                source
+            )
+        })?;
+        let extracted = staging.0.join("extracted");
+        extract_archive(&source_path, kind, &extracted, &mut issues)?;
+        (extracted, None)
+    };
+    let package_root = locate_package_root(&unpacked_root, subdir.as_deref())?;
+
+    let skill_md = std::fs::read_to_string(package_root.join("SKILL.md"))
+        .map_err(|e| format!("Cannot read SKILL.md: {}", e))?;
+    let (declared_name, version, fm_issues) = check_package_frontmatter(&skill_md);
+    issues.extend(fm_issues);
+
+    let dir_name = package_root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .filter(|n| package_root != staging.0.join("extracted") && valid_skill_name(n));
+    let name = name
+        .or(declared_name)
+        .or(dir_name)
+        .or_else(|| {
+            // Bare archive with SKILL.md at its root: fall back to the file stem.
+            source_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .map(|n| {
+                    n.trim_end_matches(".zip")
+                        .trim_end_matches(".tar.gz")
+                        .trim_end_matches(".tgz")
+                        .to_string()
+                })
+        })
+        .unwrap_or_default();
+    if !valid_skill_name(&name) {
+        return Err(format!(
+            "Invalid skill name '{}' (letters, digits, '-', '_' and '.' only)",
+            name
+        ));
+    }
+
+    let target = skills_dir.join(&name);
+    let conflicts = find_conflicts(&name, &scope, cwd.as_deref());
+    let blocked_by_existing =
+        conflicts.iter().any(|c| c.kind == "exists") && !overwrite.unwrap_or(false);
+    let has_errors = issues.iter().any(|i| i.severity == "error");
+
+    let mut files = Vec::new();
+    let mut budget = Budget::default();
+    collect_package_files(&package_root, &package_root, &mut files, &mut budget)?;
+
+    let mut report = SkillInstallReport {
+        name,
+        scope,
+        target_dir: target.to_string_lossy().to_string(),
+        version,
+        source_revision,
+        files: budget.files,
+        bytes: budget.bytes,
+        conflicts,
+        issues,
+        installed: false,
+    };
+    if dry_run.unwrap_or(false) || blocked_by_existing || has_errors {
+        return Ok(report);
+    }
+
+    // Stage next to the target so the final rename stays on one filesystem.
+    let sibling = skills_dir.join(format!(
+        ".{}.installing-{}",
+        report.name,
+        uuid::Uuid::new_v4().simple()
+    ));
+    let mut copy_budget = Budget::default();
+    if let Err(e) = copy_package_dir(
+        &package_root,
+        &sibling,
+        &mut copy_budget,
+        &mut report.issues,
+    ) {
+        let _ = std::fs::remove_dir_all(&sibling);
+        return Err(e);
+    }
+    if let Err(e) = replace_dir(&sibling, &target) {
+        let _ = std::fs::remove_dir_all(&sibling);
+        return Err(e);
+    }
+    report.installed = true;
+    eprintln!(
+        "[TOKENICODE] install_skill_package: {} → {} ({} files)",
+        source, report.target_dir, report.files
+    );
+    Ok(report)
+}
+
+fn collect_package_files(
+    root: &Path,
+    dir: &Path,
+    out: &mut Vec<(PathBuf, PathBuf)>,
+    budget: &mut Budget,
+) -> Result<(), String> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Cannot read directory: {}", e))?
+        .flatten()
+        .collect();
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if is_skipped(Path::new(&entry.file_name())) {
+            continue;
+        }
+        let Ok(ft) = entry.file_type() else {
+            continue;
+        };
+        if ft.is_dir() {
+            collect_package_files(root, &path, out, budget)?;
+        } else if ft.is_file() {
+            budget.charge(entry.metadata().map(|m| m.len()).unwrap_or(0))?;
+            let rel = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            out.push((path, rel));
+        }
+    }
+    Ok(())
+}
+
+/// Export a skill directory (or its SKILL.md path) to `dest`. The format
+/// follows the extension (`.zip` or `.tar.gz`/`.tgz`); entries are stored
+/// under a top-level `<skill-name>/` directory so the archive installs back
+/// via `install_skill_package`.
+#[tauri::command]
+pub async fn export_skill_package(
+    path_access: State<'_, PathAccessManager>,
+    skill_path: String,
+    dest: String,
+    tab_id: Option<String>,
+) -> Result<SkillExportReport, String> {
+    let skill = path_access
+        .validate(
+            Path::new(&skill_path),
+            tab_id.as_deref(),
+            PathCapability::Read,
+        )
+        .await?;
+    let skill_dir = if skill.is_file() {
+        skill
+            .parent()
+            .map(Path::to_path_buf)
+            .ok_or("Skill file has no parent directory")?
+    } else {
+        skill
+    };
+    let skill_md = std::fs::read_to_string(skill_dir.join("SKILL.md"))
+        .map_err(|e| format!("Not a skill directory (no SKILL.md): {}", e))?;
+    let (_, _, issues) = check_package_frontmatter(&skill_md);
+
+    let dest_path = path_access
+        .validate(Path::new(&dest), tab_id.as_deref(), PathCapability::Write)
+        .await?;
+    let kind =
+        archive_kind(&dest_path).ok_or("Export destination must end in .zip, .tar.gz or .tgz")?;
+    let top = skill_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or("Skill directory has no name")?;
+
+    let mut files = Vec::new();
+    let mut budget = Budget::default();
+    collect_package_files(&skill_dir, &skill_dir, &mut files, &mut budget)?;
+
+    let tmp = dest_path.with_extension("partial");
+    let result = write_archive(&tmp, kind, &top, &files);
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+    std::fs::rename(&tmp, &dest_path).map_err(|e| format!("Failed to place archive: {}", e))?;
+    let bytes = std::fs::metadata(&dest_path).map(|m| m.len()).unwrap_or(0);
+
+    Ok(SkillExportReport {
+        path: dest_path.to_string_lossy().to_string(),
+        files: files.len(),
+        bytes,
+        issues,
+    })
+}
+
+fn write_archive(
+    dest: &Path,
+    kind: ArchiveKind,
+    top: &str,
+    files: &[(PathBuf, PathBuf)],
+) -> Result<(), String> {
+    let out = std::fs::File::create(dest).map_err(|e| format!("Cannot create archive: {}", e))?;
+    // Archive paths always use '/' regardless of host OS.
+    let entry_name = |rel: &Path| {
+        let parts: Vec<String> = rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        format!("{}/{}", top, parts.join("/"))
+    };
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipWriter::new(out);
+            let options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated);
+            for (abs, rel) in files {
+                zip.start_file(entry_name(rel), options)
+                    .map_err(|e| format!("zip write error: {}", e))?;
+                let mut input =
+                    std::fs::File::open(abs).map_err(|e| format!("Cannot read file: {}", e))?;
+                std::io::copy(&mut input, &mut zip)
+                    .map_err(|e| format!("zip write error: {}", e))?;
+            }
+            zip.finish()
+                .map_err(|e| format!("zip write error: {}", e))?;
+        }
+        ArchiveKind::TarGz => {
+            let encoder = flate2::write::GzEncoder::new(out, flate2::Compression::default());
+            let mut tar = tar::Builder::new(encoder);
+            for (abs, rel) in files {
+                tar.append_path_with_name(abs, entry_name(rel))
+                    .map_err(|e| format!("tar write error: {}", e))?;
+            }
+            let mut encoder = tar
+                .into_inner()
+                .map_err(|e| format!("tar write error: {}", e))?;
+            encoder
+                .flush()
+                .map_err(|e| format!("tar write error: {}", e))?;
+            encoder
+                .finish()
+                .map_err(|e| format!("tar write error: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_skill(dir: &Path, frontmatter: &str) {
+        std::fs::create_dir_all(dir.join("assets")).unwrap();
+        std::fs::write(
+            dir.join("SKILL.md"),
+            format!("---\n{}\n---\n# Body\n", frontmatter),
+        )
+        .unwrap();
+        std::fs::write(dir.join("assets").join("ref.md"), "ref").unwrap();
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::write(dir.join(".git").join("HEAD"), "0123abcd\n").unwrap();
+    }
+
+    #[test]
+    fn frontmatter_checks_version_tools_and_model() {
+        let (name, version, issues) = check_package_frontmatter(
+            "---\nname: pdf\nversion: 1.2.0\nallowed-tools: [Read, Bash]\nmodel: sonnet\n---\n",
+        );
+        assert_eq!(name.as_deref(), Some("pdf"));
+        assert_eq!(version.as_deref(), Some("1.2.0"));
+        assert!(issues.is_empty());
+
+        let (_, version, issues) =
+            check_package_frontmatter("---\nversion: 1.0\nallowed-tools: 3\nmodel: gpt-4\n---\n");
+        assert_eq!(version.as_deref(), Some("1.0"));
+        assert!(issues
+            .iter()
+            .any(|i| i.severity == "error" && i.message.contains("allowed-tools")));
+        assert!(issues
+            .iter()
+            .any(|i| i.severity == "warning" && i.message.contains("gpt-4")));
+
+        let (_, _, issues) = check_package_frontmatter("---\ndescription: [unclosed\n---\n");
+        assert_eq!(issues[0].severity, "error");
+    }
+
+    #[test]
+    fn archive_roundtrip_locates_wrapped_root() {
+        let tmp = TempDir::new().unwrap();
+        let skill = tmp.path().join("my-skill");
+        write_skill(&skill, "version: 0.1.0");
+
+        for ext in ["zip", "tar.gz"] {
+            let mut files = Vec::new();
+            let mut budget = Budget::default();
+            collect_package_files(&skill, &skill, &mut files, &mut budget).unwrap();
+            assert_eq!(files.len(), 2, ".git must not be exported");
+
+            let archive = tmp.path().join(format!("out.{}", ext));
+            let kind = archive_kind(&archive).unwrap();
+            write_archive(&archive, kind, "my-skill", &files).unwrap();
+
+            let dest = tmp.path().join(format!("x-{}", ext));
+            let mut issues = Vec::new();
+            extract_archive(
+                &archive,
+                archive_kind(&archive).unwrap(),
+                &dest,
+                &mut issues,
+            )
+            .unwrap();
+            let root = locate_package_root(&dest, None).unwrap();
+            assert!(root.ends_with("my-skill"));
+            assert_eq!(
+                std::fs::read_to_string(root.join("assets").join("ref.md")).unwrap(),
+                "ref"
+            );
+        }
+    }
+
+    #[test]
+    fn locate_requires_subdir_for_multi_skill_repos() {
+        let tmp = TempDir::new().unwrap();
+        write_skill(&tmp.path().join("skills").join("a"), "version: 1.0.0");
+        write_skill(&tmp.path().join("skills").join("b"), "version: 1.0.0");
+        let err = locate_package_root(tmp.path(), None).unwrap_err();
+        assert!(err.contains("skills/a") && err.contains("skills/b"));
+        assert!(locate_package_root(tmp.path(), Some("skills/b"))
+            .unwrap()
+            .ends_with("b"));
+        assert!(locate_package_root(tmp.path(), Some("../etc")).is_err());
+    }
+
+    #[test]
+    fn zip_slip_entries_are_rejected() {
+        let tmp = TempDir::new().unwrap();
+        let archive = tmp.path().join("evil.tar.gz");
+        {
+            let enc = flate2::write::GzEncoder::new(
+                std::fs::File::create(&archive).unwrap(),
+                flate2::Compression::default(),
+            );
+            let mut tar = tar::Builder::new(enc);
+            let mut header = tar::Header::new_gnu();
+            let data = b"x";
+            header.set_size(1);
+            header.set_mode(0o644);
+            // set_path refuses "..", so write the raw name bytes.
+            header.as_old_mut().name[..9].copy_from_slice(b"../pwn.md");
+            header.set_cksum();
+            tar.append(&header, &data[..]).unwrap();
+            tar.into_inner().unwrap().finish().unwrap();
+        }
+        let mut issues = Vec::new();
+        let err = extract_archive(
+            &archive,
+            ArchiveKind::TarGz,
+            &tmp.path().join("d"),
+            &mut issues,
+        )
+        .unwrap_err();
+        assert!(err.contains("escapes"));
+        assert!(!tmp.path().join("pwn.md").exists());
+    }
+
+    #[test]
+    fn reads_git_head_through_refs() {
+        let tmp = TempDir::new().unwrap();
+        let git = tmp.path().join(".git");
+        std::fs::create_dir_all(git.join("refs/heads")).unwrap();
+        std::fs::write(git.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::write(
+            git.join("packed-refs"),
+            "# pack\ncafebabe refs/heads/main\n",
+        )
+        .unwrap();
+        assert_eq!(read_git_head(tmp.path()).as_deref(), Some("cafebabe"));
+        std::fs::write(git.join("refs/heads/main"), "deadbeef\n").unwrap();
+        assert_eq!(read_git_head(tmp.path()).as_deref(), Some("deadbeef"));
+    }
+}
@@ -0,0 +1,1276 @@
+//! Per-turn workspace snapshots, independent of the CLI's file checkpoints.
+//!
+//! `rewind_files` relies on the CLI's SDK checkpoints, which miss files
+//! changed through Bash and are gone when the CLI's own record is. Instead,
+//! right before every user message is written to a session's stdin we take a
+//! snapshot of its working directory:
+//!
+//!   - inside a git repository: the working tree (tracked plus untracked,
+//!     minus ignored files) is added to a scratch index, written with
+//!     `write-tree` and `commit-tree`, and kept alive by
+//!     `refs/tokenicode/snapshots/<id>`. The user's index, branches and
+//!     stash are never touched. Untracked files get the copy limits below,
+//!     so a stray dump does not end up in `.git/objects`; the ones left out
+//!     are listed in a manifest.
+//!   - anywhere else: a content-addressed copy under
+//!     `~/.tokenicode/snapshots/objects/`, described by a manifest per
+//!     snapshot. Large trees are refused rather than copied.
+//!
+//! Snapshots are listed in `~/.tokenicode/snapshots/index.json`. Restoring
+//! first snapshots the current state, so a restore can itself be undone;
+//! files that backup cannot hold are left in place rather than deleted.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use tokio::sync::Mutex;
+
+use super::git_api::{parse_unified_diff, DiffFile, DiffHunk, DiffLine};
+use crate::events::emit_session_event;
+use crate::{git_output, git_output_with_env};
+
+const STORE_DIR: &str = "snapshots";
+const INDEX_FILE: &str = "index.json";
+const REF_PREFIX: &str = "refs/tokenicode/snapshots/";
+/// Never copied into (or deleted by) a copy snapshot.
+const SKIP_DIRS: &[&str] = &[
+    ".git",
+    "node_modules",
+    "target",
+    ".next",
+    "__pycache__",
+    ".venv",
+];
+/// Limits for copy snapshots, also applied to the untracked files of a git
+/// snapshot.
+const MAX_COPY_FILES: usize = 20_000;
+/// Larger files are left out of snapshots and never deleted by a restore,
+/// since the backup taken first could not capture them either.
+const MAX_COPY_FILE_BYTES: u64 = 8 * 1024 * 1024;
+const MAX_COPY_TOTAL_BYTES: u64 = 100 * 1024 * 1024;
+/// Copy-snapshot diffs include line content only for files up to this size.
+const MAX_INLINE_DIFF_BYTES: u64 = 256 * 1024;
+const DEFAULT_MAX_AGE_DAYS: u64 = 14;
+const DEFAULT_KEEP_PER_SESSION: usize = 200;
+/// How long a turn waits for its snapshot; a slower one finishes in the
+/// background and may catch the turn's first edits.
+const TURN_SNAPSHOT_WAIT: std::time::Duration = std::time::Duration::from_secs(2);
+/// Objects younger than this survive GC even when unreferenced, since a
+/// snapshot in progress writes objects before its manifest is recorded.
+const OBJECT_GRACE: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// Snapshot commits get a fixed identity so they work without user config.
+const SNAPSHOT_IDENTITY: [(&str, &str); 4] = [
+    ("GIT_AUTHOR_NAME", "TOKENICODE"),
+    ("GIT_AUTHOR_EMAIL", "snapshots@tokenicode.invalid"),
+    ("GIT_COMMITTER_NAME", "TOKENICODE"),
+    ("GIT_COMMITTER_EMAIL", "snapshots@tokenicode.invalid"),
+];
+
+/// Serializes index read-modify-write cycles.
+static INDEX_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TurnSnapshot {
+    /// Millisecond timestamp plus a random suffix; sorts chronologically.
+    pub id: String,
+    /// Stdin id of the session that took the snapshot.
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cli_session_id: Option<String>,
+    pub cwd: String,
+    /// "git" | "copy"
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo_root: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// First line of the user message that followed the snapshot.
+    pub label: String,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct Manifest {
+    /// Relative path (`/`-separated) → stored object.
+    files: BTreeMap<String, ManifestEntry>,
+    /// Files left out by the size limits (git snapshots: untracked files
+    /// only, repository-relative).
+    skipped: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct ManifestEntry {
+    hash: String,
+    size: u64,
+    #[serde(default)]
+    executable: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SnapshotRestoreResult {
+    pub restored: Vec<String>,
+    pub removed: Vec<String>,
+    /// Files created since the snapshot that were left in place because the
+    /// backup could not capture them (too large or unreadable).
+    pub kept: Vec<String>,
+    /// Snapshot of the state just before the restore.
+    pub backup: TurnSnapshot,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct SnapshotGcReport {
+    pub removed: usize,
+    /// Bytes freed from the copy store. Git snapshot objects are left to the
+    /// repository's own `git gc`.
+    pub reclaimed_bytes: u64,
+}
+
+#[derive(Debug, Clone)]
+struct SessionTarget {
+    cwd: String,
+    cli_session_id: Option<String>,
+}
+
+/// Working directory of each running session, so stdin writes know what to
+/// snapshot.
+#[derive(Default)]
+pub struct TurnSnapshotState {
+    sessions: Mutex<HashMap<String, SessionTarget>>,
+}
+
+impl TurnSnapshotState {
+    pub async fn register(&self, stdin_id: &str, cwd: &str, cli_session_id: Option<String>) {
+        self.sessions.lock().await.insert(
+            stdin_id.to_string(),
+            SessionTarget {
+                cwd: cwd.to_string(),
+                cli_session_id,
+            },
+        );
+    }
+
+    pub async fn forget(&self, stdin_id: &str) {
+        self.sessions.lock().await.remove(stdin_id);
+    }
+
+    /// Record the CLI session id once the CLI reports it (system:init), for
+    /// later snapshots and for the ones this session already took.
+    pub async fn link_cli_session(&self, stdin_id: &str, cli_session_id: &str) {
+        {
+            let mut sessions = self.sessions.lock().await;
+            match sessions.get_mut(stdin_id) {
+                Some(t) if t.cli_session_id.as_deref() != Some(cli_session_id) => {
+                    t.cli_session_id = Some(cli_session_id.to_string());
+                }
+                _ => return,
+            }
+        }
+        let Ok(store) = store_dir() else {
+            return;
+        };
+        let _guard = INDEX_LOCK.lock().await;
+        let mut entries = load_index(&store);
+        let mut changed = false;
+        for snap in entries
+            .iter_mut()
+            .filter(|s| s.session_id == stdin_id && s.cli_session_id.is_none())
+        {
+            snap.cli_session_id = Some(cli_session_id.to_string());
+            changed = true;
+        }
+        if changed {
+            if let Err(e) = save_index(&store, &entries) {
+                eprintln!("[TOKENICODE] snapshot index update failed: {}", e);
+            }
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn new_id() -> String {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    format!("{}-{}", millis, &suffix[..8])
+}
+
+fn label_of(message: &str) -> String {
+    let line = message.trim().lines().next().unwrap_or("");
+    line.chars().take(120).collect()
+}
+
+fn store_dir() -> Result<PathBuf, String> {
+    let dir = crate::tokenicode_data_path(STORE_DIR)?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create snapshot store: {}", e))?;
+    Ok(dir)
+}
+
+fn load_index(store: &Path) -> Vec<TurnSnapshot> {
+    std::fs::read_to_string(store.join(INDEX_FILE))
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_index(store: &Path, entries: &[TurnSnapshot]) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(entries)
+        .map_err(|e| format!("Failed to serialize snapshot index: {}", e))?;
+    let tmp = store.join(format!("{}.tmp", INDEX_FILE));
+    std::fs::write(&tmp, text).map_err(|e| format!("Failed to write snapshot index: {}", e))?;
+    std::fs::rename(&tmp, store.join(INDEX_FILE))
+        .map_err(|e| format!("Failed to write snapshot index: {}", e))
+}
+
+fn lookup(store: &Path, id: &str) -> Result<TurnSnapshot, String> {
+    load_index(store)
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| format!("Snapshot not found: {}", id))
+}
+
+fn manifest_path(store: &Path, id: &str) -> PathBuf {
+    store.join("manifests").join(format!("{}.json", id))
+}
+
+fn object_path(store: &Path, hash: &str) -> PathBuf {
+    store.join("objects").join(&hash[..2]).join(hash)
+}
+
+fn read_manifest(store: &Path, id: &str) -> Result<Manifest, String> {
+    let raw = std::fs::read_to_string(manifest_path(store, id))
+        .map_err(|e| format!("Failed to read snapshot manifest: {}", e))?;
+    serde_json::from_str(&raw).map_err(|e| format!("Failed to parse snapshot manifest: {}", e))
+}
+
+/// Paths from the UI are relative to the session cwd and must stay inside it.
+fn check_rel_paths(paths: &[String]) -> Result<(), String> {
+    for p in paths {
+        let path = Path::new(p);
+        if p.is_empty()
+            || path.is_absolute()
+            || path
+                .components()
+                .any(|c| !matches!(c, std::path::Component::Normal(_)))
+        {
+            return Err(format!("Invalid snapshot path: '{}'", p));
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Git snapshots
+// ---------------------------------------------------------------------------
+
+async fn repo_root(cwd: &Path) -> Option<PathBuf> {
+    git_output(cwd, &["rev-parse", "--show-toplevel"])
+        .await
+        .ok()
+        .map(|out| PathBuf::from(out.trim()))
+}
+
+/// Tree object of the current working tree, built in a scratch copy of the
+/// index so the user's staging area is untouched. Returns the tree and the
+/// untracked files left out by the copy limits.
+async fn capture_tree(store: &Path, root: &Path) -> Result<(String, Vec<String>), String> {
+    let index = git_output(root, &["rev-parse", "--git-path", "index"]).await?;
+    let index = root.join(index.trim());
+    let scratch = store.join(format!("index-{}", uuid::Uuid::new_v4().simple()));
+    if index.is_file() {
+        // Starting from the real index keeps `add` fast via its stat cache.
+        std::fs::copy(&index, &scratch).map_err(|e| format!("Failed to copy git index: {}", e))?;
+    }
+    let result = stage_worktree(store, root, &scratch).await;
+    let _ = std::fs::remove_file(&scratch);
+    result
+}
+
+/// Stage tracked changes plus the untracked files within the copy limits
+/// into `scratch`, then write the tree.
+async fn stage_worktree(
+    store: &Path,
+    root: &Path,
+    scratch: &Path,
+) -> Result<(String, Vec<String>), String> {
+    let env = [
+        ("GIT_INDEX_FILE", scratch.as_os_str()),
+        (LITERAL_PATHSPECS.0, OsStr::new(LITERAL_PATHSPECS.1)),
+    ];
+    git_output_with_env(root, &["add", "-u", "--", "."], &env).await?;
+    let untracked = git_output_with_env(
+        root,
+        &["ls-files", "--others", "--exclude-standard", "-z"],
+        &env,
+    )
+    .await?;
+
+    let (mut accepted, mut skipped) = (Vec::new(), Vec::new());
+    let mut total = 0u64;
+    for rel in untracked.split('\0').filter(|f| !f.is_empty()) {
+        let size = std::fs::symlink_metadata(root.join(rel))
+            .map(|m| m.len())
+            .unwrap_or(0);
+        if accepted.len() >= MAX_COPY_FILES
+            || size > MAX_COPY_FILE_BYTES
+            || total + size > MAX_COPY_TOTAL_BYTES
+        {
+            skipped.push(rel.to_string());
+            continue;
+        }
+        total += size;
+        accepted.push(rel);
+    }
+    if !accepted.is_empty() {
+        let list = store.join(format!("pathspec-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::write(&list, accepted.join("\0"))
+            .map_err(|e| format!("Failed to write pathspec list: {}", e))?;
+        let from_file = format!("--pathspec-from-file={}", list.display());
+        let result =
+            git_output_with_env(root, &["add", &from_file, "--pathspec-file-nul"], &env).await;
+        let _ = std::fs::remove_file(&list);
+        result?;
+    }
+    let tree = git_output_with_env(root, &["write-tree"], &env).await?;
+    Ok((tree.trim().to_string(), skipped))
+}
+
+/// Snapshot commit of the working tree, plus the untracked files left out.
+async fn git_snapshot(
+    store: &Path,
+    root: &Path,
+    id: &str,
+) -> Result<(String, Vec<String>), String> {
+    let (tree, skipped) = capture_tree(store, root).await?;
+    let parent = git_output(root, &["rev-parse", "--verify", "--quiet", "HEAD"])
+        .await
+        .ok()
+        .map(|p| p.trim().to_string());
+    let message = format!("tokenicode snapshot {}", id);
+    let mut args = vec!["commit-tree", "--no-gpg-sign", &tree, "-m", &message];
+    if let Some(p) = parent.as_deref() {
+        args.extend(["-p", p]);
+    }
+    let env: Vec<(&str, &OsStr)> = SNAPSHOT_IDENTITY
+        .iter()
+        .map(|(k, v)| (*k, OsStr::new(v)))
+        .collect();
+    let commit = git_output_with_env(root, &args, &env)
+        .await?
+        .trim()
+        .to_string();
+    let reference = format!("{}{}", REF_PREFIX, id);
+    git_output(root, &["update-ref", &reference, &commit]).await?;
+    Ok((commit, skipped))
+}
+
+/// Pathspecs (relative to the repository root) covering the session cwd, or
+/// the given cwd-relative paths inside it.
+async fn git_scope(cwd: &Path, paths: Option<&[String]>) -> Result<Vec<String>, String> {
+    let prefix = git_output(cwd, &["rev-parse", "--show-prefix"]).await?;
+    let prefix = prefix.trim();
+    Ok(match paths {
+        Some(paths) => paths.iter().map(|p| format!("{}{}", prefix, p)).collect(),
+        None if prefix.is_empty() => vec![".".to_string()],
+        None => vec![prefix.trim_end_matches('/').to_string()],
+    })
+}
+
+/// Pathspecs from the UI are file names, never globs.
+const LITERAL_PATHSPECS: (&str, &str) = ("GIT_LITERAL_PATHSPECS", "1");
+
+/// Restore a git snapshot. Files created since are removed, except ones
+/// the snapshot left out for size, which may have existed back then.
+async fn restore_git(
+    store: &Path,
+    snap: &TurnSnapshot,
+    backup: &TurnSnapshot,
+    paths: Option<&[String]>,
+) -> Result<RestoredPaths, String> {
+    let (Some(root), Some(target), Some(current)) = (
+        snap.repo_root.as_deref(),
+        snap.commit.as_deref(),
+        backup.commit.as_deref(),
+    ) else {
+        return Err("The snapshot's repository is no longer available".to_string());
+    };
+    let root = Path::new(root);
+    let scope = git_scope(Path::new(&snap.cwd), paths).await?;
+    let env = [(LITERAL_PATHSPECS.0, OsStr::new(LITERAL_PATHSPECS.1))];
+
+    let mut args = vec![
+        "diff",
+        "--name-status",
+        "-z",
+        "--no-renames",
+        target,
+        current,
+        "--",
+    ];
+    args.extend(scope.iter().map(String::as_str));
+    let out = git_output_with_env(root, &args, &env).await?;
+    let left_out: HashSet<String> = read_manifest(store, &snap.id)
+        .map(|m| m.skipped.into_iter().collect())
+        .unwrap_or_default();
+
+    let (mut restore, mut removed, mut kept) = (Vec::new(), Vec::new(), Vec::new());
+    let mut fields = out.split('\0').filter(|f| !f.is_empty());
+    while let (Some(status), Some(path)) = (fields.next(), fields.next()) {
+        if status == "A" && left_out.contains(path) {
+            kept.push(path.to_string());
+        } else if status == "A" {
+            // Created after the snapshot.
+            let full = root.join(path);
+            std::fs::remove_file(&full).map_err(|e| format!("Failed to remove {}: {}", path, e))?;
+            remove_empty_parents(&full, root);
+            removed.push(path.to_string());
+        } else {
+            restore.push(path.to_string());
+        }
+    }
+    if !restore.is_empty() {
+        let list = store.join(format!("pathspec-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::write(&list, restore.join("\0"))
+            .map_err(|e| format!("Failed to write pathspec list: {}", e))?;
+        let source = format!("--source={}", target);
+        let from_file = format!("--pathspec-from-file={}", list.display());
+        let result = git_output_with_env(
+            root,
+            &[
+                "restore",
+                &source,
+                "--worktree",
+                &from_file,
+                "--pathspec-file-nul",
+            ],
+            &env,
+        )
+        .await;
+        let _ = std::fs::remove_file(&list);
+        result?;
+    }
+    Ok(RestoredPaths {
+        restored: restore,
+        removed,
+        kept,
+    })
+}
+
+async fn diff_git(
+    store: &Path,
+    snap: &TurnSnapshot,
+    paths: Option<&[String]>,
+) -> Result<Vec<DiffFile>, String> {
+    let (Some(root), Some(commit)) = (snap.repo_root.as_deref(), snap.commit.as_deref()) else {
+        return Err("The snapshot's repository is no longer available".to_string());
+    };
+    let root = Path::new(root);
+    let (tree, _) = capture_tree(store, root).await?;
+    let scope = git_scope(Path::new(&snap.cwd), paths).await?;
+    let mut args = vec![
+        "diff",
+        "--no-color",
+        "--no-ext-diff",
+        "-M",
+        commit,
+        &tree,
+        "--",
+    ];
+    args.extend(scope.iter().map(String::as_str));
+    let env = [(LITERAL_PATHSPECS.0, OsStr::new(LITERAL_PATHSPECS.1))];
+    Ok(parse_unified_diff(
+        &git_output_with_env(root, &args, &env).await?,
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// Copy snapshots
+// ---------------------------------------------------------------------------
+
+/// Regular files under `root` (symlinks and `SKIP_DIRS` excluded), keyed by
+/// `/`-separated relative path.
+fn walk_files(root: &Path, limit: usize) -> Result<BTreeMap<String, PathBuf>, String> {
+    let mut files = BTreeMap::new();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                if !SKIP_DIRS.iter().any(|s| entry.file_name() == *s) {
+                    stack.push(path);
+                }
+            } else if file_type.is_file() {
+                let Ok(rel) = path.strip_prefix(root) else {
+                    continue;
+                };
+                let rel = rel
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                files.insert(rel, path);
+                if files.len() > limit {
+                    return Err(format!(
+                        "More than {} files; too many for a copy snapshot",
+                        limit
+                    ));
+                }
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn hash_bytes(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+#[cfg(unix)]
+fn is_executable(meta: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_meta: &std::fs::Metadata) -> bool {
+    false
+}
+
+fn copy_snapshot(store: &Path, cwd: &Path) -> Result<Manifest, String> {
+    let mut manifest = Manifest::default();
+    let mut total = 0u64;
+    for (rel, path) in walk_files(cwd, MAX_COPY_FILES)? {
+        let Ok(meta) = std::fs::metadata(&path) else {
+            continue;
+        };
+        if meta.len() > MAX_COPY_FILE_BYTES {
+            manifest.skipped.push(rel);
+            continue;
+        }
+        total += meta.len();
+        if total > MAX_COPY_TOTAL_BYTES {
+            return Err(format!(
+                "Directory exceeds {} MB; too large for a copy snapshot",
+                MAX_COPY_TOTAL_BYTES / (1024 * 1024)
+            ));
+        }
+        let Ok(data) = std::fs::read(&path) else {
+            manifest.skipped.push(rel);
+            continue;
+        };
+        let hash = hash_bytes(&data);
+        let object = object_path(store, &hash);
+        if !object.exists() {
+            let parent = object.parent().unwrap_or(store);
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create snapshot object dir: {}", e))?;
+            let tmp = parent.join(format!("{}.tmp-{}", hash, uuid::Uuid::new_v4().simple()));
+            std::fs::write(&tmp, &data)
+                .map_err(|e| format!("Failed to write snapshot object: {}", e))?;
+            std::fs::rename(&tmp, &object)
+                .map_err(|e| format!("Failed to write snapshot object: {}", e))?;
+        }
+        manifest.files.insert(
+            rel,
+            ManifestEntry {
+                hash,
+                size: meta.len(),
+                executable: is_executable(&meta),
+            },
+        );
+    }
+    Ok(manifest)
+}
+
+fn in_scope(rel: &str, paths: Option<&[String]>) -> bool {
+    paths.is_none_or(|paths| {
+        paths.iter().any(|p| {
+            let p = p.trim_end_matches('/');
+            rel == p
+                || rel
+                    .strip_prefix(p)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    })
+}
+
+/// Remove directories left empty by a deletion, up to (not including) `stop`.
+fn remove_empty_parents(file: &Path, stop: &Path) {
+    let mut dir = file.parent();
+    while let Some(d) = dir {
+        if d == stop || !d.starts_with(stop) || std::fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+/// Paths a copy restore touched, relative to the snapshot's cwd.
+#[derive(Debug, Default)]
+struct RestoredPaths {
+    restored: Vec<String>,
+    removed: Vec<String>,
+    kept: Vec<String>,
+}
+
+/// Restore `manifest` into `cwd`. Files the snapshot does not know are only
+/// deleted when `backup` (taken just before) holds them, so the deletion can
+/// be undone; the rest are returned as kept.
+fn restore_copy(
+    store: &Path,
+    manifest: &Manifest,
+    backup: &Manifest,
+    cwd: &Path,
+    paths: Option<&[String]>,
+) -> Result<RestoredPaths, String> {
+    if let Some((rel, _)) = manifest
+        .files
+        .iter()
+        .find(|(rel, e)| in_scope(rel, paths) && !object_path(store, &e.hash).is_file())
+    {
+        return Err(format!("Snapshot content missing for {}", rel));
+    }
+    let current = walk_files(cwd, usize::MAX)?;
+    let skipped: HashSet<&str> = manifest.skipped.iter().map(String::as_str).collect();
+    let mut out = RestoredPaths::default();
+
+    for (rel, path) in &current {
+        if in_scope(rel, paths)
+            && !manifest.files.contains_key(rel)
+            && !skipped.contains(rel.as_str())
+        {
+            if !backup.files.contains_key(rel) {
+                out.kept.push(rel.clone());
+                continue;
+            }
+            std::fs::remove_file(path).map_err(|e| format!("Failed to remove {}: {}", rel, e))?;
+            remove_empty_parents(path, cwd);
+            out.removed.push(rel.clone());
+        }
+    }
+    for (rel, entry) in &manifest.files {
+        if !in_scope(rel, paths) {
+            continue;
+        }
+        let dest = cwd.join(rel);
+        let unchanged = current.contains_key(rel)
+            && std::fs::read(&dest).is_ok_and(|data| hash_bytes(&data) == entry.hash);
+        if unchanged {
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        std::fs::copy(object_path(store, &entry.hash), &dest)
+            .map_err(|e| format!("Failed to restore {}: {}", rel, e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = if entry.executable { 0o755 } else { 0o644 };
+            let _ = std::fs::set_permissions(&dest, std::fs::Permissions::from_mode(mode));
+        }
+        out.restored.push(rel.clone());
+    }
+    Ok(out)
+}
+
+/// Text lines of a file for the whole-file diffs of copy snapshots; `None`
+/// for binary or oversized content.
+fn diff_text(data: &[u8]) -> Option<String> {
+    if data.len() as u64 > MAX_INLINE_DIFF_BYTES || data.iter().take(8000).any(|b| *b == 0) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(data).to_string())
+}
+
+/// Copy snapshots keep no history to diff against, so a changed file is
+/// shown as one hunk replacing the old content with the new.
+fn whole_file_diff(rel: &str, old: Option<&[u8]>, new: Option<&[u8]>) -> DiffFile {
+    let status = match (old, new) {
+        (None, _) => "added",
+        (_, None) => "deleted",
+        _ => "modified",
+    };
+    let old_text = old.map(diff_text);
+    let new_text = new.map(diff_text);
+    let binary = matches!(old_text, Some(None)) || matches!(new_text, Some(None));
+    let mut file = DiffFile {
+        old_path: old.map(|_| rel.to_string()),
+        new_path: new.map(|_| rel.to_string()),
+        status: status.to_string(),
+        binary,
+        additions: 0,
+        deletions: 0,
+        hunks: Vec::new(),
+    };
+    if binary {
+        return file;
+    }
+    let (old_text, new_text) = (
+        old_text.flatten().unwrap_or_default(),
+        new_text.flatten().unwrap_or_default(),
+    );
+    let old_lines: Vec<&str> = old_text.lines().collect();
+    let new_lines: Vec<&str> = new_text.lines().collect();
+    let mut lines: Vec<DiffLine> = Vec::new();
+    for (i, l) in old_lines.iter().enumerate() {
+        lines.push(DiffLine {
+            kind: "del".to_string(),
+            content: l.to_string(),
+            old_line: Some(i as u32 + 1),
+            new_line: None,
+            segments: None,
+        });
+    }
+    for (i, l) in new_lines.iter().enumerate() {
+        lines.push(DiffLine {
+            kind: "add".to_string(),
+            content: l.to_string(),
+            old_line: None,
+            new_line: Some(i as u32 + 1),
+            segments: None,
+        });
+    }
+    file.deletions = old_lines.len() as u32;
+    file.additions = new_lines.len() as u32;
+    if !lines.is_empty() {
+        file.hunks.push(DiffHunk {
+            old_start: u32::from(!old_lines.is_empty()),
+            old_lines: old_lines.len() as u32,
+            new_start: u32::from(!new_lines.is_empty()),
+            new_lines: new_lines.len() as u32,
+            header: String::new(),
+            lines,
+        });
+    }
+    file
+}
+
+fn diff_copy(
+    store: &Path,
+    manifest: &Manifest,
+    cwd: &Path,
+    paths: Option<&[String]>,
+) -> Result<Vec<DiffFile>, String> {
+    let current = walk_files(cwd, usize::MAX)?;
+    let skipped: HashSet<&str> = manifest.skipped.iter().map(String::as_str).collect();
+    let names: BTreeSet<&String> = current.keys().chain(manifest.files.keys()).collect();
+    let mut files = Vec::new();
+    for rel in names {
+        if !in_scope(rel, paths) || skipped.contains(rel.as_str()) {
+            continue;
+        }
+        let new = current.get(rel).and_then(|p| std::fs::read(p).ok());
+        let old = match manifest.files.get(rel) {
+            Some(entry) => {
+                if new.as_deref().is_some_and(|d| hash_bytes(d) == entry.hash) {
+                    continue;
+                }
+                Some(
+                    std::fs::read(object_path(store, &entry.hash))
+                        .map_err(|e| format!("Snapshot content missing for {}: {}", rel, e))?,
+                )
+            }
+            None => None,
+        };
+        files.push(whole_file_diff(rel, old.as_deref(), new.as_deref()));
+    }
+    Ok(files)
+}
+
+// ---------------------------------------------------------------------------
+// Snapshot lifecycle
+// ---------------------------------------------------------------------------
+
+async fn take_snapshot(
+    store: &Path,
+    session_id: &str,
+    cli_session_id: Option<String>,
+    cwd: &Path,
+    label: String,
+) -> Result<TurnSnapshot, String> {
+    let id = new_id();
+    let mut snap = TurnSnapshot {
+        id: id.clone(),
+        session_id: session_id.to_string(),
+        cli_session_id,
+        cwd: cwd.to_string_lossy().to_string(),
+        kind: "copy".to_string(),
+        repo_root: None,
+        commit: None,
+        label,
+        created_at: now_secs(),
+    };
+    let manifest = if let Some(root) = repo_root(cwd).await {
+        let (commit, skipped) = git_snapshot(store, &root, &id).await?;
+        snap.commit = Some(commit);
+        snap.kind = "git".to_string();
+        snap.repo_root = Some(root.to_string_lossy().to_string());
+        // Git snapshots only need a manifest to list what was left out.
+        (!skipped.is_empty()).then(|| Manifest {
+            skipped,
+            ..Default::default()
+        })
+    } else {
+        let (store_buf, cwd_buf) = (store.to_path_buf(), cwd.to_path_buf());
+        let manifest = tokio::task::spawn_blocking(move || copy_snapshot(&store_buf, &cwd_buf))
+            .await
+            .map_err(|e| format!("Snapshot task failed: {}", e))??;
+        Some(manifest)
+    };
+    if let Some(manifest) = manifest {
+        let path = manifest_path(store, &id);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create manifest dir: {}", e))?;
+        }
+        let text = serde_json::to_string(&manifest)
+            .map_err(|e| format!("Failed to serialize snapshot manifest: {}", e))?;
+        std::fs::write(&path, text)
+            .map_err(|e| format!("Failed to write snapshot manifest: {}", e))?;
+    }
+
+    let _guard = INDEX_LOCK.lock().await;
+    let mut entries = load_index(store);
+    entries.push(snap.clone());
+    save_index(store, &entries)?;
+    Ok(snap)
+}
+
+/// Called right before a user message is written to a session's stdin.
+/// Waits at most `TURN_SNAPSHOT_WAIT`; failures are logged and never hold
+/// up the turn.
+pub async fn snapshot_before_turn(
+    app: &AppHandle,
+    state: &TurnSnapshotState,
+    stdin_id: &str,
+    message: &str,
+) {
+    let Some(target) = state.sessions.lock().await.get(stdin_id).cloned() else {
+        return;
+    };
+    let (app, stdin_id, label) = (app.clone(), stdin_id.to_string(), label_of(message));
+    let log_id = stdin_id.clone();
+    let task = tokio::spawn(async move {
+        let result = match store_dir() {
+            Ok(store) => {
+                take_snapshot(
+                    &store,
+                    &stdin_id,
+                    target.cli_session_id,
+                    Path::new(&target.cwd),
+                    label,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(snap) => {
+                let _ = emit_session_event(
+                    &app,
+                    &stdin_id,
+                    &format!("claude:snapshot:{}", stdin_id),
+                    snap,
+                );
+            }
+            Err(e) => eprintln!("[TOKENICODE] turn snapshot skipped for {}: {}", stdin_id, e),
+        }
+    });
+    if tokio::time::timeout(TURN_SNAPSHOT_WAIT, task)
+        .await
+        .is_err()
+    {
+        eprintln!(
+            "[TOKENICODE] turn snapshot for {} still running; sending the message",
+            log_id
+        );
+    }
+}
+
+async fn restore_snapshot(
+    store: &Path,
+    snap: &TurnSnapshot,
+    paths: Option<&[String]>,
+) -> Result<SnapshotRestoreResult, String> {
+    let cwd = Path::new(&snap.cwd);
+    if !cwd.is_dir() {
+        return Err(format!("Snapshot directory no longer exists: {}", snap.cwd));
+    }
+    let backup = take_snapshot(
+        store,
+        &snap.session_id,
+        snap.cli_session_id.clone(),
+        cwd,
+        format!("Before restoring {}", snap.id),
+    )
+    .await?;
+    let touched = if snap.kind == "git" {
+        restore_git(store, snap, &backup, paths).await?
+    } else {
+        if backup.kind != "copy" {
+            return Err(format!(
+                "{} became a git repository after the snapshot; restore it with git instead",
+                snap.cwd
+            ));
+        }
+        let manifest = read_manifest(store, &snap.id)?;
+        let backup_manifest = read_manifest(store, &backup.id)?;
+        restore_copy(store, &manifest, &backup_manifest, cwd, paths)?
+    };
+    Ok(SnapshotRestoreResult {
+        restored: touched.restored,
+        removed: touched.removed,
+        kept: touched.kept,
+        backup,
+    })
+}
+
+async fn collect_garbage(
+    store: &Path,
+    max_age_secs: u64,
+    keep_per_session: usize,
+    object_grace: std::time::Duration,
+) -> Result<SnapshotGcReport, String> {
+    let mut report = SnapshotGcReport::default();
+    let _guard = INDEX_LOCK.lock().await;
+    let mut entries = load_index(store);
+    entries.sort_by(|a, b| b.id.cmp(&a.id));
+    let now = now_secs();
+    let mut per_session: HashMap<String, usize> = HashMap::new();
+    let mut kept = Vec::new();
+    for snap in entries {
+        let seen = per_session.entry(snap.session_id.clone()).or_insert(0);
+        *seen += 1;
+        let gone = match snap.repo_root.as_deref() {
+            Some(root) => !Path::new(root).is_dir(),
+            None => !manifest_path(store, &snap.id).is_file(),
+        };
+        if !gone && *seen <= keep_per_session && now.saturating_sub(snap.created_at) <= max_age_secs
+        {
+            kept.push(snap);
+            continue;
+        }
+        if let Some(root) = snap.repo_root.as_deref().filter(|_| !gone) {
+            let reference = format!("{}{}", REF_PREFIX, snap.id);
+            let _ = git_output(Path::new(root), &["update-ref", "-d", &reference]).await;
+        }
+        let manifest = manifest_path(store, &snap.id);
+        if let Ok(meta) = std::fs::metadata(&manifest) {
+            report.reclaimed_bytes += meta.len();
+            let _ = std::fs::remove_file(&manifest);
+        }
+        report.removed += 1;
+    }
+    kept.reverse();
+    save_index(store, &kept)?;
+
+    // Sweep copy-store objects no remaining manifest refers to.
+    let referenced: HashSet<String> = kept
+        .iter()
+        .filter(|s| s.kind == "copy")
+        .filter_map(|s| read_manifest(store, &s.id).ok())
+        .flat_map(|m| m.files.into_values().map(|e| e.hash))
+        .collect();
+    let Ok(buckets) = std::fs::read_dir(store.join("objects")) else {
+        return Ok(report);
+    };
+    for bucket in buckets.flatten() {
+        for object in std::fs::read_dir(bucket.path())
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            let name = object.file_name().to_string_lossy().to_string();
+            let Ok(meta) = object.metadata() else {
+                continue;
+            };
+            let young = meta
+                .modified()
+                .ok()
+                .and_then(|m| m.elapsed().ok())
+                .is_none_or(|age| age < object_grace);
+            if !referenced.contains(&name) && !young && std::fs::remove_file(object.path()).is_ok()
+            {
+                report.reclaimed_bytes += meta.len();
+            }
+        }
+        let _ = std::fs::remove_dir(bucket.path());
+    }
+    Ok(report)
+}
+
+/// Startup hook: one GC pass with the default limits, off the main thread.
+pub fn gc_at_startup() {
+    tauri::async_runtime::spawn(async {
+        let result = match store_dir() {
+            Ok(store) => {
+                collect_garbage(
+                    &store,
+                    DEFAULT_MAX_AGE_DAYS * 86_400,
+                    DEFAULT_KEEP_PER_SESSION,
+                    OBJECT_GRACE,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(r) if r.removed > 0 => eprintln!(
+                "[TOKENICODE] snapshot gc: removed={} reclaimed={}B",
+                r.removed, r.reclaimed_bytes
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!("[TOKENICODE] snapshot gc failed: {}", e),
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// Commands
+// ---------------------------------------------------------------------------
+
+/// Snapshots newest first. `session_id` matches either the stdin id or the
+/// CLI session id; `cwd` limits to one working directory.
+#[tauri::command]
+pub async fn list_turn_snapshots(
+    session_id: Option<String>,
+    cwd: Option<String>,
+) -> Result<Vec<TurnSnapshot>, String> {
+    let mut entries: Vec<TurnSnapshot> = load_index(&store_dir()?)
+        .into_iter()
+        .filter(|s| {
+            session_id
+                .as_deref()
+                .is_none_or(|id| s.session_id == id || s.cli_session_id.as_deref() == Some(id))
+        })
+        .filter(|s| cwd.as_deref().is_none_or(|c| s.cwd == c))
+        .collect();
+    entries.reverse();
+    Ok(entries)
+}
+
+/// Changes made since the snapshot (snapshot → current working tree),
+/// optionally limited to cwd-relative `paths`.
+#[tauri::command]
+pub async fn diff_turn_snapshot(
+    snapshot_id: String,
+    paths: Option<Vec<String>>,
+) -> Result<Vec<DiffFile>, String> {
+    if let Some(p) = paths.as_deref() {
+        check_rel_paths(p)?;
+    }
+    let store = store_dir()?;
+    let snap = lookup(&store, &snapshot_id)?;
+    if snap.kind == "git" {
+        diff_git(&store, &snap, paths.as_deref()).await
+    } else {
+        let manifest = read_manifest(&store, &snap.id)?;
+        diff_copy(&store, &manifest, Path::new(&snap.cwd), paths.as_deref())
+    }
+}
+
+/// Put the working directory (or just `paths`) back to the snapshot. Files
+/// created since are removed; ignored and skipped files are left alone.
+#[tauri::command]
+pub async fn restore_turn_snapshot(
+    snapshot_id: String,
+    paths: Option<Vec<String>>,
+) -> Result<SnapshotRestoreResult, String> {
+    if let Some(p) = paths.as_deref() {
+        check_rel_paths(p)?;
+    }
+    let store = store_dir()?;
+    let snap = lookup(&store, &snapshot_id)?;
+    restore_snapshot(&store, &snap, paths.as_deref()).await
+}
+
+/// Drop snapshots older than `max_age_days` (default 14) or beyond the
+/// newest `keep_per_session` (default 200) of each session.
+#[tauri::command]
+pub async fn gc_turn_snapshots(
+    max_age_days: Option<u64>,
+    keep_per_session: Option<usize>,
+) -> Result<SnapshotGcReport, String> {
+    collect_garbage(
+        &store_dir()?,
+        max_age_days.unwrap_or(DEFAULT_MAX_AGE_DAYS) * 86_400,
+        keep_per_session.unwrap_or(DEFAULT_KEEP_PER_SESSION),
+        OBJECT_GRACE,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn git(dir: &Path, args: &[&str]) -> String {
+        git_output(dir, args).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn git_snapshot_restores_worktree_without_touching_index() {
+        let store = tempfile::tempdir().unwrap();
+        let repo = tempfile::tempdir().unwrap();
+        let dir = repo.path();
+        git(dir, &["init", "-q"]).await;
+        git(dir, &["config", "user.name", "T"]).await;
+        git(dir, &["config", "user.email", "t@example.com"]).await;
+        std::fs::write(dir.join("a.txt"), "one\n").unwrap();
+        std::fs::write(dir.join(".gitignore"), "build/\n").unwrap();
+        git(dir, &["add", "."]).await;
+        git(dir, &["commit", "-qm", "init"]).await;
+        std::fs::write(dir.join("a.txt"), "two\n").unwrap();
+        std::fs::write(dir.join("notes [draft].md"), "untracked\n").unwrap();
+
+        let snap = take_snapshot(store.path(), "s1", None, dir, "fix it".into())
+            .await
+            .unwrap();
+        assert_eq!(snap.kind, "git");
+        let status_before = git(dir, &["status", "--porcelain"]).await;
+
+        // The agent's turn.
+        std::fs::write(dir.join("a.txt"), "three\n").unwrap();
+        std::fs::remove_file(dir.join("notes [draft].md")).unwrap();
+        std::fs::create_dir_all(dir.join("src/new")).unwrap();
+        std::fs::write(dir.join("src/new/b.rs"), "fn b() {}\n").unwrap();
+        std::fs::create_dir_all(dir.join("build")).unwrap();
+        std::fs::write(dir.join("build/out.bin"), "ignored").unwrap();
+
+        let diff = diff_git(store.path(), &snap, None).await.unwrap();
+        assert_eq!(diff.len(), 3);
+
+        let result = restore_snapshot(store.path(), &snap, None).await.unwrap();
+        assert_eq!(result.removed, ["src/new/b.rs"]);
+        assert_eq!(std::fs::read_to_string(dir.join("a.txt")).unwrap(), "two\n");
+        assert!(dir.join("notes [draft].md").is_file());
+        assert!(!dir.join("src").exists());
+        assert!(dir.join("build/out.bin").is_file());
+        assert_eq!(git(dir, &["status", "--porcelain"]).await, status_before);
+
+        // The backup snapshot undoes the restore.
+        restore_snapshot(store.path(), &result.backup, Some(&["a.txt".to_string()]))
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "three\n"
+        );
+        let refs = git(dir, &["for-each-ref", "--format=%(refname)", REF_PREFIX]).await;
+        assert_eq!(refs.lines().count(), 3);
+    }
+
+    #[tokio::test]
+    async fn git_snapshot_leaves_out_oversized_untracked_files() {
+        let store = tempfile::tempdir().unwrap();
+        let repo = tempfile::tempdir().unwrap();
+        let dir = repo.path();
+        git(dir, &["init", "-q"]).await;
+        std::fs::write(dir.join("small.txt"), "kept\n").unwrap();
+        std::fs::File::create(dir.join("dump.bin"))
+            .unwrap()
+            .set_len(MAX_COPY_FILE_BYTES + 1)
+            .unwrap();
+
+        let snap = take_snapshot(store.path(), "s1", None, dir, "go".into())
+            .await
+            .unwrap();
+        let commit = snap.commit.as_deref().unwrap();
+        let tree = git(dir, &["ls-tree", "-r", "--name-only", commit]).await;
+        assert_eq!(tree, "small.txt\n");
+        let manifest = read_manifest(store.path(), &snap.id).unwrap();
+        assert_eq!(manifest.skipped, ["dump.bin"]);
+
+        // Shrunk by the agent, the backup now holds it, but the restore must
+        // not delete a file that may have existed at snapshot time.
+        std::fs::write(dir.join("dump.bin"), "small now\n").unwrap();
+        let result = restore_snapshot(store.path(), &snap, None).await.unwrap();
+        assert!(result.removed.is_empty());
+        assert_eq!(result.kept, ["dump.bin"]);
+        assert!(dir.join("dump.bin").is_file());
+    }
+
+    #[tokio::test]
+    async fn copy_snapshot_round_trip() {
+        let store = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let dir = work.path();
+        std::fs::write(dir.join("a.txt"), "one\ntwo\n").unwrap();
+        std::fs::create_dir_all(dir.join("node_modules/x")).unwrap();
+        std::fs::write(dir.join("node_modules/x/i.js"), "skip").unwrap();
+
+        let manifest = copy_snapshot(store.path(), dir).unwrap();
+        assert_eq!(manifest.files.keys().collect::<Vec<_>>(), ["a.txt"]);
+
+        std::fs::write(dir.join("a.txt"), "one\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "new\n").unwrap();
+        let diff = diff_copy(store.path(), &manifest, dir, None).unwrap();
+        assert_eq!(diff.len(), 2);
+        assert_eq!(diff[0].status, "modified");
+        assert_eq!((diff[0].deletions, diff[0].additions), (2, 1));
+        assert_eq!(diff[1].status, "added");
+
+        // Created since the snapshot and too large for the backup: kept.
+        std::fs::File::create(dir.join("big.bin"))
+            .unwrap()
+            .set_len(MAX_COPY_FILE_BYTES + 1)
+            .unwrap();
+        let backup = copy_snapshot(store.path(), dir).unwrap();
+        assert_eq!(backup.skipped, ["big.bin"]);
+        let out = restore_copy(store.path(), &manifest, &backup, dir, None).unwrap();
+        assert_eq!(out.restored, ["a.txt"]);
+        assert_eq!(out.removed, ["b.txt"]);
+        assert_eq!(out.kept, ["big.bin"]);
+        assert!(dir.join("big.bin").is_file());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+        assert!(dir.join("node_modules/x/i.js").is_file());
+        assert!(check_rel_paths(&["../etc".to_string()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn gc_drops_old_snapshots_and_orphaned_objects() {
+        let store = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        std::fs::write(work.path().join("a.txt"), "v1").unwrap();
+        let old = take_snapshot(store.path(), "s1", None, work.path(), "first".into())
+            .await
+            .unwrap();
+        std::fs::write(work.path().join("a.txt"), "v2").unwrap();
+        let new = take_snapshot(store.path(), "s1", None, work.path(), "second".into())
+            .await
+            .unwrap();
+        assert_eq!(new.kind, "copy");
+
+        let report = collect_garbage(store.path(), 86_400, 1, std::time::Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(report.removed, 1);
+        assert!(report.reclaimed_bytes > 0);
+        let left = load_index(store.path());
+        assert_eq!(left, [new]);
+        assert!(!manifest_path(store.path(), &old.id).exists());
+        assert!(!object_path(store.path(), &hash_bytes(b"v1")).exists());
+        assert!(object_path(store.path(), &hash_bytes(b"v2")).exists());
+    }
+}
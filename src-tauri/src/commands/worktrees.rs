@@ -0,0 +1,761 @@
+//! Git worktree-isolated sessions.
+//!
+//! With `isolation: "worktree"`, `start_claude_session` runs the CLI inside a
+//! `git worktree` on a fresh `tokenicode/<session>` branch instead of the
+//! user's checkout, so parallel agents on one repository never share a
+//! working tree. Worktrees live under `~/.tokenicode/worktrees/<repo>-<hash>/`
+//! and are recorded in `~/.tokenicode/worktrees.json`.
+//!
+//! A resumed session finds its worktree again (the CLI keys transcripts by
+//! cwd, so it must resume in the same directory). When the work is done the
+//! user reviews it with `diff_session_worktree`, then either merges the
+//! branch back into the branch it started from or discards it. Sessions
+//! started on a detached HEAD have no such branch and can only be discarded.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+use super::ProcessManager;
+use crate::{git_output, git_output_with_env};
+
+const REGISTRY_FILE: &str = "worktrees.json";
+const BRANCH_PREFIX: &str = "tokenicode/";
+/// Diff text returned to the UI is capped; the file list is always complete.
+const MAX_DIFF_BYTES: usize = 2 * 1024 * 1024;
+
+/// Serializes registry read-modify-write cycles.
+static REGISTRY_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ManagedWorktree {
+    /// Stdin/tab id of the session currently using the worktree.
+    pub session_id: String,
+    pub repo_root: String,
+    /// Worktree root.
+    pub path: String,
+    /// Directory the CLI runs in: the worktree plus the original cwd's
+    /// offset inside the repository.
+    pub session_cwd: String,
+    pub branch: String,
+    /// Branch checked out when the worktree was made; `None` on detached HEAD,
+    /// in which case the worktree can be reviewed or discarded but not merged.
+    pub base_branch: Option<String>,
+    pub base_commit: String,
+    /// Unix seconds.
+    pub created_at: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct WorktreeStatus {
+    #[serde(flatten)]
+    pub worktree: ManagedWorktree,
+    pub exists: bool,
+    pub dirty: bool,
+    pub commits_ahead: u32,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct WorktreeChange {
+    /// git name-status letter: A, M, D, R, C, T.
+    pub status: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct WorktreeDiff {
+    pub branch: String,
+    pub base_commit: String,
+    pub files: Vec<WorktreeChange>,
+    /// Unified diff against the base commit, uncommitted and new files included.
+    pub diff: String,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct WorktreeMergeResult {
+    pub merged: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Files that conflicted; the merge was aborted and nothing changed.
+    pub conflicts: Vec<String>,
+    pub removed: bool,
+    pub message: String,
+}
+
+// ---------------------------------------------------------------------------
+// Registry
+// ---------------------------------------------------------------------------
+
+fn load_registry() -> Vec<ManagedWorktree> {
+    crate::tokenicode_data_path(REGISTRY_FILE)
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_registry(entries: &[ManagedWorktree]) -> Result<(), String> {
+    let path = crate::tokenicode_data_path(REGISTRY_FILE)?;
+    let text = serde_json::to_string_pretty(entries)
+        .map_err(|e| format!("Failed to serialize worktree registry: {}", e))?;
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write worktree registry: {}", e))
+}
+
+fn lookup(session_id: &str) -> Result<ManagedWorktree, String> {
+    load_registry()
+        .into_iter()
+        .find(|w| w.session_id == session_id)
+        .ok_or_else(|| format!("Session {} has no managed worktree", session_id))
+}
+
+async fn ensure_not_running(
+    processes: &ProcessManager,
+    wt: &ManagedWorktree,
+) -> Result<(), String> {
+    if processes.active_ids().await.contains(&wt.session_id) {
+        return Err(
+            "The session is still running — stop it before merging or discarding its worktree"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Worktree lifecycle
+// ---------------------------------------------------------------------------
+
+/// Branch-safe slug of a session id (`desk_17:ab` → `desk_17-ab`).
+fn branch_slug(session_id: &str) -> String {
+    let slug: String = session_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let slug = slug.trim_matches('-');
+    let slug = if slug.is_empty() { "session" } else { slug };
+    slug.chars().take(40).collect()
+}
+
+/// `<managed_root>/<repo name>-<8 hex of the repo path hash>`.
+fn repo_dir(managed_root: &Path, repo_root: &Path) -> PathBuf {
+    let name = repo_root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "repo".to_string());
+    let hash = Sha256::digest(repo_root.to_string_lossy().as_bytes());
+    let short: String = hash.iter().take(4).map(|b| format!("{:02x}", b)).collect();
+    managed_root.join(format!("{}-{}", branch_slug(&name), short))
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Create a worktree for `cwd`'s repository on a new branch at HEAD.
+async fn create_worktree(
+    session_id: &str,
+    cwd: &Path,
+    managed_root: &Path,
+) -> Result<ManagedWorktree, String> {
+    let root = git_output(cwd, &["rev-parse", "--show-toplevel"])
+        .await
+        .map_err(|_| {
+            format!(
+                "Worktree isolation needs a git repository: {}",
+                cwd.display()
+            )
+        })?;
+    let root = PathBuf::from(root.trim());
+    let base_commit = git_output(&root, &["rev-parse", "--verify", "HEAD"])
+        .await
+        .map_err(|_| {
+            "The repository has no commits yet — commit once before isolating sessions".to_string()
+        })?
+        .trim()
+        .to_string();
+    let base_branch = git_output(&root, &["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .await
+        .ok()
+        .map(|b| b.trim().to_string())
+        .filter(|b| !b.is_empty());
+
+    let slug = branch_slug(session_id);
+    let dir = repo_dir(managed_root, &root);
+    let (mut branch, mut path) = (format!("{}{}", BRANCH_PREFIX, slug), dir.join(&slug));
+    for n in 2.. {
+        let taken = git_output(
+            &root,
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("refs/heads/{}", branch),
+            ],
+        )
+        .await
+        .is_ok();
+        if !taken && !path.exists() {
+            break;
+        }
+        branch = format!("{}{}-{}", BRANCH_PREFIX, slug, n);
+        path = dir.join(format!("{}-{}", slug, n));
+    }
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create worktree dir: {}", e))?;
+
+    let path_str = path.to_string_lossy().to_string();
+    git_output(
+        &root,
+        &["worktree", "add", "-b", &branch, &path_str, &base_commit],
+    )
+    .await?;
+
+    // Keep the user's position inside the repo (e.g. a package subdir).
+    let offset = match (cwd.canonicalize(), root.canonicalize()) {
+        (Ok(c), Ok(r)) => c
+            .strip_prefix(&r)
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+        _ => PathBuf::new(),
+    };
+    let session_cwd = if path.join(&offset).is_dir() {
+        path.join(&offset)
+    } else {
+        path.clone()
+    };
+
+    eprintln!(
+        "[TOKENICODE] worktree for {}: {} on {}",
+        session_id, path_str, branch
+    );
+    Ok(ManagedWorktree {
+        session_id: session_id.to_string(),
+        repo_root: root.to_string_lossy().to_string(),
+        path: path_str,
+        session_cwd: session_cwd.to_string_lossy().to_string(),
+        branch,
+        base_branch,
+        base_commit,
+        created_at: now_secs(),
+    })
+}
+
+/// Index of a registry entry this start should reuse: `cwd` already inside
+/// the worktree, the resumed transcript recorded under the worktree's cwd,
+/// or the same session id.
+fn find_reusable(
+    registry: &[ManagedWorktree],
+    session_id: &str,
+    resume_session_id: Option<&str>,
+    cwd: &Path,
+) -> Option<usize> {
+    let cwd = cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf());
+    if let Some(i) = registry.iter().position(|w| {
+        Path::new(&w.path)
+            .canonicalize()
+            .is_ok_and(|p| cwd.starts_with(p))
+    }) {
+        return Some(i);
+    }
+    let transcript_dir = resume_session_id
+        .and_then(crate::find_session_jsonl)
+        .and_then(|p| {
+            p.parent()
+                .and_then(|d| d.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+        });
+    if let Some(dir) = transcript_dir {
+        if let Some(i) = registry
+            .iter()
+            .position(|w| crate::encode_project_name(&w.session_cwd) == dir)
+        {
+            return Some(i);
+        }
+    }
+    registry.iter().position(|w| w.session_id == session_id)
+}
+
+/// Worktree for a session start, reusing the one a resumed session ran in.
+pub(crate) async fn prepare_session_worktree(
+    session_id: &str,
+    resume_session_id: Option<&str>,
+    cwd: &str,
+) -> Result<ManagedWorktree, String> {
+    let _guard = REGISTRY_LOCK.lock().await;
+    let mut registry = load_registry();
+    let cwd = Path::new(cwd);
+    if let Some(i) = find_reusable(&registry, session_id, resume_session_id, cwd) {
+        if Path::new(&registry[i].path).is_dir() {
+            registry[i].session_id = session_id.to_string();
+            let wt = registry[i].clone();
+            save_registry(&registry)?;
+            return Ok(wt);
+        }
+        // Worktree deleted behind our back — forget it and start fresh.
+        registry.remove(i);
+    }
+    let managed_root = crate::tokenicode_data_path("worktrees")?;
+    let wt = create_worktree(session_id, cwd, &managed_root).await?;
+    registry.push(wt.clone());
+    save_registry(&registry)?;
+    Ok(wt)
+}
+
+async fn is_dirty(path: &Path) -> Result<bool, String> {
+    Ok(!git_output(path, &["status", "--porcelain"])
+        .await?
+        .trim()
+        .is_empty())
+}
+
+async fn commits_ahead(wt: &ManagedWorktree) -> u32 {
+    git_output(
+        Path::new(&wt.repo_root),
+        &[
+            "rev-list",
+            "--count",
+            &format!("{}..{}", wt.base_commit, wt.branch),
+        ],
+    )
+    .await
+    .ok()
+    .and_then(|n| n.trim().parse().ok())
+    .unwrap_or(0)
+}
+
+fn parse_name_status(out: &str) -> Vec<WorktreeChange> {
+    out.lines()
+        .filter_map(|line| {
+            let mut cols = line.split('\t');
+            let status = cols.next()?.trim();
+            let first = cols.next()?.to_string();
+            let letter = status.chars().next()?.to_string();
+            Some(match cols.next() {
+                Some(second) => WorktreeChange {
+                    status: letter,
+                    path: second.to_string(),
+                    old_path: Some(first),
+                },
+                None => WorktreeChange {
+                    status: letter,
+                    path: first,
+                    old_path: None,
+                },
+            })
+        })
+        .collect()
+}
+
+/// File list and patch against the base commit, computed with `scratch` as
+/// the index.
+async fn scratch_diff(
+    wt: &ManagedWorktree,
+    scratch: &Path,
+) -> Result<(Vec<WorktreeChange>, String), String> {
+    let path = Path::new(&wt.path);
+    let env = [("GIT_INDEX_FILE", scratch.as_os_str())];
+    let untracked = git_output_with_env(
+        path,
+        &["ls-files", "--others", "--exclude-standard", "-z"],
+        &env,
+    )
+    .await?;
+    let untracked: Vec<&str> = untracked.split('\0').filter(|f| !f.is_empty()).collect();
+    if !untracked.is_empty() {
+        let mut args = vec!["add", "--intent-to-add", "--"];
+        args.extend(untracked);
+        git_output_with_env(path, &args, &env).await?;
+    }
+    let files = parse_name_status(
+        &git_output_with_env(
+            path,
+            &["diff", "--name-status", "-M", &wt.base_commit],
+            &env,
+        )
+        .await?,
+    );
+    let diff = git_output_with_env(path, &["diff", "-M", &wt.base_commit], &env).await?;
+    Ok((files, diff))
+}
+
+/// Diff of the worktree against its base commit, new files included. New
+/// files are marked intent-to-add in a scratch copy of the worktree's index,
+/// so the agent's staging area is left as it was.
+async fn diff_worktree(wt: &ManagedWorktree) -> Result<WorktreeDiff, String> {
+    let path = Path::new(&wt.path);
+    let index = git_output(path, &["rev-parse", "--git-path", "index"]).await?;
+    let index = path.join(index.trim());
+    let scratch = std::env::temp_dir().join(format!(
+        "tokenicode-index-{}",
+        uuid::Uuid::new_v4().simple()
+    ));
+    if index.is_file() {
+        std::fs::copy(&index, &scratch).map_err(|e| format!("Failed to copy git index: {}", e))?;
+    }
+    let result = scratch_diff(wt, &scratch).await;
+    let _ = std::fs::remove_file(&scratch);
+    let (files, mut diff) = result?;
+    let truncated = diff.len() > MAX_DIFF_BYTES;
+    if truncated {
+        let mut cut = MAX_DIFF_BYTES;
+        while !diff.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        diff.truncate(cut);
+    }
+    Ok(WorktreeDiff {
+        branch: wt.branch.clone(),
+        base_commit: wt.base_commit.clone(),
+        files,
+        diff,
+        truncated,
+    })
+}
+
+async fn remove_worktree(wt: &ManagedWorktree, delete_branch: bool) -> Result<(), String> {
+    let repo = Path::new(&wt.repo_root);
+    if Path::new(&wt.path).exists() {
+        git_output(repo, &["worktree", "remove", "--force", &wt.path]).await?;
+    } else {
+        git_output(repo, &["worktree", "prune"]).await?;
+    }
+    if delete_branch {
+        git_output(repo, &["branch", "-D", &wt.branch]).await?;
+    }
+    eprintln!("[TOKENICODE] removed worktree {} ({})", wt.path, wt.branch);
+    Ok(())
+}
+
+/// Undo a failed merge into the main checkout. The checkout was clean
+/// before the merge, so every path differing from HEAD came from it; only
+/// those paths are restored, then the merge state is dropped.
+async fn roll_back_merge(repo: &Path, squash: bool) {
+    let touched = git_output(repo, &["diff", "--name-only", "-z", "HEAD"])
+        .await
+        .unwrap_or_default();
+    let paths: Vec<&str> = touched.split('\0').filter(|p| !p.is_empty()).collect();
+    if !paths.is_empty() {
+        let mut args = vec!["restore", "--source=HEAD", "--staged", "--worktree", "--"];
+        args.extend(paths);
+        if let Err(e) = git_output(repo, &args).await {
+            eprintln!("[TOKENICODE] merge rollback failed: {}", e);
+        }
+    }
+    if squash {
+        // `--squash` leaves no MERGE_HEAD, only a prepared commit message.
+        if let Ok(msg) = git_output(repo, &["rev-parse", "--git-path", "SQUASH_MSG"]).await {
+            let _ = std::fs::remove_file(repo.join(msg.trim()));
+        }
+    } else {
+        let _ = git_output(repo, &["merge", "--quit"]).await;
+    }
+}
+
+async fn merge_worktree(
+    wt: &ManagedWorktree,
+    squash: bool,
+    message: Option<&str>,
+) -> Result<WorktreeMergeResult, String> {
+    let base = wt.base_branch.as_deref().ok_or_else(|| {
+        "The session started on a detached HEAD — there is no branch to merge it into".to_string()
+    })?;
+    let repo = Path::new(&wt.repo_root);
+    let worktree = Path::new(&wt.path);
+
+    // 1. Merge into the branch the session started from, never elsewhere,
+    //    and only into a clean checkout so a failed merge can be rolled back
+    //    without touching the user's own changes.
+    let current = git_output(repo, &["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .await
+        .map(|b| b.trim().to_string())
+        .map_err(|_| {
+            "The main checkout is on a detached HEAD — check out a branch first".to_string()
+        })?;
+    if current != base {
+        return Err(format!(
+            "The main checkout is on '{}' but the session started from '{}' — switch back before merging",
+            current, base
+        ));
+    }
+    if is_dirty(repo).await? {
+        return Err(
+            "The main checkout has uncommitted changes — commit or stash them before merging"
+                .to_string(),
+        );
+    }
+
+    // 2. Commit whatever the agent left uncommitted (hooks run).
+    if worktree.is_dir() && is_dirty(worktree).await? {
+        git_output(worktree, &["add", "--all"]).await?;
+        git_output(
+            worktree,
+            &[
+                "commit",
+                "-m",
+                "Uncommitted changes from TOKENICODE session",
+            ],
+        )
+        .await?;
+    }
+    if commits_ahead(wt).await == 0 {
+        return Ok(WorktreeMergeResult {
+            message: format!("{} has no changes to merge", wt.branch),
+            ..Default::default()
+        });
+    }
+
+    // 3. Merge; on conflict or a rejected commit, undo only what it touched.
+    let default_message = format!("Merge TOKENICODE session branch {}", wt.branch);
+    let message = message
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .unwrap_or(&default_message);
+    let merged = if squash {
+        match git_output(repo, &["merge", "--squash", &wt.branch]).await {
+            Ok(_) => git_output(repo, &["commit", "-m", message])
+                .await
+                .map(|_| ()),
+            Err(e) => Err(e),
+        }
+    } else {
+        git_output(repo, &["merge", "--no-ff", "-m", message, &wt.branch])
+            .await
+            .map(|_| ())
+    };
+
+    if let Err(e) = merged {
+        let conflicts: Vec<String> = git_output(repo, &["diff", "--name-only", "--diff-filter=U"])
+            .await
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        roll_back_merge(repo, squash).await;
+        if conflicts.is_empty() {
+            return Err(e);
+        }
+        return Ok(WorktreeMergeResult {
+            conflicts,
+            message: format!("Merge of {} conflicted and was aborted", wt.branch),
+            ..Default::default()
+        });
+    }
+
+    let commit = git_output(repo, &["rev-parse", "HEAD"])
+        .await
+        .ok()
+        .map(|c| c.trim().to_string());
+    Ok(WorktreeMergeResult {
+        merged: true,
+        commit,
+        message: format!("Merged {} into {}", wt.branch, current),
+        ..Default::default()
+    })
+}
+
+async fn forget(session_id: &str) -> Result<(), String> {
+    let _guard = REGISTRY_LOCK.lock().await;
+    let mut registry = load_registry();
+    registry.retain(|w| w.session_id != session_id);
+    save_registry(&registry)
+}
+
+// ---------------------------------------------------------------------------
+// Commands
+// ---------------------------------------------------------------------------
+
+/// Every managed worktree with its current state.
+#[tauri::command]
+pub async fn list_session_worktrees() -> Result<Vec<WorktreeStatus>, String> {
+    let mut out = Vec::new();
+    for wt in load_registry() {
+        let path = Path::new(&wt.path);
+        let exists = path.is_dir();
+        let dirty = exists && is_dirty(path).await.unwrap_or(false);
+        let commits_ahead = commits_ahead(&wt).await;
+        out.push(WorktreeStatus {
+            worktree: wt,
+            exists,
+            dirty,
+            commits_ahead,
+        });
+    }
+    Ok(out)
+}
+
+/// Changes in a session's worktree relative to where it branched off.
+#[tauri::command]
+pub async fn diff_session_worktree(session_id: String) -> Result<WorktreeDiff, String> {
+    diff_worktree(&lookup(&session_id)?).await
+}
+
+/// Merge a finished session's branch into the branch it started from.
+/// Uncommitted changes are committed first; on conflict the merge is aborted
+/// and the conflicting files are returned. `remove` deletes the worktree and
+/// branch after a successful merge.
+#[tauri::command]
+pub async fn merge_session_worktree(
+    processes: State<'_, ProcessManager>,
+    session_id: String,
+    squash: Option<bool>,
+    message: Option<String>,
+    remove: Option<bool>,
+) -> Result<WorktreeMergeResult, String> {
+    let wt = lookup(&session_id)?;
+    ensure_not_running(&processes, &wt).await?;
+    let mut result = merge_worktree(&wt, squash.unwrap_or(false), message.as_deref()).await?;
+    if result.merged && remove.unwrap_or(false) {
+        remove_worktree(&wt, true).await?;
+        forget(&session_id).await?;
+        result.removed = true;
+    }
+    Ok(result)
+}
+
+/// Throw away a session's worktree (and by default its branch).
+#[tauri::command]
+pub async fn discard_session_worktree(
+    processes: State<'_, ProcessManager>,
+    session_id: String,
+    keep_branch: Option<bool>,
+) -> Result<(), String> {
+    let wt = lookup(&session_id)?;
+    ensure_not_running(&processes, &wt).await?;
+    remove_worktree(&wt, !keep_branch.unwrap_or(false)).await?;
+    forget(&session_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn git(cwd: &Path, args: &[&str]) -> String {
+        git_output(cwd, args).await.unwrap()
+    }
+
+    async fn init_repo(dir: &Path) {
+        git(dir, &["init", "-q", "-b", "main"]).await;
+        git(dir, &["config", "user.email", "t@example.com"]).await;
+        git(dir, &["config", "user.name", "T"]).await;
+        std::fs::create_dir_all(dir.join("pkg")).unwrap();
+        std::fs::write(dir.join("pkg/a.txt"), "one\n").unwrap();
+        git(dir, &["add", "."]).await;
+        git(dir, &["commit", "-q", "-m", "init"]).await;
+    }
+
+    #[test]
+    fn slugs_and_name_status() {
+        assert_eq!(branch_slug("desk_17:ab/c"), "desk_17-ab-c");
+        assert_eq!(branch_slug("::"), "session");
+        let changes = parse_name_status("M\tpkg/a.txt\nR100\told.txt\tnew.txt\nA\tb.txt\n");
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[1].status, "R");
+        assert_eq!(changes[1].old_path.as_deref(), Some("old.txt"));
+        assert_eq!(changes[1].path, "new.txt");
+    }
+
+    #[tokio::test]
+    async fn worktree_diff_and_merge_round_trip() {
+        if git_output(Path::new("."), &["--version"]).await.is_err() {
+            return;
+        }
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = tmp.path().join("repo");
+        std::fs::create_dir_all(&repo).unwrap();
+        init_repo(&repo).await;
+        let managed = tmp.path().join("managed");
+
+        let wt = create_worktree("desk_1", &repo.join("pkg"), &managed)
+            .await
+            .unwrap();
+        assert_eq!(wt.branch, "tokenicode/desk_1");
+        assert_eq!(wt.base_branch.as_deref(), Some("main"));
+        assert!(wt.session_cwd.ends_with("pkg"));
+        // A second worktree for the same id gets a suffixed branch.
+        let other = create_worktree("desk_1", &repo, &managed).await.unwrap();
+        assert_eq!(other.branch, "tokenicode/desk_1-2");
+        remove_worktree(&other, true).await.unwrap();
+
+        std::fs::write(Path::new(&wt.session_cwd).join("a.txt"), "two\n").unwrap();
+        std::fs::write(Path::new(&wt.session_cwd).join("new.txt"), "new\n").unwrap();
+        let diff = diff_worktree(&wt).await.unwrap();
+        let paths: Vec<&str> = diff.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["pkg/a.txt", "pkg/new.txt"]);
+        assert!(diff.diff.contains("+two"));
+        // Reviewing leaves the worktree's index alone.
+        let status = git(Path::new(&wt.path), &["status", "--porcelain"]).await;
+        assert!(status.contains("?? pkg/new.txt"), "{}", status);
+        // The user's checkout is untouched until the merge.
+        assert_eq!(
+            std::fs::read_to_string(repo.join("pkg/a.txt")).unwrap(),
+            "one\n"
+        );
+
+        let detached = ManagedWorktree {
+            base_branch: None,
+            ..wt.clone()
+        };
+        assert!(merge_worktree(&detached, true, None).await.is_err());
+        assert!(is_dirty(Path::new(&wt.path)).await.unwrap());
+        // A dirty main checkout is refused before anything is touched.
+        std::fs::write(repo.join("pkg/a.txt"), "mine\n").unwrap();
+        git(&repo, &["add", "pkg/a.txt"]).await;
+        assert!(merge_worktree(&wt, true, None).await.is_err());
+        assert_eq!(
+            git(&repo, &["status", "--porcelain"]).await,
+            "M  pkg/a.txt\n"
+        );
+        git(&repo, &["reset", "-q", "--hard"]).await;
+
+        let result = merge_worktree(&wt, true, Some("Session work"))
+            .await
+            .unwrap();
+        assert!(result.merged, "{}", result.message);
+        assert_eq!(
+            std::fs::read_to_string(repo.join("pkg/a.txt")).unwrap(),
+            "two\n"
+        );
+        assert!(repo.join("pkg/new.txt").exists());
+        remove_worktree(&wt, true).await.unwrap();
+        assert!(!Path::new(&wt.path).exists());
+    }
+
+    #[tokio::test]
+    async fn conflicting_merge_rolls_back_cleanly() {
+        if git_output(Path::new("."), &["--version"]).await.is_err() {
+            return;
+        }
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = tmp.path().join("repo");
+        std::fs::create_dir_all(&repo).unwrap();
+        init_repo(&repo).await;
+        let wt = create_worktree("desk_2", &repo, &tmp.path().join("managed"))
+            .await
+            .unwrap();
+        std::fs::write(Path::new(&wt.path).join("pkg/a.txt"), "theirs\n").unwrap();
+        std::fs::write(Path::new(&wt.path).join("added.txt"), "new\n").unwrap();
+        std::fs::write(repo.join("pkg/a.txt"), "ours\n").unwrap();
+        git(&repo, &["commit", "-q", "-am", "ours"]).await;
+
+        for squash in [true, false] {
+            let result = merge_worktree(&wt, squash, None).await.unwrap();
+            assert!(!result.merged);
+            assert_eq!(result.conflicts, ["pkg/a.txt"]);
+            assert_eq!(git(&repo, &["status", "--porcelain"]).await, "");
+            assert!(!repo.join("added.txt").exists());
+        }
+        remove_worktree(&wt, true).await.unwrap();
+    }
+}
@@ -1,29 +1,198 @@
 //! Centralized backend→frontend event emission.
 //!
-//! All backend events should go through `emit_to_frontend`, which targets
-//! the main webview explicitly via `emit_to` and falls back to a global
-//! broadcast if the main webview is not yet available (e.g. during early
-//! setup before the main window exists).
+//! All backend events should go through one of two helpers:
 //!
-//! Routing through a single helper also gives us one place to thread a
-//! per-window label through when multi-window support lands (roadmap
-//! §4.3.8 / B12 follow-up).
+//!   - `emit_to_frontend` — app-wide events (`sessions:changed`, setup
+//!     progress, fs watcher). Broadcast once with `emit`; the frontend
+//!     listens for these with the global `listen()`, which already receives
+//!     them in every window, so sending a copy per window would deliver
+//!     duplicates.
+//!   - `emit_session_event` — per-session channels (`claude:stream:<id>`,
+//!     `claude:stderr:<id>`, `claude:exit:<id>`). Routed through the
+//!     [`WindowRouter`] to the window(s) subscribed to that session, so two
+//!     sessions detached onto separate windows never see each other's
+//!     traffic. Unbound sessions go to the main window.
 
 use serde::Serialize;
-use tauri::{AppHandle, Emitter};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use tauri::{AppHandle, Emitter, Manager};
 
 pub const MAIN_WINDOW_LABEL: &str = "main";
 
-/// Emit an event targeting the main webview, falling back to a global
-/// emit if `emit_to` fails (e.g. main window not yet created).
+/// Per-session subscription registry: session key (stdinId) → labels of
+/// the windows that receive its events. The first label is the owner — the
+/// window that shows the session tab; extra labels are read-only observers.
+#[derive(Default)]
+pub struct WindowRouter {
+    subscriptions: RwLock<BTreeMap<String, Vec<String>>>,
+}
+
+impl WindowRouter {
+    /// Make `label` the sole owner of `session_id`, dropping other subscribers.
+    pub fn bind(&self, session_id: &str, label: &str) {
+        if let Ok(mut subs) = self.subscriptions.write() {
+            subs.insert(session_id.to_string(), vec![label.to_string()]);
+        }
+    }
+
+    /// Add `label` as an additional subscriber. A session with no binding
+    /// is implicitly owned by main, which stays subscribed.
+    pub fn subscribe(&self, session_id: &str, label: &str) {
+        if let Ok(mut subs) = self.subscriptions.write() {
+            let labels = subs
+                .entry(session_id.to_string())
+                .or_insert_with(|| vec![MAIN_WINDOW_LABEL.to_string()]);
+            if !labels.iter().any(|l| l == label) {
+                labels.push(label.to_string());
+            }
+        }
+    }
+
+    /// Remove `label` from the session's subscribers. Removing the last one
+    /// drops the binding, which routes the session back to main.
+    pub fn unsubscribe(&self, session_id: &str, label: &str) {
+        if let Ok(mut subs) = self.subscriptions.write() {
+            if let Some(labels) = subs.get_mut(session_id) {
+                labels.retain(|l| l != label);
+                if labels.is_empty() {
+                    subs.remove(session_id);
+                }
+            }
+        }
+    }
+
+    /// Forget a window (closed/destroyed). Returns the sessions it owned,
+    /// which now route to main.
+    pub fn release_window(&self, label: &str) -> Vec<String> {
+        let mut orphaned = Vec::new();
+        if let Ok(mut subs) = self.subscriptions.write() {
+            subs.retain(|session_id, labels| {
+                let owned = labels.first().is_some_and(|l| l == label);
+                labels.retain(|l| l != label);
+                if owned {
+                    orphaned.push(session_id.clone());
+                }
+                !labels.is_empty()
+            });
+        }
+        orphaned
+    }
+
+    /// Windows that should receive events for `session_id` (main when unbound).
+    pub fn labels_for(&self, session_id: &str) -> Vec<String> {
+        self.subscriptions
+            .read()
+            .ok()
+            .and_then(|subs| subs.get(session_id).cloned())
+            .unwrap_or_else(|| vec![MAIN_WINDOW_LABEL.to_string()])
+    }
+
+    /// Owner window of `session_id` (main when unbound).
+    pub fn owner_of(&self, session_id: &str) -> String {
+        self.labels_for(session_id)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| MAIN_WINDOW_LABEL.to_string())
+    }
+
+    /// Sessions owned by `label`.
+    pub fn sessions_owned_by(&self, label: &str) -> Vec<String> {
+        self.subscriptions
+            .read()
+            .map(|subs| {
+                subs.iter()
+                    .filter(|(_, labels)| labels.first().is_some_and(|l| l == label))
+                    .map(|(id, _)| id.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Snapshot of every explicit binding.
+    pub fn snapshot(&self) -> BTreeMap<String, Vec<String>> {
+        self.subscriptions
+            .read()
+            .map(|subs| subs.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Emit an app-wide event to every window, once.
 pub fn emit_to_frontend<S>(app: &AppHandle, event: &str, payload: S) -> Result<(), String>
 where
     S: Serialize + Clone,
 {
-    if let Err(e1) = app.emit_to(MAIN_WINDOW_LABEL, event, payload.clone()) {
-        if let Err(e2) = app.emit(event, payload) {
-            return Err(format!("emit_to failed: {e1}, emit failed: {e2}"));
+    app.emit(event, payload)
+        .map_err(|e| format!("emit failed: {e}"))
+}
+
+/// Emit a per-session event to the window(s) subscribed to `session_id`.
+/// Falls back to `emit_to_frontend` when no subscribed window accepts it
+/// (e.g. the detached window was closed between lookup and emit).
+pub fn emit_session_event<S>(
+    app: &AppHandle,
+    session_id: &str,
+    event: &str,
+    payload: S,
+) -> Result<(), String>
+where
+    S: Serialize + Clone,
+{
+    let labels = match app.try_state::<WindowRouter>() {
+        Some(router) => router.labels_for(session_id),
+        None => vec![MAIN_WINDOW_LABEL.to_string()],
+    };
+    let delivered = labels
+        .iter()
+        .filter(|label| app.emit_to(label.as_str(), event, payload.clone()).is_ok())
+        .count();
+    if delivered == 0 {
+        if let Err(e1) = app.emit(event, payload) {
+            return Err(format!("emit_to {:?} failed, emit failed: {e1}", labels));
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unbound_sessions_route_to_main() {
+        let router = WindowRouter::default();
+        assert_eq!(router.labels_for("desk_1"), vec![MAIN_WINDOW_LABEL]);
+        assert_eq!(router.owner_of("desk_1"), MAIN_WINDOW_LABEL);
+    }
+
+    #[test]
+    fn bind_subscribe_and_release() {
+        let router = WindowRouter::default();
+        router.bind("desk_1", "session-a");
+        router.subscribe("desk_1", MAIN_WINDOW_LABEL);
+        router.subscribe("desk_2", "session-a");
+        assert_eq!(router.labels_for("desk_1"), vec!["session-a", "main"]);
+        assert_eq!(router.labels_for("desk_2"), vec!["main", "session-a"]);
+        assert_eq!(router.sessions_owned_by("session-a"), vec!["desk_1"]);
+
+        let orphaned = router.release_window("session-a");
+        assert_eq!(orphaned, vec!["desk_1"]);
+        assert_eq!(router.owner_of("desk_1"), MAIN_WINDOW_LABEL);
+        assert_eq!(router.labels_for("desk_2"), vec!["main"]);
+        assert!(router
+            .snapshot()
+            .values()
+            .flatten()
+            .all(|l| l != "session-a"));
+    }
+
+    #[test]
+    fn unsubscribing_last_label_drops_binding() {
+        let router = WindowRouter::default();
+        router.bind("desk_1", "session-a");
+        router.unsubscribe("desk_1", "session-a");
+        assert!(router.snapshot().is_empty());
+        assert_eq!(router.owner_of("desk_1"), MAIN_WINDOW_LABEL);
+    }
+}
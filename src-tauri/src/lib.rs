@@ -8,8 +8,8 @@ mod protocol;
 // code paths.
 mod windows_ps;
 
-use crate::events::emit_to_frontend;
-use crate::path_access::{PathAccessManager, PathCapability};
+use crate::events::{emit_session_event, emit_to_frontend, WindowRouter};
+use crate::path_access::{open_checked, GrantScope, PathAccessManager, PathCapability, PathGrant};
 use commands::{
     BypassModeMap, ManagedProcess, ProcessManager, SessionInfo, StartSessionParams, StdinManager,
 };
@@ -20,7 +20,7 @@ use serde_json::Value;
 use std::collections::{BTreeMap, HashMap};
 use std::process::Stdio;
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter, State};
+use tauri::{AppHandle, Emitter, Manager, State};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
 use tokio::sync::Mutex as TokioMutex;
@@ -412,7 +412,7 @@ async fn is_proxy_reachable(proxy_url: &str) -> bool {
 /// Logic: if a proxy URL is found in env/login-shell, probe the proxy port first.
 /// If reachable → use proxy; if not (VPN off) → bypass and connect directly.
 /// This makes the app "just work" regardless of VPN state.
-async fn build_smart_http_client(
+pub(crate) async fn build_smart_http_client(
     connect_timeout: std::time::Duration,
     request_timeout: std::time::Duration,
 ) -> reqwest::Client {
@@ -1410,7 +1410,7 @@ mod provider_capability_tests {
 /// Find the JSONL file for a given session UUID by scanning ~/.claude/projects/*/.
 /// Returns the path if found, None otherwise.
 /// Validates that session_id looks like a UUID to prevent path traversal.
-fn find_session_jsonl(session_id: &str) -> Option<std::path::PathBuf> {
+pub(crate) fn find_session_jsonl(session_id: &str) -> Option<std::path::PathBuf> {
     // Reject non-UUID session IDs to prevent path traversal (e.g. "../../../etc/passwd")
     if uuid::Uuid::parse_str(session_id).is_err() {
         eprintln!(
@@ -1595,32 +1595,29 @@ fn strip_thinking_from_value(value: &mut serde_json::Value) -> Option<usize> {
 /// Phase 4 §5.4 (S10): write a per-session MCP config scratch file so the
 /// CLI's `--strict-mcp-config` doesn't strip the user's configured servers.
 ///
-/// Reads `~/.claude.json`, extracts the `mcpServers` object, and writes
-/// `{"mcpServers": {...}}` into `~/.tokenicode/mcp-session-<stdin_id>.json`.
+/// Merges `mcpServers` from `~/.claude.json` and the approved entries of
+/// `{cwd}/.mcp.json`, drops the servers the session's `McpSelection`
+/// excludes, adds the built-in `tokenicode` server entry when given and not
+/// excluded too, and writes `{"mcpServers": {...}}` into
+/// `~/.tokenicode/mcp-session-<stdin_id>.json`.
 /// Returns `None` when there are no servers to carry over (or on I/O error).
-fn build_mcp_scratch_config(stdin_id: &str) -> Option<std::path::PathBuf> {
+fn build_mcp_scratch_config(
+    stdin_id: &str,
+    cwd: &str,
+    selection: Option<&commands::mcp_manager::McpSelection>,
+    builtin: Option<serde_json::Value>,
+) -> Option<std::path::PathBuf> {
     let home = dirs::home_dir()?;
-    let claude_json = home.join(".claude.json");
-    let raw = std::fs::read_to_string(&claude_json).ok()?;
-    let value: serde_json::Value = serde_json::from_str(&raw).ok()?;
-
-    // Tolerate the double-nested mcpServers.mcpServers shape the frontend
-    // already auto-fixes; prefer the outer layer when present.
-    let mut servers = value
-        .get("mcpServers")
-        .cloned()
-        .unwrap_or(serde_json::Value::Object(Default::default()));
-    if let Some(inner) = servers.get("mcpServers").cloned() {
-        if inner.is_object() {
-            servers = inner;
-        }
+    let mut servers = commands::mcp_manager::session_servers(Some(cwd), selection);
+    let builtin_allowed =
+        selection.is_none_or(|sel| sel.allows(commands::builtin_mcp::SERVER_NAME));
+    if let Some(entry) = builtin.filter(|_| builtin_allowed) {
+        servers.insert(commands::builtin_mcp::SERVER_NAME.to_string(), entry);
     }
 
     // No servers → skip --mcp-config entirely (CLI starts faster).
-    match &servers {
-        serde_json::Value::Object(m) if m.is_empty() => return None,
-        serde_json::Value::Object(_) => {}
-        _ => return None,
+    if servers.is_empty() {
+        return None;
     }
 
     let dir = home.join(".tokenicode");
@@ -1668,7 +1665,8 @@ async fn start_claude_session(
     stdin_mgr: State<'_, StdinManager>,
     bypass_modes: State<'_, BypassModeMap>,
     path_access: State<'_, PathAccessManager>,
-    params: StartSessionParams,
+    snapshots: State<'_, commands::turn_snapshots::TurnSnapshotState>,
+    mut params: StartSessionParams,
 ) -> Result<SessionInfo, String> {
     // Phase 3 §3.1: register the per-session cwd as a fixed path-access root
     // so all file commands running in this working directory are allowed.
@@ -1679,6 +1677,30 @@ async fn start_claude_session(
         .session_id
         .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
 
+    // Worktree isolation: run the CLI in a managed `git worktree` on its own
+    // branch so parallel sessions on one repo don't share a working tree.
+    // A resumed session gets the worktree it ran in before.
+    let worktree = if params.isolation.as_deref() == Some("worktree") {
+        let wt = commands::worktrees::prepare_session_worktree(
+            &session_id,
+            params.resume_session_id.as_deref(),
+            &params.cwd,
+        )
+        .await?;
+        path_access
+            .register_cwd(std::path::Path::new(&wt.session_cwd))
+            .await;
+        params.cwd = wt.session_cwd.clone();
+        Some(wt)
+    } else {
+        None
+    };
+
+    // Every user turn is preceded by a workspace snapshot of this cwd.
+    snapshots
+        .register(&session_id, &params.cwd, params.resume_session_id.clone())
+        .await;
+
     // Clean up any existing process with the same session_id
     stdin_mgr.remove(&session_id).await;
     state.remove(&session_id).await;
@@ -1709,7 +1731,35 @@ async fn start_claude_session(
     // inside the session. Solution: write the mcpServers block from
     // ~/.claude.json into a scratch file at ~/.tokenicode/mcp-session-<id>.json
     // and pass it via --mcp-config. Cleaned up on process exit.
-    let mcp_scratch_path = build_mcp_scratch_config(&session_id);
+    // The session's server selection comes from the params, else from the
+    // one remembered for this session (or the session being resumed).
+    let mcp_selection = match params.mcp_selection.clone() {
+        Some(sel) => {
+            // A resume keeps its CLI session id, so store under that as well.
+            let ids =
+                std::iter::once(session_id.as_str()).chain(params.resume_session_id.as_deref());
+            for id in ids {
+                if let Err(e) = commands::mcp_manager::store_selection(id, &sel) {
+                    eprintln!("[TOKENICODE] Failed to store MCP selection: {}", e);
+                }
+            }
+            Some(sel)
+        }
+        None => {
+            let mut ids = vec![session_id.as_str()];
+            ids.extend(params.resume_session_id.as_deref());
+            commands::mcp_manager::stored_selection(&ids)
+        }
+    };
+    let builtin_mcp = app
+        .try_state::<commands::builtin_mcp::BuiltinMcpState>()
+        .and_then(|s| s.session_entry(&session_id));
+    let mcp_scratch_path = build_mcp_scratch_config(
+        &session_id,
+        &params.cwd,
+        mcp_selection.as_ref(),
+        builtin_mcp,
+    );
     if let Some(ref scratch) = mcp_scratch_path {
         args.push("--mcp-config".to_string());
         args.push(scratch.to_string_lossy().to_string());
@@ -2244,6 +2294,21 @@ async fn start_claude_session(
                 Err(_) => continue, // skip non-JSON lines
             };
 
+            // system:init carries the CLI session id; per-session state kept
+            // under the desk id is linked to it so a resume finds it again.
+            if json.get("type").and_then(|v| v.as_str()) == Some("system")
+                && json.get("subtype").and_then(|v| v.as_str()) == Some("init")
+            {
+                if let Some(cli_id) = json.get("session_id").and_then(|v| v.as_str()) {
+                    commands::mcp_manager::link_selection(&sid_clone, cli_id);
+                    if let Some(snapshots) =
+                        app_clone.try_state::<commands::turn_snapshots::TurnSnapshotState>()
+                    {
+                        snapshots.link_cli_session(&sid_clone, cli_id).await;
+                    }
+                }
+            }
+
             // Intercept control_request messages for SDK control protocol routing.
             // All modes use --permission-prompt-tool stdio. In bypass mode, we
             // auto-approve tool permissions here (zero frontend overhead) but route
@@ -2343,7 +2408,12 @@ async fn start_claude_session(
                                 "parent_tool_use_id": parent_tool_use_id,
                                 "agent_id": agent_id,
                             });
-                            let _ = emit_to_frontend(&app_clone, &stream_event, perm_payload);
+                            let _ = emit_session_event(
+                                &app_clone,
+                                &sid_clone,
+                                &stream_event,
+                                perm_payload,
+                            );
                             continue; // Don't forward to stream as normal msg
                         }
                         "hook_callback" => {
@@ -2432,7 +2502,8 @@ async fn start_claude_session(
                     json
                 }
             };
-            if let Err(e) = emit_to_frontend(&app_clone, &stream_event, json_to_emit) {
+            if let Err(e) = emit_session_event(&app_clone, &sid_clone, &stream_event, json_to_emit)
+            {
                 emit_fail_count += 1;
                 // Log every 10 failures to avoid flooding stderr when the
                 // WebView is unresponsive for a sustained period.
@@ -2469,14 +2540,16 @@ async fn start_claude_session(
             sid_clone, line_count
         );
         // Emit process_exit on the stream channel (primary detection)
-        let _ = emit_to_frontend(
+        let _ = emit_session_event(
             &app_clone,
+            &sid_clone,
             &stream_event,
             serde_json::json!({"type": "process_exit"}),
         );
         // Also emit on the dedicated exit channel (backup detection via onSessionExit)
-        let _ = emit_to_frontend(
+        let _ = emit_session_event(
             &app_clone,
+            &sid_clone,
             &format!("claude:exit:{}", sid_clone),
             serde_json::json!(null),
         );
@@ -2494,6 +2567,14 @@ async fn start_claude_session(
 
         // Phase 4 §5.4 (S10): remove the per-session MCP scratch config.
         cleanup_mcp_scratch_config(&sid_clone);
+        if let Some(builtin) = app_clone.try_state::<commands::builtin_mcp::BuiltinMcpState>() {
+            builtin.release_session(&sid_clone);
+        }
+        if let Some(snapshots) =
+            app_clone.try_state::<commands::turn_snapshots::TurnSnapshotState>()
+        {
+            snapshots.forget(&sid_clone).await;
+        }
 
         // Signal kill_session that the process has fully exited
         exit_notify_clone.notify_one();
@@ -2506,8 +2587,9 @@ async fn start_claude_session(
         let reader = BufReader::with_capacity(256 * 1024, stderr);
         let mut lines = reader.lines();
         while let Ok(Some(line)) = lines.next_line().await {
-            let _ = emit_to_frontend(
+            let _ = emit_session_event(
                 &app_clone2,
+                &sid_clone2,
                 &format!("claude:stderr:{}", sid_clone2),
                 serde_json::json!(line),
             );
@@ -2516,6 +2598,8 @@ async fn start_claude_session(
 
     // Send the first message via stdin as NDJSON (skip if prompt is empty — pre-warm mode)
     if !params.prompt.is_empty() {
+        commands::turn_snapshots::snapshot_before_turn(&app, &snapshots, &sid, &params.prompt)
+            .await;
         let first_msg = serde_json::json!({
             "type": "user",
             "message": {
@@ -2531,15 +2615,19 @@ async fn start_claude_session(
         cli_session_id: params.resume_session_id.clone(),
         pid,
         cli_path: claude_bin.clone(),
+        worktree,
     })
 }
 
 #[tauri::command]
 async fn send_stdin(
+    app: AppHandle,
     stdin_mgr: State<'_, StdinManager>,
+    snapshots: State<'_, commands::turn_snapshots::TurnSnapshotState>,
     session_id: String,
     message: String,
 ) -> Result<(), String> {
+    commands::turn_snapshots::snapshot_before_turn(&app, &snapshots, &session_id, &message).await;
     // Wrap user text in stream-json NDJSON format
     let json_msg = serde_json::json!({
         "type": "user",
@@ -2697,7 +2785,7 @@ fn tracked_sessions_path() -> std::path::PathBuf {
 /// Load the set of tracked session IDs.
 /// If the tracking file is missing or empty, rebuild from ~/.claude/projects/
 /// to recover from index loss (e.g., after update, disk issue, new machine).
-fn load_tracked_sessions() -> std::collections::HashSet<String> {
+pub(crate) fn load_tracked_sessions() -> std::collections::HashSet<String> {
     use std::io::BufRead;
     let path = tracked_sessions_path();
     let mut set = std::collections::HashSet::new();
@@ -2791,6 +2879,13 @@ fn load_tracked_sessions() -> std::collections::HashSet<String> {
 /// Register a CLI session ID as managed by TOKENICODE
 #[tauri::command]
 async fn track_session(session_id: String) -> Result<(), String> {
+    append_tracked_session(&session_id)
+}
+
+/// Append a session ID to tracked_sessions.txt. Shared by `track_session`
+/// and backend-side flows (e.g. session import) that create sessions
+/// without a frontend round-trip.
+pub(crate) fn append_tracked_session(session_id: &str) -> Result<(), String> {
     // Defense-in-depth: never persist desk-generated temporary IDs
     if session_id.starts_with("desk_") {
         return Ok(());
@@ -2842,10 +2937,8 @@ fn cleanup_tracked_sessions() {
     }
 }
 
-/// Delete a session: remove from tracking file and delete the .jsonl file
-#[tauri::command]
-async fn delete_session(session_id: String, session_path: String) -> Result<(), String> {
-    // Remove from tracking file
+/// Remove a session ID from tracked_sessions.txt (no-op if the file is missing).
+pub(crate) fn remove_tracked_session(session_id: &str) -> Result<(), String> {
     let track_path = tracked_sessions_path();
     if track_path.exists() {
         use std::io::BufRead;
@@ -2865,6 +2958,14 @@ async fn delete_session(session_id: String, session_path: String) -> Result<(),
         std::fs::rename(&tmp, &track_path)
             .map_err(|e| format!("Failed to rename tracked sessions: {}", e))?;
     }
+    Ok(())
+}
+
+/// Delete a session: remove from tracking file and delete the .jsonl file
+#[tauri::command]
+async fn delete_session(session_id: String, session_path: String) -> Result<(), String> {
+    // Remove from tracking file
+    remove_tracked_session(&session_id)?;
     // Delete the .jsonl file — validate path is under ~/.claude/projects/ (P0-1 fix)
     if !session_path.is_empty() {
         let target = std::path::Path::new(&session_path);
@@ -2880,6 +2981,15 @@ async fn delete_session(session_id: String, session_path: String) -> Result<(),
                     canonical
                 ));
             }
+            // Drop the attachments only this session referenced.
+            match commands::attachments::release_session_attachments(&canonical) {
+                Ok((files, bytes)) if files > 0 => eprintln!(
+                    "[TOKENICODE] Removed {} attachment(s) ({} bytes) of deleted session {}",
+                    files, bytes, session_id
+                ),
+                Ok(_) => {}
+                Err(e) => eprintln!("[TOKENICODE] Attachment cleanup failed: {}", e),
+            }
             std::fs::remove_file(&canonical)
                 .map_err(|e| format!("Failed to delete session file: {}", e))?;
         }
@@ -2964,7 +3074,7 @@ async fn list_sessions() -> Result<Vec<Value>, String> {
 /// Search across tracked session JSONL files for a query string.
 /// Returns matching sessions with snippets, sorted by match_count descending (max 50).
 #[tauri::command]
-async fn search_sessions(query: String) -> Result<Vec<Value>, String> {
+pub(crate) async fn search_sessions(query: String) -> Result<Vec<Value>, String> {
     if query.len() < 2 {
         return Ok(vec![]);
     }
@@ -3157,7 +3267,7 @@ fn search_session_file(path: &std::path::Path, query_lower: &str) -> Option<serd
 
 /// Extract preview (first user message) and cwd from a session .jsonl file.
 /// Returns (preview, cwd) — cwd may be empty if not found.
-fn extract_session_info(path: &std::path::Path) -> (String, String) {
+pub(crate) fn extract_session_info(path: &std::path::Path) -> (String, String) {
     use std::io::BufRead;
     let file = match std::fs::File::open(path) {
         Ok(f) => f,
@@ -3256,6 +3366,16 @@ fn extract_session_info(path: &std::path::Path) -> (String, String) {
     (preview, cwd)
 }
 
+/// Encode a project path the way Claude CLI names its
+/// `~/.claude/projects/<encoded>/` directories: every character that is not
+/// ASCII alphanumeric becomes `-` (so `/Users/a/my.app` → `-Users-a-my-app`).
+/// This is the lossy inverse of `decode_project_name`.
+pub(crate) fn encode_project_name(path: &str) -> String {
+    path.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
 /// Decode project directory name back to readable path.
 ///
 /// Claude CLI encodes paths by replacing `/` with `-`, e.g.:
@@ -3273,7 +3393,7 @@ fn extract_session_info(path: &std::path::Path) -> (String, String) {
 /// candidate span of dash-separated parts, try joining them with the
 /// original `-`, then ` ` (space), then `.` — whichever produces a path
 /// that actually exists on disk wins.
-fn decode_project_name(encoded: &str) -> String {
+pub(crate) fn decode_project_name(encoded: &str) -> String {
     // Detect Windows-style encoded paths: "C-Users-..." (drive letter prefix without leading dash)
     // vs Unix-style: "-Users-..." (leading dash = root /)
     let is_windows_path = encoded.len() >= 2
@@ -3769,11 +3889,49 @@ async fn add_path_grant(
     path_access: State<'_, PathAccessManager>,
     tab_id: String,
     path: String,
-) -> Result<(), String> {
+    capability: Option<PathCapability>,
+    expires_in_secs: Option<u64>,
+    project: Option<String>,
+) -> Result<PathGrant, String> {
+    // With `project`, the grant is persisted and applies to every tab of that
+    // project; otherwise it belongs to the tab. Defaults to read-only.
+    let scope = match project {
+        Some(p) => GrantScope::Project(std::path::PathBuf::from(p)),
+        None => GrantScope::Tab(tab_id),
+    };
+    let expires_at_ms = expires_in_secs.map(|secs| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+            .saturating_add(secs.saturating_mul(1000))
+    });
     path_access
-        .add_grant(&tab_id, std::path::Path::new(&path))
-        .await;
-    Ok(())
+        .grant(
+            std::path::Path::new(&path),
+            capability.unwrap_or(PathCapability::Read),
+            scope,
+            expires_at_ms,
+        )
+        .await
+}
+
+/// Active grants for a tab (plus project grants of open projects), or all
+/// grants when `tab_id` is omitted.
+#[tauri::command]
+async fn list_path_grants(
+    path_access: State<'_, PathAccessManager>,
+    tab_id: Option<String>,
+) -> Result<Vec<PathGrant>, String> {
+    path_access.list_grants(tab_id.as_deref()).await
+}
+
+#[tauri::command]
+async fn revoke_path_grant(
+    path_access: State<'_, PathAccessManager>,
+    grant_id: String,
+) -> Result<bool, String> {
+    path_access.revoke_grant(&grant_id).await
 }
 
 #[tauri::command]
@@ -3803,82 +3961,55 @@ async fn read_file_tree(
     // (preview, read) work even before the first CLI session is started.
     path_access.register_cwd(std::path::Path::new(&path)).await;
     let max_depth = depth.unwrap_or(5);
-    let root = std::path::Path::new(&path);
+    let root = std::path::PathBuf::from(&path);
     if !root.exists() {
         return Err("Directory does not exist".to_string());
     }
-    Ok(read_dir_recursive(root, 0, max_depth))
-}
-
-fn read_dir_recursive(dir: &std::path::Path, current_depth: u32, max_depth: u32) -> Vec<FileNode> {
-    let mut nodes = vec![];
-    let entries = match std::fs::read_dir(dir) {
-        Ok(e) => e,
-        Err(_) => return nodes,
-    };
-
-    // PATCH C (v0.10.5): snapshot is_dir() + lowercase name BEFORE sort_by.
-    //
-    // The previous closure called `a.path().is_dir()` inline — a filesystem
-    // syscall on every comparison. While the sort runs, the Claude CLI
-    // concurrently writes SDK checkpoint / temp files into the workspace,
-    // so the same entry can return `true` on one call and `false` on the next.
-    // Rust 1.81+'s strict total-order check detects this violation and panics
-    // the tokio worker thread, tearing down all async tauri commands (CLI
-    // detection, file scan, chat) → "CLI env / file manager disappears after
-    // first response" + flood of "Couldn't find callback id" warnings.
-    let mut entries_meta: Vec<(std::fs::DirEntry, bool, String)> = entries
-        .flatten()
-        .map(|e| {
-            let is_dir = e.path().is_dir();
-            let lower = e.file_name().to_string_lossy().to_lowercase();
-            (e, is_dir, lower)
+    tokio::task::spawn_blocking(move || {
+        let filter = commands::file_tree::TreeFilter::for_root(&root);
+        let ignore = commands::ignore_rules::IgnoreMatcher::for_dir(&root);
+        read_dir_recursive(&root, &filter, &ignore, 0, max_depth)
+    })
+    .await
+    .map_err(|e| format!("Failed to read file tree: {}", e))
+}
+
+/// Gitignore-aware and capped per directory; see `commands::file_tree` for
+/// the lazy and streaming variants.
+fn read_dir_recursive(
+    dir: &std::path::Path,
+    filter: &commands::file_tree::TreeFilter,
+    ignore: &commands::ignore_rules::IgnoreMatcher,
+    current_depth: u32,
+    max_depth: u32,
+) -> Vec<FileNode> {
+    let listing = commands::file_tree::list_dir(dir, filter, ignore, 0, None);
+    listing
+        .entries
+        .into_iter()
+        .map(|entry| {
+            let path = std::path::PathBuf::from(&entry.path);
+            let children = if entry.is_dir && current_depth < max_depth {
+                Some(read_dir_recursive(
+                    &path,
+                    filter,
+                    &ignore.child(&path),
+                    current_depth + 1,
+                    max_depth,
+                ))
+            } else if entry.is_dir {
+                Some(vec![]) // Placeholder for unexpanded dirs
+            } else {
+                None
+            };
+            FileNode {
+                name: entry.name,
+                path: entry.path,
+                is_dir: entry.is_dir,
+                children,
+            }
         })
-        .collect();
-    entries_meta.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.2.cmp(&b.2)));
-
-    for (entry, _, _) in entries_meta {
-        let name = entry.file_name().to_string_lossy().to_string();
-        // Skip specific ignored dirs (but show dotfiles like .claude, .github, .vscode)
-        if name == "node_modules"
-            || name == "target"
-            || name == "__pycache__"
-            || name == ".git"
-            || name == ".DS_Store"
-            || name == "Thumbs.db"
-            || name == ".venv"
-            || name == "venv"
-            || name == ".env"
-            || name == "dist"
-            || name == "build"
-            || name == ".next"
-            || name == ".nuxt"
-            || name == ".parcel-cache"
-            || name == "coverage"
-            || name == ".turbo"
-            || name == ".svelte-kit"
-        {
-            continue;
-        }
-
-        let path = entry.path();
-        let is_dir = path.is_dir();
-        let children = if is_dir && current_depth < max_depth {
-            Some(read_dir_recursive(&path, current_depth + 1, max_depth))
-        } else if is_dir {
-            Some(vec![]) // Placeholder for unexpanded dirs
-        } else {
-            None
-        };
-
-        nodes.push(FileNode {
-            name,
-            path: path.to_string_lossy().to_string(),
-            is_dir,
-            children,
-        });
-    }
-    nodes
+        .collect()
 }
 
 #[tauri::command]
@@ -3974,7 +4105,9 @@ async fn write_file_content(
     path: String,
     content: String,
     tab_id: Option<String>,
-) -> Result<(), String> {
+    expected_hash: Option<String>,
+    expected_mtime_ms: Option<u64>,
+) -> Result<commands::safe_write::WriteOutcome, String> {
     let p = path_access
         .validate(
             std::path::Path::new(&path),
@@ -3982,7 +4115,9 @@ async fn write_file_content(
             PathCapability::Write,
         )
         .await?;
-    std::fs::write(&p, &content).map_err(|e| format!("Cannot write file: {}", e))
+    // Refuse to clobber changes made since the caller read the file (e.g. by
+    // the agent); without an expected version this is a plain atomic save.
+    commands::safe_write::write_checked(&p, &content, expected_hash.as_deref(), expected_mtime_ms)
 }
 
 #[tauri::command]
@@ -4006,9 +4141,21 @@ async fn copy_file(
             PathCapability::Write,
         )
         .await?;
-    std::fs::copy(&s, &d)
-        .map(|_| ())
-        .map_err(|e| format!("Cannot copy file: {}", e))
+    // Re-check both ends through their handles so neither can be swapped for
+    // a symlink or special file after validation.
+    let mut src_file = open_checked(&s, std::fs::OpenOptions::new().read(true))?;
+    let mut dest_file = open_checked(
+        &d,
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true),
+    )?;
+    std::io::copy(&mut src_file, &mut dest_file).map_err(|e| format!("Cannot copy file: {}", e))?;
+    if let Ok(meta) = src_file.metadata() {
+        let _ = dest_file.set_permissions(meta.permissions());
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -4018,15 +4165,16 @@ async fn rename_file(
     dest: String,
     tab_id: Option<String>,
 ) -> Result<(), String> {
+    // Rename moves the entry itself: a symlink is renamed, never its target.
     let s = path_access
-        .validate(
+        .validate_entry(
             std::path::Path::new(&src),
             tab_id.as_deref(),
             PathCapability::Write,
         )
         .await?;
     let d = path_access
-        .validate(
+        .validate_entry(
             std::path::Path::new(&dest),
             tab_id.as_deref(),
             PathCapability::Write,
@@ -4041,8 +4189,9 @@ async fn delete_file(
     path: String,
     tab_id: Option<String>,
 ) -> Result<(), String> {
+    // Trash the entry itself: deleting a symlink never touches its target.
     let p = path_access
-        .validate(
+        .validate_entry(
             std::path::Path::new(&path),
             tab_id.as_deref(),
             PathCapability::Delete,
@@ -4255,14 +4404,16 @@ async fn list_recent_projects() -> Result<Vec<Value>, String> {
     Ok(result)
 }
 
-/// Start watching a directory for file changes, emit events to frontend
+/// Start watching a directory for file changes, emit coalesced `fs:change`
+/// batches to the frontend
 #[tauri::command]
 async fn watch_directory(
     app: AppHandle,
     state: State<'_, WatcherManager>,
+    path_index: State<'_, commands::path_index::PathIndexState>,
     path: String,
 ) -> Result<(), String> {
-    use notify::{Event, EventKind, RecursiveMode, Watcher};
+    use notify::{Event, RecursiveMode, Watcher};
 
     // Stop existing watcher for this path if any
     {
@@ -4270,50 +4421,18 @@ async fn watch_directory(
         watchers.remove(&path);
     }
 
-    let app_clone = app.clone();
-    let path_clone = path.clone();
-
-    // Directories whose changes are noise for the UI (high-frequency writes by CLI, git, etc.)
-    const IGNORED_SEGMENTS: &[&str] = &[
-        ".claude",
-        ".git",
-        "node_modules",
-        ".next",
-        "target",
-        "__pycache__",
-        ".venv",
-    ];
+    // Raw events are coalesced per root (gitignore filtering, dedup, rename
+    // pairing, debounce) and emitted as `fs:change` batches by the flusher.
+    let coalescer = Arc::new(std::sync::Mutex::new(commands::fs_watch::FsCoalescer::new(
+        std::path::Path::new(&path),
+    )));
+    commands::fs_watch::spawn_flusher(app.clone(), path.clone(), &coalescer);
 
     let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
         if let Ok(event) = res {
-            let kind = match event.kind {
-                EventKind::Create(_) => "created",
-                EventKind::Modify(_) => "modified",
-                EventKind::Remove(_) => "removed",
-                _ => return,
-            };
-            // Filter out paths under ignored directories to prevent UI render storms
-            let paths: Vec<String> = event
-                .paths
-                .iter()
-                .filter(|p| {
-                    !p.components()
-                        .any(|c| IGNORED_SEGMENTS.iter().any(|seg| c.as_os_str() == *seg))
-                })
-                .map(|p| p.to_string_lossy().to_string())
-                .collect();
-            if paths.is_empty() {
-                return;
+            if let Ok(mut pending) = coalescer.lock() {
+                pending.push(&event, std::time::Instant::now());
             }
-            let _ = emit_to_frontend(
-                &app_clone,
-                "fs:change",
-                serde_json::json!({
-                    "kind": kind,
-                    "paths": paths,
-                    "root": path_clone,
-                }),
-            );
         }
     })
     .map_err(|e| format!("Failed to create watcher: {}", e))?;
@@ -4322,6 +4441,7 @@ async fn watch_directory(
         .watch(std::path::Path::new(&path), RecursiveMode::Recursive)
         .map_err(|e| format!("Failed to watch: {}", e))?;
 
+    path_index.attach(&path);
     let mut watchers = state.watchers.lock().await;
     watchers.insert(path, watcher);
 
@@ -4329,9 +4449,14 @@ async fn watch_directory(
 }
 
 #[tauri::command]
-async fn unwatch_directory(state: State<'_, WatcherManager>, path: String) -> Result<(), String> {
+async fn unwatch_directory(
+    state: State<'_, WatcherManager>,
+    path_index: State<'_, commands::path_index::PathIndexState>,
+    path: String,
+) -> Result<(), String> {
     let mut watchers = state.watchers.lock().await;
     watchers.remove(&path);
+    path_index.detach(&path);
     Ok(())
 }
 
@@ -4364,26 +4489,8 @@ async fn save_temp_file(
 ) -> Result<String, String> {
     // If a working directory is provided, save inside it so Claude CLI can access the file.
     // Falls back to system temp if cwd is not set.
-    let tmp = if let Some(ref dir) = cwd {
-        let p = std::path::PathBuf::from(dir)
-            .join(".tokenicode")
-            .join("tmp");
-        if std::fs::create_dir_all(&p).is_ok() {
-            // Ensure .tokenicode is gitignored in user's project
-            let gitignore = std::path::PathBuf::from(dir)
-                .join(".tokenicode")
-                .join(".gitignore");
-            if !gitignore.exists() {
-                let _ = std::fs::write(&gitignore, "*\n");
-            }
-            p
-        } else {
-            std::env::temp_dir().join("tokenicode")
-        }
-    } else {
-        std::env::temp_dir().join("tokenicode")
-    };
-    std::fs::create_dir_all(&tmp).map_err(|e| format!("Failed to create temp dir: {}", e))?;
+    let tmp = commands::attachments::attachment_dir(cwd.as_deref())?;
+    commands::attachment_gc::remember_attachment_dir(&tmp);
 
     // Split name into stem + extension, append timestamp + counter for uniqueness
     let path_buf = std::path::PathBuf::from(&name);
@@ -4426,9 +4533,9 @@ struct UnifiedCommand {
     name: String,
     description: String,
     source: String,   // "builtin" | "global" | "project"
-    category: String, // "builtin" | "command" | "skill"
+    category: String, // "builtin" | "command" | "skill" | "agent"
     has_args: bool,
-    path: Option<String>, // Only for skills, points to SKILL.md
+    path: Option<String>, // Only for skills and agents, points to the .md file
     immediate: bool,      // true = execute immediately (no message sent)
     #[serde(skip_serializing_if = "Option::is_none")]
     execution: Option<String>, // "ui" | "cli" | "session" — how command is executed
@@ -4533,11 +4640,11 @@ async fn list_slash_commands(cwd: Option<String>) -> Result<Vec<SlashCommand>, S
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-struct SkillInfo {
-    name: String,
+pub(crate) struct SkillInfo {
+    pub(crate) name: String,
     description: String,
-    path: String,
-    scope: String,
+    pub(crate) path: String,
+    pub(crate) scope: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     disable_model_invocation: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -4554,18 +4661,25 @@ struct SkillInfo {
     agent: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     version: Option<String>,
+    /// Lint findings for this SKILL.md (see `commands::skill_lint`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) diagnostics: Vec<commands::skill_lint::SkillDiagnostic>,
 }
 
 /// YAML frontmatter fields for SKILL.md files
 #[derive(Debug, Deserialize, Default)]
-struct SkillFrontmatter {
+pub(crate) struct SkillFrontmatter {
     #[serde(default)]
     description: Option<String>,
     #[serde(default, rename = "disable-model-invocation")]
     disable_model_invocation: Option<bool>,
     #[serde(default, rename = "user-invocable")]
     user_invocable: Option<bool>,
-    #[serde(default, rename = "allowed-tools")]
+    #[serde(
+        default,
+        rename = "allowed-tools",
+        deserialize_with = "deserialize_tool_list"
+    )]
     allowed_tools: Option<Vec<String>>,
     #[serde(default, rename = "argument-hint")]
     argument_hint: Option<String>,
@@ -4579,12 +4693,48 @@ struct SkillFrontmatter {
     version: Option<String>,
 }
 
+/// `allowed-tools` may be a YAML list or the comma-separated string form the
+/// CLI also accepts (`allowed-tools: Read, Grep, Bash(git:*)`).
+pub(crate) fn deserialize_tool_list<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ToolList {
+        List(Vec<String>),
+        Csv(String),
+    }
+    Ok(match Option::<ToolList>::deserialize(deserializer)? {
+        Some(ToolList::List(v)) => Some(v),
+        Some(ToolList::Csv(s)) => Some(
+            s.split(',')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+        ),
+        None => None,
+    })
+}
+
 /// Parse YAML frontmatter from a SKILL.md file content.
 /// Returns (parsed frontmatter, body text after frontmatter).
 fn parse_skill_frontmatter(content: &str) -> (SkillFrontmatter, &str) {
+    parse_frontmatter::<SkillFrontmatter>(content)
+}
+
+/// Parse `---` delimited YAML frontmatter into `T` (skills, agents).
+/// Missing or invalid frontmatter yields `T::default()` and the whole content
+/// as body, so a broken file still shows up in listings.
+pub(crate) fn parse_frontmatter<T>(content: &str) -> (T, &str)
+where
+    T: serde::de::DeserializeOwned + Default,
+{
     let trimmed = content.trim_start();
     if !trimmed.starts_with("---") {
-        return (SkillFrontmatter::default(), content);
+        return (T::default(), content);
     }
     // Find the closing ---
     let after_open = &trimmed[3..];
@@ -4594,12 +4744,12 @@ fn parse_skill_frontmatter(content: &str) -> (SkillFrontmatter, &str) {
         let body = trimmed.get(body_start..).unwrap_or("");
         // Skip leading newline in body
         let body = body.strip_prefix('\n').unwrap_or(body);
-        match serde_yaml::from_str::<SkillFrontmatter>(yaml_str) {
+        match serde_yaml::from_str::<T>(yaml_str) {
             Ok(fm) => (fm, body),
-            Err(_) => (SkillFrontmatter::default(), content),
+            Err(_) => (T::default(), content),
         }
     } else {
-        (SkillFrontmatter::default(), content)
+        (T::default(), content)
     }
 }
 
@@ -4669,7 +4819,7 @@ fn update_frontmatter_field(content: &str, field: &str, value: Option<&str>) ->
 
 /// Scan and return all available skills (global + project)
 #[tauri::command]
-async fn list_skills(cwd: Option<String>) -> Result<Vec<SkillInfo>, String> {
+pub(crate) async fn list_skills(cwd: Option<String>) -> Result<Vec<SkillInfo>, String> {
     let mut skills: Vec<SkillInfo> = vec![];
 
     // Helper: scan a skills directory for */SKILL.md
@@ -4717,6 +4867,10 @@ async fn list_skills(cwd: Option<String>) -> Result<Vec<SkillInfo>, String> {
                         context: fm.context,
                         agent: fm.agent,
                         version: fm.version,
+                        diagnostics: commands::skill_lint::lint_skill_content(
+                            &content,
+                            &entry_path,
+                        ),
                     });
                 }
             }
@@ -4738,6 +4892,13 @@ async fn list_skills(cwd: Option<String>) -> Result<Vec<SkillInfo>, String> {
         skills.extend(scan_skills_dir(&project_dir, "project"));
     }
 
+    commands::skill_lint::add_duplicate_diagnostics(
+        "Skill",
+        skills
+            .iter_mut()
+            .map(|s| (s.name.as_str(), s.scope.as_str(), &mut s.diagnostics)),
+    );
+
     Ok(skills)
 }
 
@@ -5024,6 +5185,21 @@ async fn list_all_commands(cwd: Option<String>) -> Result<Vec<UnifiedCommand>, S
         commands.extend(scan_skills_dir(&project_dir, "project"));
     }
 
+    // 6. Agents: ~/.claude/agents/*.md and {cwd}/.claude/agents/*.md,
+    // invoked by mentioning `@agent-<name>` in a prompt
+    for agent in commands::agents::collect_agents(cwd.as_deref()) {
+        commands.push(UnifiedCommand {
+            name: format!("@agent-{}", agent.name),
+            description: agent.description,
+            source: agent.scope,
+            category: "agent".to_string(),
+            has_args: true,
+            path: Some(agent.path),
+            immediate: false,
+            execution: None,
+        });
+    }
+
     Ok(commands)
 }
 
@@ -5111,6 +5287,56 @@ fn resolve_git_binary() -> Option<&'static str> {
         .as_deref()
 }
 
+/// Run git in `cwd` with backend-built arguments and return stdout.
+/// No allowlist here — callers never forward user-chosen subcommands (that
+/// is what `run_git_command` is for). Prompts are disabled so a missing
+/// credential fails instead of hanging; hooks run as usual.
+pub(crate) async fn git_output(cwd: &std::path::Path, args: &[&str]) -> Result<String, String> {
+    git_output_with_env(cwd, args, &[]).await
+}
+
+/// `git_output` with extra environment variables (e.g. `GIT_INDEX_FILE` for
+/// operations on a scratch index).
+pub(crate) async fn git_output_with_env(
+    cwd: &std::path::Path,
+    args: &[&str],
+    envs: &[(&str, &std::ffi::OsStr)],
+) -> Result<String, String> {
+    #[cfg(target_os = "macos")]
+    let git_bin = resolve_git_binary()
+        .ok_or_else(|| "git not available (no Xcode CLT or Homebrew git found)".to_string())?;
+    #[cfg(not(target_os = "macos"))]
+    let git_bin = "git";
+
+    let mut cmd = Command::new(git_bin);
+    cmd.args(args)
+        .current_dir(cwd)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .envs(envs.iter().copied())
+        .stdin(Stdio::null());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(0x08000000);
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let detail = if stderr.trim().is_empty() {
+            stdout.trim()
+        } else {
+            stderr.trim()
+        };
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            detail
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
 /// Run a git command in a specific working directory and return stdout.
 /// Only allows safe, read-or-restore git operations.
 #[tauri::command]
@@ -5126,11 +5352,17 @@ async fn run_git_command(cwd: String, args: Vec<String>) -> Result<String, Strin
         "rev-parse",
         "hash-object",
         "cat-file",
+        "worktree",
     ];
     let subcmd = args.first().map(|s| s.as_str()).unwrap_or("");
     if !allowed_subcommands.contains(&subcmd) {
         return Err(format!("Git subcommand '{}' not allowed", subcmd));
     }
+    // `worktree` is read-only here: creating and removing worktrees goes
+    // through the managed commands in `commands::worktrees`.
+    if subcmd == "worktree" && args.get(1).map(|s| s.as_str()) != Some("list") {
+        return Err("Only `git worktree list` is allowed".to_string());
+    }
 
     // P1-1: Reject null bytes in args (could truncate strings in C-level APIs)
     for arg in &args {
@@ -7583,7 +7815,7 @@ async fn save_custom_previews(data: Value) -> Result<(), String> {
     std::fs::write(&path, content).map_err(|e| format!("Failed to write session names: {}", e))
 }
 
-fn tokenicode_data_path(filename: &str) -> Result<std::path::PathBuf, String> {
+pub(crate) fn tokenicode_data_path(filename: &str) -> Result<std::path::PathBuf, String> {
     let home = dirs::home_dir().ok_or("Cannot find home dir")?;
     let dir = home.join(".tokenicode");
     if !dir.exists() {
@@ -7635,56 +7867,27 @@ async fn save_archived_sessions(data: Value) -> Result<(), String> {
     std::fs::write(&path, content).map_err(|e| format!("Failed to write archived sessions: {}", e))
 }
 
-/// Max wall-clock time the title-gen spawn may run. Beyond this we return
-/// `Ok(None)` — the frontend shows a default title and the user can rename
-/// later. Chosen for two reasons:
-///   1. Title gen is best-effort cosmetic metadata; hanging the main stream on
-///      it (v0.5.2-era regression) is never acceptable.
-///   2. Haiku round-trips complete in ~2–4s typical. 10s covers cold TLS +
-///      provider warmup without tolerating outright hangs.
-const TITLE_GEN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
-
-/// Generate a short AI title for a session by spawning a separate Claude CLI process.
-/// Uses Haiku model for fast, cheap title generation. Completely isolated from the
-/// main conversation channel — spawns a new process that exits after one response.
+/// Run a single prompt through a separate, throwaway Claude CLI process on
+/// the haiku tier. Completely isolated from the main conversation channel —
+/// the process exits after one response. Shared by title and commit-message
+/// generation.
 ///
 /// Returns:
-///   - `Ok(Some(title))` — successful, usable title
-///   - `Ok(None)` — timeout, empty/unparseable output, or provider missing haiku
-///     mapping. Caller should fall back to default title. Never blocks the UI.
-///   - `Err(msg)` — hard failure worth surfacing (binary missing, bad input).
-#[tauri::command]
-async fn generate_session_title(
-    user_message: String,
-    assistant_message: String,
-    provider_id: Option<String>,
+///   - `Ok(Some(stdout))` — the process exited successfully
+///   - `Ok(None)` — timeout, non-zero exit, or provider missing haiku mapping
+///   - `Err(msg)` — hard failure worth surfacing (binary missing, bad input)
+pub(crate) async fn run_isolated_prompt(
+    prompt: String,
+    provider_id: Option<&str>,
+    timeout: std::time::Duration,
+    tag: &str,
 ) -> Result<Option<String>, String> {
-    // Safe UTF-8 truncation (don't slice mid-character)
-    fn safe_truncate(s: &str, max_bytes: usize) -> &str {
-        if s.len() <= max_bytes {
-            return s;
-        }
-        let mut end = max_bytes;
-        while end > 0 && !s.is_char_boundary(end) {
-            end -= 1;
-        }
-        &s[..end]
-    }
-
-    let user_msg = safe_truncate(&user_message, 500);
-    let asst_msg = safe_truncate(&assistant_message, 500);
-
-    let prompt = format!(
-        "Generate a very short title (5-10 words, in the same language as the conversation) for this conversation. Reply with ONLY the title text, no quotes, no extra text, no explanation.\n\nUser: {}\n\nAssistant: {}",
-        user_msg, asst_msg
-    );
-
     // Resolve model and env vars for provider
-    let (provider_env, provider_keys_to_remove, model_name) = if let Some(ref pid) = provider_id {
+    let (provider_env, provider_keys_to_remove, model_name) = if let Some(pid) = provider_id {
         let (env, keys, _args, _caps) = resolve_provider_env(Some(pid))?;
         // Find haiku tier mapping from provider
         let providers_file = load_providers()?;
-        let provider = providers_file.providers.iter().find(|p| p.id == *pid);
+        let provider = providers_file.providers.iter().find(|p| p.id == pid);
         let haiku_model = provider.and_then(|p| {
             p.model_mappings
                 .iter()
@@ -7695,10 +7898,7 @@ async fn generate_session_title(
             Some(m) => (env, keys, m),
             None => {
                 // Provider has no haiku mapping — degrade silently, don't error.
-                eprintln!(
-                    "[title-gen] provider {} has no haiku mapping, skipping",
-                    pid
-                );
+                eprintln!("[{}] provider {} has no haiku mapping, skipping", tag, pid);
                 return Ok(None);
             }
         }
@@ -7775,18 +7975,15 @@ async fn generate_session_title(
     // main streaming loop forever.
     let child = cmd
         .spawn()
-        .map_err(|e| format!("Failed to spawn claude for title gen: {}", e))?;
+        .map_err(|e| format!("Failed to spawn claude for {}: {}", tag, e))?;
 
-    let output = match tokio::time::timeout(TITLE_GEN_TIMEOUT, child.wait_with_output()).await {
+    let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
         Ok(Ok(out)) => out,
         Ok(Err(e)) => {
-            return Err(format!("Failed to wait for title gen process: {}", e));
+            return Err(format!("Failed to wait for {} process: {}", tag, e));
         }
         Err(_) => {
-            eprintln!(
-                "[title-gen] timed out after {}s, returning default title",
-                TITLE_GEN_TIMEOUT.as_secs()
-            );
+            eprintln!("[{}] timed out after {}s", tag, timeout.as_secs());
             return Ok(None);
         }
     };
@@ -7794,14 +7991,71 @@ async fn generate_session_title(
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
         eprintln!(
-            "[title-gen] process failed (status={:?}): {}",
+            "[{}] process failed (status={:?}): {}",
+            tag,
             output.status.code(),
             stderr.chars().take(200).collect::<String>()
         );
         return Ok(None);
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(Some(String::from_utf8_lossy(&output.stdout).to_string()))
+}
+
+/// Max wall-clock time the title-gen spawn may run. Beyond this we return
+/// `Ok(None)` — the frontend shows a default title and the user can rename
+/// later. Chosen for two reasons:
+///   1. Title gen is best-effort cosmetic metadata; hanging the main stream on
+///      it (v0.5.2-era regression) is never acceptable.
+///   2. Haiku round-trips complete in ~2–4s typical. 10s covers cold TLS +
+///      provider warmup without tolerating outright hangs.
+const TITLE_GEN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Generate a short AI title for a session by spawning a separate Claude CLI process.
+/// Uses Haiku model for fast, cheap title generation. Completely isolated from the
+/// main conversation channel — spawns a new process that exits after one response.
+///
+/// Returns:
+///   - `Ok(Some(title))` — successful, usable title
+///   - `Ok(None)` — timeout, empty/unparseable output, or provider missing haiku
+///     mapping. Caller should fall back to default title. Never blocks the UI.
+///   - `Err(msg)` — hard failure worth surfacing (binary missing, bad input).
+#[tauri::command]
+async fn generate_session_title(
+    user_message: String,
+    assistant_message: String,
+    provider_id: Option<String>,
+) -> Result<Option<String>, String> {
+    // Safe UTF-8 truncation (don't slice mid-character)
+    fn safe_truncate(s: &str, max_bytes: usize) -> &str {
+        if s.len() <= max_bytes {
+            return s;
+        }
+        let mut end = max_bytes;
+        while end > 0 && !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        &s[..end]
+    }
+
+    let user_msg = safe_truncate(&user_message, 500);
+    let asst_msg = safe_truncate(&assistant_message, 500);
+
+    let prompt = format!(
+        "Generate a very short title (5-10 words, in the same language as the conversation) for this conversation. Reply with ONLY the title text, no quotes, no extra text, no explanation.\n\nUser: {}\n\nAssistant: {}",
+        user_msg, asst_msg
+    );
+
+    let Some(stdout) = run_isolated_prompt(
+        prompt,
+        provider_id.as_deref(),
+        TITLE_GEN_TIMEOUT,
+        "title-gen",
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
 
     // Parse JSON output — Claude CLI --output-format json returns:
     // { "type": "result", "result": "the title text", ... }
@@ -7951,6 +8205,14 @@ pub fn run() {
         .manage(BypassModeMap::new())
         .manage(WatcherManager::default())
         .manage(PathAccessManager::new())
+        .manage(WindowRouter::default())
+        .manage(commands::file_index::FileIndexState::default())
+        .manage(commands::builtin_mcp::BuiltinMcpState::default())
+        .manage(commands::turn_snapshots::TurnSnapshotState::default())
+        .manage(commands::file_tree::FileTreeState::default())
+        .manage(commands::project_search::SearchState::default())
+        .manage(commands::path_index::PathIndexState::default())
+        .manage(commands::file_reader::FileReaderState::default())
         .plugin(tauri_plugin_process::init())
         .setup(|app| {
             // titleBarStyle: "Overlay" in tauri.conf.json handles macOS traffic lights
@@ -7959,6 +8221,20 @@ pub fn run() {
             // One-time cleanup: purge desk_* entries from tracked_sessions.txt
             cleanup_tracked_sessions();
 
+            // Policy-driven archive / compress / delete pass (no-op unless
+            // ~/.tokenicode/retention.json enables it). Runs on its own thread.
+            commands::session_retention::run_retention_at_startup(app.handle().clone());
+
+            // Expire old per-turn workspace snapshots.
+            commands::turn_snapshots::gc_at_startup();
+
+            // Remove unreferenced attachments past their grace period.
+            commands::attachment_gc::run_gc_at_startup();
+
+            // Built-in MCP server (open file, ask user, tabs, session search)
+            // injected into every session's scratch config.
+            commands::builtin_mcp::start(app.handle().clone());
+
             // Propagate proxy env vars from login shell to the process environment
             // so that ALL HTTP clients (including the updater plugin) can reach
             // external services through the proxy.
@@ -7998,6 +8274,14 @@ pub fn run() {
 
             Ok(())
         })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Destroyed = event {
+                commands::session_windows::handle_window_destroyed(
+                    window.app_handle(),
+                    window.label(),
+                );
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             start_claude_session,
             send_stdin,
@@ -8009,8 +8293,83 @@ pub fn run() {
             list_sessions,
             search_sessions,
             load_session,
+            commands::session_import::import_session,
+            commands::session_diff::compare_sessions,
+            commands::session_retention::load_retention_policy,
+            commands::session_retention::save_retention_policy,
+            commands::session_retention::apply_retention_policy,
+            commands::session_retention::list_compressed_sessions,
+            commands::session_retention::restore_archived_session,
+            commands::session_retention::take_retention_changes,
+            commands::file_index::find_sessions_touching_file,
+            commands::file_index::get_session_changed_files,
+            commands::file_index::rebuild_file_index,
+            commands::session_windows::detach_session_window,
+            commands::session_windows::claim_session,
+            commands::session_windows::attach_session_to_main,
+            commands::session_windows::subscribe_session_events,
+            commands::session_windows::unsubscribe_session_events,
+            commands::session_windows::list_session_windows,
+            commands::skill_packages::install_skill_package,
+            commands::skill_packages::export_skill_package,
+            commands::skill_lint::lint_skills,
+            commands::command_templates::render_slash_command,
+            commands::agents::list_agents,
+            commands::agents::read_agent,
+            commands::agents::write_agent,
+            commands::agents::delete_agent,
+            commands::agents::validate_agent,
+            commands::mcp_manager::list_mcp_servers,
+            commands::mcp_manager::save_mcp_server,
+            commands::mcp_manager::delete_mcp_server,
+            commands::mcp_manager::check_mcp_server,
+            commands::mcp_manager::get_session_mcp_selection,
+            commands::mcp_manager::set_session_mcp_selection,
+            commands::builtin_mcp::respond_mcp_request,
+            commands::worktrees::list_session_worktrees,
+            commands::worktrees::diff_session_worktree,
+            commands::worktrees::merge_session_worktree,
+            commands::worktrees::discard_session_worktree,
+            commands::git_api::git_status,
+            commands::git_api::git_diff,
+            commands::git_api::git_log,
+            commands::git_api::git_blame,
+            commands::git_ops::git_stage,
+            commands::git_ops::git_unstage,
+            commands::git_ops::git_commit,
+            commands::git_ops::git_create_branch,
+            commands::git_ops::git_switch_branch,
+            commands::git_ops::generate_commit_message,
+            commands::turn_snapshots::list_turn_snapshots,
+            commands::turn_snapshots::diff_turn_snapshot,
+            commands::turn_snapshots::restore_turn_snapshot,
+            commands::turn_snapshots::gc_turn_snapshots,
+            commands::file_tree::list_directory,
+            commands::file_tree::stream_file_tree,
+            commands::file_tree::cancel_file_tree,
+            commands::file_tree::get_file_tree_config,
+            commands::file_tree::set_file_tree_config,
+            commands::project_search::search_in_project,
+            commands::project_search::cancel_project_search,
+            commands::path_index::fuzzy_find_files,
+            commands::path_index::reset_path_index,
+            commands::file_reader::detect_file_encoding,
+            commands::file_reader::read_file_lines,
+            commands::file_reader::tail_file_lines,
+            commands::file_reader::search_in_file,
+            commands::file_reader::count_file_lines,
+            commands::safe_write::read_file_versioned,
+            commands::safe_write::get_file_version,
+            commands::safe_write::merge_file_versions,
+            commands::attachments::save_attachment,
+            commands::attachment_gc::load_attachment_gc_config,
+            commands::attachment_gc::save_attachment_gc_config,
+            commands::attachment_gc::collect_attachment_garbage,
+            commands::attachment_gc::list_attachment_usage,
             add_path_grant,
             clear_path_grants,
+            list_path_grants,
+            revoke_path_grant,
             decode_project_dir,
             read_file_tree,
             read_file_content,
@@ -8082,7 +8441,7 @@ pub fn run() {
 
 #[cfg(test)]
 mod decode_tests {
-    use super::decode_project_name;
+    use super::{decode_project_name, encode_project_name};
     use tempfile::TempDir;
 
     /// Encode a Unix absolute path the way Claude CLI encodes project dirs:
@@ -8174,6 +8533,16 @@ mod decode_tests {
         );
     }
 
+    #[test]
+    fn test_encode_matches_cli_naming() {
+        assert_eq!(
+            encode_project_name("/Users/a/my.app/ppt maker"),
+            "-Users-a-my-app-ppt-maker"
+        );
+        assert_eq!(encode_project_name("C:\\dev\\proj"), "C--dev-proj");
+        assert_eq!(encode_project_name("/home/a/.claude"), "-home-a--claude");
+    }
+
     #[test]
     fn test_no_false_positive_without_dir() {
         // When the hyphenated path does NOT exist on disk, the decoder
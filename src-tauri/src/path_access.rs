@@ -8,37 +8,97 @@
 //!
 //! 1. **Fixed roots** — paths that are always allowed (project cwd[s],
 //!    `~/.claude.json`, `~/.claude/`, `~/.tokenicode/`, system temp dir).
-//! 2. **Session grants** — paths that the user has explicitly authorized at
-//!    runtime (via the native file dialog, OS drag-drop, or a Markdown
-//!    "authorize" button). Grants are scoped per stdin/tab id and cleared
-//!    when the tab goes away.
+//! 2. **Grants** — paths that the user has explicitly authorized at runtime
+//!    (via the native file dialog, OS drag-drop, or a Markdown "authorize"
+//!    button). Each grant carries a capability (read < write < delete) and
+//!    an optional expiry. Tab grants live in memory and are cleared when the
+//!    tab goes away; project grants are persisted to
+//!    `~/.tokenicode/path_grants.json` and apply while that project is open.
 //!
 //! All paths are canonicalized before comparison to prevent `..` traversal.
 //! When the filesystem cannot canonicalize a non-existing target (e.g. write
 //! to a new file), the closest existing ancestor is canonicalized instead
-//! and the remaining tail is re-joined on top.
+//! and the remaining tail is re-joined on top; dangling symlinks on the way
+//! are followed by hand so a write cannot escape through them. Commands that
+//! write open the validated path with `open_checked`, which re-validates it
+//! through the file handle.
 
-use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
 use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
-/// Capability requested when validating a path.
+/// Capability requested when validating a path, and held by a grant.
 ///
-/// Kept as a distinct type so callers must state intent; today it is not
-/// used to change the allowlist but logging/metrics can branch on it later.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Ordered: a grant allows its own capability and every lower one, so a
+/// `Write` grant also allows reads. Fixed roots allow everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PathCapability {
     Read,
     Write,
     Delete,
 }
 
+impl PathCapability {
+    pub fn allows(self, requested: PathCapability) -> bool {
+        requested <= self
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            PathCapability::Read => "read",
+            PathCapability::Write => "write",
+            PathCapability::Delete => "delete",
+        }
+    }
+}
+
+/// Who a grant belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantScope {
+    /// One tab/stdin id; in memory only.
+    Tab(String),
+    /// A project directory (canonical); persisted, active while the project
+    /// is a registered root.
+    Project(PathBuf),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathGrant {
+    pub id: String,
+    pub path: PathBuf,
+    pub capability: PathCapability,
+    pub scope: GrantScope,
+    pub granted_at_ms: u64,
+    pub expires_at_ms: Option<u64>,
+}
+
+impl PathGrant {
+    fn is_live(&self, now_ms: u64) -> bool {
+        self.expires_at_ms.is_none_or(|at| at > now_ms)
+    }
+
+    /// Whether the grant is in effect for a request from `tab_id`. Without a
+    /// tab context any tab's grant counts (file tree scan etc.).
+    fn applies_to(&self, tab_id: Option<&str>, roots: &[PathBuf]) -> bool {
+        match &self.scope {
+            GrantScope::Tab(owner) => tab_id.is_none_or(|id| id == owner),
+            GrantScope::Project(project) => roots.iter().any(|r| r == project),
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct PathAccessManager {
     fixed_roots: Arc<Mutex<Vec<PathBuf>>>,
-    session_grants: Arc<Mutex<HashMap<String, HashSet<PathBuf>>>>,
+    grants: Arc<Mutex<Vec<PathGrant>>>,
+    /// Where project grants are persisted; `None` keeps everything in memory.
+    store_path: Option<PathBuf>,
 }
 
 impl PathAccessManager {
@@ -57,18 +117,32 @@ impl PathAccessManager {
         // System temp dir — save_temp_file writes here when cwd is missing.
         push_canonical(&mut roots, std::env::temp_dir());
 
+        let store_path = dirs::home_dir().map(|h| h.join(".tokenicode").join("path_grants.json"));
+        let grants = store_path
+            .as_deref()
+            .map(load_project_grants)
+            .unwrap_or_default();
+
         Self {
             fixed_roots: Arc::new(Mutex::new(roots)),
-            session_grants: Arc::new(Mutex::new(HashMap::new())),
+            grants: Arc::new(Mutex::new(grants)),
+            store_path,
         }
     }
 
     /// Empty constructor for tests — no implicit roots at all.
     #[cfg(test)]
     pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Test constructor that persists project grants to `store`.
+    #[cfg(test)]
+    pub fn with_store(store: &Path) -> Self {
         Self {
-            fixed_roots: Arc::new(Mutex::new(Vec::new())),
-            session_grants: Arc::new(Mutex::new(HashMap::new())),
+            grants: Arc::new(Mutex::new(load_project_grants(store))),
+            store_path: Some(store.to_path_buf()),
+            ..Self::default()
         }
     }
 
@@ -79,95 +153,364 @@ impl PathAccessManager {
         push_canonical(&mut roots, cwd.to_path_buf());
     }
 
-    /// Add a path grant for the given tab/stdin id. The path is canonicalized
-    /// first so symlinks don't break the comparison later.
+    /// Add a read grant for the given tab/stdin id.
     pub async fn add_grant(&self, tab_id: &str, path: &Path) {
+        // Tab grants are never persisted, so this cannot fail.
+        let _ = self
+            .grant(
+                path,
+                PathCapability::Read,
+                GrantScope::Tab(tab_id.to_string()),
+                None,
+            )
+            .await;
+    }
+
+    /// Add a grant. The path is canonicalized first so symlinks don't break
+    /// the comparison later. Granting a path the scope already holds merges
+    /// into the existing grant, keeping the higher capability and the later
+    /// expiry.
+    pub async fn grant(
+        &self,
+        path: &Path,
+        capability: PathCapability,
+        scope: GrantScope,
+        expires_at_ms: Option<u64>,
+    ) -> Result<PathGrant, String> {
         let canonical = canonicalize_best_effort(path);
-        let mut grants = self.session_grants.lock().await;
-        grants
-            .entry(tab_id.to_string())
-            .or_default()
-            .insert(canonical);
+        let scope = match scope {
+            GrantScope::Project(project) => GrantScope::Project(canonicalize_best_effort(&project)),
+            tab => tab,
+        };
+        let persisted = matches!(scope, GrantScope::Project(_));
+
+        let mut grants = self.grants.lock().await;
+        let now = now_ms();
+        let grant = match grants
+            .iter_mut()
+            .find(|g| g.scope == scope && g.path == canonical && g.is_live(now))
+        {
+            Some(existing) => {
+                existing.capability = existing.capability.max(capability);
+                existing.expires_at_ms = match (existing.expires_at_ms, expires_at_ms) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    _ => None,
+                };
+                existing.clone()
+            }
+            None => {
+                let grant = PathGrant {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    path: canonical,
+                    capability,
+                    scope,
+                    granted_at_ms: now,
+                    expires_at_ms,
+                };
+                grants.push(grant.clone());
+                grant
+            }
+        };
+        if persisted {
+            self.persist(&grants)?;
+        }
+        Ok(grant)
     }
 
     /// Clear all grants for a tab. Called from `teardownSession` / tab close.
     pub async fn clear_grants(&self, tab_id: &str) {
-        let mut grants = self.session_grants.lock().await;
-        grants.remove(tab_id);
+        let mut grants = self.grants.lock().await;
+        grants.retain(|g| !matches!(&g.scope, GrantScope::Tab(owner) if owner == tab_id));
+    }
+
+    /// Revoke one grant by id. Returns whether it existed.
+    pub async fn revoke_grant(&self, grant_id: &str) -> Result<bool, String> {
+        let mut grants = self.grants.lock().await;
+        let Some(pos) = grants.iter().position(|g| g.id == grant_id) else {
+            return Ok(false);
+        };
+        let removed = grants.remove(pos);
+        if matches!(removed.scope, GrantScope::Project(_)) {
+            self.persist(&grants)?;
+        }
+        Ok(true)
+    }
+
+    /// Active (unexpired) grants in effect for `tab_id` — that tab's grants
+    /// plus project grants of open projects — or every grant when `None`.
+    /// Expired grants are dropped on the way.
+    pub async fn list_grants(&self, tab_id: Option<&str>) -> Result<Vec<PathGrant>, String> {
+        let roots = self.roots().await;
+        let mut grants = self.grants.lock().await;
+        let now = now_ms();
+        let before = grants.len();
+        let expired_project = grants
+            .iter()
+            .any(|g| !g.is_live(now) && matches!(g.scope, GrantScope::Project(_)));
+        grants.retain(|g| g.is_live(now));
+        if expired_project && grants.len() != before {
+            self.persist(&grants)?;
+        }
+
+        let mut listed: Vec<PathGrant> = grants
+            .iter()
+            .filter(|g| tab_id.is_none() || g.applies_to(tab_id, &roots))
+            .cloned()
+            .collect();
+        listed.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(listed)
+    }
+
+    /// Fixed roots, canonicalized (cwd registrations included).
+    pub async fn roots(&self) -> Vec<PathBuf> {
+        self.fixed_roots.lock().await.clone()
+    }
+
+    /// Live grants held by one tab, sorted by path.
+    pub async fn grants_for(&self, tab_id: &str) -> Vec<PathGrant> {
+        let grants = self.grants.lock().await;
+        let now = now_ms();
+        let mut held: Vec<PathGrant> = grants
+            .iter()
+            .filter(|g| g.is_live(now))
+            .filter(|g| matches!(&g.scope, GrantScope::Tab(owner) if owner == tab_id))
+            .cloned()
+            .collect();
+        held.sort_by(|a, b| a.path.cmp(&b.path));
+        held
     }
 
-    /// Check whether `path` is allowed. Returns the canonical path on success.
-    /// When `tab_id` is `None`, only fixed roots and grants across ALL tabs
-    /// are consulted (used by commands that don't have a tab context yet, e.g.
-    /// the file tree scanner).
+    /// Check whether `path` is allowed for `cap`. Returns the canonical path
+    /// on success. When `tab_id` is `None`, only fixed roots, project grants
+    /// and grants across ALL tabs are consulted (used by commands that don't
+    /// have a tab context yet, e.g. the file tree scanner).
+    ///
+    /// FIFOs, sockets and device nodes are refused even inside allowed roots.
     pub async fn validate(
         &self,
         path: &Path,
         tab_id: Option<&str>,
-        _cap: PathCapability,
+        cap: PathCapability,
     ) -> Result<PathBuf, String> {
         let canonical = canonicalize_best_effort(path);
+        self.check_allowed(&canonical, tab_id, cap).await?;
+        reject_special_file(&canonical)?;
+        Ok(canonical)
+    }
 
-        let roots = self.fixed_roots.lock().await;
-        if roots.iter().any(|root| path_starts_with(&canonical, root)) {
-            return Ok(canonical);
+    /// Like `validate`, but for commands that act on the directory entry
+    /// itself (rename, delete). A symlink in the last component is not
+    /// followed: the link's own location must be allowed, and the returned
+    /// path names the link, so deleting it never touches its target.
+    pub async fn validate_entry(
+        &self,
+        path: &Path,
+        tab_id: Option<&str>,
+        cap: PathCapability,
+    ) -> Result<PathBuf, String> {
+        let is_link = path
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink());
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return self.validate(path, tab_id, cap).await;
+        };
+        if !is_link {
+            return self.validate(path, tab_id, cap).await;
         }
-        drop(roots);
-
-        let grants = self.session_grants.lock().await;
-        if let Some(tid) = tab_id {
-            if let Some(set) = grants.get(tid) {
-                if set.iter().any(|g| path_starts_with(&canonical, g)) {
-                    return Ok(canonical);
-                }
-            }
-        } else {
-            // No tab context — any tab's grant is acceptable (file tree scan etc.)
-            for set in grants.values() {
-                if set.iter().any(|g| path_starts_with(&canonical, g)) {
-                    return Ok(canonical);
-                }
+        let entry = canonicalize_best_effort(parent).join(name);
+        self.check_allowed(&entry, tab_id, cap).await?;
+        Ok(entry)
+    }
+
+    async fn check_allowed(
+        &self,
+        canonical: &Path,
+        tab_id: Option<&str>,
+        cap: PathCapability,
+    ) -> Result<(), String> {
+        let roots = self.roots().await;
+        if roots.iter().any(|root| path_starts_with(canonical, root)) {
+            return Ok(());
+        }
+
+        let grants = self.grants.lock().await;
+        let now = now_ms();
+        let mut held: Option<PathCapability> = None;
+        let matching = grants.iter().filter(|g| {
+            g.is_live(now) && g.applies_to(tab_id, &roots) && path_starts_with(canonical, &g.path)
+        });
+        for grant in matching {
+            if grant.capability.allows(cap) {
+                return Ok(());
             }
+            held = held.max(Some(grant.capability));
         }
 
+        if let Some(held) = held {
+            return Err(format!(
+                "Path '{}' is only authorized for {} access; {} was requested.",
+                canonical.display(),
+                held.label(),
+                cap.label()
+            ));
+        }
         Err(format!(
             "Path '{}' is outside the allowed workspace. If this is a legitimate \
              external file, authorize it via the file dialog first.",
             canonical.display()
         ))
     }
+
+    /// Write the live project grants to the store, if there is one.
+    fn persist(&self, grants: &[PathGrant]) -> Result<(), String> {
+        let Some(store) = &self.store_path else {
+            return Ok(());
+        };
+        let now = now_ms();
+        let persisted: Vec<&PathGrant> = grants
+            .iter()
+            .filter(|g| g.is_live(now) && matches!(g.scope, GrantScope::Project(_)))
+            .collect();
+        if let Some(dir) = store.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create grant store dir: {}", e))?;
+        }
+        let content = serde_json::to_string_pretty(&persisted)
+            .map_err(|e| format!("Failed to serialize path grants: {}", e))?;
+        std::fs::write(store, content).map_err(|e| format!("Failed to write path grants: {}", e))
+    }
+}
+
+/// Unexpired project grants from the store; a missing or corrupt file
+/// yields none.
+fn load_project_grants(store: &Path) -> Vec<PathGrant> {
+    let Ok(content) = std::fs::read_to_string(store) else {
+        return Vec::new();
+    };
+    let grants: Vec<PathGrant> = match serde_json::from_str(&content) {
+        Ok(g) => g,
+        Err(e) => {
+            eprintln!("[TOKENICODE] Ignoring unreadable path grant store: {}", e);
+            return Vec::new();
+        }
+    };
+    let now = now_ms();
+    grants
+        .into_iter()
+        .filter(|g| g.is_live(now) && matches!(g.scope, GrantScope::Project(_)))
+        .collect()
 }
 
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Refuse FIFOs, sockets and device nodes: reading a FIFO blocks forever
+/// and none of them are workspace files. Missing paths pass.
+pub fn reject_special_file(path: &Path) -> Result<(), String> {
+    match std::fs::metadata(path) {
+        Ok(meta) if !meta.is_file() && !meta.is_dir() => Err(format!(
+            "Path '{}' is not a regular file or directory",
+            path.display()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Open a path returned by `validate` and re-check it through the handle,
+/// closing the window between validation and use (TOCTOU). The last
+/// component must not be a symlink and the opened object must be a regular
+/// file; on Unix the path must also still resolve to itself and to the
+/// handle's inode, so a directory swapped for a symlink in between is caught.
+pub fn open_checked(canonical: &Path, options: &mut OpenOptions) -> Result<File, String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        // O_NONBLOCK: a FIFO swapped in after validation must not hang the open.
+        options.custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK);
+    }
+    let file = options
+        .open(canonical)
+        .map_err(|e| format!("Cannot open '{}': {}", canonical.display(), e))?;
+    let meta = file
+        .metadata()
+        .map_err(|e| format!("Cannot open '{}': {}", canonical.display(), e))?;
+    if !meta.is_file() {
+        return Err(format!(
+            "Path '{}' is not a regular file",
+            canonical.display()
+        ));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let same_path = canonical.canonicalize().is_ok_and(|c| c == canonical);
+        let same_inode = std::fs::metadata(canonical)
+            .is_ok_and(|m| m.dev() == meta.dev() && m.ino() == meta.ino());
+        if !same_path || !same_inode {
+            return Err(format!(
+                "Path '{}' changed while it was being opened",
+                canonical.display()
+            ));
+        }
+    }
+    Ok(file)
+}
+
+/// Symlinks followed by hand before `canonicalize_best_effort` gives up.
+const MAX_LINK_HOPS: usize = 40;
+
 /// Canonicalize aggressively, falling back to lexical normalization when the
 /// target does not exist yet (e.g. `write_file_content` to a new path).
 fn canonicalize_best_effort(path: &Path) -> PathBuf {
+    resolve(path, MAX_LINK_HOPS)
+}
+
+fn resolve(path: &Path, hops: usize) -> PathBuf {
     if let Ok(c) = path.canonicalize() {
         return c;
     }
-    // Walk up until we find an existing ancestor, canonicalize it, then re-join.
-    let mut ancestors = path.ancestors().skip(1);
-    for anc in ancestors.by_ref() {
+    // Walk up until we find an existing ancestor, canonicalize it, then
+    // re-join the rest lexically (so `missing/../..` cannot climb out of it).
+    // A dangling symlink on the way is followed by hand: writing through it
+    // creates its target, which may lie outside every allowed root.
+    for anc in path.ancestors().filter(|a| !a.as_os_str().is_empty()) {
+        let rel = path.strip_prefix(anc).unwrap_or(Path::new(""));
         if let Ok(c) = anc.canonicalize() {
-            let rel = path.strip_prefix(anc).unwrap_or(path);
-            return c.join(rel);
+            return join_lexical(c, rel);
+        }
+        let dangling = anc
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink());
+        if dangling && hops > 0 {
+            if let Ok(target) = std::fs::read_link(anc) {
+                let base = anc.parent().unwrap_or(Path::new(""));
+                return resolve(&base.join(target).join(rel), hops - 1);
+            }
         }
     }
     // Nothing exists on this path — fall back to lexical normalization.
     normalize_lexical(path)
 }
 
-fn normalize_lexical(path: &Path) -> PathBuf {
-    let mut out = PathBuf::new();
-    for comp in path.components() {
+fn join_lexical(mut base: PathBuf, rel: &Path) -> PathBuf {
+    for comp in rel.components() {
         match comp {
             Component::ParentDir => {
-                out.pop();
+                base.pop();
             }
             Component::CurDir => {}
-            other => out.push(other.as_os_str()),
+            other => base.push(other.as_os_str()),
         }
     }
-    out
+    base
+}
+
+fn normalize_lexical(path: &Path) -> PathBuf {
+    join_lexical(PathBuf::new(), path)
 }
 
 fn path_starts_with(path: &Path, prefix: &Path) -> bool {
@@ -301,4 +644,275 @@ mod tests {
             .await
             .expect("tab_id=None should consult all grants");
     }
+
+    #[tokio::test]
+    async fn grant_enforces_capability_and_expiry() {
+        let outside = TempDir::new().unwrap();
+        let mgr = PathAccessManager::empty();
+        let f = outside.path().join("notes.txt");
+        std::fs::write(&f, "x").unwrap();
+
+        mgr.add_grant("tab1", &f).await;
+        mgr.validate(&f, Some("tab1"), PathCapability::Read)
+            .await
+            .expect("read grant allows reads");
+        let err = mgr
+            .validate(&f, Some("tab1"), PathCapability::Write)
+            .await
+            .expect_err("read grant must not allow writes");
+        assert!(err.contains("only authorized for read"), "{}", err);
+
+        // Upgrading merges into the same grant; write implies read but not delete.
+        let tab = GrantScope::Tab("tab1".to_string());
+        mgr.grant(&f, PathCapability::Write, tab.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(mgr.grants_for("tab1").await.len(), 1);
+        mgr.validate(&f, Some("tab1"), PathCapability::Write)
+            .await
+            .expect("write grant allows writes");
+        mgr.validate(&f, Some("tab1"), PathCapability::Delete)
+            .await
+            .expect_err("write grant must not allow deletes");
+
+        // An expired grant no longer counts and is dropped from listings.
+        let other = outside.path().join("other.txt");
+        mgr.grant(&other, PathCapability::Delete, tab, Some(now_ms() - 1))
+            .await
+            .unwrap();
+        mgr.validate(&other, Some("tab1"), PathCapability::Read)
+            .await
+            .expect_err("expired grant must be ignored");
+        assert_eq!(mgr.list_grants(Some("tab1")).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn project_grants_persist_while_project_open() {
+        let project = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let state = TempDir::new().unwrap();
+        let store = state.path().join("path_grants.json");
+        let f = outside.path().join("shared.txt");
+        std::fs::write(&f, "x").unwrap();
+
+        let mgr = PathAccessManager::with_store(&store);
+        let grant = mgr
+            .grant(
+                &f,
+                PathCapability::Write,
+                GrantScope::Project(project.path().to_path_buf()),
+                None,
+            )
+            .await
+            .unwrap();
+        mgr.add_grant("tab1", outside.path()).await;
+
+        // A fresh manager (app restart) reloads only the project grant, which
+        // applies once the project is opened, for any tab.
+        let restarted = PathAccessManager::with_store(&store);
+        restarted
+            .validate(&f, Some("tab9"), PathCapability::Write)
+            .await
+            .expect_err("project grant is inactive until the project is open");
+        restarted.register_cwd(project.path()).await;
+        restarted
+            .validate(&f, Some("tab9"), PathCapability::Write)
+            .await
+            .expect("project grant applies to any tab of the open project");
+        let listed = restarted.list_grants(None).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, grant.id);
+
+        assert!(restarted.revoke_grant(&grant.id).await.unwrap());
+        restarted
+            .validate(&f, Some("tab9"), PathCapability::Read)
+            .await
+            .expect_err("revoked grant must be gone");
+        assert!(PathAccessManager::with_store(&store)
+            .list_grants(None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn dangling_symlink_and_missing_dir_traversal_cannot_escape() {
+        let tmp = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let mgr = PathAccessManager::empty();
+        mgr.register_cwd(tmp.path()).await;
+
+        // A link inside the workspace whose (missing) target is outside:
+        // writing to it would create the outside file.
+        let target = outside.path().join("planted.txt");
+        let link = tmp.path().join("innocent.txt");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        mgr.validate(&link, Some("tab1"), PathCapability::Write)
+            .await
+            .expect_err("dangling link to outside must be rejected");
+        let nested = tmp.path().join("dir-link");
+        std::os::unix::fs::symlink(outside.path().join("new-dir"), &nested).unwrap();
+        mgr.validate(&nested.join("x.txt"), Some("tab1"), PathCapability::Write)
+            .await
+            .expect_err("dangling directory link to outside must be rejected");
+
+        // `..` after a component that does not exist yet.
+        let climb = tmp
+            .path()
+            .join("not-yet/../..")
+            .join(outside.path().file_name().unwrap())
+            .join("x.txt");
+        mgr.validate(&climb, Some("tab1"), PathCapability::Write)
+            .await
+            .expect_err("'..' past a missing dir must be rejected");
+
+        // Deleting/renaming the link acts on the link, which lives inside.
+        let entry = mgr
+            .validate_entry(&link, Some("tab1"), PathCapability::Delete)
+            .await
+            .unwrap();
+        assert_eq!(entry.file_name().unwrap(), "innocent.txt");
+        assert!(path_starts_with(
+            &entry,
+            &tmp.path().canonicalize().unwrap()
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn special_files_and_swapped_paths_are_refused() {
+        let tmp = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let mgr = PathAccessManager::empty();
+        mgr.register_cwd(tmp.path()).await;
+
+        let fifo = tmp.path().join("pipe");
+        let c_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let err = mgr
+            .validate(&fifo, Some("tab1"), PathCapability::Read)
+            .await
+            .expect_err("FIFO must be refused");
+        assert!(err.contains("not a regular file"), "{}", err);
+
+        // Validated as a plain file, then swapped for a symlink before use.
+        let f = tmp.path().join("notes.txt");
+        std::fs::write(&f, "mine").unwrap();
+        let checked = mgr
+            .validate(&f, Some("tab1"), PathCapability::Write)
+            .await
+            .unwrap();
+        let secret = outside.path().join("secret.txt");
+        std::fs::write(&secret, "theirs").unwrap();
+        std::fs::remove_file(&f).unwrap();
+        std::os::unix::fs::symlink(&secret, &f).unwrap();
+        open_checked(&checked, OpenOptions::new().write(true))
+            .expect_err("swapped-in symlink must not be opened");
+
+        // A directory swapped for a symlink changes what the path resolves to.
+        let dir = tmp.path().join("sub");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "a").unwrap();
+        let checked = mgr
+            .validate(&dir.join("a.txt"), Some("tab1"), PathCapability::Read)
+            .await
+            .unwrap();
+        std::fs::rename(&dir, tmp.path().join("sub-old")).unwrap();
+        std::fs::write(outside.path().join("a.txt"), "x").unwrap();
+        std::os::unix::fs::symlink(outside.path(), &dir).unwrap();
+        open_checked(&checked, OpenOptions::new().read(true))
+            .expect_err("path resolving elsewhere after validation must fail");
+        assert_eq!(std::fs::read_to_string(&secret).unwrap(), "theirs");
+    }
+
+    /// Deterministic xorshift generator, so failures reproduce.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[(self.next() % items.len() as u64) as usize]
+        }
+    }
+
+    const SEGMENTS: &[&str] = &[
+        "a", "b", "ab", "a.b", "a b", "é", "..", ".", "a..", ".a", "A", "a-", "zz",
+    ];
+
+    fn random_rel(rng: &mut Rng) -> PathBuf {
+        let len = rng.next() % 6;
+        (0..len).map(|_| rng.pick(SEGMENTS)).collect()
+    }
+
+    #[test]
+    fn fuzz_path_starts_with_matches_component_prefix() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..5000 {
+            let base = Path::new("/").join(random_rel(&mut rng));
+            let path = base.join(random_rel(&mut rng));
+            let other = Path::new("/").join(random_rel(&mut rng));
+
+            let expected = |p: &Path, q: &Path| {
+                let pc: Vec<_> = p.components().collect();
+                let qc: Vec<_> = q.components().collect();
+                pc.len() >= qc.len() && pc[..qc.len()] == qc[..]
+            };
+            assert!(path_starts_with(&path, &base), "{:?} / {:?}", path, base);
+            assert_eq!(
+                path_starts_with(&path, &other),
+                expected(&path, &other),
+                "{:?} / {:?}",
+                path,
+                other
+            );
+            // A string prefix that splits a component is never a path prefix.
+            let mut comps: Vec<_> = base.components().collect();
+            if let Some(Component::Normal(last)) = comps.pop() {
+                let longer = format!("{}x", last.to_string_lossy());
+                let sibling: PathBuf = comps
+                    .iter()
+                    .map(|c| c.as_os_str())
+                    .chain([std::ffi::OsStr::new(&longer)])
+                    .collect();
+                assert!(!path_starts_with(&sibling, &base), "{:?}", sibling);
+            }
+        }
+    }
+
+    #[test]
+    fn fuzz_canonicalize_agrees_with_lexical_without_symlinks() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().canonicalize().unwrap();
+        for dir in ["a/b", "ab", "a.b/é", "zz"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("a/file"), "x").unwrap();
+
+        let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+        for _ in 0..3000 {
+            let path = root.join(random_rel(&mut rng)).join(random_rel(&mut rng));
+            let resolved = canonicalize_best_effort(&path);
+            let lexical = normalize_lexical(&path);
+            // With no symlinks in the tree, resolution is purely lexical...
+            assert_eq!(resolved, lexical, "{:?}", path);
+            // ...and never keeps `.`/`..` components or turns relative.
+            assert!(resolved.is_absolute(), "{:?}", path);
+            assert!(
+                resolved
+                    .components()
+                    .all(|c| !matches!(c, Component::ParentDir | Component::CurDir)),
+                "{:?}",
+                resolved
+            );
+            // Normalization is idempotent.
+            assert_eq!(normalize_lexical(&lexical), lexical);
+        }
+    }
 }
@@ -8,6 +8,7 @@ import { SettingsPanel } from './components/settings/SettingsPanel';
 import { ImageLightbox } from './components/shared/ImageLightbox';
 import { ChangelogModal } from './components/shared/ChangelogModal';
 import { Toast } from './components/shared/Toast';
+import { McpRequestHandler } from './components/shared/McpRequestHandler';
 import { useSettingsStore } from './stores/settingsStore';
 import { useProviderStore } from './stores/providerStore';
 import type { ColorTheme, Theme } from './stores/settingsStore';
@@ -20,6 +21,7 @@ import { bridge, onFileChange } from './lib/tauri-bridge';
 import { parseSessionMessages } from './lib/session-loader';
 import { hasRecoverableFrontendSession } from './lib/sessionLifecycle';
 import { useAutoUpdateCheck } from './hooks/useAutoUpdateCheck';
+import { useAppearance } from './hooks/useAppearance';
 import { useT } from './lib/i18n';
 import { openUrl } from '@tauri-apps/plugin-opener';
 import './App.css';
@@ -89,7 +91,6 @@ async function updateDockIcon(colorTheme: ColorTheme, _theme: Theme) {
 function App() {
   const theme = useSettingsStore((s) => s.theme);
   const colorTheme = useSettingsStore((s) => s.colorTheme);
-  const fontSize = useSettingsStore((s) => s.fontSize);
   const settingsOpen = useSettingsStore((s) => s.settingsOpen);
   const workingDirectory = useSettingsStore((s) => s.workingDirectory);
   const lastSeenVersion = useSettingsStore((s) => s.lastSeenVersion);
@@ -459,10 +460,19 @@ function App() {
   // TK-329: On app startup (incl. browser refresh), detect and kill orphaned backend processes.
   // After refresh, frontend state (stdinToTab, listeners) is wiped, but Rust ProcessManager
   // may still hold live child processes. Kill any that have no corresponding frontend mapping.
+  // Processes claimed by a detached session window are that window's to keep.
   useEffect(() => {
-    bridge.listActiveProcesses().then((activeIds) => {
+    Promise.all([
+      bridge.listActiveProcesses(),
+      bridge.listSessionWindows().catch(() => []),
+    ]).then(([activeIds, bindings]) => {
       if (!activeIds.length) return;
-      const orphaned = activeIds.filter((id) => !hasRecoverableFrontendSession(id));
+      const detached = new Set(
+        bindings.filter((b) => b.owner !== 'main').map((b) => b.session_id),
+      );
+      const orphaned = activeIds.filter(
+        (id) => !detached.has(id) && !hasRecoverableFrontendSession(id),
+      );
       for (const id of orphaned) {
         console.log('[TOKENICODE:cleanup] killing orphaned process:', id);
         const ownerTabId = useSessionStore.getState().getTabForStdin(id);
@@ -531,49 +541,13 @@ function App() {
     return () => document.removeEventListener('contextmenu', handler);
   }, []);
 
-  // Apply dark/light mode class to document
-  useEffect(() => {
-    const root = document.documentElement;
-    if (theme === 'dark') {
-      root.classList.add('dark');
-    } else if (theme === 'light') {
-      root.classList.remove('dark');
-    } else {
-      const mq = window.matchMedia('(prefers-color-scheme: dark)');
-      const apply = () => {
-        if (mq.matches) root.classList.add('dark');
-        else root.classList.remove('dark');
-      };
-      apply();
-      mq.addEventListener('change', apply);
-      return () => mq.removeEventListener('change', apply);
-    }
-  }, [theme]);
-
-  // Apply color theme class to document
-  useEffect(() => {
-    const root = document.documentElement;
-    root.classList.remove('theme-blue', 'theme-orange', 'theme-green');
-    if (colorTheme === 'blue') {
-      root.classList.add('theme-blue');
-    } else if (colorTheme === 'orange') {
-      root.classList.add('theme-orange');
-    } else if (colorTheme === 'green') {
-      root.classList.add('theme-green');
-    }
-    // 'black' is the default — no class needed
-  }, [colorTheme]);
+  useAppearance();
 
   // Update macOS dock icon when color theme changes
   useEffect(() => {
     updateDockIcon(colorTheme, theme);
   }, [colorTheme, theme]);
 
-  // Apply font size to document root
-  useEffect(() => {
-    document.documentElement.style.fontSize = `${fontSize}px`;
-  }, [fontSize]);
-
   // Cmd+/- global shortcut for font size
   useEffect(() => {
     const handler = (e: KeyboardEvent) => {
@@ -664,25 +638,29 @@ function App() {
     };
   }, [workingDirectory]);
 
-  // Listen for file change events from the watcher
-  // Debounce tree refresh for created/removed events (structure changes)
+  // Listen for file change batches from the watcher (already coalesced and
+  // gitignore-filtered in Rust)
+  // Debounce tree refresh for structure changes
   const refreshTimerRef = useRef<ReturnType<typeof setTimeout> | null>(null);
 
   useEffect(() => {
     const unlisten = onFileChange((event) => {
-      // Defense-in-depth: skip paths under noisy directories (also filtered in Rust)
-      const filtered = event.paths.filter((p) =>
-        !/(^|[/\\])(\.(claude|git)|node_modules|__pycache__)[/\\]/.test(p)
-      );
-      if (filtered.length === 0) return;
-
-      for (const filePath of filtered) {
-        markFileChanged(filePath, event.kind);
+      for (const filePath of event.created) markFileChanged(filePath, 'created');
+      for (const filePath of event.modified) markFileChanged(filePath, 'modified');
+      for (const filePath of event.removed) markFileChanged(filePath, 'removed');
+      for (const { from, to } of event.renamed) {
+        markFileChanged(from, 'removed');
+        markFileChanged(to, 'created');
       }
 
-      // When files are created or removed, the tree structure changes —
-      // debounce a full tree reload (300ms to batch rapid changes)
-      if (event.kind === 'created' || event.kind === 'removed') {
+      // When files are created, removed or renamed, the tree structure
+      // changes — debounce a full tree reload (300ms to batch rapid changes)
+      if (
+        event.overflow ||
+        event.created.length > 0 ||
+        event.removed.length > 0 ||
+        event.renamed.length > 0
+      ) {
         if (refreshTimerRef.current) clearTimeout(refreshTimerRef.current);
         refreshTimerRef.current = setTimeout(() => {
           refreshTree();
@@ -706,6 +684,7 @@ function App() {
       <CommandPalette />
       {settingsOpen && <SettingsPanel />}
       <ImageLightbox />
+      <McpRequestHandler />
       {showChangelog && currentAppVersion && (
         <ChangelogModal
           version={currentAppVersion}
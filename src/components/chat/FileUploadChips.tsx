@@ -37,6 +37,7 @@ export function FileUploadChips({ files, onRemove, isProcessing }: FileUploadChi
       {files.map((file) => (
         <div
           key={file.id}
+          title={file.previewText}
           className="inline-flex items-center gap-1.5 pl-2.5 pr-1 py-1.5
             rounded-lg border border-border-subtle bg-bg-secondary/50
             text-xs text-text-muted flex-shrink-0 group
@@ -1,14 +1,15 @@
-import { useEffect, useMemo, useCallback, useState } from 'react';
+import { useEffect, useMemo, useCallback, useRef, useState } from 'react';
 import { useSessionStore } from '../../stores/sessionStore';
 import { useChatStore, generateMessageId } from '../../stores/chatStore';
 import { useSettingsStore } from '../../stores/settingsStore';
 import { useFileStore } from '../../stores/fileStore';
 import { useAgentStore } from '../../stores/agentStore';
-import { bridge, SessionListItem } from '../../lib/tauri-bridge';
+import { ArchiveChanges, bridge, onSessionWindowMoved, SessionListItem } from '../../lib/tauri-bridge';
 import { listen } from '@tauri-apps/api/event';
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
 import { save } from '@tauri-apps/plugin-dialog';
 import { useT } from '../../lib/i18n';
-import { parseSessionMessages } from '../../lib/session-loader';
+import { applyLoadedSession, parseSessionMessages } from '../../lib/session-loader';
 import { SessionGroup } from './SessionGroup';
 import { SessionItem } from './SessionItem';
 import { SessionContextMenu, ProjectContextMenu } from './SessionContextMenu';
@@ -139,6 +140,8 @@ export function ConversationList() {
       return new Set(data ? JSON.parse(data) : []);
     } catch { return new Set(); }
   });
+  const archivedRef = useRef(archivedSessions);
+  archivedRef.current = archivedSessions;
   const [showArchived, setShowArchived] = useState(false);
 
   // Multi-select
@@ -171,7 +174,21 @@ export function ConversationList() {
     bridge.saveArchivedSessions([...next]).catch(() => {});
   }, []);
 
-  // Load pinned/archived from backend on init
+  // The archived list is ours; retention and restores only report edits to
+  // it (on `sessions:changed`, or queued for `takeRetentionChanges`).
+  const applyArchiveChanges = useCallback((changes: ArchiveChanges | null | undefined) => {
+    if (!changes) return;
+    const current = archivedRef.current;
+    const next = new Set(current);
+    changes.archived?.forEach((id) => next.add(id));
+    changes.unarchived?.forEach((id) => next.delete(id));
+    if (next.size === current.size && [...next].every((id) => current.has(id))) return;
+    archivedRef.current = next;
+    persistArchived(next);
+  }, [persistArchived]);
+
+  // Load pinned/archived from backend on init, then apply retention edits
+  // made before this listener existed (the startup pass runs early).
   useEffect(() => {
     bridge.loadPinnedSessions?.()
       .then((data: string[]) => {
@@ -180,10 +197,16 @@ export function ConversationList() {
       .catch(() => {});
     bridge.loadArchivedSessions?.()
       .then((data: string[]) => {
-        if (data?.length) setArchivedSessions(new Set(data));
+        if (data?.length) {
+          archivedRef.current = new Set(data);
+          setArchivedSessions(archivedRef.current);
+        }
       })
+      .catch(() => {})
+      .then(() => bridge.takeRetentionChanges())
+      .then(applyArchiveChanges)
       .catch(() => {});
-  }, []);
+  }, [applyArchiveChanges]);
 
   // Initial fetch + polling
   useEffect(() => {
@@ -204,14 +227,15 @@ export function ConversationList() {
     return () => clearInterval(interval);
   }, []);
 
-  // Listen for sessions:changed event for instant refresh
+  // Listen for sessions:changed event for instant refresh.
   useEffect(() => {
     let unlisten: (() => void) | undefined;
-    listen('sessions:changed', () => {
+    listen<ArchiveChanges | null>('sessions:changed', (event) => {
       fetchSessions();
+      applyArchiveChanges(event.payload);
     }).then((fn) => { unlisten = fn; }).catch(() => {});
     return () => { unlisten?.(); };
-  }, [fetchSessions]);
+  }, [fetchSessions, applyArchiveChanges]);
 
   // Debounce content search: 300ms after searchQuery changes, ≥2 chars
   useEffect(() => {
@@ -370,25 +394,7 @@ export function ConversationList() {
       if (useSessionStore.getState().selectedSessionId !== sessionId) {
         return;
       }
-      const { messages, agents } = parseSessionMessages(rawMessages);
-
-      // Apply agents
-      for (const agent of agents) {
-        agentActions.upsertAgent(agent);
-      }
-
-      // Apply messages
-      for (const msg of messages) {
-        if (msg.toolResultContent) {
-          // For messages that have tool results, add the base message first, then update
-          const { toolResultContent, ...baseMsg } = msg;
-          addMessage(sessionId, baseMsg);
-          useChatStore.getState().updateMessage(sessionId, msg.id, { toolResultContent });
-        } else {
-          addMessage(sessionId, msg);
-        }
-      }
-
+      applyLoadedSession(sessionId, parseSessionMessages(rawMessages));
       setSessionStatus(sessionId, 'completed');
     } catch (err) {
       if (useSessionStore.getState().selectedSessionId !== sessionId) return;
@@ -478,6 +484,43 @@ export function ConversationList() {
   }, [deleteAllTarget, executeDelete, fetchSessions]);
 
   // --- Context menu handlers ---
+  // --- Detached windows ---
+  const handleOpenInWindow = useCallback((session: SessionListItem) => {
+    bridge.detachSessionWindow(session.id, displayName(session) || undefined)
+      .catch((err) => console.error('Failed to open session window:', err));
+  }, [displayName]);
+
+  // The detached window claims its session once loaded: stop our process for
+  // it (its stream would keep landing here) and drop the tab. A session that
+  // comes back to this window is reloaded from disk to pick up its new turns.
+  const handleLoadSessionRef = useRef(handleLoadSession);
+  handleLoadSessionRef.current = handleLoadSession;
+  useEffect(() => {
+    const ownLabel = getCurrentWebviewWindow().label;
+    const unlisten = onSessionWindowMoved(async ({ session_id: sessionId, label }) => {
+      if (label === ownLabel) {
+        await fetchSessions();
+        const session = useSessionStore.getState().sessions.find((s) => s.id === sessionId);
+        if (session) handleLoadSessionRef.current(session);
+        return;
+      }
+      const tab = useChatStore.getState().getTab(sessionId);
+      if (!tab) return;
+      const stdinId = tab.sessionMeta.stdinId;
+      if (stdinId) {
+        await teardownSession(stdinId, sessionId, 'switch');
+        await waitForStdinCleared(sessionId, stdinId).catch(() => {});
+      }
+      if (useSessionStore.getState().selectedSessionId === sessionId) {
+        setSelected('');
+        useSettingsStore.getState().setWorkingDirectory('');
+      }
+      useChatStore.getState().removeTab(sessionId);
+      useAgentStore.getState().clearCacheForTab(sessionId);
+    });
+    return () => { unlisten.then((fn) => fn()); };
+  }, [fetchSessions, setSelected]);
+
   const handleContextMenu = useCallback((e: React.MouseEvent, session: SessionListItem) => {
     e.preventDefault();
     e.stopPropagation();
@@ -833,6 +876,10 @@ export function ConversationList() {
           onDelete={handleDeleteSingle}
           onPin={handleTogglePin}
           onArchive={handleToggleArchive}
+          onOpenInWindow={handleOpenInWindow}
+          openInWindowBlocked={
+            useChatStore.getState().getTab(contextMenu.session.id)?.sessionStatus === 'running'
+          }
           isPinned={pinnedSessions.has(contextMenu.session.id)}
           isArchived={archivedSessions.has(contextMenu.session.id)}
           onClose={() => setContextMenu(null)}
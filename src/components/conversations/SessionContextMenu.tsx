@@ -13,6 +13,9 @@ interface SessionContextMenuProps {
   onDelete: (session: SessionListItem) => void;
   onPin?: (session: SessionListItem) => void;
   onArchive?: (session: SessionListItem) => void;
+  onOpenInWindow?: (session: SessionListItem) => void;
+  /** The session is running; moving it would stop its process. */
+  openInWindowBlocked?: boolean;
   isPinned?: boolean;
   isArchived?: boolean;
   onClose: () => void;
@@ -28,6 +31,8 @@ export function SessionContextMenu({
   onDelete,
   onPin,
   onArchive,
+  onOpenInWindow,
+  openInWindowBlocked,
   isPinned,
   isArchived,
   onClose,
@@ -102,6 +107,24 @@ export function SessionContextMenu({
         </button>
       )}
 
+      {onOpenInWindow && session.path && (
+        <button
+          onClick={() => { onClose(); onOpenInWindow(session); }}
+          disabled={openInWindowBlocked}
+          title={openInWindowBlocked ? t('conv.openInWindowRunning') : undefined}
+          className="w-full flex items-center gap-2.5 px-3 py-1.5
+            text-xs text-text-primary hover:bg-bg-secondary transition-smooth
+            disabled:opacity-30 disabled:cursor-not-allowed"
+        >
+          <svg width="12" height="12" viewBox="0 0 16 16" fill="none"
+            stroke="currentColor" strokeWidth="1.5" strokeLinecap="round" strokeLinejoin="round">
+            <rect x="1.5" y="4.5" width="10" height="10" rx="1.5" />
+            <path d="M9 1.5h5.5V7M14.5 1.5L8 8" />
+          </svg>
+          {t('conv.openInWindow')}
+        </button>
+      )}
+
       {session.path && (
         <button
           onClick={() => { onClose(); onRevealInFinder(session); }}
@@ -116,6 +116,7 @@ export function FilePreview() {
   const saveFile = useFileStore((s) => s.saveFile);
   const discardEdits = useFileStore((s) => s.discardEdits);
   const isSaving = useFileStore((s) => s.isSaving);
+  const saveConflict = useFileStore((s) => s.saveConflict);
   const changedFiles = useFileStore((s) => s.changedFiles);
   const reloadContent = useFileStore((s) => s.reloadContent);
   const showUnsavedDialog = useFileStore((s) => s.showUnsavedDialog);
@@ -213,6 +214,11 @@ export function FilePreview() {
           {/* Save / Discard buttons — visible when editing with unsaved changes */}
           {isEditing && isDirty && (
             <div className="flex items-center gap-1 animate-fade-in">
+              {saveConflict && (
+                <span className="px-1.5 text-xs text-error">
+                  {t('files.saveConflict')}
+                </span>
+              )}
               <button
                 onClick={discardEdits}
                 className="px-2.5 py-1 rounded-lg text-xs font-medium
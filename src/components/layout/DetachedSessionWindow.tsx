@@ -0,0 +1,111 @@
+import { useEffect, useState } from 'react';
+import { getCurrentWindow } from '@tauri-apps/api/window';
+import { ChatPanel } from '../chat/ChatPanel';
+import { ImageLightbox } from '../shared/ImageLightbox';
+import { Toast } from '../shared/Toast';
+import { bridge } from '../../lib/tauri-bridge';
+import { applyLoadedSession, parseSessionMessages } from '../../lib/session-loader';
+import { teardownSession, waitForStdinCleared } from '../../lib/sessionLifecycle';
+import { useAppearance } from '../../hooks/useAppearance';
+import { useChatStore } from '../../stores/chatStore';
+import { useSessionStore } from '../../stores/sessionStore';
+import { useSettingsStore } from '../../stores/settingsStore';
+import { useProviderStore } from '../../stores/providerStore';
+import { useT } from '../../lib/i18n';
+
+/** Stop the session's CLI process before the window lets go of it, so its
+ *  stream does not fall back to a main window that no longer has the tab. */
+async function stopProcess(sessionId: string) {
+  const stdinId = useChatStore.getState().getTab(sessionId)?.sessionMeta.stdinId;
+  if (!stdinId) return;
+  await teardownSession(stdinId, sessionId, 'switch');
+  await waitForStdinCleared(sessionId, stdinId).catch(() => {});
+}
+
+/**
+ * Root of a window opened by `bridge.detachSessionWindow`: one session's chat,
+ * no sidebar. On mount it loads the session from disk and claims it, which
+ * makes the main window drop its tab. Processes started here are claimed by
+ * `spawnSession`. Closing the window or "Move back" returns the session to main,
+ * stopping its CLI process; main resumes it from the transcript.
+ */
+export function DetachedSessionWindow({ sessionId }: { sessionId: string }) {
+  const t = useT();
+  const [loadError, setLoadError] = useState<string | null>(null);
+  useAppearance();
+
+  useEffect(() => {
+    let cancelled = false;
+    useSessionStore.getState().loadCustomPreviewsFromDisk();
+    useProviderStore.getState().load();
+
+    (async () => {
+      const sessionState = useSessionStore.getState();
+      await sessionState.fetchSessions();
+      const session = useSessionStore.getState().sessions.find((s) => s.id === sessionId);
+      if (!session?.path) throw new Error(`Session ${sessionId} not found`);
+
+      const { ensureTab, setSessionMeta, setSessionStatus } = useChatStore.getState();
+      ensureTab(sessionId);
+      sessionState.setSelectedSession(sessionId);
+      sessionState.setCliResumeId(sessionId, sessionId);
+      setSessionMeta(sessionId, { sessionId, stdinId: undefined });
+      const dir = session.project?.startsWith('/') ? session.project : session.projectDir || session.project || '';
+      useSettingsStore.getState().setWorkingDirectory(dir);
+      getCurrentWindow().setTitle(sessionState.getDisplayName(session) || session.id).catch(() => {});
+
+      const rawMessages = await bridge.loadSession(session.path);
+      if (cancelled) return;
+      applyLoadedSession(sessionId, parseSessionMessages(rawMessages));
+      setSessionStatus(sessionId, 'completed');
+      await bridge.claimSession(sessionId);
+    })().catch((err) => {
+      if (!cancelled) setLoadError(String(err));
+    });
+
+    return () => { cancelled = true; };
+  }, [sessionId]);
+
+  useEffect(() => {
+    const unlisten = getCurrentWindow().onCloseRequested(() => stopProcess(sessionId));
+    return () => { unlisten.then((fn) => fn()); };
+  }, [sessionId]);
+
+  const moveToMain = async () => {
+    await stopProcess(sessionId);
+    await bridge.attachSessionToMain(sessionId).catch(() => {});
+    await getCurrentWindow().close();
+  };
+
+  return (
+    <>
+      <div className="flex flex-col h-full w-full overflow-hidden gradient-bg">
+        <div
+          data-tauri-drag-region
+          className="h-[28px] flex-shrink-0 flex items-center justify-end px-3"
+        >
+          <button
+            onClick={moveToMain}
+            title={t('conv.moveToMainHint')}
+            className="px-2 py-0.5 text-[11px] rounded-md text-text-muted
+              hover:text-text-primary hover:bg-bg-tertiary transition-smooth cursor-pointer"
+          >
+            {t('conv.moveToMain')}
+          </button>
+        </div>
+        <div className="flex-1 min-w-0 flex flex-col bg-bg-chat overflow-hidden">
+          {loadError ? (
+            <div className="m-auto text-xs text-text-muted text-center px-6">
+              <p className="text-text-primary mb-1">{t('conv.detachedLoadFailed')}</p>
+              <p>{loadError}</p>
+            </div>
+          ) : (
+            <ChatPanel key={sessionId} />
+          )}
+        </div>
+      </div>
+      <ImageLightbox />
+      <Toast />
+    </>
+  );
+}
@@ -179,7 +179,7 @@ export function ProviderManager({ alwaysExpanded = false }: { alwaysExpanded?: b
       if (!filePath) return;
       const exportTabId = useSessionStore.getState().selectedSessionId;
       if (exportTabId) {
-        await bridge.addPathGrant(exportTabId, filePath).catch(() => {});
+        await bridge.addPathGrant(exportTabId, filePath, { capability: 'write' }).catch(() => {});
       }
       await bridge.writeFileContent(filePath, json, exportTabId || undefined);
     } catch (e) {
@@ -0,0 +1,140 @@
+import { useEffect, useState } from 'react';
+import { createPortal } from 'react-dom';
+import { bridge, onMcpRequest, type McpUiRequest } from '../../lib/tauri-bridge';
+import { useFileStore } from '../../stores/fileStore';
+import { useSessionStore } from '../../stores/sessionStore';
+import { useChatStore } from '../../stores/chatStore';
+import { useT } from '../../lib/i18n';
+
+type AskRequest = Extract<McpUiRequest, { kind: 'ask_user' }>;
+
+/** The open session tabs, as the `list_open_tabs` tool reports them. */
+function openTabs() {
+  const { sessions, selectedSessionId, getDisplayName } = useSessionStore.getState();
+  const tabs = useChatStore.getState().tabs;
+  return Array.from(tabs.values()).map((tab) => {
+    const session = sessions.find((s) => s.id === tab.tabId);
+    return {
+      id: tab.tabId,
+      title: session ? getDisplayName(session) : tab.tabId,
+      cwd: session?.projectDir ?? null,
+      status: tab.sessionStatus,
+      active: tab.tabId === selectedSessionId,
+    };
+  });
+}
+
+/**
+ * Answers the built-in MCP server's UI requests (`mcp:request`): opens files
+ * in the viewer, lists open tabs and asks the user `ask_user` questions one
+ * at a time.
+ */
+export function McpRequestHandler() {
+  const t = useT();
+  const [questions, setQuestions] = useState<AskRequest[]>([]);
+  const [selected, setSelected] = useState<Set<string>>(new Set());
+  const [freeText, setFreeText] = useState('');
+
+  useEffect(() => {
+    const unlisten = onMcpRequest((request) => {
+      switch (request.kind) {
+        case 'open_file':
+          useFileStore.getState().selectFile(request.payload.path)
+            .then(() => bridge.respondMcpRequest(request.request_id, true))
+            .catch((e) => bridge.respondMcpRequest(request.request_id, undefined, String(e)));
+          break;
+        case 'list_tabs':
+          bridge.respondMcpRequest(request.request_id, openTabs()).catch(() => {});
+          break;
+        case 'ask_user':
+          setQuestions((q) => [...q, request]);
+          break;
+      }
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  const current = questions[0];
+  if (!current) return null;
+  const { question, options, multi_select, allow_free_text } = current.payload;
+
+  const finish = (answer?: string) => {
+    const reply = answer !== undefined
+      ? bridge.respondMcpRequest(current.request_id, answer)
+      : bridge.respondMcpRequest(current.request_id, undefined, 'The user dismissed the question');
+    reply.catch(() => {}); // already timed out on the backend
+    setQuestions((q) => q.slice(1));
+    setSelected(new Set());
+    setFreeText('');
+  };
+
+  const toggle = (option: string) => {
+    setSelected((prev) => {
+      const next = new Set(multi_select ? prev : []);
+      if (prev.has(option)) next.delete(option);
+      else next.add(option);
+      return next;
+    });
+  };
+
+  const answer = freeText.trim() || options.filter((o) => selected.has(o)).join(', ');
+
+  return createPortal(
+    <div className="fixed inset-0 z-[10000] flex items-center justify-center bg-black/40">
+      <div
+        className="bg-bg-card border border-border-subtle rounded-xl p-5
+          shadow-lg max-w-md w-full mx-4 animate-fade-in"
+      >
+        <h3 className="text-sm font-semibold text-text-primary mb-2">{t('mcp.askUser')}</h3>
+        <p className="text-sm text-text-primary mb-3 whitespace-pre-wrap">{question}</p>
+        {options.length > 0 && (
+          <div className="flex flex-col gap-1.5 mb-3">
+            {options.map((option) => (
+              <button
+                key={option}
+                onClick={() => toggle(option)}
+                className={`text-left px-3 py-1.5 text-xs rounded-lg border transition-smooth cursor-pointer
+                  ${selected.has(option)
+                    ? 'border-accent bg-accent/10 text-accent'
+                    : 'border-border-subtle text-text-primary hover:bg-bg-secondary'
+                  }`}
+              >
+                {option}
+              </button>
+            ))}
+          </div>
+        )}
+        {(allow_free_text || options.length === 0) && (
+          <textarea
+            value={freeText}
+            onChange={(e) => setFreeText(e.target.value)}
+            placeholder={t('mcp.askUserPlaceholder')}
+            rows={3}
+            className="w-full mb-3 px-3 py-2 text-xs rounded-lg bg-bg-secondary
+              border border-border-subtle text-text-primary outline-none resize-none"
+          />
+        )}
+        <div className="flex justify-end gap-2">
+          <button
+            onClick={() => finish()}
+            className="px-3 py-1.5 text-xs rounded-lg bg-bg-secondary
+              text-text-muted hover:bg-bg-tertiary transition-smooth cursor-pointer"
+          >
+            {t('mcp.askUserDismiss')}
+          </button>
+          <button
+            onClick={() => finish(answer)}
+            disabled={!answer}
+            className="px-3 py-1.5 text-xs rounded-lg bg-accent/10 text-accent
+              hover:bg-accent/20 transition-smooth cursor-pointer disabled:opacity-50"
+          >
+            {t('mcp.askUserSend')}
+          </button>
+        </div>
+      </div>
+    </div>,
+    document.body,
+  );
+}
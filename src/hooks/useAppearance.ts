@@ -0,0 +1,48 @@
+import { useEffect } from 'react';
+import { useSettingsStore } from '../stores/settingsStore';
+
+/** Apply the theme, color theme and font size settings to the document root.
+ *  Every window runs this — the main window and detached session windows. */
+export function useAppearance(): void {
+  const theme = useSettingsStore((s) => s.theme);
+  const colorTheme = useSettingsStore((s) => s.colorTheme);
+  const fontSize = useSettingsStore((s) => s.fontSize);
+
+  // Apply dark/light mode class to document
+  useEffect(() => {
+    const root = document.documentElement;
+    if (theme === 'dark') {
+      root.classList.add('dark');
+    } else if (theme === 'light') {
+      root.classList.remove('dark');
+    } else {
+      const mq = window.matchMedia('(prefers-color-scheme: dark)');
+      const apply = () => {
+        if (mq.matches) root.classList.add('dark');
+        else root.classList.remove('dark');
+      };
+      apply();
+      mq.addEventListener('change', apply);
+      return () => mq.removeEventListener('change', apply);
+    }
+  }, [theme]);
+
+  // Apply color theme class to document
+  useEffect(() => {
+    const root = document.documentElement;
+    root.classList.remove('theme-blue', 'theme-orange', 'theme-green');
+    if (colorTheme === 'blue') {
+      root.classList.add('theme-blue');
+    } else if (colorTheme === 'orange') {
+      root.classList.add('theme-orange');
+    } else if (colorTheme === 'green') {
+      root.classList.add('theme-green');
+    }
+    // 'black' is the default — no class needed
+  }, [colorTheme]);
+
+  // Apply font size to document root
+  useEffect(() => {
+    document.documentElement.style.fontSize = `${fontSize}px`;
+  }, [fontSize]);
+}
@@ -1,5 +1,5 @@
 import { useState, useCallback, useEffect, useRef } from 'react';
-import { bridge } from '../lib/tauri-bridge';
+import { bridge, AttachmentKind } from '../lib/tauri-bridge';
 import { getCurrentWindow } from '@tauri-apps/api/window';
 import { isTreeDragActive } from '../lib/drag-state';
 import { useSettingsStore } from '../stores/settingsStore';
@@ -17,6 +17,11 @@ export interface FileAttachment {
   type: string;
   isImage: boolean;
   preview?: string;   // Base64 data URL for image thumbnails
+  kind?: AttachmentKind;
+  previewText?: string;  // Extracted text (PDF/DOCX/text) for the chip tooltip
+  width?: number;
+  height?: number;
+  downscaled?: boolean;
 }
 
 // --- Helper ---
@@ -124,7 +129,7 @@ export function useFileAttachments() {
           // Read file bytes and save via Rust (into working directory for CLI access)
           const bytes = await readFileAsBytes(file);
           const cwd = useSettingsStore.getState().workingDirectory;
-          const tempPath = await bridge.saveTempFile(
+          const info = await bridge.saveAttachment(
             file.name,
             Array.from(bytes),
             cwd || undefined,
@@ -133,11 +138,17 @@ export function useFileAttachments() {
           newFiles.push({
             id: generateFileId(),
             name: file.name,
-            path: tempPath,
-            size: file.size,
-            type: file.type || 'application/octet-stream',
-            isImage: isImageMime(file.type),
+            path: info.path,
+            size: info.size,
+            // Backend sniffs magic bytes — pasted files often have no/wrong type
+            type: info.mime,
+            isImage: info.kind === 'image',
             preview,
+            kind: info.kind,
+            previewText: info.preview_text ?? undefined,
+            width: info.width ?? undefined,
+            height: info.height ?? undefined,
+            downscaled: info.downscaled,
           });
         } catch (err) {
           console.error('Failed to add file:', file.name, err);
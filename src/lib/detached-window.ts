@@ -0,0 +1,16 @@
+/**
+ * Detached session windows.
+ *
+ * `bridge.detachSessionWindow` opens the app again with
+ * `?detachedSession=<sessionId>`; that window renders only the one session
+ * (see `DetachedSessionWindow`) instead of the full app shell.
+ */
+
+/** The session this window was detached for, or null in the main window. */
+export function getDetachedSessionId(): string | null {
+  return new URLSearchParams(window.location.search).get('detachedSession');
+}
+
+export function isDetachedWindow(): boolean {
+  return getDetachedSessionId() !== null;
+}
@@ -92,6 +92,11 @@ const messages: Record<Locale, Record<string, string>> = {
     'conv.unpin': '取消置顶',
     'conv.archive': '归档',
     'conv.unarchive': '取消归档',
+    'conv.openInWindow': '在新窗口中打开',
+    'conv.moveToMain': '移回主窗口',
+    'conv.moveToMainHint': '移回时会停止正在运行的任务，下次发送消息时继续',
+    'conv.openInWindowRunning': '任务运行中，停止后才能在新窗口中打开',
+    'conv.detachedLoadFailed': '无法在此窗口中打开任务',
     'conv.showArchived': '显示归档',
     'conv.selectMode': '选择',
     'conv.selected': '已选 {n} 个',
@@ -142,6 +147,7 @@ const messages: Record<Locale, Record<string, string>> = {
     'files.edit': '编辑',
     'files.save': '保存',
     'files.saving': '保存中...',
+    'files.saveConflict': '文件已在磁盘上被修改，请处理标记的冲突后再次保存',
     'files.discard': '放弃',
     'files.unsavedChanges': '未保存的更改',
     'files.unsavedTitle': '未保存的更改',
@@ -603,6 +609,10 @@ const messages: Record<Locale, Record<string, string>> = {
     // MCP panel
     'panel.mcp': 'MCP',
     'mcp.title': 'MCP 服务器',
+    'mcp.askUser': 'Claude 的提问',
+    'mcp.askUserPlaceholder': '输入回答…',
+    'mcp.askUserSend': '发送',
+    'mcp.askUserDismiss': '忽略',
     'mcp.add': '添加',
     'mcp.edit': '编辑',
     'mcp.delete': '删除',
@@ -824,6 +834,11 @@ const messages: Record<Locale, Record<string, string>> = {
     'conv.unpin': 'Unpin',
     'conv.archive': 'Archive',
     'conv.unarchive': 'Unarchive',
+    'conv.openInWindow': 'Open in New Window',
+    'conv.moveToMain': 'Move Back to Main Window',
+    'conv.moveToMainHint': 'Moving stops the running task; it resumes on your next message',
+    'conv.openInWindowRunning': 'Stop the task before opening it in a new window',
+    'conv.detachedLoadFailed': 'Could not open the task in this window',
     'conv.showArchived': 'Show Archived',
     'conv.selectMode': 'Select',
     'conv.selected': '{n} selected',
@@ -874,6 +889,7 @@ const messages: Record<Locale, Record<string, string>> = {
     'files.edit': 'Edit',
     'files.save': 'Save',
     'files.saving': 'Saving...',
+    'files.saveConflict': 'File changed on disk. Resolve the marked conflicts and save again.',
     'files.discard': 'Discard',
     'files.unsavedChanges': 'Unsaved changes',
     'files.unsavedTitle': 'Unsaved Changes',
@@ -1335,6 +1351,10 @@ const messages: Record<Locale, Record<string, string>> = {
     // MCP panel
     'panel.mcp': 'MCP',
     'mcp.title': 'MCP Servers',
+    'mcp.askUser': 'Question from Claude',
+    'mcp.askUserPlaceholder': 'Type an answer…',
+    'mcp.askUserSend': 'Send',
+    'mcp.askUserDismiss': 'Dismiss',
     'mcp.add': 'Add',
     'mcp.edit': 'Edit',
     'mcp.delete': 'Delete',
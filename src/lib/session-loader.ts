@@ -1,6 +1,7 @@
 import type { ChatMessage } from '../stores/chatStore';
-import { generateMessageId } from '../stores/chatStore';
+import { generateMessageId, useChatStore } from '../stores/chatStore';
 import type { AgentPhase } from '../stores/agentStore';
+import { useAgentStore } from '../stores/agentStore';
 
 export interface AgentData {
   id: string;
@@ -265,3 +266,22 @@ export function parseSessionMessages(rawMessages: any[]): LoadedSession {
 
   return { messages, agents, mainAgentStartTime: sessionStartTime };
 }
+
+/** Apply a parsed session to `tabId`: agents first, then messages. Tool
+ *  results are added as an update so the tool card renders completed. */
+export function applyLoadedSession(tabId: string, loaded: LoadedSession): void {
+  const agentActions = useAgentStore.getState();
+  for (const agent of loaded.agents) {
+    agentActions.upsertAgent(agent);
+  }
+  const { addMessage, updateMessage } = useChatStore.getState();
+  for (const msg of loaded.messages) {
+    if (msg.toolResultContent) {
+      const { toolResultContent, ...baseMsg } = msg;
+      addMessage(tabId, baseMsg);
+      updateMessage(tabId, msg.id, { toolResultContent });
+    } else {
+      addMessage(tabId, msg);
+    }
+  }
+}
@@ -20,6 +20,7 @@ import { useChatStore, generateInterruptedId } from '../stores/chatStore';
 import type { SessionStatus } from '../stores/chatStore';
 import { useSessionStore } from '../stores/sessionStore';
 import { streamController } from '../stream/instance';
+import { isDetachedWindow } from './detached-window';
 import type { CliPermissionMode, SessionMode, ThinkingLevel } from '../stores/settingsStore';
 
 // ---------------------------------------------------------------------------
@@ -251,6 +252,12 @@ export async function spawnSession(params: SpawnParams): Promise<SpawnResult> {
     });
     rollbacks.push(unlistenExit);
 
+    // 3e. Session channels route to main unless bound elsewhere, so a
+    // detached window must claim each process it starts before it runs.
+    if (isDetachedWindow()) {
+      await bridge.claimSession(stdinId);
+    }
+
     // Store unlisten functions in global map
     if (!(window as any).__claudeUnlisteners) {
       (window as any).__claudeUnlisteners = {};
@@ -1,5 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
 import { listen, UnlistenFn } from '@tauri-apps/api/event';
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
 
 // --- Types ---
 
@@ -27,6 +28,41 @@ export interface StartSessionParams {
    *  before resuming. This prevents "invalid thinking signature" 400 errors when switching
    *  to a different model that can't verify the old model's cryptographic signatures. */
   model_switch?: boolean;
+  /** MCP servers for this session; omitted = the selection remembered for it. */
+  mcp_selection?: McpSelection;
+  /** 'worktree' runs the session in a managed git worktree on a new branch. */
+  isolation?: 'worktree';
+}
+
+/** Which configured MCP servers a session gets. */
+export interface McpSelection {
+  disabled: string[];
+  /** When set, only these servers are included (before `disabled`). */
+  only?: string[];
+}
+
+export interface McpServerEntry {
+  name: string;
+  scope: 'global' | 'project';
+  transport: 'stdio' | 'http' | 'sse' | string;
+  /** Raw entry as stored in ~/.claude.json / .mcp.json. */
+  config: Record<string, unknown>;
+  /** A project entry with the same name replaces this one in sessions. */
+  overridden: boolean;
+  issues?: string[];
+}
+
+export interface McpHealthReport {
+  ok: boolean;
+  transport: string;
+  server_name?: string;
+  server_version?: string;
+  protocol_version?: string;
+  tools: { name: string; description?: string }[];
+  error?: string;
+  /** Tail of a stdio server's stderr. */
+  stderr?: string;
+  elapsed_ms: number;
 }
 
 export interface SessionInfo {
@@ -39,6 +75,154 @@ export interface SessionInfo {
   cli_session_id: string | null;
   pid: number;
   cli_path: string;
+  /** Present for worktree-isolated sessions; `session_cwd` is the CLI's cwd. */
+  worktree?: ManagedWorktree;
+}
+
+export interface ManagedWorktree {
+  session_id: string;
+  repo_root: string;
+  path: string;
+  session_cwd: string;
+  branch: string;
+  /** null when the repo was on a detached HEAD. */
+  base_branch: string | null;
+  base_commit: string;
+  created_at: number;
+}
+
+export interface WorktreeStatus extends ManagedWorktree {
+  exists: boolean;
+  dirty: boolean;
+  commits_ahead: number;
+}
+
+export interface WorktreeDiff {
+  branch: string;
+  base_commit: string;
+  files: { status: string; path: string; old_path?: string }[];
+  diff: string;
+  truncated: boolean;
+}
+
+export interface WorktreeMergeResult {
+  merged: boolean;
+  commit?: string;
+  /** Non-empty when the merge conflicted and was aborted. */
+  conflicts: string[];
+  removed: boolean;
+  message: string;
+}
+
+export interface GitFileStatus {
+  path: string;
+  /** Source path of a rename or copy. */
+  orig_path?: string;
+  status: 'added' | 'modified' | 'deleted' | 'renamed' | 'copied' | 'type-changed' | 'unmerged';
+  similarity?: number;
+}
+
+export interface GitStatus {
+  /** null on a detached HEAD. */
+  branch: string | null;
+  /** null before the first commit. */
+  head: string | null;
+  upstream: string | null;
+  ahead: number;
+  behind: number;
+  staged: GitFileStatus[];
+  unstaged: GitFileStatus[];
+  untracked: string[];
+  conflicted: GitFileStatus[];
+}
+
+export interface DiffLine {
+  kind: 'context' | 'add' | 'del' | 'change';
+  content: string;
+  old_line?: number;
+  new_line?: number;
+  /** Word-level pieces, only when requested with `wordDiff`. */
+  segments?: { kind: 'context' | 'add' | 'del'; text: string }[];
+}
+
+export interface DiffHunk {
+  old_start: number;
+  old_lines: number;
+  new_start: number;
+  new_lines: number;
+  header: string;
+  lines: DiffLine[];
+}
+
+export interface DiffFile {
+  old_path: string | null;
+  new_path: string | null;
+  status: 'added' | 'deleted' | 'modified' | 'renamed' | 'copied';
+  binary: boolean;
+  additions: number;
+  deletions: number;
+  hunks: DiffHunk[];
+}
+
+export interface GitCommit {
+  hash: string;
+  short_hash: string;
+  parents: string[];
+  author_name: string;
+  author_email: string;
+  /** Unix seconds. */
+  author_time: number;
+  refs: string[];
+  subject: string;
+  body: string;
+}
+
+export interface GitLogPage {
+  commits: GitCommit[];
+  skip: number;
+  has_more: boolean;
+}
+
+export interface GitBlame {
+  lines: { line: number; commit: string; orig_line: number; content: string }[];
+  commits: Record<string, {
+    author: string;
+    author_email: string;
+    author_time: number;
+    summary: string;
+    uncommitted: boolean;
+  }>;
+}
+
+export interface GitCommitResult {
+  commit: string;
+  /** null when committed on a detached HEAD. */
+  branch: string | null;
+  summary: string;
+  output: string;
+}
+
+export interface TurnSnapshot {
+  id: string;
+  /** Stdin id of the session that took the snapshot. */
+  session_id: string;
+  cli_session_id?: string;
+  cwd: string;
+  kind: 'git' | 'copy';
+  repo_root?: string;
+  commit?: string;
+  /** First line of the user message that followed the snapshot. */
+  label: string;
+  created_at: number;
+}
+
+export interface SnapshotRestoreResult {
+  restored: string[];
+  removed: string[];
+  /** Created since the snapshot but left in place: the backup could not hold them. */
+  kept: string[];
+  /** Snapshot of the state just before the restore (restore it to undo). */
+  backup: TurnSnapshot;
 }
 
 export interface SessionListItem {
@@ -59,6 +243,162 @@ export interface ContentSearchResult {
   match_role: 'user' | 'assistant';
 }
 
+export interface ImportedSession {
+  session_id: string;
+  path: string;
+  project: string;
+  project_dir: string;
+  message_count: number;
+  /** cwd recorded in the source file before it was rebased onto `project` */
+  original_cwd: string;
+  /** True when the source id collided locally and a fresh UUID was assigned */
+  renamed: boolean;
+  warnings: string[];
+}
+
+export interface TokenUsage {
+  input_tokens: number;
+  output_tokens: number;
+  cache_creation_input_tokens: number;
+  cache_read_input_tokens: number;
+}
+
+export interface TurnSummary {
+  index: number;
+  prompt: string;
+  tools: string[];
+  files_touched: string[];
+  usage: TokenUsage;
+  duration_ms: number | null;
+  final_text: string;
+}
+
+export interface SessionSummary {
+  session_id: string;
+  path: string;
+  cwd: string;
+  models: string[];
+  turn_count: number;
+  usage: TokenUsage;
+  duration_ms: number;
+  tool_counts: Record<string, number>;
+}
+
+export interface TurnDiff {
+  left: TurnSummary | null;
+  right: TurnSummary | null;
+  prompt_matches: boolean;
+  tools_only_left: string[];
+  tools_only_right: string[];
+  files_only_left: string[];
+  files_only_right: string[];
+  /** right − left */
+  token_delta: TokenUsage;
+  duration_delta_ms: number | null;
+  final_text_identical: boolean;
+  final_text_similarity: number;
+}
+
+export interface SessionComparison {
+  left: SessionSummary;
+  right: SessionSummary;
+  turns: TurnDiff[];
+  token_delta: TokenUsage;
+  duration_delta_ms: number;
+}
+
+export interface RetentionRule {
+  enabled: boolean;
+  /** Archive after N idle days; null = never. */
+  archive_after_days: number | null;
+  /** Gzip archived JSONL into ~/.tokenicode/archive/. */
+  compress_archived: boolean;
+  /** Hard-delete after N idle days; null = never. */
+  delete_after_days: number | null;
+  use_trash: boolean;
+}
+
+export interface RetentionPolicy {
+  version: number;
+  default: RetentionRule;
+  /** Per-project overrides keyed by project cwd. */
+  projects: Record<string, RetentionRule>;
+}
+
+export interface RetentionReport {
+  dry_run: boolean;
+  archived: string[];
+  compressed: string[];
+  deleted: string[];
+  bytes_reclaimed: number;
+  errors: string[];
+}
+
+/** Edits to the sidebar's archived list, carried by `sessions:changed`. */
+export interface ArchiveChanges {
+  archived?: string[];
+  unarchived?: string[];
+}
+
+export interface CompressedSession {
+  session_id: string;
+  project: string;
+  project_dir: string;
+  archive_path: string;
+  last_active_ms: number;
+  archived_at_ms: number;
+  original_bytes: number;
+  compressed_bytes: number;
+}
+
+export interface RestoredSession {
+  session_id: string;
+  /** Restored JSONL under ~/.claude/projects/. */
+  path: string;
+  /** The sidebar should drop the id from its archived list. */
+  was_archived: boolean;
+}
+
+export interface FileTouch {
+  /** Absolute, normalized path. */
+  path: string;
+  /** Edit | Write | MultiEdit | NotebookEdit | Bash */
+  tool: string;
+  message_uuid: string;
+  timestamp: string;
+  /** Bash command excerpt (Bash touches only). */
+  command?: string;
+}
+
+export interface FileHistoryEntry {
+  session_id: string;
+  project: string;
+  jsonl_path: string;
+  last_touched: string;
+  touches: FileTouch[];
+}
+
+export interface ChangedFile {
+  path: string;
+  relative_path: string;
+  touches: FileTouch[];
+}
+
+export interface FileIndexStats {
+  sessions: number;
+  files: number;
+  touches: number;
+  reindexed: number;
+}
+
+export interface SessionWindowBinding {
+  session_id: string;
+  /** Window label showing the session tab. */
+  owner: string;
+  /** Every window receiving the session's events (owner first). */
+  subscribers: string[];
+}
+
 export interface FileNode {
   name: string;
   path: string;
@@ -66,6 +406,239 @@ export interface FileNode {
   children: FileNode[] | null;
 }
 
+export interface TreeEntry {
+  name: string;
+  path: string;
+  is_dir: boolean;
+  is_symlink: boolean;
+}
+
+export interface DirListing {
+  dir: string;
+  entries: TreeEntry[];
+  /** Visible entries before paging. */
+  total: number;
+  /** More entries exist; fetch them with a larger `offset`. */
+  truncated: boolean;
+}
+
+/** Per-project explorer settings (gitignore-style globs). */
+export interface FileTreeConfig {
+  /** Shown even when gitignored, e.g. `.env`. */
+  include: string[];
+  /** Always hidden. */
+  exclude: string[];
+  max_entries_per_dir?: number;
+}
+
+export interface FileTreeChunk {
+  request_id: string;
+  dirs: DirListing[];
+  done: boolean;
+  capped?: boolean;
+  cancelled?: boolean;
+}
+
+export interface SearchOptions {
+  query: string;
+  /** Default `literal`. */
+  mode?: 'literal' | 'regex';
+  /** `smart` (default) ignores case unless the query has an uppercase letter. */
+  case?: 'smart' | 'sensitive' | 'insensitive';
+  whole_word?: boolean;
+  /** Gitignore-style globs; when set, only matching files are searched. */
+  include?: string[];
+  exclude?: string[];
+  /** Default true. */
+  respect_gitignore?: boolean;
+  max_results?: number;
+  context_lines?: number;
+  max_file_size?: number;
+}
+
+export interface SearchMatch {
+  /** 1-based. */
+  line: number;
+  text: string;
+  /** `[start, end)` offsets into `text`, usable with `String.slice`. */
+  ranges: [number, number][];
+  before: string[];
+  after: string[];
+}
+
+export interface FileMatches {
+  path: string;
+  matches: SearchMatch[];
+}
+
+export interface SearchChunk {
+  request_id: string;
+  files: FileMatches[];
+  done: boolean;
+  truncated?: boolean;
+  cancelled?: boolean;
+}
+
+export interface SearchSummary {
+  files_searched: number;
+  files_matched: number;
+  matches: number;
+  truncated: boolean;
+  cancelled: boolean;
+  elapsed_ms: number;
+}
+
+export interface FuzzyMatch {
+  path: string;
+  rel_path: string;
+  score: number;
+  /** Matched character offsets in `rel_path`, for highlighting. */
+  positions: number[];
+  /** Among the files the agent edited most recently. */
+  recent: boolean;
+}
+
+export interface FuzzyFindResult {
+  matches: FuzzyMatch[];
+  total_files: number;
+  /** The project has more files than the index holds. */
+  capped: boolean;
+}
+
+export interface FileEncodingInfo {
+  /** WHATWG name, e.g. `UTF-8`, `UTF-16LE`, `GBK`. */
+  encoding: string;
+  bom: boolean;
+  binary: boolean;
+  size: number;
+}
+
+export interface FileLines {
+  lines: string[];
+  /** 1-based; null for a tail until the file has been counted. */
+  start_line: number | null;
+  start_byte: number;
+  end_byte: number;
+  eof: boolean;
+  total_lines: number | null;
+  size: number;
+  encoding: string;
+  /** Some lines were cut at the per-line limit. */
+  clipped: boolean;
+}
+
+export interface FileSearchResult {
+  matches: SearchMatch[];
+  truncated: boolean;
+  /** Pass as `startLine` to continue a search that stopped early. */
+  next_line: number | null;
+  total_lines: number | null;
+  encoding: string;
+}
+
+export interface FileVersion {
+  /** Hex SHA-256 of the content. */
+  hash: string;
+  mtime_ms: number;
+  size: number;
+}
+
+/** Version a save expects on disk; the hash wins when both are given. */
+export interface ExpectedFileVersion {
+  hash?: string;
+  mtimeMs?: number;
+}
+
+export type WriteOutcome =
+  | { status: 'written'; version: FileVersion }
+  | {
+      status: 'conflict';
+      /** Null if the file was deleted. */
+      current: FileVersion | null;
+      /** Present when the file is UTF-8 text and at most 1MB. */
+      current_content: string | null;
+    };
+
+export interface VersionedContent {
+  content: string;
+  version: FileVersion;
+}
+
+export interface MergeResult {
+  merged: string;
+  conflicts: number;
+  clean: boolean;
+}
+
+export type PathCapability = 'read' | 'write' | 'delete';
+
+export interface PathGrant {
+  id: string;
+  path: string;
+  /** A grant also allows every lower capability (read < write < delete). */
+  capability: PathCapability;
+  /** `{ tab }` grants are in-memory; `{ project }` grants are persisted. */
+  scope: { tab: string } | { project: string };
+  granted_at_ms: number;
+  expires_at_ms: number | null;
+}
+
+export interface PathGrantOptions {
+  /** Defaults to 'read'. */
+  capability?: PathCapability;
+  expiresInSecs?: number;
+  /** Persist the grant for this project instead of scoping it to the tab. */
+  project?: string;
+}
+
+export type AttachmentKind = 'image' | 'pdf' | 'document' | 'text' | 'archive' | 'binary';
+
+export interface AttachmentInfo {
+  /** Stored path, as passed to the CLI. */
+  path: string;
+  /** Name the user attached it under. */
+  name: string;
+  mime: string;
+  kind: AttachmentKind;
+  /** Stored size (after downscaling). */
+  size: number;
+  original_size: number;
+  sha256: string;
+  width: number | null;
+  height: number | null;
+  downscaled: boolean;
+  /** An identical file was already stored and is reused. */
+  deduplicated: boolean;
+  /** Start of the text content, for PDF, DOCX and text files. */
+  preview_text: string | null;
+}
+
+export interface AttachmentGcConfig {
+  /** Run the collection at startup. */
+  enabled: boolean;
+  /** Unreferenced attachments younger than this are kept. */
+  min_age_days: number;
+}
+
+export interface AttachmentUsage {
+  path: string;
+  size: number;
+  modified_ms: number;
+  /** Ids of the sessions referencing the file. */
+  sessions: string[];
+}
+
+export interface AttachmentGcReport {
+  dry_run: boolean;
+  scanned: number;
+  referenced: number;
+  /** Unreferenced but younger than `min_age_days`. */
+  kept_recent: number;
+  removed: string[];
+  reclaimed_bytes: number;
+  errors: string[];
+}
+
 export interface RecentProject {
   name: string;
   path: string;
@@ -73,10 +646,15 @@ export interface RecentProject {
   lastUsed: number;
 }
 
+/** A coalesced batch of watcher changes; each path appears in one list only. */
 export interface FileChangeEvent {
-  kind: 'created' | 'modified' | 'removed';
-  paths: string[];
   root: string;
+  created: string[];
+  modified: string[];
+  removed: string[];
+  renamed: { from: string; to: string }[];
+  /** Too many changes to list (or events were lost): reload the tree. */
+  overflow?: boolean;
 }
 
 export interface SlashCommand {
@@ -99,6 +677,91 @@ export interface SkillInfo {
   context?: string;
   agent?: string;
   version?: string;
+  /** Lint findings; omitted when the skill is clean. */
+  diagnostics?: SkillDiagnostic[];
+}
+
+export interface SkillDiagnostic {
+  severity: 'error' | 'warning';
+  /** yaml-syntax | yaml-schema | unknown-key | unknown-tool | missing-description | duplicate-name | missing-file */
+  code: string;
+  message: string;
+  /** 1-based line in SKILL.md */
+  line?: number;
+}
+
+export interface SkillLintReport {
+  name: string;
+  scope: 'global' | 'project';
+  path: string;
+  diagnostics: SkillDiagnostic[];
+}
+
+/** Subagent definition from ~/.claude/agents or .claude/agents. */
+export interface AgentInfo {
+  name: string;
+  description: string;
+  path: string;
+  scope: 'global' | 'project';
+  /** Absent = inherits all tools. */
+  tools?: string[];
+  model?: string;
+  color?: string;
+  diagnostics?: SkillDiagnostic[];
+}
+
+export interface ShellSnippetResult {
+  command: string;
+  status: 'ran' | 'blocked' | 'failed';
+  exit_code?: number;
+  reason?: string;
+}
+
+export interface RenderedCommand {
+  name: string;
+  path: string;
+  /** Final prompt text to send to the CLI. */
+  prompt: string;
+  model?: string;
+  allowed_tools?: string[];
+  included_files: string[];
+  shell_snippets: ShellSnippetResult[];
+  /** Ask the user, then render again with `allowShell: true`. */
+  needs_shell_permission: boolean;
+  warnings: string[];
+}
+
+export interface PackageIssue {
+  severity: 'error' | 'warning';
+  message: string;
+}
+
+export interface SkillConflict {
+  /** exists: same name in target scope; shadows/shadowed: same name in the other scope */
+  kind: 'exists' | 'shadows' | 'shadowed';
+  scope: 'global' | 'project';
+  path: string;
+  existing_version?: string;
+}
+
+export interface SkillInstallReport {
+  name: string;
+  scope: 'global' | 'project';
+  target_dir: string;
+  version?: string;
+  source_revision?: string;
+  files: number;
+  bytes: number;
+  conflicts: SkillConflict[];
+  issues: PackageIssue[];
+  installed: boolean;
+}
+
+export interface SkillExportReport {
+  path: string;
+  files: number;
+  bytes: number;
+  issues: PackageIssue[];
 }
 
 export interface CliStatus {
@@ -187,7 +850,7 @@ export interface UnifiedCommand {
   name: string;
   description: string;
   source: 'builtin' | 'global' | 'project';
-  category: 'builtin' | 'command' | 'skill';
+  category: 'builtin' | 'command' | 'skill' | 'agent';
   has_args: boolean;
   path?: string;
   immediate: boolean;
@@ -235,6 +898,75 @@ export const bridge = {
   loadSession: (path: string) =>
     invoke<any[]>('load_session', { path }),
 
+  /** Import an exported session JSON or raw CLI JSONL from another machine,
+   *  rebasing its cwd onto `projectPath` so it can be resumed locally. */
+  importSession: (sourcePath: string, projectPath: string) =>
+    invoke<ImportedSession>('import_session', { sourcePath, projectPath }),
+
+  /** Align two session JSONLs by turn and report tool/file/token/duration/text differences. */
+  compareSessions: (leftPath: string, rightPath: string) =>
+    invoke<SessionComparison>('compare_sessions', { leftPath, rightPath }),
+
+  // Session retention (archive / compress / delete by idle age)
+  loadRetentionPolicy: () =>
+    invoke<RetentionPolicy>('load_retention_policy'),
+
+  saveRetentionPolicy: (policy: RetentionPolicy) =>
+    invoke<void>('save_retention_policy', { policy }),
+
+  /** Run the retention pass now; `dryRun` reports without touching files. */
+  applyRetentionPolicy: (dryRun?: boolean) =>
+    invoke<RetentionReport>('apply_retention_policy', { dryRun }),
+
+  listCompressedSessions: () =>
+    invoke<CompressedSession[]>('list_compressed_sessions'),
+
+  /** Archive-list edits from retention passes since the last call. */
+  takeRetentionChanges: () =>
+    invoke<ArchiveChanges>('take_retention_changes'),
+
+  /**
+   * Decompress an archived session back into ~/.claude/projects/. archived.json
+   * is left alone; the sidebar drops the id on the `sessions:changed` event.
+   */
+  restoreArchivedSession: (sessionId: string) =>
+    invoke<RestoredSession>('restore_archived_session', { sessionId }),
+
+  // Files-touched index
+  /** Sessions that modified `path`, newest first; `recursive` treats it as a directory. */
+  findSessionsTouchingFile: (path: string, recursive?: boolean) =>
+    invoke<FileHistoryEntry[]>('find_sessions_touching_file', { path, recursive }),
+
+  getSessionChangedFiles: (sessionId: string) =>
+    invoke<ChangedFile[]>('get_session_changed_files', { sessionId }),
+
+  rebuildFileIndex: () =>
+    invoke<FileIndexStats>('rebuild_file_index'),
+
+  // Multi-window session routing
+  /** Open (or focus) a dedicated window for a session. The new window loads
+   *  `?detachedSession=<sessionId>`, loads the session and calls `claimSession`
+   *  for it, which tells the main window to drop the tab. Returns the label. */
+  detachSessionWindow: (sessionId: string, title?: string) =>
+    invoke<string>('detach_session_window', { sessionId, title }),
+
+  /** Bind a session (or a stdinId's stream events) to the calling window. */
+  claimSession: (sessionId: string) =>
+    invoke<void>('claim_session', { sessionId }),
+
+  /** Move a session back to the main window, closing its window if now empty. */
+  attachSessionToMain: (sessionId: string) =>
+    invoke<void>('attach_session_to_main', { sessionId }),
+
+  subscribeSessionEvents: (sessionId: string) =>
+    invoke<void>('subscribe_session_events', { sessionId }),
+
+  unsubscribeSessionEvents: (sessionId: string) =>
+    invoke<void>('unsubscribe_session_events', { sessionId }),
+
+  listSessionWindows: () =>
+    invoke<SessionWindowBinding[]>('list_session_windows'),
+
   openInVscode: (path: string) =>
     invoke<void>('open_in_vscode', { path }),
 
@@ -253,11 +985,93 @@ export const bridge = {
   readFileTree: (path: string, depth?: number) =>
     invoke<FileNode[]>('read_file_tree', { path, depth }),
 
+  /** One directory of the explorer, gitignore-aware and paged. */
+  listDirectory: (
+    root: string,
+    dir: string,
+    opts?: { offset?: number; limit?: number },
+    tabId?: string,
+  ) =>
+    invoke<DirListing>('list_directory', { root, dir, ...opts, tabId: tabId ?? null }),
+
+  /** Breadth-first walk streamed as `file-tree:chunk` events (see `onFileTreeChunk`). */
+  streamFileTree: (root: string, requestId: string, depth?: number) =>
+    invoke<void>('stream_file_tree', { root, requestId, depth }),
+
+  cancelFileTree: (requestId: string) =>
+    invoke<void>('cancel_file_tree', { requestId }),
+
+  getFileTreeConfig: (root: string) =>
+    invoke<FileTreeConfig>('get_file_tree_config', { root }),
+
+  setFileTreeConfig: (root: string, config: FileTreeConfig) =>
+    invoke<void>('set_file_tree_config', { root, config }),
+
+  /** Content search streamed as `search:chunk` events (see `onSearchChunk`). */
+  searchInProject: (root: string, requestId: string, options: SearchOptions, tabId?: string) =>
+    invoke<SearchSummary>('search_in_project', { root, requestId, options, tabId: tabId ?? null }),
+
+  cancelProjectSearch: (requestId: string) =>
+    invoke<void>('cancel_project_search', { requestId }),
+
+  /** Quick-open: ranked files below `root`. An empty query lists recent agent edits. */
+  fuzzyFindFiles: (root: string, query: string, limit?: number, tabId?: string) =>
+    invoke<FuzzyFindResult>('fuzzy_find_files', { root, query, limit, tabId: tabId ?? null }),
+
+  resetPathIndex: (root: string) =>
+    invoke<void>('reset_path_index', { root }),
+
   readFileContent: (path: string, tabId?: string) =>
     invoke<string>('read_file_content', { path, tabId: tabId ?? null }),
 
-  writeFileContent: (path: string, content: string, tabId?: string) =>
-    invoke<void>('write_file_content', { path, content, tabId: tabId ?? null }),
+  readFileVersioned: (path: string, tabId?: string) =>
+    invoke<VersionedContent>('read_file_versioned', { path, tabId: tabId ?? null }),
+
+  detectFileEncoding: (path: string, tabId?: string) =>
+    invoke<FileEncodingInfo>('detect_file_encoding', { path, tabId: tabId ?? null }),
+
+  /** Lines `startLine`.. of any size file; `encoding` forces a label such as `gbk`. */
+  readFileLines: (
+    path: string,
+    startLine: number,
+    opts?: { lineCount?: number; encoding?: string },
+    tabId?: string,
+  ) =>
+    invoke<FileLines>('read_file_lines', { path, startLine, ...opts, tabId: tabId ?? null }),
+
+  tailFileLines: (path: string, opts?: { lineCount?: number; encoding?: string }, tabId?: string) =>
+    invoke<FileLines>('tail_file_lines', { path, ...opts, tabId: tabId ?? null }),
+
+  searchInFile: (
+    path: string,
+    options: SearchOptions,
+    opts?: { startLine?: number; encoding?: string },
+    tabId?: string,
+  ) =>
+    invoke<FileSearchResult>('search_in_file', { path, options, ...opts, tabId: tabId ?? null }),
+
+  countFileLines: (path: string, encoding?: string, tabId?: string) =>
+    invoke<number>('count_file_lines', { path, encoding, tabId: tabId ?? null }),
+
+  writeFileContent: (
+    path: string,
+    content: string,
+    tabId?: string,
+    expected?: ExpectedFileVersion,
+  ) =>
+    invoke<WriteOutcome>('write_file_content', {
+      path,
+      content,
+      tabId: tabId ?? null,
+      expectedHash: expected?.hash ?? null,
+      expectedMtimeMs: expected?.mtimeMs ?? null,
+    }),
+
+  getFileVersion: (path: string, tabId?: string) =>
+    invoke<FileVersion | null>('get_file_version', { path, tabId: tabId ?? null }),
+
+  mergeFileVersions: (base: string, ours: string, theirs: string) =>
+    invoke<MergeResult>('merge_file_versions', { base, ours, theirs }),
 
   copyFile: (src: string, dest: string, tabId?: string) =>
     invoke<void>('copy_file', { src, dest, tabId: tabId ?? null }),
@@ -272,8 +1086,21 @@ export const bridge = {
     invoke<void>('create_directory', { path, tabId: tabId ?? null }),
 
   /** Add a path grant for the given tab (authorize external file access). */
-  addPathGrant: (tabId: string, path: string) =>
-    invoke<void>('add_path_grant', { tabId, path }),
+  addPathGrant: (tabId: string, path: string, options?: PathGrantOptions) =>
+    invoke<PathGrant>('add_path_grant', {
+      tabId,
+      path,
+      capability: options?.capability ?? null,
+      expiresInSecs: options?.expiresInSecs ?? null,
+      project: options?.project ?? null,
+    }),
+
+  /** Active grants for a tab (plus open projects' grants), or all grants. */
+  listPathGrants: (tabId?: string) =>
+    invoke<PathGrant[]>('list_path_grants', { tabId: tabId ?? null }),
+
+  revokePathGrant: (grantId: string) =>
+    invoke<boolean>('revoke_path_grant', { grantId }),
 
   /** Revoke all grants for the given tab (called on tab close / teardown). */
   clearPathGrants: (tabId: string) =>
@@ -305,6 +1132,25 @@ export const bridge = {
   saveTempFile: (name: string, data: number[], cwd?: string) =>
     invoke<string>('save_temp_file', { name, data, cwd: cwd || null }),
 
+  /** Store an attachment (type-sniffed, downscaled, deduplicated) and describe it. */
+  saveAttachment: (name: string, data: number[], cwd?: string) =>
+    invoke<AttachmentInfo>('save_attachment', { name, data, cwd: cwd || null }),
+
+  loadAttachmentGcConfig: () =>
+    invoke<AttachmentGcConfig>('load_attachment_gc_config'),
+
+  saveAttachmentGcConfig: (config: AttachmentGcConfig) =>
+    invoke<void>('save_attachment_gc_config', { config }),
+
+  collectAttachmentGarbage: (minAgeDays?: number, dryRun?: boolean) =>
+    invoke<AttachmentGcReport>('collect_attachment_garbage', {
+      minAgeDays: minAgeDays ?? null,
+      dryRun: dryRun ?? null,
+    }),
+
+  listAttachmentUsage: () =>
+    invoke<AttachmentUsage[]>('list_attachment_usage'),
+
   getFileSize: (path: string, tabId?: string) =>
     invoke<number>('get_file_size', { path, tabId: tabId ?? null }),
 
@@ -335,14 +1181,190 @@ export const bridge = {
   toggleSkillEnabled: (path: string, enabled: boolean, tabId?: string) =>
     invoke<void>('toggle_skill_enabled', { path, enabled, tabId: tabId ?? null }),
 
-  // Unified commands (commands + skills)
+  /** Install a skill from a .zip/.tar.gz or local directory (git checkout).
+   *  Returns `installed: false` with conflicts/issues when blocked or on dry run. */
+  installSkillPackage: (params: {
+    source: string;
+    scope: 'global' | 'project';
+    cwd?: string;
+    name?: string;
+    subdir?: string;
+    overwrite?: boolean;
+    dryRun?: boolean;
+    tabId?: string;
+  }) =>
+    invoke<SkillInstallReport>('install_skill_package', {
+      ...params,
+      tabId: params.tabId ?? null,
+    }),
+
+  lintSkills: (cwd?: string) =>
+    invoke<SkillLintReport[]>('lint_skills', { cwd }),
+
+  /** Export a skill directory to `dest` (.zip, .tar.gz or .tgz). */
+  exportSkillPackage: (skillPath: string, dest: string, tabId?: string) =>
+    invoke<SkillExportReport>('export_skill_package', { skillPath, dest, tabId: tabId ?? null }),
+
+  /** Expand a custom slash command ($ARGUMENTS/$1…, @file includes, !`shell`)
+   *  into the prompt the CLI would send. */
+  renderSlashCommand: (
+    name: string,
+    args?: string,
+    cwd?: string,
+    allowShell?: boolean,
+    tabId?: string,
+  ) =>
+    invoke<RenderedCommand>('render_slash_command', {
+      name,
+      arguments: args,
+      cwd,
+      allowShell,
+      tabId: tabId ?? null,
+    }),
+
+  // Agents (~/.claude/agents, .claude/agents)
+  listAgents: (cwd?: string) =>
+    invoke<AgentInfo[]>('list_agents', { cwd }),
+
+  readAgent: (path: string, tabId?: string) =>
+    invoke<string>('read_agent', { path, tabId: tabId ?? null }),
+
+  /** Saves the file and returns its diagnostics. */
+  writeAgent: (path: string, content: string, tabId?: string) =>
+    invoke<SkillDiagnostic[]>('write_agent', { path, content, tabId: tabId ?? null }),
+
+  deleteAgent: (path: string, tabId?: string) =>
+    invoke<void>('delete_agent', { path, tabId: tabId ?? null }),
+
+  validateAgent: (content: string) =>
+    invoke<SkillDiagnostic[]>('validate_agent', { content }),
+
+  // Unified commands (commands + skills + agents)
   listAllCommands: (cwd?: string) =>
     invoke<UnifiedCommand[]>('list_all_commands', { cwd }),
 
+  // MCP servers (~/.claude.json, {cwd}/.mcp.json)
+  listMcpServers: (cwd?: string) =>
+    invoke<McpServerEntry[]>('list_mcp_servers', { cwd }),
+
+  /** Create or replace an entry; pass `previousName` to rename. */
+  saveMcpServer: (
+    scope: 'global' | 'project',
+    name: string,
+    config: Record<string, unknown>,
+    cwd?: string,
+    previousName?: string,
+  ) =>
+    invoke<McpServerEntry>('save_mcp_server', { scope, cwd, name, config, previousName }),
+
+  deleteMcpServer: (scope: 'global' | 'project', name: string, cwd?: string) =>
+    invoke<void>('delete_mcp_server', { scope, cwd, name }),
+
+  /** Run initialize + tools/list against a server entry. */
+  checkMcpServer: (config: Record<string, unknown>, cwd?: string, timeoutSecs?: number) =>
+    invoke<McpHealthReport>('check_mcp_server', { config, cwd, timeoutSecs }),
+
+  getSessionMcpSelection: (sessionId: string) =>
+    invoke<McpSelection>('get_session_mcp_selection', { sessionId }),
+
+  /** Applies the next time the session's CLI process starts. */
+  setSessionMcpSelection: (sessionId: string, selection: McpSelection) =>
+    invoke<void>('set_session_mcp_selection', { sessionId, selection }),
+
+  /** Answer an `mcp:request` (see `onMcpRequest`); pass `error` to fail the tool call. */
+  respondMcpRequest: (requestId: string, result?: unknown, error?: string) =>
+    invoke<void>('respond_mcp_request', { requestId, result, error }),
+
+  // Worktree-isolated sessions
+  listSessionWorktrees: () =>
+    invoke<WorktreeStatus[]>('list_session_worktrees'),
+
+  diffSessionWorktree: (sessionId: string) =>
+    invoke<WorktreeDiff>('diff_session_worktree', { sessionId }),
+
+  /** Merge the session branch into the branch it started from. */
+  mergeSessionWorktree: (
+    sessionId: string,
+    opts?: { squash?: boolean; message?: string; remove?: boolean },
+  ) =>
+    invoke<WorktreeMergeResult>('merge_session_worktree', { sessionId, ...opts }),
+
+  discardSessionWorktree: (sessionId: string, keepBranch?: boolean) =>
+    invoke<void>('discard_session_worktree', { sessionId, keepBranch }),
+
   // Git commands (safe, allowlisted operations only)
   runGitCommand: (cwd: string, args: string[]) =>
     invoke<string>('run_git_command', { cwd, args }),
 
+  // Typed git queries
+  gitStatus: (cwd: string) =>
+    invoke<GitStatus>('git_status', { cwd }),
+
+  gitDiff: (
+    cwd: string,
+    opts?: {
+      staged?: boolean;
+      base?: string;
+      paths?: string[];
+      contextLines?: number;
+      wordDiff?: boolean;
+    },
+  ) => invoke<DiffFile[]>('git_diff', { cwd, ...opts }),
+
+  gitLog: (
+    cwd: string,
+    opts?: { skip?: number; limit?: number; rev?: string; path?: string },
+  ) => invoke<GitLogPage>('git_log', { cwd, ...opts }),
+
+  /** Blame lines `startLine..=endLine` (1-based). */
+  gitBlame: (cwd: string, path: string, startLine: number, endLine: number, rev?: string) =>
+    invoke<GitBlame>('git_blame', { cwd, path, startLine, endLine, rev }),
+
+  // Guarded git writes — never forced, hooks always run. Detached HEAD is
+  // refused unless `allowDetached` is passed after confirming with the user.
+  gitStage: (cwd: string, paths: string[]) =>
+    invoke<void>('git_stage', { cwd, paths }),
+
+  gitUnstage: (cwd: string, paths: string[]) =>
+    invoke<void>('git_unstage', { cwd, paths }),
+
+  /** `message` may be omitted only when amending (keeps the old message). */
+  gitCommit: (
+    cwd: string,
+    opts: { message?: string; amend?: boolean; allowDetached?: boolean },
+  ) => invoke<GitCommitResult>('git_commit', { cwd, ...opts }),
+
+  /** Switching away from a detached HEAD (`checkout`) needs `allowDetached`. */
+  gitCreateBranch: (
+    cwd: string,
+    name: string,
+    opts?: { startPoint?: string; checkout?: boolean; allowDetached?: boolean },
+  ) => invoke<void>('git_create_branch', { cwd, name, ...opts }),
+
+  gitSwitchBranch: (cwd: string, name: string, allowDetached?: boolean) =>
+    invoke<void>('git_switch_branch', { cwd, name, allowDetached }),
+
+  /** Draft a message for the staged changes; null when generation is unavailable. */
+  generateCommitMessage: (cwd: string, providerId?: string) =>
+    invoke<string | null>('generate_commit_message', { cwd, providerId }),
+
+  // Per-turn workspace snapshots (taken automatically before each user message)
+  listTurnSnapshots: (filter?: { sessionId?: string; cwd?: string }) =>
+    invoke<TurnSnapshot[]>('list_turn_snapshots', { ...filter }),
+
+  /** Changes since the snapshot; `paths` are relative to the session cwd. */
+  diffTurnSnapshot: (snapshotId: string, paths?: string[]) =>
+    invoke<DiffFile[]>('diff_turn_snapshot', { snapshotId, paths }),
+
+  restoreTurnSnapshot: (snapshotId: string, paths?: string[]) =>
+    invoke<SnapshotRestoreResult>('restore_turn_snapshot', { snapshotId, paths }),
+
+  gcTurnSnapshots: (maxAgeDays?: number, keepPerSession?: number) =>
+    invoke<{ removed: number; reclaimed_bytes: number }>('gc_turn_snapshots', {
+      maxAgeDays,
+      keepPerSession,
+    }),
+
   // Rewind files via SDK control protocol (fast, in-process) with CLI spawn fallback
   rewindFiles: (stdinId: string, userMessageId: string, sessionId: string, cwd: string) =>
     invoke<void>('send_control_request', {
@@ -523,12 +1545,14 @@ export function onPermissionRequest(
 }
 
 /** Listen for NDJSON stream events from a Claude CLI process.
+ *  Session channels are routed per window (see `detachSessionWindow`), so
+ *  this listens on the current window rather than globally.
  *  @param stdinId - Desk-generated process key (NOT the CLI session UUID) */
 export function onClaudeStream(
   stdinId: string,
   callback: (message: any) => void,
 ): Promise<UnlistenFn> {
-  return listen<any>(
+  return getCurrentWebviewWindow().listen<any>(
     `claude:stream:${stdinId}`,
     (event) => callback(event.payload),
   );
@@ -540,7 +1564,7 @@ export function onClaudeStderr(
   stdinId: string,
   callback: (line: string) => void,
 ): Promise<UnlistenFn> {
-  return listen<string>(
+  return getCurrentWebviewWindow().listen<string>(
     `claude:stderr:${stdinId}`,
     (event) => callback(event.payload),
   );
@@ -552,12 +1576,24 @@ export function onSessionExit(
   stdinId: string,
   callback: (code: number | null) => void,
 ): Promise<UnlistenFn> {
-  return listen<number | null>(
+  return getCurrentWebviewWindow().listen<number | null>(
     `claude:exit:${stdinId}`,
     (event) => callback(event.payload),
   );
 }
 
+/** Fires after the workspace snapshot taken before each user turn.
+ *  @param stdinId - Desk-generated process key (NOT the CLI session UUID) */
+export function onTurnSnapshot(
+  stdinId: string,
+  callback: (snapshot: TurnSnapshot) => void,
+): Promise<UnlistenFn> {
+  return getCurrentWebviewWindow().listen<TurnSnapshot>(
+    `claude:snapshot:${stdinId}`,
+    (event) => callback(event.payload),
+  );
+}
+
 export function onSetupInstallOutput(
   callback: (event: SetupOutputEvent) => void,
 ): Promise<UnlistenFn> {
@@ -611,3 +1647,52 @@ export function onFileChange(
     (event) => callback(event.payload),
   );
 }
+
+export function onFileTreeChunk(
+  callback: (chunk: FileTreeChunk) => void,
+): Promise<UnlistenFn> {
+  return listen<FileTreeChunk>(
+    'file-tree:chunk',
+    (event) => callback(event.payload),
+  );
+}
+
+export function onSearchChunk(
+  callback: (chunk: SearchChunk) => void,
+): Promise<UnlistenFn> {
+  return listen<SearchChunk>(
+    'search:chunk',
+    (event) => callback(event.payload),
+  );
+}
+
+/** A built-in MCP tool call that needs the UI. Answer with `bridge.respondMcpRequest`. */
+export type McpUiRequest =
+  | { request_id: string; session_id: string; kind: 'open_file'; payload: { path: string; line?: number | null } }
+  | {
+      request_id: string;
+      session_id: string;
+      kind: 'ask_user';
+      payload: { question: string; options: string[]; multi_select: boolean; allow_free_text: boolean };
+    }
+  | { request_id: string; session_id: string; kind: 'list_tabs'; payload: Record<string, never> };
+
+/** Listen for built-in MCP requests routed to this window's sessions. */
+export function onMcpRequest(
+  callback: (request: McpUiRequest) => void,
+): Promise<UnlistenFn> {
+  return getCurrentWebviewWindow().listen<McpUiRequest>(
+    'mcp:request',
+    (event) => callback(event.payload),
+  );
+}
+
+/** A session changed owner window (`label`); other windows should drop its tab. */
+export function onSessionWindowMoved(
+  callback: (event: { session_id: string; label: string }) => void,
+): Promise<UnlistenFn> {
+  return listen<{ session_id: string; label: string }>(
+    'window:session-moved',
+    (event) => callback(event.payload),
+  );
+}
@@ -1,6 +1,8 @@
 import React from "react";
 import ReactDOM from "react-dom/client";
 import App from "./App";
+import { DetachedSessionWindow } from "./components/layout/DetachedSessionWindow";
+import { getDetachedSessionId } from "./lib/detached-window";
 
 class ErrorBoundary extends React.Component<
   { children: React.ReactNode },
@@ -89,10 +91,14 @@ class ErrorBoundary extends React.Component<
   }
 }
 
+const detachedSessionId = getDetachedSessionId();
+
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
     <ErrorBoundary>
-      <App />
+      {detachedSessionId
+        ? <DetachedSessionWindow sessionId={detachedSessionId} />
+        : <App />}
     </ErrorBoundary>
   </React.StrictMode>,
 );
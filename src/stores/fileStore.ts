@@ -1,5 +1,5 @@
 import { create } from 'zustand';
-import { bridge, FileNode, RecentProject } from '../lib/tauri-bridge';
+import { bridge, FileNode, FileVersion, RecentProject } from '../lib/tauri-bridge';
 
 export type FileChangeKind = 'created' | 'modified' | 'removed';
 export type PreviewMode = 'preview' | 'source' | 'edit';
@@ -13,6 +13,8 @@ interface FileState {
   isLoading: boolean;
   selectedFile: string | null;
   fileContent: string | null;
+  /** On-disk version `fileContent` was read from; saves are checked against it */
+  fileVersion: FileVersion | null;
   isLoadingContent: boolean;
   previewMode: PreviewMode;
   rootPath: string;
@@ -20,6 +22,8 @@ interface FileState {
   // Editing state
   editContent: string | null;     // buffer for edits (null = not dirty)
   isSaving: boolean;
+  /** Last save hit a change on disk that could not be merged automatically */
+  saveConflict: boolean;
 
   // Unsaved changes navigation guard
   pendingNavigation: string | null;
@@ -70,11 +74,13 @@ export const useFileStore = create<FileState>()((set, get) => ({
   isLoading: false,
   selectedFile: null,
   fileContent: null,
+  fileVersion: null,
   isLoadingContent: false,
   previewMode: 'preview' as PreviewMode,
   rootPath: '',
   editContent: null,
   isSaving: false,
+  saveConflict: false,
   pendingNavigation: null,
   showUnsavedDialog: false,
   recentProjects: [],
@@ -138,9 +144,9 @@ export const useFileStore = create<FileState>()((set, get) => ({
 
     // Toggle selection: click again to deselect
     if (selectedFile === path) {
-      set({ selectedFile: null, fileContent: null, isLoadingContent: false, editContent: null });
+      set({ selectedFile: null, fileContent: null, fileVersion: null, isLoadingContent: false, editContent: null, saveConflict: false });
     } else {
-      set({ selectedFile: path, fileContent: null, isLoadingContent: true, previewMode: 'preview', editContent: null });
+      set({ selectedFile: path, fileContent: null, fileVersion: null, isLoadingContent: true, previewMode: 'preview', editContent: null, saveConflict: false });
 
       // Binary-preview files: skip text reading, render with file:// URL in FilePreview
       const ext = path.split('.').pop()?.toLowerCase() || '';
@@ -164,10 +170,10 @@ export const useFileStore = create<FileState>()((set, get) => ({
         }
       } else {
         try {
-          const content = await bridge.readFileContent(path);
+          const { content, version } = await bridge.readFileVersioned(path);
           // Only update if selectedFile hasn't changed during the async call
           if (get().selectedFile === path) {
-            set({ fileContent: content, isLoadingContent: false });
+            set({ fileContent: content, fileVersion: version, isLoadingContent: false });
           }
         } catch {
           if (get().selectedFile === path) {
@@ -178,9 +184,9 @@ export const useFileStore = create<FileState>()((set, get) => ({
     }
   },
 
-  clearSelection: () => set({ selectedFile: null, fileContent: null, isLoadingContent: false, editContent: null }),
+  clearSelection: () => set({ selectedFile: null, fileContent: null, fileVersion: null, isLoadingContent: false, editContent: null, saveConflict: false }),
 
-  closePreview: () => set({ selectedFile: null, fileContent: null, isLoadingContent: false, editContent: null }),
+  closePreview: () => set({ selectedFile: null, fileContent: null, fileVersion: null, isLoadingContent: false, editContent: null, saveConflict: false }),
 
   setPreviewMode: (mode: PreviewMode) => {
     const state = get();
@@ -195,20 +201,45 @@ export const useFileStore = create<FileState>()((set, get) => ({
   setEditContent: (content: string) => set({ editContent: content }),
 
   saveFile: async () => {
-    const { selectedFile, editContent } = get();
+    const { selectedFile, editContent, fileContent, fileVersion } = get();
     if (!selectedFile || editContent === null) return;
     set({ isSaving: true });
     try {
-      await bridge.writeFileContent(selectedFile, editContent);
+      let content = editContent;
+      let outcome = await bridge.writeFileContent(
+        selectedFile, content, undefined, fileVersion ? { hash: fileVersion.hash } : undefined,
+      );
+      // Changed on disk since it was loaded (e.g. by the agent): merge both edits
+      if (outcome.status === 'conflict' && outcome.current && outcome.current_content !== null && fileContent !== null) {
+        const merge = await bridge.mergeFileVersions(fileContent, editContent, outcome.current_content);
+        if (!merge.clean) {
+          // Stay in the editor with conflict markers; the next save is checked against the disk version
+          set({
+            fileContent: outcome.current_content, fileVersion: outcome.current,
+            editContent: merge.merged, isSaving: false, saveConflict: true,
+          });
+          return;
+        }
+        content = merge.merged;
+        outcome = await bridge.writeFileContent(selectedFile, content, undefined, { hash: outcome.current.hash });
+      }
+      if (outcome.status === 'conflict') {
+        // Deleted, binary or too large to merge — saving again overwrites it
+        set({ fileVersion: outcome.current, isSaving: false, saveConflict: true });
+        return;
+      }
       // Update fileContent to match saved content
-      set({ fileContent: editContent, editContent: null, isSaving: false, previewMode: 'preview' });
+      set({
+        fileContent: content, fileVersion: outcome.version, editContent: null,
+        isSaving: false, saveConflict: false, previewMode: 'preview',
+      });
     } catch {
       set({ isSaving: false });
     }
   },
 
   discardEdits: () => {
-    set({ editContent: null, previewMode: 'preview' });
+    set({ editContent: null, saveConflict: false, previewMode: 'preview' });
   },
 
   setRootPath: (path: string) => set({ rootPath: path }),
@@ -239,8 +270,8 @@ export const useFileStore = create<FileState>()((set, get) => ({
         const dataUrl = await bridge.readFileBase64(path);
         if (get().selectedFile === path) set({ fileContent: dataUrl });
       } else {
-        const content = await bridge.readFileContent(path);
-        if (get().selectedFile === path) set({ fileContent: content });
+        const { content, version } = await bridge.readFileVersioned(path);
+        if (get().selectedFile === path) set({ fileContent: content, fileVersion: version });
       }
     } catch {
       // Silently fail — keep existing content
@@ -277,7 +308,8 @@ export const useFileStore = create<FileState>()((set, get) => ({
     const pending = get().pendingNavigation;
     await get().saveFile();
     set({ showUnsavedDialog: false, pendingNavigation: null });
-    if (pending) get().selectFile(pending);
+    // Stay on the file if the save hit a conflict the user has to resolve
+    if (pending && !get().saveConflict) get().selectFile(pending);
   },
 
   cancelNavigation: () => {